@@ -0,0 +1,3580 @@
+// SPDX-License-Identifier: MIT
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::env;
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, BufReader, Read};
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::RwLock;
+
+use chrono::{DateTime, Utc};
+use flate2::read::GzDecoder;
+use log::{debug, info, warn};
+use rand::RngCore;
+use rand::rngs::OsRng;
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+use reqwest::header;
+use reqwest::StatusCode;
+use serde::{Deserialize, Deserializer, Serialize};
+use serde::de::{DeserializeOwned, Visitor};
+
+use crate::cipher::{self, AccountKeys, AuthRequestKeys, CipherError, CipherSuite, CipherString, Kdf};
+
+
+// Sync payloads of large vaults compress very well
+const SYNC_ENCODINGS: &str = "br, gzip";
+// Error of the identity server when the password or refresh token is wrong
+const INVALID_GRANT: &str = "invalid_grant";
+
+// Second factor provider type of a token remembered from a previous login
+const TWO_FACTOR_REMEMBER: usize = 5;
+const TWO_FACTOR_WEBAUTHN: &str = "7";
+// Duo of the user and of the organization
+const TWO_FACTOR_DUO: &[&str] = &["2", "6"];
+
+// Service the tokens of the session are stored under in the keyring
+#[cfg(feature = "keyring")]
+const KEYRING_SERVICE: &str = "bwtui";
+
+
+/// Error of any request or cache access.
+#[derive(Debug, failure::Fail)]
+pub enum ApiError {
+        #[fail(display = "prelogin failed: {}", error)]
+        PreloginFailed {
+                error: String,
+        },
+        #[fail(display = "authentication failed: {}", error)]
+        LoginFailed {
+                error: String,
+        },
+        // The identity server rejected the password or refresh token itself,
+        // rather than the request failing
+        #[fail(display = "authentication failed: {}", error)]
+        InvalidGrant {
+                error: String,
+        },
+        #[fail(display = "a second factor is needed to log in")]
+        TwoFactorRequired {
+                // Types of the second factors set up for the account
+                providers: Vec<usize>,
+                // What a security key has to sign, if it's one of them
+                webauthn: Option<WebAuthnChallenge>,
+                // Where to approve the login with Duo, if it's one of them
+                duo: Option<String>,
+        },
+        #[fail(display = "the server asks for a captcha, as it doesn't know this device")]
+        CaptchaRequired,
+        #[fail(display = "the server emailed a code to verify this new device")]
+        DeviceVerificationRequired,
+        #[fail(display = "registration failed: {}", error)]
+        RegistrationFailed {
+                error: String,
+        },
+        #[fail(display = "failed to retrieve {}: {}", endpoint, error)]
+        RequestFailed {
+                endpoint: String,
+                error: String,
+        },
+        #[fail(display = "could not reach {}: {}", endpoint, error)]
+        Offline {
+                endpoint: String,
+                error: String,
+        },
+        #[fail(display = "access token rejected by {}", endpoint)]
+        Unauthorized {
+                endpoint: String,
+        },
+        #[fail(display = "the session expired and can't be renewed without logging in again")]
+        SessionExpired,
+        #[fail(display = "the security stamp of the account changed, the session was revoked")]
+        SecurityStampChanged,
+        #[fail(display = "{} doesn't exist (anymore)", endpoint)]
+        NotFound {
+                endpoint: String,
+        },
+        #[fail(display = "failed to write sync data: {}", error)]
+        VaultDataWriteFailed {
+                error: String,
+        },
+        #[fail(display = "failed to read sync data: {}", error)]
+        VaultDataReadFailed {
+                error: String,
+        },
+}
+
+// Each item in the vault carries one of a few object type names, share those
+// instead of allocating them over and over again.
+const KNOWN_OBJECTS: &[&str] = &[
+        "cipher", "cipherDetails", "collection", "collectionDetails", "domains",
+        "folder", "policy", "profile", "profileOrganization", "sync",
+];
+
+struct ObjectVisitor;
+
+impl<'de> Visitor<'de> for ObjectVisitor {
+        type Value = Cow<'static, str>;
+
+        fn expecting(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+                fmt.write_str("object type name")
+        }
+
+        fn visit_str<E: serde::de::Error>(self, value: &str) -> Result<Self::Value, E> {
+                let known = KNOWN_OBJECTS.iter().find(|known| **known == value);
+
+                Ok(match known {
+                        Some(known) => Cow::Borrowed(*known),
+                        None => Cow::Owned(value.to_owned()),
+                })
+        }
+
+        fn visit_unit<E: serde::de::Error>(self) -> Result<Self::Value, E> {
+                Ok(Cow::Borrowed(""))
+        }
+}
+
+fn intern_object<'de, D>(deserializer: D) -> Result<Cow<'static, str>, D::Error>
+        where D: Deserializer<'de>
+{
+        if deserializer.is_human_readable() {
+                deserializer.deserialize_any(ObjectVisitor)
+        } else {
+                deserializer.deserialize_str(ObjectVisitor)
+        }
+}
+
+
+// Vaultwarden sends `null` for some fields the official server always fills
+// (or omits entirely), treat those like missing fields. The binary cache never
+// contains nulls for them.
+
+fn null_as_default<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+        where D: Deserializer<'de>,
+              T: Default + Deserialize<'de>
+{
+        if deserializer.is_human_readable() {
+                Ok(Option::deserialize(deserializer)?.unwrap_or_default())
+        } else {
+                T::deserialize(deserializer)
+        }
+}
+
+fn null_as_true<'de, D>(deserializer: D) -> Result<bool, D::Error>
+        where D: Deserializer<'de>
+{
+        if deserializer.is_human_readable() {
+                Ok(Option::deserialize(deserializer)?.unwrap_or(true))
+        } else {
+                bool::deserialize(deserializer)
+        }
+}
+
+fn default_true() -> bool {
+        true
+}
+
+
+fn default_client_id() -> String {
+        "connector".to_owned()
+}
+
+
+/// Access token of a logged in session, along with the keys derived from the
+/// master password.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct AuthData {
+        access_token: String,
+        expires_in: usize,
+        token_type: String,
+        // Missing in caches written by older versions, in which case the token
+        // is treated as expired.
+        #[serde(default)]
+        issued_at: Option<DateTime<Utc>>,
+        // Renews the access token without the master password, missing for
+        // caches written by older versions.
+        #[serde(default)]
+        refresh_token: Option<String>,
+        // Client the tokens were issued to, the refresh token only works for it
+        #[serde(default = "default_client_id")]
+        client_id: String,
+        // Where the tokens were issued, missing for caches written by older
+        // versions which only talked to the servers of the settings
+        #[serde(default)]
+        server: Option<ServerUrls>,
+        // The tokens are kept in the keyring of the OS rather than in here,
+        // see `save_auth`
+        #[serde(default)]
+        tokens_in_keyring: bool,
+
+        kdf: usize,
+        pub kdf_iterations: usize,
+        // Argon2id only, missing for caches written by older versions
+        #[serde(default)]
+        kdf_memory: Option<usize>,
+        #[serde(default)]
+        kdf_parallelism: Option<usize>,
+
+        #[serde(skip)]
+        pub cipher: CipherSuite,
+        // Remembered second factor, to renew the access token without asking
+        // again. Only kept on disk encrypted, see `RememberedTwoFactor`.
+        #[serde(skip)]
+        two_factor_token: Option<String>,
+}
+
+
+/// Token the server handed out to skip the second factor on later logins
+/// from this device, encrypted with the master key.
+#[derive(Deserialize, Serialize)]
+struct RememberedTwoFactor {
+        email: String,
+        token: CipherString,
+}
+
+
+/// Vault key encrypted with a key derived from a PIN the way the master key is
+/// derived from the master password, to unlock without the latter.
+#[derive(Deserialize, Serialize)]
+struct PinProtectedKey {
+        email: String,
+        key: CipherString,
+}
+
+
+/// Tokens of a session, as kept in the keyring of the OS.
+#[derive(Deserialize, Serialize)]
+struct SessionTokens {
+        access_token: String,
+        refresh_token: Option<String>,
+}
+
+
+/// Login through single sign-on, waiting for the browser to come back with
+/// the authorization code.
+pub struct SsoLogin {
+        /// Where to send the browser
+        pub url: String,
+        pub redirect_uri: String,
+        /// Returned along with the code, anything else isn't from this login
+        pub state: String,
+        // PKCE secret, only its hash was sent along
+        code_verifier: String,
+}
+
+
+/// Login waiting to be approved by another device of the account, which
+/// hands out the keys of the vault instead of the master password.
+pub struct DeviceLogin {
+        pub id: String,
+        email: String,
+        // Stands in for the master password hash in the token request
+        access_code: String,
+        keys: AuthRequestKeys,
+}
+
+impl DeviceLogin {
+        /// Fingerprint phrase the approving device shows for this request.
+        pub fn fingerprint(&self) -> String {
+                self.keys.fingerprint(&self.email)
+        }
+}
+
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct DeviceLoginRequest<'a> {
+        email: &'a str,
+        public_key: &'a str,
+        device_identifier: &'a str,
+        access_code: &'a str,
+        // Log in and unlock the vault
+        #[serde(rename = "type")]
+        type_: usize,
+}
+
+
+#[derive(Debug, Deserialize)]
+struct DeviceLoginResponse {
+        #[serde(alias = "Id")]
+        id: String,
+        // Encrypted with the public key of the request: the vault key, or the
+        // master key if the master password hash is set as well
+        #[serde(alias = "Key", default)]
+        key: Option<String>,
+        #[serde(alias = "MasterPasswordHash", alias = "masterPasswordHash", default)]
+        master_password_hash: Option<String>,
+        // Unset until answered
+        #[serde(alias = "RequestApproved", alias = "requestApproved", default)]
+        request_approved: Option<bool>,
+}
+
+
+#[derive(Deserialize)]
+struct SsoPrevalidateResponse {
+        #[serde(alias = "Token")]
+        token: String,
+}
+
+
+/// Second factor sent along with the master password.
+struct TwoFactor<'a> {
+        provider: usize,
+        token: &'a str,
+        // Whether to have the server hand out a token for later logins
+        remember: bool,
+}
+
+
+impl AuthData {
+        /// Picks up the second factor remembered on a previous login, so that
+        /// the access token of a cached session can be renewed. Has to be
+        /// called before the vault is unlocked with `set_decrypt_key`.
+        pub fn read_two_factor_token(&mut self, email: &str) {
+                self.two_factor_token = read_two_factor_token(email, &self.cipher);
+        }
+
+        /// Key derivation of the account, to derive the master key again.
+        pub fn kdf(&self) -> Kdf {
+                Kdf {
+                        kdf: self.kdf,
+                        iterations: self.kdf_iterations,
+                        memory: self.kdf_memory,
+                        parallelism: self.kdf_parallelism,
+                }
+        }
+
+        /// Servers the session was logged in to, which later requests have
+        /// to go to as well.
+        pub fn server(&self) -> Option<&ServerUrls> {
+                self.server.as_ref()
+        }
+
+        /// Whether the access token expired or is about to within the next
+        /// few minutes.
+        pub fn expires_soon(&self) -> bool {
+                match self.issued_at {
+                        Some(issued_at) => {
+                                let expires_at = issued_at
+                                        + chrono::Duration::seconds(self.expires_in as i64);
+
+                                Utc::now() + chrono::Duration::minutes(5) >= expires_at
+                        }
+                        None => true,
+                }
+        }
+}
+
+
+#[derive(Debug, Deserialize)]
+struct PreloginResponseData {
+        #[serde(alias = "Kdf")]
+        kdf: usize,
+        #[serde(alias = "KdfIterations", alias = "kdfIterations")]
+        kdf_iterations: usize,
+        #[serde(alias = "KdfMemory", alias = "kdfMemory", default)]
+        kdf_memory: Option<usize>,
+        #[serde(alias = "KdfParallelism", alias = "kdfParallelism", default)]
+        kdf_parallelism: Option<usize>,
+}
+
+impl PreloginResponseData {
+        fn kdf(&self) -> Kdf {
+                Kdf {
+                        kdf: self.kdf,
+                        iterations: self.kdf_iterations,
+                        memory: self.kdf_memory,
+                        parallelism: self.kdf_parallelism,
+                }
+        }
+}
+
+
+#[derive(Debug, Default, Deserialize)]
+struct ErrorModel {
+        #[serde(alias = "Message")]
+        message: Option<String>,
+}
+
+/// Error body as returned by the identity and API servers, which fields are
+/// set differs between endpoints.
+#[derive(Debug, Default, Deserialize)]
+struct ErrorResponse {
+        error: Option<String>,
+        error_description: Option<String>,
+        #[serde(alias = "ErrorModel", alias = "errorModel")]
+        error_model: Option<ErrorModel>,
+        #[serde(alias = "Message")]
+        message: Option<String>,
+        // Set by the identity server if a second factor is needed, keyed by
+        // provider type
+        #[serde(alias = "TwoFactorProviders2")]
+        two_factor_providers: Option<HashMap<String, serde_json::Value>>,
+        // Plain list of the provider types, as numbers or strings depending
+        // on the server, all some older ones send
+        #[serde(alias = "TwoFactorProviders")]
+        two_factor_provider_list: Option<Vec<serde_json::Value>>,
+        // Set by the identity server if a captcha has to be solved first
+        #[serde(alias = "HCaptcha_SiteKey")]
+        hcaptcha_site_key: Option<String>,
+        // Stands in for the captcha when logging in with the second factor
+        #[serde(alias = "CaptchaBypassToken")]
+        captcha_bypass_token: Option<String>,
+}
+
+/// Assertion options of the WebAuthn second factor, binary values are
+/// base64url encoded.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WebAuthnChallenge {
+        pub challenge: String,
+        pub rp_id: Option<String>,
+        #[serde(default)]
+        pub allow_credentials: Vec<WebAuthnCredential>,
+        pub user_verification: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct WebAuthnCredential {
+        pub id: String,
+}
+
+
+impl ErrorResponse {
+        /// Types of the second factors the server asks for, empty if none.
+        fn two_factor_providers(&self) -> Vec<usize> {
+                if let Some(providers) = &self.two_factor_providers {
+                        return providers.keys().filter_map(|key| key.parse().ok()).collect();
+                }
+
+                self.two_factor_provider_list.iter()
+                        .flatten()
+                        .filter_map(|provider| match provider {
+                                serde_json::Value::Number(number) => number.as_u64().map(|n| n as usize),
+                                serde_json::Value::String(string) => string.parse().ok(),
+                                _ => None,
+                        })
+                        .collect()
+        }
+
+        fn webauthn_challenge(&self) -> Option<WebAuthnChallenge> {
+                let options = self.two_factor_providers.as_ref()?.get(TWO_FACTOR_WEBAUTHN)?;
+
+                serde_json::from_value(options.clone()).ok()
+        }
+
+        /// Universal Prompt of Duo, older servers send parameters for the
+        /// discontinued iframe instead.
+        fn duo_auth_url(&self) -> Option<String> {
+                let providers = self.two_factor_providers.as_ref()?;
+
+                TWO_FACTOR_DUO.iter()
+                        .filter_map(|provider| providers.get(*provider))
+                        .filter_map(|options| options.get("AuthUrl").or_else(|| options.get("authUrl")))
+                        .filter_map(|url| url.as_str())
+                        .map(str::to_owned)
+                        .next()
+        }
+
+        /// Whether the login was refused until the new device is verified
+        /// with the code the server emailed.
+        fn device_verification_required(&self) -> bool {
+                let message = self.error_model.as_ref().and_then(|model| model.message.as_ref())
+                        .or(self.message.as_ref())
+                        .or(self.error_description.as_ref());
+
+                message.map_or(false, |message| {
+                        message.to_lowercase().contains("new device verification required")
+                })
+        }
+
+        /// The most descriptive message, falling back to the HTTP status.
+        fn message(self, status: StatusCode) -> String {
+                self.error_model.and_then(|model| model.message)
+                        .or(self.message)
+                        .or(self.error_description)
+                        .or(self.error)
+                        .unwrap_or_else(|| status.to_string())
+        }
+}
+
+
+#[derive(Debug, Deserialize)]
+struct LoginResponseData {
+        access_token: String,
+        expires_in: usize,
+        token_type: String,
+        refresh_token: Option<String>,
+        #[serde(alias = "TwoFactorToken")]
+        two_factor_token: Option<String>,
+}
+
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Profile {
+        #[serde(alias = "Object", default, deserialize_with = "intern_object")]
+        object: Cow<'static, str>,
+        #[serde(alias = "Id", alias = "id")]
+        pub uuid: Uuid,
+        #[serde(alias = "Name", default, deserialize_with = "null_as_default")]
+        pub name: String,
+        #[serde(alias = "Email")]
+        pub email: String,
+        #[serde(alias = "EmailVerified", alias = "emailVerified", default, deserialize_with = "null_as_default")]
+        pub email_verified: bool,
+        #[serde(alias = "Premium", default, deserialize_with = "null_as_default")]
+        pub premium: bool,
+        #[serde(alias = "MasterPasswordHint", alias = "masterPasswordHint")]
+        pub master_password_hint: Option<String>,
+        #[serde(alias = "Culture", alias = "culture", default, deserialize_with = "null_as_default")]
+        pub language: String,
+        #[serde(alias = "TwoFactorEnabled", alias = "twoFactorEnabled", default, deserialize_with = "null_as_default")]
+        pub tfa_enabled: bool,
+        #[serde(alias = "Key")]
+        pub key: CipherString,
+        #[serde(alias = "PrivateKey", alias = "privateKey")]
+        pub private_key: Option<CipherString>,
+        #[serde(alias = "SecurityStamp", alias = "securityStamp", default, deserialize_with = "null_as_default")]
+        pub security_stamp: String,
+        #[serde(alias = "Organizations", default, deserialize_with = "null_as_default")]
+        pub organizations: Vec<Organization>,
+}
+
+// Roles of a user within an organization
+pub const ORG_USER_OWNER: usize = 0;
+pub const ORG_USER_ADMIN: usize = 1;
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Organization {
+        #[serde(alias = "Object", default, deserialize_with = "intern_object")]
+        object: Cow<'static, str>,
+        #[serde(alias = "Id", alias = "id")]
+        pub uuid: Uuid,
+        #[serde(alias = "Name", default, deserialize_with = "null_as_default")]
+        pub name: String,
+        #[serde(alias = "Type", alias = "type")]
+        pub type_: usize,
+        #[serde(alias = "Enabled", default, deserialize_with = "null_as_default")]
+        pub enabled: bool,
+        #[serde(alias = "UseEvents", alias = "useEvents", default, deserialize_with = "null_as_default")]
+        pub use_events: bool,
+        // Organization key, encrypted with the user's public key
+        #[serde(alias = "Key")]
+        pub key: Option<String>,
+}
+
+impl Organization {
+        pub fn is_admin(&self) -> bool {
+                self.type_ == ORG_USER_OWNER || self.type_ == ORG_USER_ADMIN
+        }
+}
+
+// Types of organization policies bwtui enforces
+pub const POLICY_MASTER_PASSWORD: usize = 1;
+pub const POLICY_PERSONAL_OWNERSHIP: usize = 5;
+pub const POLICY_MAXIMUM_VAULT_TIMEOUT: usize = 9;
+pub const POLICY_DISABLE_PERSONAL_VAULT_EXPORT: usize = 10;
+
+/// Rule an organization sets for its members.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Policy {
+        #[serde(alias = "Object", default, deserialize_with = "intern_object")]
+        object: Cow<'static, str>,
+        #[serde(alias = "OrganizationId", alias = "organizationId")]
+        pub organization_id: Uuid,
+        #[serde(alias = "Type", alias = "type")]
+        pub type_: usize,
+        #[serde(alias = "Enabled", default, deserialize_with = "null_as_default")]
+        pub enabled: bool,
+        #[serde(alias = "Data", default, deserialize_with = "null_as_default")]
+        pub data: PolicyData,
+}
+
+/// Settings of a policy, which of them are set depends on its type.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct PolicyData {
+        // Maximum vault timeout
+        #[serde(alias = "Minutes", default)]
+        pub minutes: Option<u64>,
+        // Master password requirements
+        #[serde(alias = "MinLength", alias = "minLength", default)]
+        pub min_length: Option<usize>,
+        #[serde(alias = "MinComplexity", alias = "minComplexity", default)]
+        pub min_complexity: Option<u8>,
+        #[serde(alias = "RequireUpper", alias = "requireUpper", default, deserialize_with = "null_as_default")]
+        pub require_upper: bool,
+        #[serde(alias = "RequireLower", alias = "requireLower", default, deserialize_with = "null_as_default")]
+        pub require_lower: bool,
+        #[serde(alias = "RequireNumbers", alias = "requireNumbers", default, deserialize_with = "null_as_default")]
+        pub require_numbers: bool,
+        #[serde(alias = "RequireSpecial", alias = "requireSpecial", default, deserialize_with = "null_as_default")]
+        pub require_special: bool,
+}
+
+/// Entry of an organization's event log.
+#[derive(Debug, Deserialize)]
+pub struct Event {
+        #[serde(alias = "Type", alias = "type")]
+        pub type_: usize,
+        #[serde(alias = "ActingUserId", alias = "actingUserId")]
+        pub acting_user_id: Option<Uuid>,
+        #[serde(alias = "CipherId", alias = "cipherId")]
+        pub cipher_id: Option<Uuid>,
+        #[serde(alias = "Date")]
+        pub date: DateTime<Utc>,
+        #[serde(alias = "IpAddress", alias = "ipAddress")]
+        pub ip_address: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct EventsResponse {
+        #[serde(alias = "Data", default, deserialize_with = "null_as_default")]
+        data: Vec<Event>,
+        #[serde(alias = "ContinuationToken", alias = "continuationToken")]
+        continuation_token: Option<String>,
+}
+
+/// Member of an organization, as listed to its admins.
+#[derive(Debug, Deserialize)]
+pub struct OrganizationMember {
+        #[serde(alias = "UserId", alias = "userId")]
+        pub user_id: Option<Uuid>,
+        #[serde(alias = "Name", default, deserialize_with = "null_as_default")]
+        pub name: String,
+        #[serde(alias = "Email", default, deserialize_with = "null_as_default")]
+        pub email: String,
+}
+
+// Kinds of emergency access
+pub const EMERGENCY_VIEW: usize = 0;
+pub const EMERGENCY_TAKEOVER: usize = 1;
+
+// Progress of an emergency access grant
+pub const EMERGENCY_INVITED: usize = 0;
+pub const EMERGENCY_ACCEPTED: usize = 1;
+pub const EMERGENCY_CONFIRMED: usize = 2;
+pub const EMERGENCY_RECOVERY_INITIATED: usize = 3;
+pub const EMERGENCY_RECOVERY_APPROVED: usize = 4;
+
+/// Emergency access grant, either given by the user (to a grantee) or to the
+/// user (by a grantor).
+#[derive(Clone, Debug, Deserialize)]
+pub struct EmergencyAccess {
+        #[serde(alias = "Id", alias = "id")]
+        pub uuid: Uuid,
+        #[serde(alias = "GranteeId", alias = "granteeId")]
+        pub grantee_id: Option<Uuid>,
+        #[serde(alias = "GrantorId", alias = "grantorId")]
+        pub grantor_id: Option<Uuid>,
+        #[serde(alias = "Email", default, deserialize_with = "null_as_default")]
+        pub email: String,
+        #[serde(alias = "Name", default, deserialize_with = "null_as_default")]
+        pub name: String,
+        #[serde(alias = "Type", alias = "type")]
+        pub type_: usize,
+        #[serde(alias = "Status")]
+        pub status: usize,
+        #[serde(alias = "WaitTimeDays", alias = "waitTimeDays")]
+        pub wait_time_days: usize,
+}
+
+/// Vault of a grantor, as seen by a grantee with view access.
+#[derive(Debug, Deserialize)]
+pub struct EmergencyAccessView {
+        // Key of the grantor, encrypted with the grantee's public key
+        #[serde(alias = "KeyEncrypted", alias = "keyEncrypted")]
+        pub key_encrypted: String,
+        #[serde(alias = "Ciphers", default, deserialize_with = "null_as_default")]
+        pub ciphers: Vec<CipherEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EmergencyAccessTakeover {
+        #[serde(alias = "KeyEncrypted", alias = "keyEncrypted")]
+        pub key_encrypted: String,
+        #[serde(alias = "Kdf")]
+        pub kdf: usize,
+        #[serde(alias = "KdfIterations", alias = "kdfIterations")]
+        pub kdf_iterations: usize,
+}
+
+#[derive(Debug, Deserialize)]
+struct PublicKeyResponse {
+        #[serde(alias = "PublicKey", alias = "publicKey")]
+        public_key: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct EmergencyInviteRequest<'a> {
+        email: &'a str,
+        #[serde(rename = "type")]
+        type_: usize,
+        wait_time_days: usize,
+}
+
+#[derive(Serialize)]
+struct KeyRequest<'a> {
+        key: &'a str,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PasswordRequest<'a> {
+        new_master_password_hash: &'a str,
+        key: &'a CipherString,
+}
+
+/// Device which logged into the account at some point.
+#[derive(Clone, Debug, Deserialize)]
+pub struct Device {
+        #[serde(alias = "Id", alias = "id")]
+        pub uuid: Uuid,
+        #[serde(alias = "Name", default, deserialize_with = "null_as_default")]
+        pub name: String,
+        #[serde(alias = "Type", alias = "type")]
+        pub type_: usize,
+        #[serde(alias = "Identifier")]
+        pub identifier: String,
+        #[serde(alias = "CreationDate", alias = "creationDate")]
+        pub creation_date: DateTime<Utc>,
+        #[serde(alias = "LastActivityDate", alias = "lastActivityDate")]
+        pub last_activity_date: Option<DateTime<Utc>>,
+}
+
+/// Login attempt of a new device, waiting to be approved by a known one.
+#[derive(Clone, Debug, Deserialize)]
+pub struct AuthRequest {
+        #[serde(alias = "Id", alias = "id")]
+        pub uuid: Uuid,
+        #[serde(alias = "RequestDeviceType", alias = "requestDeviceType", default, deserialize_with = "null_as_default")]
+        pub device_type: String,
+        #[serde(alias = "RequestIpAddress", alias = "requestIpAddress", default, deserialize_with = "null_as_default")]
+        pub ip_address: String,
+        #[serde(alias = "CreationDate", alias = "creationDate")]
+        pub creation_date: DateTime<Utc>,
+        // Unset while pending
+        #[serde(alias = "RequestApproved", alias = "requestApproved")]
+        pub approved: Option<bool>,
+        // Of the requesting device, base64 encoded, for the fingerprint phrase
+        #[serde(alias = "PublicKey", alias = "publicKey", default, deserialize_with = "null_as_default")]
+        pub public_key: String,
+}
+
+impl AuthRequest {
+        /// Fingerprint phrase of the request, as the requesting device shows
+        /// it for the account of `email`.
+        pub fn fingerprint(&self, email: &str) -> Option<String> {
+                base64::decode(&self.public_key).ok()
+                        .filter(|public_key| !public_key.is_empty())
+                        .map(|public_key| cipher::fingerprint_phrase(email, &public_key))
+        }
+}
+
+pub const SEND_TEXT: usize = 0;
+pub const SEND_FILE: usize = 1;
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct SendText {
+        #[serde(alias = "Text")]
+        pub text: Option<CipherString>,
+        #[serde(alias = "Hidden", default, deserialize_with = "null_as_default")]
+        pub hidden: bool,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct SendFile {
+        #[serde(alias = "Id", alias = "id")]
+        pub id: Option<String>,
+        #[serde(alias = "FileName", alias = "fileName")]
+        pub file_name: CipherString,
+        #[serde(alias = "SizeName", alias = "sizeName", default, deserialize_with = "null_as_default")]
+        pub size_name: String,
+}
+
+/// Text or file shared through a public link.
+#[derive(Clone, Debug, Deserialize)]
+pub struct SendEntry {
+        #[serde(alias = "Id", alias = "id")]
+        pub uuid: Uuid,
+        #[serde(alias = "AccessId", alias = "accessId")]
+        pub access_id: String,
+        #[serde(alias = "Type", alias = "type")]
+        pub type_: usize,
+        #[serde(alias = "Name")]
+        pub name: CipherString,
+        #[serde(alias = "Notes")]
+        pub notes: Option<CipherString>,
+        // Key material of the send, encrypted with the user's key
+        #[serde(alias = "Key")]
+        pub key: CipherString,
+        #[serde(alias = "Text")]
+        pub text: Option<SendText>,
+        #[serde(alias = "File")]
+        pub file: Option<SendFile>,
+        #[serde(alias = "MaxAccessCount", alias = "maxAccessCount")]
+        pub max_access_count: Option<usize>,
+        #[serde(alias = "AccessCount", alias = "accessCount", default, deserialize_with = "null_as_default")]
+        pub access_count: usize,
+        // Only tells whether there is one, the hash is never sent back
+        #[serde(alias = "Password")]
+        pub password: Option<String>,
+        #[serde(alias = "Disabled", default, deserialize_with = "null_as_default")]
+        pub disabled: bool,
+        #[serde(alias = "HideEmail", alias = "hideEmail", default, deserialize_with = "null_as_default")]
+        pub hide_email: bool,
+        #[serde(alias = "ExpirationDate", alias = "expirationDate")]
+        pub expiration_date: Option<DateTime<Utc>>,
+        #[serde(alias = "DeletionDate", alias = "deletionDate")]
+        pub deletion_date: DateTime<Utc>,
+}
+
+impl SendEntry {
+        /// Public link of the send on the web vault at `web_vault`, the one of
+        /// the server it was made on, `material` being its decrypted key
+        /// material.
+        pub fn link(&self, web_vault: &str, material: &[u8]) -> String {
+                format!("{}/#/send/{}/{}", web_vault, self.access_id,
+                        base64::encode_config(material, base64::URL_SAFE_NO_PAD))
+        }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SendTextRequest<'a> {
+        text: Option<&'a CipherString>,
+        hidden: bool,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SendFileRequest<'a> {
+        id: Option<&'a str>,
+        file_name: &'a CipherString,
+        size_name: &'a str,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SendRequest<'a> {
+        #[serde(rename = "type")]
+        type_: usize,
+        key: &'a CipherString,
+        name: &'a CipherString,
+        notes: Option<&'a CipherString>,
+        text: Option<SendTextRequest<'a>>,
+        file: Option<SendFileRequest<'a>>,
+        max_access_count: Option<usize>,
+        expiration_date: Option<DateTime<Utc>>,
+        deletion_date: DateTime<Utc>,
+        // Leaving it out keeps the current password
+        password: Option<&'a str>,
+        disabled: bool,
+        hide_email: bool,
+}
+
+impl<'a> From<&'a SendEntry> for SendRequest<'a> {
+        fn from(send: &'a SendEntry) -> Self {
+                SendRequest {
+                        type_: send.type_,
+                        key: &send.key,
+                        name: &send.name,
+                        notes: send.notes.as_ref(),
+                        text: send.text.as_ref().map(|text| SendTextRequest {
+                                text: text.text.as_ref(),
+                                hidden: text.hidden,
+                        }),
+                        file: send.file.as_ref().map(|file| SendFileRequest {
+                                id: file.id.as_ref().map(String::as_str),
+                                file_name: &file.file_name,
+                                size_name: &file.size_name,
+                        }),
+                        max_access_count: send.max_access_count,
+                        expiration_date: send.expiration_date,
+                        deletion_date: send.deletion_date,
+                        password: None,
+                        disabled: send.disabled,
+                        hide_email: send.hide_email,
+                }
+        }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct AuthRequestResponse {
+        device_identifier: String,
+        request_approved: bool,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SecretVerificationRequest<'a> {
+        master_password_hash: &'a str,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ChangePasswordRequest<'a> {
+        master_password_hash: &'a str,
+        new_master_password_hash: &'a str,
+        master_password_hint: Option<&'a str>,
+        key: &'a CipherString,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct TwoFactorEmailRequest<'a> {
+        email: &'a str,
+        master_password_hash: &'a str,
+        device_identifier: &'a str,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct NewDeviceOtpRequest<'a> {
+        email: &'a str,
+        master_password_hash: &'a str,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PasswordHintRequest<'a> {
+        email: &'a str,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RegisterKeys<'a> {
+        public_key: &'a str,
+        encrypted_private_key: &'a CipherString,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RegisterRequest<'a> {
+        email: &'a str,
+        name: &'a str,
+        master_password_hash: &'a str,
+        master_password_hint: Option<&'a str>,
+        key: &'a CipherString,
+        kdf: usize,
+        kdf_iterations: usize,
+        keys: RegisterKeys<'a>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CollectionRequest<'a> {
+        name: &'a CipherString,
+        groups: &'a [Uuid],
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CipherCollectionsRequest<'a> {
+        collection_ids: &'a [Uuid],
+}
+
+#[derive(Debug, Deserialize)]
+struct ListResponse<T> {
+        #[serde(alias = "Data", default = "Vec::new")]
+        data: Vec<T>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Folder {
+        #[serde(alias = "Object", default, deserialize_with = "intern_object")]
+        object: Cow<'static, str>,
+        #[serde(alias = "Id", alias = "id")]
+        pub uuid: Uuid,
+        #[serde(alias = "Name")]
+        pub name: CipherString,
+        #[serde(alias = "RevisionDate", alias = "revisionDate")]
+        pub last_changed: DateTime<Utc>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Collection {
+        #[serde(alias = "Object", default, deserialize_with = "intern_object")]
+        object: Cow<'static, str>,
+        #[serde(alias = "Id", alias = "id")]
+        pub uuid: Uuid,
+        #[serde(alias = "OrganizationId", alias = "organizationId")]
+        pub organization_id: Uuid,
+        // Encrypted with the organization key
+        #[serde(alias = "Name")]
+        pub name: CipherString,
+}
+
+// Item types
+pub const CIPHER_LOGIN: usize = 1;
+pub const CIPHER_SECURE_NOTE: usize = 2;
+
+// Custom field types
+pub const FIELD_TEXT: usize = 0;
+pub const FIELD_HIDDEN: usize = 1;
+pub const FIELD_BOOLEAN: usize = 2;
+pub const FIELD_LINKED: usize = 3;
+
+// Login properties a linked custom field can refer to
+pub const LINKED_USERNAME: usize = 100;
+pub const LINKED_PASSWORD: usize = 101;
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct CipherEntryFields {
+        #[serde(alias = "Type", alias = "type")]
+        pub type_: usize,
+        #[serde(alias = "Name")]
+        pub name: Option<CipherString>,
+        #[serde(alias = "Value")]
+        pub value: Option<CipherString>,
+        // Only set for linked fields, which don't carry a value of their own
+        #[serde(alias = "LinkedId", alias = "linkedId")]
+        pub linked_id: Option<usize>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct CipherEntryHistory {
+        #[serde(alias = "Password")]
+        pub password: CipherString,
+        #[serde(alias = "LastUsedDate", alias = "lastUsedDate")]
+        pub last_used_date: DateTime<Utc>
+}
+
+// URI match types, how an item URI is compared to a page
+pub const URI_MATCH_DOMAIN: usize = 0;
+pub const URI_MATCH_HOST: usize = 1;
+pub const URI_MATCH_STARTS_WITH: usize = 2;
+pub const URI_MATCH_EXACT: usize = 3;
+pub const URI_MATCH_REGEX: usize = 4;
+pub const URI_MATCH_NEVER: usize = 5;
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct CipherEntryUriMatch {
+        #[serde(alias = "Uri")]
+        pub uri: Option<CipherString>,
+        #[serde(alias = "Match", alias = "match")]
+        pub match_: Option<usize>,
+}
+
+/// Passkey stored on a login item.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Fido2Credential {
+        #[serde(alias = "RpId", alias = "rpId")]
+        pub rp_id: CipherString,
+        #[serde(alias = "RpName", alias = "rpName")]
+        pub rp_name: Option<CipherString>,
+        #[serde(alias = "UserName", alias = "userName")]
+        pub user_name: Option<CipherString>,
+        #[serde(alias = "UserDisplayName", alias = "userDisplayName")]
+        pub user_display_name: Option<CipherString>,
+        #[serde(alias = "CreationDate", alias = "creationDate")]
+        pub creation_date: DateTime<Utc>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct CipherEntryData {
+        #[serde(alias = "Uri")]
+        pub uri: Option<CipherString>,
+        #[serde(alias = "Uris")]
+        pub uris: Option<Vec<CipherEntryUriMatch>>,
+        #[serde(alias = "Username")]
+        pub username: Option<CipherString>,
+        #[serde(alias = "Password")]
+        pub password: Option<CipherString>,
+        #[serde(alias = "PasswordRevisionDate", alias = "passwordRevisionDate")]
+        pub assword_last_changed: Option<DateTime<Utc>>,
+        #[serde(alias = "Totp")]
+        pub totp: Option<CipherString>,
+        #[serde(alias = "Name")]
+        pub name: CipherString,
+        #[serde(alias = "Notes")]
+        pub notes: Option<CipherString>,
+        #[serde(alias = "Fields")]
+        pub fields: Option<Vec<CipherEntryFields>>,
+        #[serde(alias = "PasswordHistory", alias = "passwordHistory")]
+        pub password_history: Option<Vec<CipherEntryHistory>>,
+        #[serde(alias = "Fido2Credentials", alias = "fido2Credentials")]
+        pub fido2_credentials: Option<Vec<Fido2Credential>>,
+}
+
+/// Kind of a secure note, only generic ones (0) exist so far.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct SecureNote {
+        #[serde(alias = "Type", alias = "type", default, deserialize_with = "null_as_default")]
+        pub type_: usize,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct CipherEntry {
+        #[serde(alias = "Object", default, deserialize_with = "intern_object")]
+        object: Cow<'static, str>,
+        #[serde(alias = "CollectionIds", alias = "collectionIds", default, deserialize_with = "null_as_default")]
+        pub collection_ids: Vec<Uuid>,
+        #[serde(alias = "FolderId", alias = "folderId")]
+        pub folder_id: Option<Uuid>,
+        #[serde(alias = "Favorite", default, deserialize_with = "null_as_default")]
+        pub favorite: bool,
+        #[serde(alias = "Edit", default = "default_true", deserialize_with = "null_as_true")]
+        pub edit: bool,
+        #[serde(alias = "Id", alias = "id")]
+        pub uuid: Uuid,
+        #[serde(alias = "OrganizationId", alias = "organizationId")]
+        pub organization_id: Option<Uuid>,
+        #[serde(alias = "Type", alias = "type")]
+        pub type_: usize,
+        #[serde(alias = "Data")]
+        pub data: CipherEntryData,
+        #[serde(alias = "Name")]
+        pub name: CipherString,
+        #[serde(alias = "Notes")]
+        pub notes: Option<CipherString>,
+        #[serde(alias = "Login", skip)]
+        pub login: Option<CipherEntryData>,
+        #[serde(alias = "Card")]
+        pub card: Option<String>,
+        #[serde(alias = "Identity")]
+        pub identity: Option<String>,
+        #[serde(alias = "SecureNote", alias = "secureNote")]
+        pub secure_note: Option<SecureNote>,
+        #[serde(alias = "Fields")]
+        pub fields: Option<Vec<CipherEntryFields>>,
+        #[serde(alias = "PasswordHistory", alias = "passwordHistory")]
+        pub password_history: Option<Vec<CipherEntryHistory>>,
+        // Attachment metadata isn't used (yet), so skip it while parsing
+        // instead of keeping it around.
+        #[serde(alias = "Attachments", skip)]
+        pub attachments: Option<String>,
+        #[serde(alias = "OrganizationUseTotp", alias = "organizationUseTotp", default, deserialize_with = "null_as_default")]
+        pub organization_tfa: bool,
+        #[serde(alias = "RevisionDate", alias = "revisionDate")]
+        pub last_changed: DateTime<Utc>,
+        // Set for items which are in the trash
+        #[serde(alias = "DeletedDate", alias = "deletedDate")]
+        pub deleted_date: Option<DateTime<Utc>>,
+        // Set for archived items, which are hidden from the default view
+        #[serde(alias = "ArchivedDate", alias = "archivedDate")]
+        pub archived_date: Option<DateTime<Utc>>,
+}
+
+impl CipherEntry {
+        /// Custom fields of the item, older servers only put them in its data.
+        pub fn custom_fields(&self) -> &[CipherEntryFields] {
+                self.fields.as_ref()
+                        .or_else(|| self.data.fields.as_ref())
+                        .map_or(&[], |fields| fields.as_slice())
+        }
+
+        /// Resolves the value a custom field refers to, for linked fields
+        /// this is the referenced login property.
+        pub fn field_value<'a>(&'a self, field: &'a CipherEntryFields) -> Option<&'a CipherString> {
+                if field.type_ != FIELD_LINKED {
+                        return field.value.as_ref();
+                }
+
+                match field.linked_id? {
+                        LINKED_USERNAME => self.data.username.as_ref(),
+                        LINKED_PASSWORD => self.data.password.as_ref(),
+                        _ => None,
+                }
+        }
+
+        /// Looks up a custom field by name, ignoring case.
+        pub fn find_field(&self, cipher: &CipherSuite, name: &str) -> Option<&CipherEntryFields> {
+                self.custom_fields()
+                        .iter()
+                        .find(|field| {
+                                field.name.as_ref()
+                                        .and_then(|field| field.decrypt(cipher))
+                                        .map_or(false, |field| field.eq_ignore_ascii_case(name))
+                        })
+        }
+
+        /// Copy of the item with all values encrypted with `to` instead of
+        /// `from`. Only logins and secure notes without passkeys are fully
+        /// known to bwtui, anything else would lose data.
+        pub fn reencrypt(&self, from: &CipherSuite, to: &CipherSuite) -> Result<CipherEntry, CipherError> {
+                let has_passkeys = self.data.fido2_credentials.as_ref()
+                        .map_or(false, |credentials| !credentials.is_empty());
+
+                if (self.type_ != CIPHER_LOGIN && self.type_ != CIPHER_SECURE_NOTE) || has_passkeys {
+                        return Err(CipherError::UnsupportedItem);
+                }
+
+                let value = |value: &CipherString| value.reencrypt(from, to);
+                let optional = |value: &Option<CipherString>| {
+                        value.as_ref()
+                                .map(|value| value.reencrypt(from, to))
+                                .transpose()
+                };
+
+                let fields = |fields: &Option<Vec<CipherEntryFields>>| {
+                        fields.as_ref()
+                                .map(|fields| fields.iter()
+                                        .map(|field| Ok(CipherEntryFields {
+                                                name: optional(&field.name)?,
+                                                value: optional(&field.value)?,
+                                                ..field.clone()
+                                        }))
+                                        .collect::<Result<Vec<_>, CipherError>>())
+                                .transpose()
+                };
+
+                let history = |history: &Option<Vec<CipherEntryHistory>>| {
+                        history.as_ref()
+                                .map(|history| history.iter()
+                                        .map(|entry| Ok(CipherEntryHistory {
+                                                password: value(&entry.password)?,
+                                                last_used_date: entry.last_used_date,
+                                        }))
+                                        .collect::<Result<Vec<_>, CipherError>>())
+                                .transpose()
+                };
+
+                let uris = self.data.uris.as_ref()
+                        .map(|uris| uris.iter()
+                                .map(|uri| Ok(CipherEntryUriMatch {
+                                        uri: optional(&uri.uri)?,
+                                        match_: uri.match_,
+                                }))
+                                .collect::<Result<Vec<_>, CipherError>>())
+                        .transpose()?;
+
+                let data = CipherEntryData {
+                        uri: optional(&self.data.uri)?,
+                        uris,
+                        username: optional(&self.data.username)?,
+                        password: optional(&self.data.password)?,
+                        totp: optional(&self.data.totp)?,
+                        name: value(&self.data.name)?,
+                        notes: optional(&self.data.notes)?,
+                        fields: fields(&self.data.fields)?,
+                        password_history: history(&self.data.password_history)?,
+                        ..self.data.clone()
+                };
+
+                Ok(CipherEntry {
+                        name: value(&self.name)?,
+                        notes: optional(&self.notes)?,
+                        fields: fields(&self.fields)?,
+                        password_history: history(&self.password_history)?,
+                        data,
+                        ..self.clone()
+                })
+        }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct UriRequest<'a> {
+        uri: &'a CipherString,
+        #[serde(rename = "match")]
+        match_: Option<usize>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct LoginRequest<'a> {
+        uris: Vec<UriRequest<'a>>,
+        username: Option<&'a CipherString>,
+        password: Option<&'a CipherString>,
+        totp: Option<&'a CipherString>,
+        password_revision_date: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SecureNoteRequest {
+        #[serde(rename = "type")]
+        type_: usize,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct FieldRequest<'a> {
+        #[serde(rename = "type")]
+        type_: usize,
+        name: Option<&'a CipherString>,
+        value: Option<&'a CipherString>,
+        linked_id: Option<usize>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PasswordHistoryRequest<'a> {
+        password: &'a CipherString,
+        last_used_date: DateTime<Utc>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CipherRequest<'a> {
+        #[serde(rename = "type")]
+        type_: usize,
+        organization_id: Option<Uuid>,
+        folder_id: Option<Uuid>,
+        favorite: bool,
+        name: &'a CipherString,
+        notes: Option<&'a CipherString>,
+        login: Option<LoginRequest<'a>>,
+        secure_note: Option<SecureNoteRequest>,
+        fields: Vec<FieldRequest<'a>>,
+        password_history: Vec<PasswordHistoryRequest<'a>>,
+        // Unset for new items
+        last_known_revision_date: Option<DateTime<Utc>>,
+}
+
+impl<'a> From<&'a CipherEntry> for CipherRequest<'a> {
+        fn from(entry: &'a CipherEntry) -> Self {
+                let data = &entry.data;
+
+                let login = if entry.type_ == CIPHER_LOGIN {
+                        // Items of old servers only have the single legacy URI
+                        let legacy = data.uri.iter()
+                                .filter(|_| data.uris.is_none())
+                                .map(|uri| UriRequest { uri, match_: None });
+
+                        let uris = data.uris.iter()
+                                .flatten()
+                                .filter_map(|uri| {
+                                        uri.uri.as_ref().map(|value| UriRequest { uri: value, match_: uri.match_ })
+                                })
+                                .chain(legacy)
+                                .collect();
+
+                        Some(LoginRequest {
+                                uris,
+                                username: data.username.as_ref(),
+                                password: data.password.as_ref(),
+                                totp: data.totp.as_ref(),
+                                password_revision_date: data.assword_last_changed,
+                        })
+                } else {
+                        None
+                };
+
+                let secure_note = if entry.type_ == CIPHER_SECURE_NOTE {
+                        // Generic, the only type there is
+                        Some(SecureNoteRequest { type_: 0 })
+                } else {
+                        None
+                };
+
+                let history = entry.password_history.as_ref()
+                        .or_else(|| data.password_history.as_ref());
+
+                CipherRequest {
+                        type_: entry.type_,
+                        organization_id: entry.organization_id,
+                        folder_id: entry.folder_id,
+                        favorite: entry.favorite,
+                        name: &entry.name,
+                        notes: entry.notes.as_ref().or_else(|| data.notes.as_ref()),
+                        login,
+                        secure_note,
+                        fields: entry.custom_fields()
+                                .iter()
+                                .map(|field| FieldRequest {
+                                        type_: field.type_,
+                                        name: field.name.as_ref(),
+                                        value: field.value.as_ref(),
+                                        linked_id: field.linked_id,
+                                })
+                                .collect(),
+                        password_history: history.iter()
+                                .flat_map(|history| history.iter())
+                                .map(|entry| PasswordHistoryRequest {
+                                        password: &entry.password,
+                                        last_used_date: entry.last_used_date,
+                                })
+                                .collect(),
+                        last_known_revision_date: Some(entry.last_changed),
+                }
+        }
+}
+
+/// Outcome of updating an item.
+#[derive(Debug)]
+pub enum Update {
+        Saved,
+        // Changed elsewhere, with the server's version
+        Conflict(CipherEntry),
+}
+
+/// Login or secure note to create, with all values already encrypted.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct NewCipher {
+        pub type_: usize,
+        pub folder_id: Option<Uuid>,
+        pub favorite: bool,
+        pub name: CipherString,
+        pub notes: Option<CipherString>,
+        pub uris: Vec<CipherString>,
+        pub username: Option<CipherString>,
+        pub password: Option<CipherString>,
+        pub totp: Option<CipherString>,
+        // Type, name and value of custom fields
+        pub fields: Vec<(usize, CipherString, CipherString)>,
+}
+
+impl<'a> From<&'a NewCipher> for CipherRequest<'a> {
+        fn from(cipher: &'a NewCipher) -> Self {
+                let login = if cipher.type_ == CIPHER_LOGIN {
+                        Some(LoginRequest {
+                                uris: cipher.uris.iter()
+                                        .map(|uri| UriRequest { uri, match_: None })
+                                        .collect(),
+                                username: cipher.username.as_ref(),
+                                password: cipher.password.as_ref(),
+                                totp: cipher.totp.as_ref(),
+                                password_revision_date: None,
+                        })
+                } else {
+                        None
+                };
+
+                let secure_note = if cipher.type_ == CIPHER_SECURE_NOTE {
+                        Some(SecureNoteRequest { type_: 0 })
+                } else {
+                        None
+                };
+
+                CipherRequest {
+                        type_: cipher.type_,
+                        organization_id: None,
+                        folder_id: cipher.folder_id,
+                        favorite: cipher.favorite,
+                        name: &cipher.name,
+                        notes: cipher.notes.as_ref(),
+                        login,
+                        secure_note,
+                        fields: cipher.fields.iter()
+                                .map(|(type_, name, value)| FieldRequest {
+                                        type_: *type_,
+                                        name: Some(name),
+                                        value: Some(value),
+                                        linked_id: None,
+                                })
+                                .collect(),
+                        password_history: Vec::new(),
+                        last_known_revision_date: None,
+                }
+        }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct FolderRequest<'a> {
+        name: &'a CipherString,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CipherShareRequest<'a> {
+        cipher: CipherRequest<'a>,
+        collection_ids: &'a [Uuid],
+}
+
+/// Group of domains the official clients treat as the same site.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct GlobalDomains {
+        #[serde(alias = "Type", alias = "type")]
+        pub type_: usize,
+        #[serde(alias = "Domains", default, deserialize_with = "null_as_default")]
+        pub domains: Vec<String>,
+        #[serde(alias = "Excluded", default, deserialize_with = "null_as_default")]
+        pub excluded: bool,
+}
+
+/// Equivalent domains, i.e. domains whose login items apply to each other.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct Domains {
+        #[serde(alias = "EquivalentDomains", alias = "equivalentDomains", default, deserialize_with = "null_as_default")]
+        pub equivalent_domains: Vec<Vec<String>>,
+        #[serde(alias = "GlobalEquivalentDomains", alias = "globalEquivalentDomains", default, deserialize_with = "null_as_default")]
+        pub global_equivalent_domains: Vec<GlobalDomains>,
+}
+
+impl Domains {
+        /// All other domains considered equivalent to `domain`.
+        pub fn equivalent_to(&self, domain: &str) -> Vec<&str> {
+                let global = self.global_equivalent_domains.iter()
+                        .filter(|group| !group.excluded)
+                        .map(|group| &group.domains);
+
+                let mut domains = self.equivalent_domains.iter()
+                        .chain(global)
+                        .filter(|group| group.iter().any(|d| d.eq_ignore_ascii_case(domain)))
+                        .flatten()
+                        .map(|d| d.as_str())
+                        .filter(|d| !d.eq_ignore_ascii_case(domain))
+                        .collect::<Vec<&str>>();
+
+                domains.sort();
+                domains.dedup();
+                domains
+        }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct DomainsRequest<'a> {
+        equivalent_domains: &'a [Vec<String>],
+        excluded_global_equivalent_domains: Vec<usize>,
+}
+
+/// Everything a sync returns: profile, folders, collections and items, all
+/// values still encrypted.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct VaultData {
+        // Set locally after each successful sync, used to tell which of two
+        // caches written by different bwtui instances is more recent.
+        // Must stay the first field, so that `SyncStamp` can be read from the
+        // binary cache format too.
+        #[serde(default)]
+        pub synced_at: Option<DateTime<Utc>>,
+
+        #[serde(alias = "Object", default, deserialize_with = "intern_object")]
+        object: Cow<'static, str>,
+        #[serde(alias = "Profile")]
+        pub profile: Profile,
+        #[serde(alias = "Folders", default, deserialize_with = "null_as_default")]
+        pub folders: Vec<Folder>,
+        #[serde(alias = "Collections", default, deserialize_with = "null_as_default")]
+        pub collections: Vec<Collection>,
+        #[serde(alias = "Ciphers", default, deserialize_with = "null_as_default")]
+        pub ciphers: Vec<CipherEntry>,
+        #[serde(alias = "Domains")]
+        pub domains: Option<Domains>,
+        #[serde(alias = "Policies", default, deserialize_with = "null_as_default")]
+        pub policies: Vec<Policy>,
+
+        // How far the local clock is behind the server, taken from the last
+        // sync response. Used to correct TOTP codes.
+        #[serde(skip)]
+        pub clock_skew: Option<chrono::Duration>,
+        // Cache validators of the sync response, cached on their own so that
+        // the vault cache format doesn't change.
+        #[serde(skip)]
+        validators: Option<SyncValidators>,
+}
+
+impl VaultData {
+        /// Enabled policies of type `type_` which apply to the user. Like with
+        /// the official clients, owners and admins are exempt from the
+        /// policies of their organization.
+        pub fn applying_policies(&self, type_: usize) -> impl Iterator<Item = &Policy> {
+                let organizations = &self.profile.organizations;
+
+                self.policies.iter()
+                        .filter(move |policy| policy.enabled && policy.type_ == type_)
+                        .filter(move |policy| {
+                                organizations.iter()
+                                        .find(|org| org.uuid == policy.organization_id)
+                                        .map_or(false, |org| org.enabled && !org.is_admin())
+                        })
+        }
+}
+
+/// `ETag` and `Last-Modified` of the last sync, for asking the server whether
+/// anything changed since.
+#[derive(Debug, Deserialize, Serialize)]
+struct SyncValidators {
+        // Of the vault these belong to, they are only sent for that one
+        synced_at: Option<DateTime<Utc>>,
+        etag: Option<String>,
+        last_modified: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SyncStamp {
+        #[serde(default)]
+        synced_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug)]
+pub struct AppData {
+        pub auth: AuthData,
+        pub vault: VaultData,
+}
+
+/// Region of the official bitwarden servers, accounts only exist in one.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub enum Region {
+        Us,
+        Eu,
+}
+
+impl Default for Region {
+        fn default() -> Self {
+                Region::Us
+        }
+}
+
+impl Region {
+        pub fn domain(self) -> &'static str {
+                match self {
+                        Region::Us => "bitwarden.com",
+                        Region::Eu => "bitwarden.eu",
+                }
+        }
+
+        pub fn urls(self) -> ServerUrls {
+                ServerUrls {
+                        identity: format!("https://identity.{}", self.domain()),
+                        api: format!("https://api.{}", self.domain()),
+                        web_vault: format!("https://vault.{}", self.domain()),
+                }
+        }
+}
+
+/// Where the servers of an installation are.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct ServerUrls {
+        pub identity: String,
+        pub api: String,
+        pub web_vault: String,
+}
+
+impl ServerUrls {
+        /// URLs of a self-hosted installation (Vaultwarden or Bitwarden) at
+        /// `base`, which serves all of them under the same host. Plain HTTP
+        /// would send the master password hash and tokens unencrypted, so it
+        /// is only allowed for servers on this machine.
+        pub fn self_hosted(base: &str) -> Result<Self, String> {
+                let base = base.trim().trim_end_matches('/');
+                let url = reqwest::Url::parse(base)
+                        .map_err(|e| format!("invalid server URL {}: {}", base, e))?;
+
+                match url.scheme() {
+                        "https" => (),
+                        "http" if url.host_str().map_or(false, is_loopback) => (),
+                        _ => return Err("the server URL has to start with https://, http:// is only \
+                                         allowed for localhost".to_owned()),
+                }
+
+                Ok(ServerUrls {
+                        identity: format!("{}/identity", base),
+                        api: format!("{}/api", base),
+                        web_vault: base.to_owned(),
+                })
+        }
+}
+
+/// Whether `host` is this machine, by name or address.
+fn is_loopback(host: &str) -> bool {
+        host == "localhost"
+                || host.trim_start_matches('[').trim_end_matches(']')
+                        .parse::<IpAddr>()
+                        .map_or(false, |ip| ip.is_loopback())
+}
+
+/// Preferences picked in the setup on first launch, when to lock can be
+/// changed later.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct Settings {
+        pub region: Region,
+        // Self-hosted installation, used instead of the region
+        #[serde(default)]
+        pub server: Option<ServerUrls>,
+        // Minutes without input after which the vault is locked
+        pub lock_timeout: Option<u64>,
+        // Also locked when resumed after a suspend or system sleep
+        #[serde(default)]
+        pub lock_on_suspend: bool,
+        // Seconds after which copied values are cleared from the clipboard
+        pub clear_clipboard: Option<u64>,
+}
+
+impl Settings {
+        /// Servers the account is on.
+        pub fn urls(&self) -> ServerUrls {
+                self.server.clone().unwrap_or_else(|| self.region.urls())
+        }
+}
+
+/// Where the vault view was left, to reopen it the same way.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct ViewState {
+        // Name of the sort column and whether it is sorted ascending
+        pub sort: Option<(String, bool)>,
+        pub favorites: bool,
+        pub folders: Vec<Uuid>,
+        pub archived: bool,
+        pub search: String,
+        pub selected: Option<Uuid>,
+}
+
+
+/// Random base64url string of `bytes` random bytes.
+fn random_token(bytes: usize) -> String {
+        let mut token = vec![0u8; bytes];
+        OsRng.fill_bytes(&mut token);
+
+        base64::encode_config(&token, base64::URL_SAFE_NO_PAD)
+}
+
+
+/// PKCE code challenge of the S256 method.
+fn pkce_challenge(code_verifier: &str) -> String {
+        base64::encode_config(&Sha256::digest(code_verifier.as_bytes()), base64::URL_SAFE_NO_PAD)
+}
+
+
+/// Extracts the most descriptive message from an error response, falling back
+/// to the HTTP status.
+fn error_message(response: &mut reqwest::Response) -> String {
+        let status = response.status();
+        let body: ErrorResponse = response.json().unwrap_or_default();
+
+        body.message(status)
+}
+
+
+/// Handle for talking to the bitwarden servers. Connections (and TLS sessions)
+/// are kept alive and reused across all requests made through it.
+pub struct Api {
+        client: reqwest::Client,
+        // Replaced when another server is picked
+        server: RwLock<ServerUrls>,
+        // Sent instead of solving a captcha, see `set_captcha_response`
+        captcha_response: RwLock<Option<String>>,
+        // Emailed code verifying a new device, see `set_new_device_otp`
+        new_device_otp: RwLock<Option<String>>,
+}
+
+impl Api {
+        /// Client for the servers picked in the settings, the official ones of
+        /// a region or a self-hosted installation.
+        pub fn new() -> Self {
+                let urls = read_settings().map(|settings| settings.urls())
+                        .unwrap_or_else(|_| Region::default().urls());
+
+                Self::with_server(urls)
+        }
+
+        pub fn with_server(urls: ServerUrls) -> Self {
+                // gzip responses are decompressed transparently, syncs also
+                // accept brotli which `read_sync` decompresses
+                let client = reqwest::Client::builder()
+                        .gzip(true)
+                        .build()
+                        .expect("failed to initialize the TLS backend");
+
+                Self {
+                        client,
+                        server: RwLock::new(urls),
+                        captcha_response: RwLock::new(None),
+                        new_device_otp: RwLock::new(None),
+                }
+        }
+
+        /// Talks to the servers at `urls` from now on.
+        pub fn set_server(&self, urls: ServerUrls) {
+                *self.server.write().unwrap() = urls;
+        }
+
+        /// Servers talked to at the moment.
+        pub fn server(&self) -> ServerUrls {
+                self.server.read().unwrap().clone()
+        }
+
+        /// Has the next logins pass the captcha the server asks for with
+        /// `client_secret`, the one of the personal API key of the account,
+        /// as bwtui can't show captchas.
+        pub fn set_captcha_response(&self, client_secret: Option<String>) {
+                *self.captcha_response.write().unwrap() = client_secret;
+        }
+
+        /// Has the next login verify this device with the `code` the server
+        /// emailed, see `ApiError::DeviceVerificationRequired`.
+        pub fn set_new_device_otp(&self, code: Option<String>) {
+                *self.new_device_otp.write().unwrap() = code;
+        }
+
+        fn api_url(&self) -> String {
+                self.server.read().unwrap().api.clone()
+        }
+
+        fn token_url(&self) -> String {
+                format!("{}/connect/token", self.server.read().unwrap().identity)
+        }
+
+        /// Base URL of the web vault, which is the origin WebAuthn assertions
+        /// are made for.
+        pub fn web_vault_url(&self) -> String {
+                self.server.read().unwrap().web_vault.clone()
+        }
+
+        fn perform_prelogin(&self, email: &str) -> Result<PreloginResponseData, ApiError> {
+                let url = format!("{}/accounts/prelogin", self.api_url());
+                debug!("prelogin at {}", url);
+
+                let mut data = HashMap::new();
+                data.insert("email", email);
+
+                let mut response = self.client.post(&url)
+                        .json(&data)
+                        .send()
+                        .map_err(|e| ApiError::PreloginFailed { error: e.to_string() })?;
+
+                if response.status().is_success() {
+                        let data: PreloginResponseData = response
+                                .json()
+                                .map_err(|e| ApiError::PreloginFailed { error: e.to_string() })?;
+
+                        Ok(data)
+                } else {
+                        warn!("prelogin failed with status {}", response.status());
+                        Err(ApiError::PreloginFailed { error: error_message(&mut response) })
+                }
+        }
+
+        fn perform_token_auth(&self, email: &str, cipher: &CipherSuite, two_factor: Option<TwoFactor>)
+                -> Result<LoginResponseData, ApiError>
+        {
+                let provider = two_factor.as_ref().map(|two_factor| two_factor.provider.to_string());
+
+                let mut data = HashMap::new();
+                data.insert("grant_type", "password");
+                data.insert("username", email);
+                data.insert("client_id", "connector");
+                data.insert("password", &cipher.master_key_hash);
+
+                if let (Some(provider), Some(two_factor)) = (&provider, &two_factor) {
+                        data.insert("twoFactorProvider", provider);
+                        data.insert("twoFactorToken", two_factor.token);
+                        data.insert("twoFactorRemember", if two_factor.remember { "1" } else { "0" });
+                }
+
+                self.request_token(data)
+        }
+
+        /// Sends a token request with `data` of the grant, along with what
+        /// identifies bwtui as a device.
+        fn request_token(&self, grant: HashMap<&str, &str>) -> Result<LoginResponseData, ApiError> {
+                let device_id = device_identifier().to_hyphenated().to_string();
+                let captcha_response = self.captcha_response.read().unwrap().clone();
+                let new_device_otp = self.new_device_otp.read().unwrap().clone();
+
+                let mut data: HashMap<&str, &str> = grant;
+                if let Some(captcha_response) = &captcha_response {
+                        data.insert("captchaResponse", captcha_response);
+                }
+                if let Some(new_device_otp) = &new_device_otp {
+                        data.insert("newDeviceOtp", new_device_otp);
+                }
+
+                data.insert("scope", "api offline_access");
+                data.insert("deviceType", "3");
+                data.insert("deviceIdentifier", &device_id);
+                data.insert("deviceName", "bwtui");
+
+                let url = self.token_url();
+                debug!("requesting an access token at {}", url);
+
+                let mut response = self.client.post(&url)
+                        .form(&data)
+                        .send()
+                        .map_err(|e| ApiError::Offline { endpoint: url.clone(), error: e.to_string() })?;
+
+                if response.status().is_success() {
+                        let data: LoginResponseData = response
+                                .json()
+                                .map_err(|e| ApiError::LoginFailed { error: e.to_string() })?;
+
+                        // Only good for one login
+                        self.set_captcha_response(None);
+                        self.set_new_device_otp(None);
+
+                        Ok(data)
+                } else {
+                        let status = response.status();
+                        warn!("access token request failed with status {}", status);
+
+                        let body: ErrorResponse = response.json().unwrap_or_default();
+                        let providers = body.two_factor_providers();
+
+                        if body.hcaptcha_site_key.is_some() {
+                                Err(ApiError::CaptchaRequired)
+                        } else if body.device_verification_required() {
+                                Err(ApiError::DeviceVerificationRequired)
+                        } else if providers.is_empty() {
+                                let invalid_grant = body.error.as_ref().map(String::as_str) == Some(INVALID_GRANT);
+                                let error = body.message(status);
+
+                                if invalid_grant {
+                                        Err(ApiError::InvalidGrant { error })
+                                } else {
+                                        Err(ApiError::LoginFailed { error })
+                                }
+                        } else {
+                                if body.captcha_bypass_token.is_some() {
+                                        self.set_captcha_response(body.captcha_bypass_token.clone());
+                                }
+
+                                let webauthn = body.webauthn_challenge();
+                                let duo = body.duo_auth_url();
+                                Err(ApiError::TwoFactorRequired { providers, webauthn, duo })
+                        }
+                }
+        }
+
+        /// Starts a login through the identity provider of the organization
+        /// with the SSO `identifier`, the browser being sent back to
+        /// `redirect_uri` with the code for `finish_sso`.
+        pub fn start_sso(&self, identifier: &str, redirect_uri: &str) -> Result<SsoLogin, ApiError> {
+                let identity = self.server.read().unwrap().identity.clone();
+
+                let url = reqwest::Url::parse_with_params(&format!("{}/sso/prevalidate", identity),
+                                                          &[("domainHint", identifier)])
+                        .map_err(|e| ApiError::LoginFailed { error: e.to_string() })?;
+                debug!("prevalidating SSO at {}", url);
+
+                let mut response = self.client.get(url.clone())
+                        .send()
+                        .map_err(|e| ApiError::Offline { endpoint: url.to_string(), error: e.to_string() })?;
+
+                if !response.status().is_success() {
+                        return Err(ApiError::LoginFailed { error: error_message(&mut response) });
+                }
+
+                let SsoPrevalidateResponse { token } = response.json()
+                        .map_err(|e| ApiError::LoginFailed { error: e.to_string() })?;
+
+                let code_verifier = random_token(48);
+                let state = random_token(32);
+
+                let url = reqwest::Url::parse_with_params(&format!("{}/connect/authorize", identity), &[
+                        ("client_id", "cli"),
+                        ("redirect_uri", redirect_uri),
+                        ("response_type", "code"),
+                        ("scope", "api offline_access"),
+                        ("state", &state),
+                        ("code_challenge", &pkce_challenge(&code_verifier)),
+                        ("code_challenge_method", "S256"),
+                        ("response_mode", "query"),
+                        ("domain_hint", identifier),
+                        ("ssoToken", &token),
+                ]).map_err(|e| ApiError::LoginFailed { error: e.to_string() })?;
+
+                Ok(SsoLogin {
+                        url: url.into_string(),
+                        redirect_uri: redirect_uri.to_owned(),
+                        state,
+                        code_verifier,
+                })
+        }
+
+        /// Exchanges the `code` the identity provider redirected back with for
+        /// an access token. The master password is still needed to decrypt
+        /// the vault.
+        pub fn finish_sso(&self, email: &str, password: &str, login: &SsoLogin, code: &str)
+                -> Result<AuthData, ApiError>
+        {
+                let prelogin = self.perform_prelogin(email)?;
+                let cipher = CipherSuite::with_kdf(email, password, &prelogin.kdf())
+                        .map_err(|e| ApiError::LoginFailed { error: e.to_string() })?;
+
+                let mut data = HashMap::new();
+                data.insert("grant_type", "authorization_code");
+                data.insert("client_id", "cli");
+                data.insert("code", code);
+                data.insert("code_verifier", &login.code_verifier);
+                data.insert("redirect_uri", &login.redirect_uri);
+
+                let LoginResponseData { access_token, expires_in, token_type, refresh_token, .. } =
+                        self.request_token(data)?;
+
+                info!("logged in with SSO, access token valid for {}s", expires_in);
+
+                Ok(AuthData {
+                        access_token,
+                        expires_in,
+                        token_type,
+                        issued_at: Some(Utc::now()),
+                        refresh_token,
+                        client_id: "cli".to_owned(),
+                        server: Some(self.server()),
+                        tokens_in_keyring: false,
+                        kdf: prelogin.kdf,
+                        kdf_iterations: prelogin.kdf_iterations,
+                        kdf_memory: prelogin.kdf_memory,
+                        kdf_parallelism: prelogin.kdf_parallelism,
+                        cipher,
+                        two_factor_token: None,
+                })
+        }
+
+        /// Asks the other devices of the account to approve logging in on this
+        /// one, see `check_device_login`.
+        pub fn start_device_login(&self, email: &str) -> Result<DeviceLogin, ApiError> {
+                let keys = AuthRequestKeys::generate()
+                        .map_err(|e| ApiError::LoginFailed { error: e.to_string() })?;
+                let public_key = keys.public_key();
+                let access_code = random_token(18);
+                let device_id = device_identifier().to_hyphenated().to_string();
+
+                let url = format!("{}/auth-requests", self.api_url());
+                debug!("requesting a login approval at {}", url);
+
+                let body = DeviceLoginRequest {
+                        email,
+                        public_key: &public_key,
+                        device_identifier: &device_id,
+                        access_code: &access_code,
+                        type_: 0,
+                };
+
+                let mut response = self.client.post(&url)
+                        .header("Device-Type", "3")
+                        .json(&body)
+                        .send()
+                        .map_err(|e| ApiError::Offline { endpoint: url.clone(), error: e.to_string() })?;
+
+                if !response.status().is_success() {
+                        return Err(ApiError::LoginFailed { error: error_message(&mut response) });
+                }
+
+                let DeviceLoginResponse { id, .. } = response.json()
+                        .map_err(|e| ApiError::LoginFailed { error: e.to_string() })?;
+
+                Ok(DeviceLogin {
+                        id,
+                        email: email.to_owned(),
+                        access_code,
+                        keys,
+                })
+        }
+
+        /// Logs in once `request` was approved on another device, `None` while
+        /// it wasn't answered yet. The returned cipher suite may already hold
+        /// the vault key, see `CipherSuite::is_unlocked`.
+        pub fn check_device_login(&self, request: &DeviceLogin) -> Result<Option<AuthData>, ApiError> {
+                let url = reqwest::Url::parse_with_params(
+                        &format!("{}/auth-requests/{}/response", self.api_url(), request.id),
+                        &[("code", &request.access_code)],
+                ).map_err(|e| ApiError::LoginFailed { error: e.to_string() })?;
+
+                let mut response = self.client.get(url.clone())
+                        .header("Device-Type", "3")
+                        .send()
+                        .map_err(|e| ApiError::Offline { endpoint: url.to_string(), error: e.to_string() })?;
+
+                if !response.status().is_success() {
+                        return Err(ApiError::LoginFailed { error: error_message(&mut response) });
+                }
+
+                let answer: DeviceLoginResponse = response.json()
+                        .map_err(|e| ApiError::LoginFailed { error: e.to_string() })?;
+
+                match answer.request_approved {
+                        None => return Ok(None),
+                        Some(false) => {
+                                return Err(ApiError::LoginFailed { error: "denied on the other device".to_owned() });
+                        }
+                        Some(true) => (),
+                }
+
+                let key = answer.key
+                        .ok_or_else(|| ApiError::LoginFailed { error: "approved without the keys".to_owned() })?;
+                let key = request.keys.decrypt(&key)
+                        .map_err(|e| ApiError::LoginFailed { error: e.to_string() })?;
+
+                let cipher = match answer.master_password_hash {
+                        Some(hash) => {
+                                let hash = request.keys.decrypt(&hash).ok()
+                                        .and_then(|hash| String::from_utf8(hash).ok())
+                                        .ok_or_else(|| ApiError::LoginFailed {
+                                                error: "failed to decrypt the master password hash".to_owned(),
+                                        })?;
+
+                                CipherSuite::from_master_key(&key, hash)
+                        }
+                        None => CipherSuite::from_key(&key),
+                }.map_err(|e| ApiError::LoginFailed { error: e.to_string() })?;
+
+                // Needed to unlock the cache with the master password later on
+                let prelogin = self.perform_prelogin(&request.email)?;
+
+                let mut data = HashMap::new();
+                data.insert("grant_type", "password");
+                data.insert("username", request.email.as_str());
+                data.insert("client_id", "connector");
+                data.insert("password", request.access_code.as_str());
+                data.insert("authRequest", request.id.as_str());
+
+                let LoginResponseData { access_token, expires_in, token_type, refresh_token, .. } =
+                        self.request_token(data)?;
+
+                info!("logged in with a device, access token valid for {}s", expires_in);
+
+                Ok(Some(AuthData {
+                        access_token,
+                        expires_in,
+                        token_type,
+                        issued_at: Some(Utc::now()),
+                        refresh_token,
+                        client_id: default_client_id(),
+                        server: Some(self.server()),
+                        tokens_in_keyring: false,
+                        kdf: prelogin.kdf,
+                        kdf_iterations: prelogin.kdf_iterations,
+                        kdf_memory: prelogin.kdf_memory,
+                        kdf_parallelism: prelogin.kdf_parallelism,
+                        cipher,
+                        two_factor_token: None,
+                }))
+        }
+
+        /// Logs in with the master password. The returned cipher suite only
+        /// holds the master key, see `CipherSuite::set_decrypt_key`. Fails with
+        /// `ApiError::TwoFactorRequired` if the account has a second factor
+        /// that wasn't remembered, see `authenticate_two_factor`.
+        pub fn authenticate(&self, email: &str, password: &str) -> Result<AuthData, ApiError> {
+                self.login(email, password, None)
+        }
+
+        /// Logs in with the master password and the `code` of the second
+        /// factor of type `provider`. With `remember`, the next logins from
+        /// this device won't need it until the server forgets about it.
+        pub fn authenticate_two_factor(&self, email: &str, password: &str, provider: usize,
+                                       code: &str, remember: bool) -> Result<AuthData, ApiError>
+        {
+                self.login(email, password, Some(TwoFactor { provider, token: code, remember }))
+        }
+
+        /// Has the server email a login code, for accounts with the email
+        /// second factor. Sending it again invalidates the previous code.
+        pub fn send_two_factor_email(&self, email: &str, password: &str) -> Result<(), ApiError> {
+                let prelogin = self.perform_prelogin(email)?;
+                let cipher = CipherSuite::with_kdf(email, password, &prelogin.kdf())
+                        .map_err(|e| ApiError::LoginFailed { error: e.to_string() })?;
+                let device_id = device_identifier().to_hyphenated().to_string();
+
+                let url = format!("{}/two-factor/send-email-login", self.api_url());
+                debug!("requesting a login code at {}", url);
+
+                let body = TwoFactorEmailRequest {
+                        email,
+                        master_password_hash: &cipher.master_key_hash,
+                        device_identifier: &device_id,
+                };
+
+                let mut response = self.client.post(&url)
+                        .json(&body)
+                        .send()
+                        .map_err(|e| ApiError::Offline { endpoint: url.clone(), error: e.to_string() })?;
+
+                if response.status().is_success() {
+                        Ok(())
+                } else {
+                        Err(ApiError::RequestFailed { endpoint: url, error: error_message(&mut response) })
+                }
+        }
+
+        /// Has the server email a new code verifying this device, the previous
+        /// one stops working.
+        pub fn resend_new_device_otp(&self, email: &str, password: &str) -> Result<(), ApiError> {
+                let prelogin = self.perform_prelogin(email)?;
+                let cipher = CipherSuite::with_kdf(email, password, &prelogin.kdf())
+                        .map_err(|e| ApiError::LoginFailed { error: e.to_string() })?;
+
+                let url = format!("{}/accounts/resend-new-device-otp", self.api_url());
+                debug!("requesting a device verification code at {}", url);
+
+                let body = NewDeviceOtpRequest {
+                        email,
+                        master_password_hash: &cipher.master_key_hash,
+                };
+
+                let mut response = self.client.post(&url)
+                        .json(&body)
+                        .send()
+                        .map_err(|e| ApiError::Offline { endpoint: url.clone(), error: e.to_string() })?;
+
+                if response.status().is_success() {
+                        Ok(())
+                } else {
+                        Err(ApiError::RequestFailed { endpoint: url, error: error_message(&mut response) })
+                }
+        }
+
+        /// Has the server email the master password hint of `email`. It
+        /// answers the same whether or not the account exists or has a hint.
+        pub fn request_password_hint(&self, email: &str) -> Result<(), ApiError> {
+                let url = format!("{}/accounts/password-hint", self.api_url());
+                debug!("requesting the master password hint at {}", url);
+
+                let mut response = self.client.post(&url)
+                        .json(&PasswordHintRequest { email })
+                        .send()
+                        .map_err(|e| ApiError::Offline { endpoint: url.clone(), error: e.to_string() })?;
+
+                if response.status().is_success() {
+                        Ok(())
+                } else {
+                        Err(ApiError::RequestFailed { endpoint: url, error: error_message(&mut response) })
+                }
+        }
+
+        fn login(&self, email: &str, password: &str, two_factor: Option<TwoFactor>)
+                -> Result<AuthData, ApiError>
+        {
+                let prelogin = self.perform_prelogin(email)?;
+
+                debug!("deriving the master key (kdf {}, {} iterations)", prelogin.kdf, prelogin.kdf_iterations);
+                let cipher = CipherSuite::with_kdf(email, password, &prelogin.kdf())
+                        .map_err(|e| ApiError::LoginFailed { error: e.to_string() })?;
+
+                let remembered = match two_factor {
+                        Some(_) => None,
+                        None => read_two_factor_token(email, &cipher),
+                };
+                let two_factor = two_factor.or_else(|| remembered.as_ref().map(|token| TwoFactor {
+                        provider: TWO_FACTOR_REMEMBER,
+                        token,
+                        remember: false,
+                }));
+
+                let response = match self.perform_token_auth(email, &cipher, two_factor) {
+                        Err(err @ ApiError::TwoFactorRequired { .. }) if remembered.is_some() => {
+                                info!("the remembered second factor expired");
+                                let _ = remove_data("two_factor");
+                                return Err(err);
+                        }
+                        response => response?,
+                };
+                let LoginResponseData { access_token, expires_in, token_type, refresh_token, two_factor_token } =
+                        response;
+
+                info!("logged in, access token valid for {}s", expires_in);
+
+                if let Some(token) = &two_factor_token {
+                        if let Err(err) = save_two_factor_token(email, &cipher, token) {
+                                warn!("failed to remember the second factor: {}", err);
+                        }
+                }
+
+                Ok(AuthData {
+                        access_token,
+                        expires_in,
+                        token_type,
+                        issued_at: Some(Utc::now()),
+                        refresh_token,
+                        client_id: default_client_id(),
+                        server: Some(self.server()),
+                        tokens_in_keyring: false,
+                        kdf: prelogin.kdf,
+                        kdf_iterations: prelogin.kdf_iterations,
+                        kdf_memory: prelogin.kdf_memory,
+                        kdf_parallelism: prelogin.kdf_parallelism,
+                        cipher,
+                        two_factor_token: two_factor_token.or(remembered),
+                })
+        }
+
+        /// Has the server forget the refresh token of the session, so that it
+        /// can't be used anymore once bwtui logged out.
+        pub fn revoke(&self, auth_data: &AuthData) -> Result<(), ApiError> {
+                let refresh_token = match &auth_data.refresh_token {
+                        Some(refresh_token) => refresh_token,
+                        None => return Ok(()),
+                };
+
+                let url = format!("{}/connect/revocation", self.server.read().unwrap().identity);
+                debug!("revoking the refresh token at {}", url);
+
+                let mut data = HashMap::new();
+                data.insert("token", refresh_token.as_str());
+                data.insert("token_type_hint", "refresh_token");
+                data.insert("client_id", auth_data.client_id.as_str());
+
+                let mut response = self.client.post(&url)
+                        .form(&data)
+                        .send()
+                        .map_err(|e| ApiError::Offline { endpoint: url.clone(), error: e.to_string() })?;
+
+                if response.status().is_success() {
+                        Ok(())
+                } else {
+                        Err(ApiError::RequestFailed { endpoint: url, error: error_message(&mut response) })
+                }
+        }
+
+        /// Creates a new account, the keys have to be generated beforehand with
+        /// the same `kdf_iterations`.
+        pub fn register(&self, email: &str, name: &str, hint: Option<&str>,
+                        kdf_iterations: usize, keys: &AccountKeys) -> Result<(), ApiError>
+        {
+                let url = format!("{}/accounts/register", self.api_url());
+
+                let body = RegisterRequest {
+                        email,
+                        name,
+                        master_password_hash: &keys.master_key_hash,
+                        master_password_hint: hint,
+                        key: &keys.key,
+                        // PBKDF2-SHA256, the only one supported
+                        kdf: 0,
+                        kdf_iterations,
+                        keys: RegisterKeys {
+                                public_key: &keys.public_key,
+                                encrypted_private_key: &keys.private_key,
+                        },
+                };
+
+                let mut response = self.client.post(&url)
+                        .json(&body)
+                        .send()
+                        .map_err(|e| ApiError::RegistrationFailed { error: e.to_string() })?;
+
+                if response.status().is_success() {
+                        Ok(())
+                } else {
+                        Err(ApiError::RegistrationFailed { error: error_message(&mut response) })
+                }
+        }
+
+        /// Renews the access token with the refresh token handed out along
+        /// with it, without needing the master password.
+        pub fn refresh(&self, auth_data: &mut AuthData) -> Result<(), ApiError> {
+                let refresh_token = auth_data.refresh_token.clone()
+                        .ok_or_else(|| ApiError::LoginFailed { error: "no refresh token".to_owned() })?;
+
+                let mut data = HashMap::new();
+                data.insert("grant_type", "refresh_token");
+                data.insert("client_id", auth_data.client_id.as_str());
+                data.insert("refresh_token", &refresh_token);
+
+                let LoginResponseData { access_token, expires_in, token_type, refresh_token: renewed, .. } =
+                        self.request_token(data)?;
+
+                info!("access token refreshed, valid for {}s", expires_in);
+
+                auth_data.access_token = access_token;
+                auth_data.expires_in = expires_in;
+                auth_data.token_type = token_type;
+                auth_data.issued_at = Some(Utc::now());
+                // Rotated on use by the server, otherwise still valid
+                if renewed.is_some() {
+                        auth_data.refresh_token = renewed;
+                }
+
+                Ok(())
+        }
+
+        /// Requests a new access token, e.g. after the previous one expired:
+        /// with the refresh token if there is one, and using the already
+        /// derived master key otherwise or if it got revoked.
+        pub fn reauthenticate(&self, email: &str, auth_data: &mut AuthData, cipher: &CipherSuite)
+                -> Result<(), ApiError>
+        {
+                if auth_data.refresh_token.is_some() {
+                        match self.refresh(auth_data) {
+                                Ok(()) => return Ok(()),
+                                // Only forgotten once revoked, it still works
+                                // after network errors
+                                Err(ApiError::InvalidGrant { error }) => {
+                                        info!("refresh token rejected: {}", error);
+                                        auth_data.refresh_token = None;
+                                }
+                                Err(err) => return Err(err),
+                        }
+                }
+
+                // Unlocked with a PIN, or logged in with SSO or another
+                // device: there is no master password hash to log in with
+                if cipher.master_key_hash.is_empty() {
+                        return Err(ApiError::SessionExpired);
+                }
+
+                let two_factor = auth_data.two_factor_token.as_ref().map(|token| TwoFactor {
+                        provider: TWO_FACTOR_REMEMBER,
+                        token,
+                        remember: false,
+                });
+                let LoginResponseData { access_token, expires_in, token_type, refresh_token, .. } =
+                        self.perform_token_auth(email, cipher, two_factor)?;
+
+                info!("access token renewed");
+
+                auth_data.access_token = access_token;
+                auth_data.expires_in = expires_in;
+                auth_data.token_type = token_type;
+                auth_data.issued_at = Some(Utc::now());
+                auth_data.refresh_token = refresh_token;
+                auth_data.client_id = default_client_id();
+
+                Ok(())
+        }
+
+        /// Runs `request`, re-authenticating and retrying it once if the access
+        /// token got rejected. An access token about to expire is renewed
+        /// beforehand. Fails with `SessionExpired` if logging in again needs
+        /// the user, e.g. for a second factor.
+        pub fn retry_unauthorized<T, F>(&self, email: &str, auth_data: &mut AuthData,
+                                        cipher: &CipherSuite, request: F) -> Result<T, ApiError>
+                where F: Fn(&Api, &AuthData) -> Result<T, ApiError>
+        {
+                if auth_data.expires_soon() {
+                        if let Err(err) = self.reauthenticate(email, auth_data, cipher) {
+                                // The request tells whether the old one still works
+                                warn!("failed to renew the access token: {}", err);
+                        }
+                }
+
+                match request(self, auth_data) {
+                        Err(ApiError::Unauthorized { endpoint }) => {
+                                info!("access token rejected by {}, renewing it", endpoint);
+                                self.reauthenticate(email, auth_data, cipher)
+                                        .map_err(|err| match err {
+                                                ApiError::TwoFactorRequired { .. }
+                                                | ApiError::CaptchaRequired
+                                                | ApiError::DeviceVerificationRequired => ApiError::SessionExpired,
+                                                err => err,
+                                        })?;
+                                request(self, auth_data)
+                        }
+                        result => result,
+                }
+        }
+
+        /// Sends an authenticated request, mapping failures the same way for
+        /// all endpoints. Failing to send at all means the server couldn't be
+        /// reached, callers may fall back to the cached vault then.
+        fn send(&self, url: &str, auth_data: &AuthData, request: reqwest::RequestBuilder)
+                -> Result<reqwest::Response, ApiError>
+        {
+                let auth_header = format!("{} {}", auth_data.token_type, auth_data.access_token);
+
+                debug!("request to {}", url);
+
+                let mut response = request
+                        .header(header::AUTHORIZATION, auth_header)
+                        .send()
+                        .map_err(|e| {
+                                warn!("{} couldn't be reached: {}", url, e);
+                                ApiError::Offline { endpoint: url.to_owned(), error: e.to_string() }
+                        })?;
+
+                debug!("{} answered with status {}", url, response.status());
+
+                // Only sent for conditional requests, which handle it themselves
+                if response.status().is_success() || response.status() == StatusCode::NOT_MODIFIED {
+                        Ok(response)
+                } else if response.status() == StatusCode::UNAUTHORIZED {
+                        Err(ApiError::Unauthorized { endpoint: url.to_owned() })
+                } else if response.status() == StatusCode::NOT_FOUND {
+                        Err(ApiError::NotFound { endpoint: url.to_owned() })
+                } else {
+                        Err(ApiError::RequestFailed {
+                                endpoint: url.to_owned(),
+                                error: error_message(&mut response),
+                        })
+                }
+        }
+
+        fn json_response<T>(url: &str, mut response: reqwest::Response) -> Result<T, ApiError>
+                where T: DeserializeOwned
+        {
+                response.json()
+                        .map_err(|e| ApiError::RequestFailed { endpoint: url.to_owned(), error: e.to_string() })
+        }
+
+        fn get_json<T>(&self, url: &str, auth_data: &AuthData, query: &[(&str, &str)])
+                -> Result<T, ApiError>
+                where T: DeserializeOwned
+        {
+                let response = self.send(url, auth_data, self.client.get(url).query(query))?;
+                Self::json_response(url, response)
+        }
+
+        fn post(&self, url: &str, auth_data: &AuthData) -> Result<reqwest::Response, ApiError> {
+                self.send(url, auth_data, self.client.post(url))
+        }
+
+        /// Fetches all events of an organization between `start` and `end`,
+        /// following the server's pagination.
+        pub fn organization_events(&self, auth_data: &AuthData, organization: Uuid,
+                                   start: DateTime<Utc>, end: DateTime<Utc>)
+                -> Result<Vec<Event>, ApiError>
+        {
+                let url = format!("{}/organizations/{}/events", self.api_url(), organization);
+                let start = start.to_rfc3339();
+                let end = end.to_rfc3339();
+
+                let mut events = Vec::new();
+                let mut continuation_token = None;
+
+                loop {
+                        let mut query = vec![("start", start.as_str()), ("end", end.as_str())];
+                        if let Some(token) = &continuation_token {
+                                query.push(("continuationToken", token.as_str()));
+                        }
+
+                        let response: EventsResponse = self.get_json(&url, auth_data, &query)?;
+                        events.extend(response.data);
+
+                        match response.continuation_token {
+                                Some(token) => continuation_token = Some(token),
+                                None => return Ok(events),
+                        }
+                }
+        }
+
+        pub fn organization_members(&self, auth_data: &AuthData, organization: Uuid)
+                -> Result<Vec<OrganizationMember>, ApiError>
+        {
+                let url = format!("{}/organizations/{}/users", self.api_url(), organization);
+                let response: ListResponse<OrganizationMember> = self.get_json(&url, auth_data, &[])?;
+
+                Ok(response.data)
+        }
+
+        /// All collections of an organization, including those the user isn't
+        /// assigned to. Only available to admins.
+        pub fn organization_collections(&self, auth_data: &AuthData, organization: Uuid)
+                -> Result<Vec<Collection>, ApiError>
+        {
+                let url = format!("{}/organizations/{}/collections", self.api_url(), organization);
+                let response: ListResponse<Collection> = self.get_json(&url, auth_data, &[])?;
+
+                Ok(response.data)
+        }
+
+        pub fn create_collection(&self, auth_data: &AuthData, organization: Uuid, name: &CipherString)
+                -> Result<Collection, ApiError>
+        {
+                let url = format!("{}/organizations/{}/collections", self.api_url(), organization);
+                let body = CollectionRequest { name, groups: &[] };
+
+                let response = self.send(&url, auth_data, self.client.post(&url).json(&body))?;
+                Self::json_response(&url, response)
+        }
+
+        pub fn rename_collection(&self, auth_data: &AuthData, collection: &Collection, name: &CipherString)
+                -> Result<(), ApiError>
+        {
+                let url = format!("{}/organizations/{}/collections/{}",
+                                  self.api_url(), collection.organization_id, collection.uuid);
+                let body = CollectionRequest { name, groups: &[] };
+
+                self.send(&url, auth_data, self.client.put(&url).json(&body))?;
+                Ok(())
+        }
+
+        pub fn delete_collection(&self, auth_data: &AuthData, collection: &Collection)
+                -> Result<(), ApiError>
+        {
+                let url = format!("{}/organizations/{}/collections/{}",
+                                  self.api_url(), collection.organization_id, collection.uuid);
+
+                self.send(&url, auth_data, self.client.delete(&url))?;
+                Ok(())
+        }
+
+        /// Replaces the collections an organization item is assigned to.
+        pub fn set_cipher_collections(&self, auth_data: &AuthData, cipher: Uuid, collections: &[Uuid])
+                -> Result<(), ApiError>
+        {
+                let url = format!("{}/ciphers/{}/collections-admin", self.api_url(), cipher);
+                let body = CipherCollectionsRequest { collection_ids: collections };
+
+                self.send(&url, auth_data, self.client.post(&url).json(&body))?;
+                Ok(())
+        }
+
+        /// Moves a personal item into an organization. `cipher` must already
+        /// be encrypted with the organization key and name it.
+        pub fn share_cipher(&self, auth_data: &AuthData, cipher: &CipherEntry, collections: &[Uuid])
+                -> Result<(), ApiError>
+        {
+                let url = format!("{}/ciphers/{}/share", self.api_url(), cipher.uuid);
+                let body = CipherShareRequest {
+                        cipher: CipherRequest::from(cipher),
+                        collection_ids: collections,
+                };
+
+                self.send(&url, auth_data, self.client.put(&url).json(&body))?;
+                Ok(())
+        }
+
+        /// Archives an item, or brings it back from the archive.
+        pub fn set_cipher_archived(&self, auth_data: &AuthData, cipher: Uuid, archived: bool)
+                -> Result<(), ApiError>
+        {
+                let action = if archived { "archive" } else { "unarchive" };
+                let url = format!("{}/ciphers/{}/{}", self.api_url(), cipher, action);
+
+                self.send(&url, auth_data, self.client.put(&url))?;
+                Ok(())
+        }
+
+        /// Creates an item, it shows up with the next sync.
+        pub fn create_cipher(&self, auth_data: &AuthData, cipher: &NewCipher) -> Result<(), ApiError> {
+                let url = format!("{}/ciphers", self.api_url());
+                let body = CipherRequest::from(cipher);
+
+                self.send(&url, auth_data, self.client.post(&url).json(&body))?;
+                Ok(())
+        }
+
+        /// Fetches a single item, `None` if it was deleted for good meanwhile.
+        pub fn cipher(&self, auth_data: &AuthData, cipher: Uuid) -> Result<Option<CipherEntry>, ApiError> {
+                let url = format!("{}/ciphers/{}", self.api_url(), cipher);
+
+                match self.get_json(&url, auth_data, &[]) {
+                        Ok(entry) => Ok(Some(entry)),
+                        Err(ApiError::NotFound { .. }) => Ok(None),
+                        Err(err) => Err(err),
+                }
+        }
+
+        /// Updates an item, unless it was changed elsewhere since `cipher` was
+        /// synced. The server's version is returned then, rather than silently
+        /// overwriting it.
+        pub fn update_cipher(&self, auth_data: &AuthData, cipher: &CipherEntry) -> Result<Update, ApiError> {
+                if let Some(current) = self.cipher(auth_data, cipher.uuid)? {
+                        if current.last_changed > cipher.last_changed {
+                                return Ok(Update::Conflict(current));
+                        }
+                }
+
+                let url = format!("{}/ciphers/{}", self.api_url(), cipher.uuid);
+                let body = CipherRequest::from(cipher);
+
+                match self.send(&url, auth_data, self.client.put(&url).json(&body)) {
+                        Ok(_) => Ok(Update::Saved),
+                        // Changed right between the check and the update, the
+                        // server compares the revision dates too.
+                        Err(ApiError::RequestFailed { ref error, .. }) if error.contains("out of date") => {
+                                match self.cipher(auth_data, cipher.uuid)? {
+                                        Some(current) => Ok(Update::Conflict(current)),
+                                        None => Err(ApiError::NotFound { endpoint: url }),
+                                }
+                        }
+                        Err(err) => Err(err),
+                }
+        }
+
+        pub fn create_folder(&self, auth_data: &AuthData, name: &CipherString) -> Result<Folder, ApiError> {
+                let url = format!("{}/folders", self.api_url());
+                let body = FolderRequest { name };
+
+                let response = self.send(&url, auth_data, self.client.post(&url).json(&body))?;
+                Self::json_response(&url, response)
+        }
+
+        /// Moves an item to the trash, it can still be restored from the web
+        /// vault.
+        pub fn delete_cipher(&self, auth_data: &AuthData, cipher: Uuid) -> Result<(), ApiError> {
+                let url = format!("{}/ciphers/{}/delete", self.api_url(), cipher);
+
+                self.send(&url, auth_data, self.client.put(&url))?;
+                Ok(())
+        }
+
+        /// Rotates the security stamp of the account, which revokes the access
+        /// tokens of all sessions, including this one.
+        pub fn deauthorize_sessions(&self, auth_data: &AuthData, master_password_hash: &str)
+                -> Result<(), ApiError>
+        {
+                let url = format!("{}/accounts/security-stamp", self.api_url());
+                let body = SecretVerificationRequest { master_password_hash };
+
+                self.send(&url, auth_data, self.client.post(&url).json(&body))?;
+                Ok(())
+        }
+
+        /// Changes the master password, `key` being the vault key protected
+        /// by the new one. The server logs out all sessions afterwards.
+        pub fn change_password(&self, auth_data: &AuthData, master_password_hash: &str,
+                               new_master_password_hash: &str, hint: Option<&str>,
+                               key: &CipherString) -> Result<(), ApiError>
+        {
+                let url = format!("{}/accounts/password", self.api_url());
+                let body = ChangePasswordRequest {
+                        master_password_hash,
+                        new_master_password_hash,
+                        master_password_hint: hint,
+                        key,
+                };
+
+                self.send(&url, auth_data, self.client.post(&url).json(&body))?;
+                Ok(())
+        }
+
+        pub fn devices(&self, auth_data: &AuthData) -> Result<Vec<Device>, ApiError> {
+                let url = format!("{}/devices", self.api_url());
+                let response: ListResponse<Device> = self.get_json(&url, auth_data, &[])?;
+
+                Ok(response.data)
+        }
+
+        /// Removes a device, logging it out.
+        pub fn delete_device(&self, auth_data: &AuthData, device: Uuid) -> Result<(), ApiError> {
+                let url = format!("{}/devices/{}", self.api_url(), device);
+
+                self.send(&url, auth_data, self.client.delete(&url))?;
+                Ok(())
+        }
+
+        pub fn auth_requests(&self, auth_data: &AuthData) -> Result<Vec<AuthRequest>, ApiError> {
+                let url = format!("{}/auth-requests", self.api_url());
+                let response: ListResponse<AuthRequest> = self.get_json(&url, auth_data, &[])?;
+
+                Ok(response.data)
+        }
+
+        pub fn deny_auth_request(&self, auth_data: &AuthData, request: Uuid) -> Result<(), ApiError> {
+                let url = format!("{}/auth-requests/{}", self.api_url(), request);
+                let body = AuthRequestResponse {
+                        device_identifier: device_identifier().to_hyphenated().to_string(),
+                        request_approved: false,
+                };
+
+                self.send(&url, auth_data, self.client.put(&url).json(&body))?;
+                Ok(())
+        }
+
+        /// Replaces the custom equivalent domains and excluded global ones.
+        pub fn update_domains(&self, auth_data: &AuthData, domains: &Domains)
+                -> Result<Domains, ApiError>
+        {
+                let url = format!("{}/settings/domains", self.api_url());
+                let body = DomainsRequest {
+                        equivalent_domains: &domains.equivalent_domains,
+                        excluded_global_equivalent_domains: domains.global_equivalent_domains.iter()
+                                .filter(|group| group.excluded)
+                                .map(|group| group.type_)
+                                .collect(),
+                };
+
+                let response = self.send(&url, auth_data, self.client.put(&url).json(&body))?;
+                Self::json_response(&url, response)
+        }
+
+        pub fn sends(&self, auth_data: &AuthData) -> Result<Vec<SendEntry>, ApiError> {
+                let url = format!("{}/sends", self.api_url());
+                let response: ListResponse<SendEntry> = self.get_json(&url, auth_data, &[])?;
+
+                Ok(response.data)
+        }
+
+        /// Stores the settings of an existing send, its content stays as is.
+        pub fn update_send(&self, auth_data: &AuthData, send: &SendEntry)
+                -> Result<SendEntry, ApiError>
+        {
+                let url = format!("{}/sends/{}", self.api_url(), send.uuid);
+                let body = SendRequest::from(send);
+
+                let response = self.send(&url, auth_data, self.client.put(&url).json(&body))?;
+                Self::json_response(&url, response)
+        }
+
+        pub fn delete_send(&self, auth_data: &AuthData, send: Uuid) -> Result<(), ApiError> {
+                let url = format!("{}/sends/{}", self.api_url(), send);
+
+                self.send(&url, auth_data, self.client.delete(&url))?;
+                Ok(())
+        }
+
+        /// Grants given by the user to others.
+        pub fn emergency_access_trusted(&self, auth_data: &AuthData)
+                -> Result<Vec<EmergencyAccess>, ApiError>
+        {
+                let url = format!("{}/emergency-access/trusted", self.api_url());
+                let response: ListResponse<EmergencyAccess> = self.get_json(&url, auth_data, &[])?;
+
+                Ok(response.data)
+        }
+
+        /// Grants given to the user by others.
+        pub fn emergency_access_granted(&self, auth_data: &AuthData)
+                -> Result<Vec<EmergencyAccess>, ApiError>
+        {
+                let url = format!("{}/emergency-access/granted", self.api_url());
+                let response: ListResponse<EmergencyAccess> = self.get_json(&url, auth_data, &[])?;
+
+                Ok(response.data)
+        }
+
+        pub fn emergency_access_invite(&self, auth_data: &AuthData, email: &str, type_: usize,
+                                       wait_time_days: usize) -> Result<(), ApiError>
+        {
+                let url = format!("{}/emergency-access/invite", self.api_url());
+                let body = EmergencyInviteRequest { email, type_, wait_time_days };
+
+                self.send(&url, auth_data, self.client.post(&url).json(&body))?;
+                Ok(())
+        }
+
+        /// Hands the user's key, encrypted for the grantee, over to the server.
+        pub fn emergency_access_confirm(&self, auth_data: &AuthData, access: Uuid, key: &str)
+                -> Result<(), ApiError>
+        {
+                let url = format!("{}/emergency-access/{}/confirm", self.api_url(), access);
+
+                self.send(&url, auth_data, self.client.post(&url).json(&KeyRequest { key }))?;
+                Ok(())
+        }
+
+        /// Runs one of the simple state transitions, i.e. "initiate", "approve"
+        /// or "reject".
+        pub fn emergency_access_action(&self, auth_data: &AuthData, access: Uuid, action: &str)
+                -> Result<(), ApiError>
+        {
+                let url = format!("{}/emergency-access/{}/{}", self.api_url(), access, action);
+
+                self.post(&url, auth_data)?;
+                Ok(())
+        }
+
+        pub fn emergency_access_delete(&self, auth_data: &AuthData, access: Uuid)
+                -> Result<(), ApiError>
+        {
+                let url = format!("{}/emergency-access/{}", self.api_url(), access);
+
+                self.send(&url, auth_data, self.client.delete(&url))?;
+                Ok(())
+        }
+
+        pub fn emergency_access_view(&self, auth_data: &AuthData, access: Uuid)
+                -> Result<EmergencyAccessView, ApiError>
+        {
+                let url = format!("{}/emergency-access/{}/view", self.api_url(), access);
+                let response = self.post(&url, auth_data)?;
+
+                Self::json_response(&url, response)
+        }
+
+        pub fn emergency_access_takeover(&self, auth_data: &AuthData, access: Uuid)
+                -> Result<EmergencyAccessTakeover, ApiError>
+        {
+                let url = format!("{}/emergency-access/{}/takeover", self.api_url(), access);
+                let response = self.post(&url, auth_data)?;
+
+                Self::json_response(&url, response)
+        }
+
+        /// Sets a new master password for the grantor after a takeover.
+        pub fn emergency_access_password(&self, auth_data: &AuthData, access: Uuid,
+                                         master_password_hash: &str, key: &CipherString)
+                -> Result<(), ApiError>
+        {
+                let url = format!("{}/emergency-access/{}/password", self.api_url(), access);
+                let body = PasswordRequest { new_master_password_hash: master_password_hash, key };
+
+                self.send(&url, auth_data, self.client.post(&url).json(&body))?;
+                Ok(())
+        }
+
+        /// Public key of another user, DER encoded.
+        pub fn user_public_key(&self, auth_data: &AuthData, user: Uuid) -> Result<Vec<u8>, ApiError> {
+                let url = format!("{}/users/{}/public-key", self.api_url(), user);
+                let response: PublicKeyResponse = self.get_json(&url, auth_data, &[])?;
+
+                base64::decode(&response.public_key)
+                        .map_err(|e| ApiError::RequestFailed { endpoint: url, error: e.to_string() })
+        }
+
+        /// Fetches the whole vault.
+        pub fn sync(&self, auth_data: &AuthData) -> Result<VaultData, ApiError> {
+                let url = format!("{}/sync", self.api_url());
+
+                let request = self.client.get(&url)
+                        .header(header::ACCEPT_ENCODING, SYNC_ENCODINGS);
+                let response = self.send(&url, auth_data, request)?;
+
+                self.read_sync(url, response)
+        }
+
+        /// Fetches the whole vault unless it didn't change since the cached
+        /// sync at `synced_at`, which needs the server to send `ETag` or
+        /// `Last-Modified`. `None` means the cached vault is still current.
+        pub fn sync_if_changed(&self, auth_data: &AuthData, synced_at: Option<DateTime<Utc>>)
+                -> Result<Option<VaultData>, ApiError>
+        {
+                let url = format!("{}/sync", self.api_url());
+                let mut request = self.client.get(&url)
+                        .header(header::ACCEPT_ENCODING, SYNC_ENCODINGS);
+
+                let validators = read_data_from::<SyncValidators>("sync").ok()
+                        .filter(|validators| validators.synced_at.is_some() && validators.synced_at == synced_at);
+
+                if let Some(validators) = validators {
+                        if let Some(etag) = validators.etag {
+                                request = request.header(header::IF_NONE_MATCH, etag);
+                        }
+                        if let Some(last_modified) = validators.last_modified {
+                                request = request.header(header::IF_MODIFIED_SINCE, last_modified);
+                        }
+                }
+
+                let response = self.send(&url, auth_data, request)?;
+
+                if response.status() == StatusCode::NOT_MODIFIED {
+                        info!("vault unchanged since the last sync");
+                        return Ok(None);
+                }
+
+                self.read_sync(url, response).map(Some)
+        }
+
+        fn read_sync(&self, url: String, response: reqwest::Response) -> Result<VaultData, ApiError> {
+                let header_value = |name: header::HeaderName| response.headers()
+                        .get(name)
+                        .and_then(|value| value.to_str().ok())
+                        .map(str::to_owned);
+
+                let etag = header_value(header::ETAG);
+                let last_modified = header_value(header::LAST_MODIFIED);
+                let encoding = header_value(header::CONTENT_ENCODING);
+
+                let received_at = Utc::now();
+                let server_time = response.headers()
+                        .get(header::DATE)
+                        .and_then(|date| date.to_str().ok())
+                        .and_then(|date| DateTime::parse_from_rfc2822(date).ok());
+
+                // reqwest still decompresses gzip itself although Accept-Encoding
+                // is set by us, and drops the Content-Encoding header then. So
+                // only brotli is left to us, and gzip if the client was built
+                // without decompression.
+                let reader: Box<dyn Read> = match encoding.as_ref().map(String::as_str) {
+                        Some("br") => Box::new(brotli::Decompressor::new(response, 4096)),
+                        Some("gzip") => Box::new(GzDecoder::new(response)),
+                        None | Some("identity") => Box::new(response),
+                        Some(encoding) => {
+                                return Err(ApiError::RequestFailed {
+                                        endpoint: url,
+                                        error: format!("unsupported content encoding {}", encoding),
+                                });
+                        }
+                };
+
+                // Deserialize straight from the connection instead of buffering
+                // the whole body first, sync payloads of large vaults get huge.
+                let reader = BufReader::new(reader);
+                let mut data: VaultData = serde_json::from_reader(reader)
+                        .map_err(|e| ApiError::RequestFailed {
+                                endpoint: url.clone(),
+                                error: e.to_string(),
+                        })?;
+
+                data.synced_at = Some(Utc::now());
+                data.clock_skew = server_time
+                        .map(|server_time| server_time.with_timezone(&Utc) - received_at);
+
+                if etag.is_some() || last_modified.is_some() {
+                        data.validators = Some(SyncValidators {
+                                synced_at: data.synced_at,
+                                etag,
+                                last_modified,
+                        });
+                }
+
+                info!("synced {} items and {} folders", data.ciphers.len(), data.folders.len());
+
+                Ok(data)
+        }
+}
+
+
+/// Directory of the local cache, shared by everything using this crate.
+pub fn get_app_data_path() -> Result<PathBuf, String> {
+        let project_dirs = directories::ProjectDirs::from("", "", "bwtui")
+                .ok_or("could not retrieve data directory path")?;
+
+        let target_dir = project_dirs.data_local_dir();
+
+        fs::create_dir_all(target_dir)
+                .map_err(|_| "could not create data directory")?;
+
+        let mut path = PathBuf::new();
+        path.push(target_dir);
+
+        Ok(path)
+}
+
+
+/// Options for creating files only the user may read, e.g. logs. On Windows
+/// this is the default for files in the user's profile.
+pub fn private_file() -> OpenOptions {
+        let mut options = OpenOptions::new();
+
+        #[cfg(unix)]
+        {
+                use std::os::unix::fs::OpenOptionsExt;
+                options.mode(0o600);
+        }
+
+        options
+}
+
+
+/// On-disk format of the local cache. Binary is used by default, JSON can be
+/// chosen by setting `BWTUI_CACHE_FORMAT=json`, which is handy for debugging.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum CacheFormat {
+        Binary,
+        Json,
+}
+
+impl CacheFormat {
+        fn preferred() -> Self {
+                match env::var("BWTUI_CACHE_FORMAT") {
+                        Ok(ref format) if format == "json" => CacheFormat::Json,
+                        _ => CacheFormat::Binary,
+                }
+        }
+
+        fn other(self) -> Self {
+                match self {
+                        CacheFormat::Binary => CacheFormat::Json,
+                        CacheFormat::Json => CacheFormat::Binary,
+                }
+        }
+
+        fn filename(self, name: &str) -> String {
+                match self {
+                        CacheFormat::Binary => format!("{}.bin", name),
+                        CacheFormat::Json => format!("{}.json", name),
+                }
+        }
+}
+
+
+/// Writes `data` to a new file at `path`, making sure it's on disk when
+/// returning.
+fn write_file<T>(path: &Path, format: CacheFormat, data: &T) -> Result<(), String>
+        where T: Serialize
+{
+        // The session tokens, PIN protected key and remembered second factor
+        // are among the data, whether or not the keyring is used
+        let file = private_file()
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .open(path)
+                .map_err(|e| e.to_string())?;
+
+        let mut writer = BufWriter::new(file);
+        match format {
+                CacheFormat::Binary => bincode::serialize_into(&mut writer, data)
+                        .map_err(|e| e.to_string())?,
+                CacheFormat::Json => serde_json::to_writer(&mut writer, data)
+                        .map_err(|e| e.to_string())?,
+        }
+
+        let file = writer.into_inner().map_err(|e| e.to_string())?;
+        file.sync_all().map_err(|e| e.to_string())
+}
+
+
+fn save_data_to<T>(name: &str, data: &T) -> Result<(), ApiError>
+        where T: Serialize
+{
+        let format = CacheFormat::preferred();
+        debug!("writing {}", format.filename(name));
+
+        let dir = get_app_data_path()
+                .map_err(|error| ApiError::VaultDataWriteFailed { error })?;
+
+        // Written next to the file and renamed over it, so that other
+        // instances watching the cache never read it half-written and a crash
+        // keeps the previous copy
+        let path = dir.join(format.filename(name));
+        let tmp = dir.join(format!("{}.{}.tmp", format.filename(name), process::id()));
+
+        let written = write_file(&tmp, format, data)
+                .and_then(|()| fs::rename(&tmp, &path).map_err(|e| e.to_string()));
+        if let Err(error) = written {
+                let _ = fs::remove_file(&tmp);
+                return Err(ApiError::VaultDataWriteFailed { error });
+        }
+
+        // Don't leave a stale copy in the other format behind.
+        let _ = fs::remove_file(dir.join(format.other().filename(name)));
+
+        Ok(())
+}
+
+
+fn read_data_in<T>(name: &str, format: CacheFormat) -> Result<T, ApiError>
+        where T: DeserializeOwned
+{
+        let mut path = get_app_data_path()
+                .map_err(|error| ApiError::VaultDataReadFailed { error })?;
+        path.push(format.filename(name));
+
+        let file = File::open(path)
+                .map_err(|e| ApiError::VaultDataReadFailed { error: e.to_string() })?;
+
+        let reader = BufReader::new(file);
+        match format {
+                CacheFormat::Binary => bincode::deserialize_from(reader)
+                        .map_err(|e| ApiError::VaultDataReadFailed { error: e.to_string() }),
+                CacheFormat::Json => serde_json::from_reader(reader)
+                        .map_err(|e| ApiError::VaultDataReadFailed { error: e.to_string() }),
+        }
+}
+
+
+fn read_data_from<T>(name: &str) -> Result<T, ApiError>
+        where T: DeserializeOwned
+{
+        let format = CacheFormat::preferred();
+
+        read_data_in(name, format)
+                .or_else(|_| read_data_in(name, format.other()))
+}
+
+
+pub fn read_app_data() -> Result<AppData, ApiError> {
+        let auth = read_auth()?;
+        let vault = read_data_from("vault")?;
+
+        Ok(AppData {
+                auth,
+                vault,
+        })
+}
+
+
+pub fn save_settings(settings: &Settings) -> Result<(), ApiError> {
+        save_data_to("settings", settings)
+}
+
+
+pub fn read_settings() -> Result<Settings, ApiError> {
+        read_data_from("settings")
+}
+
+
+pub fn save_view_state(state: &ViewState) -> Result<(), ApiError> {
+        save_data_to("view", state)
+}
+
+
+pub fn read_view_state() -> Result<ViewState, ApiError> {
+        read_data_from("view")
+}
+
+
+/// Log of sensitive actions, each entry encrypted on its own.
+pub fn save_activity(entries: &[CipherString]) -> Result<(), ApiError> {
+        save_data_to("activity", &entries)
+}
+
+
+pub fn read_activity() -> Result<Vec<CipherString>, ApiError> {
+        read_data_from("activity")
+}
+
+
+/// Changes made while offline, each operation encrypted on its own.
+pub fn save_journal(operations: &[CipherString]) -> Result<(), ApiError> {
+        save_data_to("journal", &operations)
+}
+
+
+pub fn read_journal() -> Result<Vec<CipherString>, ApiError> {
+        read_data_from("journal")
+}
+
+
+pub fn read_vault_data() -> Result<VaultData, ApiError> {
+        read_data_from("vault")
+}
+
+
+pub fn save_auth_data(auth: &AuthData) -> Result<(), ApiError> {
+        save_auth(auth)
+}
+
+
+// One entry per data directory, in case several are used
+#[cfg(feature = "keyring")]
+fn keyring_user() -> Result<String, String> {
+        get_app_data_path().map(|dir| dir.display().to_string())
+}
+
+
+#[cfg(feature = "keyring")]
+fn save_tokens(tokens: &SessionTokens) -> Result<(), String> {
+        let user = keyring_user()?;
+        let tokens = serde_json::to_string(tokens).map_err(|e| e.to_string())?;
+
+        os_keyring::Keyring::new(KEYRING_SERVICE, &user)
+                .set_password(&tokens)
+                .map_err(|e| e.to_string())
+}
+
+#[cfg(not(feature = "keyring"))]
+fn save_tokens(_: &SessionTokens) -> Result<(), String> {
+        Err("built without the keyring feature".to_owned())
+}
+
+
+#[cfg(feature = "keyring")]
+fn read_tokens() -> Result<SessionTokens, String> {
+        let user = keyring_user()?;
+        let tokens = os_keyring::Keyring::new(KEYRING_SERVICE, &user)
+                .get_password()
+                .map_err(|e| e.to_string())?;
+
+        serde_json::from_str(&tokens).map_err(|e| e.to_string())
+}
+
+#[cfg(not(feature = "keyring"))]
+fn read_tokens() -> Result<SessionTokens, String> {
+        Err("built without the keyring feature".to_owned())
+}
+
+
+#[cfg(feature = "keyring")]
+fn remove_tokens() {
+        let user = match keyring_user() {
+                Ok(user) => user,
+                Err(_) => return,
+        };
+
+        // Most likely there were none
+        if let Err(err) = os_keyring::Keyring::new(KEYRING_SERVICE, &user).delete_password() {
+                debug!("no tokens removed from the keyring: {}", err);
+        }
+}
+
+#[cfg(not(feature = "keyring"))]
+fn remove_tokens() {}
+
+
+/// Caches the session, its tokens going to the keyring of the OS if there is
+/// one and into the cache file otherwise.
+fn save_auth(auth: &AuthData) -> Result<(), ApiError> {
+        let mut cached = auth.clone();
+
+        let tokens = SessionTokens {
+                access_token: auth.access_token.clone(),
+                refresh_token: auth.refresh_token.clone(),
+        };
+
+        match save_tokens(&tokens) {
+                Ok(()) => {
+                        cached.access_token = String::new();
+                        cached.refresh_token = None;
+                        cached.tokens_in_keyring = true;
+                }
+                Err(err) => {
+                        debug!("keeping the tokens in the cache: {}", err);
+                        cached.tokens_in_keyring = false;
+                }
+        }
+
+        save_data_to("auth", &cached)
+}
+
+
+fn read_auth() -> Result<AuthData, ApiError> {
+        let mut auth: AuthData = read_data_from("auth")?;
+
+        if auth.tokens_in_keyring {
+                match read_tokens() {
+                        Ok(tokens) => {
+                                auth.access_token = tokens.access_token;
+                                auth.refresh_token = tokens.refresh_token;
+                        }
+                        Err(err) => {
+                                // Renewed with the master password then
+                                warn!("failed to read the tokens from the keyring: {}", err);
+                                auth.issued_at = None;
+                        }
+                }
+        }
+
+        Ok(auth)
+}
+
+
+fn remove_auth() -> Result<(), ApiError> {
+        remove_tokens();
+        remove_data("auth")
+}
+
+
+fn save_two_factor_token(email: &str, cipher: &CipherSuite, token: &str) -> Result<(), ApiError> {
+        let token = cipher.encrypt_with_master_key(token)
+                .ok_or_else(|| ApiError::VaultDataWriteFailed { error: "failed to encrypt".to_owned() })?;
+
+        save_data_to("two_factor", &RememberedTwoFactor { email: email.to_owned(), token })
+}
+
+
+fn read_two_factor_token(email: &str, cipher: &CipherSuite) -> Option<String> {
+        let remembered: RememberedTwoFactor = read_data_from("two_factor").ok()?;
+
+        if remembered.email != email {
+                return None;
+        }
+
+        cipher.decrypt_with_master_key(&remembered.token)
+}
+
+
+/// Lets `pin` unlock the vault of `cipher` from now on, the PIN being run
+/// through the same key derivation as the master password.
+pub fn save_pin_key(email: &str, pin: &str, kdf: &Kdf, cipher: &CipherSuite) -> Result<(), ApiError> {
+        let key = CipherSuite::with_kdf(email, pin, kdf)
+                .and_then(|pin_cipher| pin_cipher.protect_key(cipher))
+                .map_err(|e| ApiError::VaultDataWriteFailed { error: e.to_string() })?;
+
+        save_data_to("pin", &PinProtectedKey { email: email.to_owned(), key })
+}
+
+
+/// Whether a PIN was set to unlock the vault of `email`.
+pub fn has_pin_key(email: &str) -> bool {
+        read_data_from::<PinProtectedKey>("pin")
+                .map(|protected| protected.email == email)
+                .unwrap_or(false)
+}
+
+
+/// Unlocks the vault with the PIN, `None` if it's wrong or none was set. The
+/// master key isn't known then, so the returned suite can't log in again.
+pub fn unlock_with_pin(email: &str, pin: &str, kdf: &Kdf) -> Option<CipherSuite> {
+        let protected: PinProtectedKey = read_data_from("pin").ok()?;
+
+        if protected.email != email {
+                return None;
+        }
+
+        let mut cipher = match CipherSuite::with_kdf(email, pin, kdf) {
+                Ok(cipher) => cipher,
+                Err(e) => {
+                        warn!("failed to derive the PIN key: {}", e);
+                        return None;
+                }
+        };
+        cipher.set_decrypt_key(&protected.key).ok()?;
+        // Derived from the PIN, the server would reject it
+        cipher.master_key_hash = String::new();
+
+        Some(cipher)
+}
+
+
+pub fn remove_pin_key() -> Result<(), ApiError> {
+        remove_data("pin")
+}
+
+
+/// Identifier of this installation, kept across logins so that the server
+/// doesn't list a new device for every single one of them.
+pub fn device_identifier() -> Uuid {
+        let path = match get_app_data_path() {
+                Ok(dir) => dir.join("device_id"),
+                Err(_) => return Uuid::new_v4(),
+        };
+
+        if let Some(uuid) = fs::read_to_string(&path).ok().and_then(|id| id.trim().parse().ok()) {
+                return uuid;
+        }
+
+        let uuid = Uuid::new_v4();
+        let _ = fs::write(&path, uuid.to_hyphenated().to_string());
+
+        uuid
+}
+
+
+/// Makes the next login use a new device identifier, so that the server
+/// lists it as another device, e.g. after the old one was removed there. A
+/// remembered second factor only works for the old one and is forgotten.
+pub fn reset_device_identifier() -> Result<(), ApiError> {
+        remove_data("two_factor")?;
+
+        let path = get_app_data_path()
+                .map_err(|error| ApiError::VaultDataWriteFailed { error })?
+                .join("device_id");
+
+        match fs::remove_file(path) {
+                Err(ref e) if e.kind() != io::ErrorKind::NotFound => {
+                        Err(ApiError::VaultDataWriteFailed { error: e.to_string() })
+                }
+                _ => Ok(()),
+        }
+}
+
+
+fn remove_data(name: &str) -> Result<(), ApiError> {
+        let dir = get_app_data_path()
+                .map_err(|error| ApiError::VaultDataWriteFailed { error })?;
+
+        for format in &[CacheFormat::Binary, CacheFormat::Json] {
+                match fs::remove_file(dir.join(format.filename(name))) {
+                        Err(ref e) if e.kind() != io::ErrorKind::NotFound => {
+                                return Err(ApiError::VaultDataWriteFailed { error: e.to_string() });
+                        }
+                        _ => (),
+                }
+        }
+
+        Ok(())
+}
+
+
+/// Forgets the cached access token, e.g. after all sessions were revoked. The
+/// vault data itself is kept.
+pub fn remove_auth_data() -> Result<(), ApiError> {
+        remove_auth()
+}
+
+
+/// Forgets the cached session and vault, e.g. once they can't be decrypted
+/// anymore after the master password was changed on another device. Pending
+/// changes and the activity log are kept, they're encrypted with the vault key
+/// which stays the same.
+pub fn remove_app_data() -> Result<(), ApiError> {
+        remove_auth()?;
+        remove_data("vault")?;
+        remove_data("sync")
+}
+
+
+/// Forgets everything cached for the account when logging out: the session,
+/// the vault, pending changes, the activity log, the remembered second
+/// factor, the PIN and where the vault view was left. Only the settings are kept.
+pub fn remove_account_data() -> Result<(), ApiError> {
+        remove_app_data()?;
+
+        for name in &["journal", "activity", "two_factor", "pin", "view"] {
+                remove_data(name)?;
+        }
+
+        Ok(())
+}
+
+
+/// Caches the session and vault locally, unless another process cached a
+/// more recent sync meanwhile.
+pub fn save_app_data(auth: &AuthData, vault: &VaultData) -> Result<(), ApiError> {
+        // Another instance might have synced in the meantime, never replace
+        // its newer cache with our older data.
+        if let Ok(stamp) = read_data_from::<SyncStamp>("vault") {
+                if stamp.synced_at > vault.synced_at {
+                        return Ok(());
+                }
+        }
+
+        save_auth(auth)?;
+        save_data_to("vault", vault)?;
+
+        // Validators of an older sync must not be sent for this one
+        match &vault.validators {
+                Some(validators) => save_data_to("sync", validators)?,
+                None => {
+                        let _ = remove_data("sync");
+                }
+        }
+
+        Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+        use super::*;
+
+        // RFC 7636, appendix B
+        #[test]
+        fn pkce_challenge_matches_rfc() {
+                assert_eq!(
+                        pkce_challenge("dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk"),
+                        "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM");
+        }
+
+        #[test]
+        fn self_hosted_needs_https() {
+                let urls = ServerUrls::self_hosted(" https://vault.example.com/ ").unwrap();
+                assert_eq!(urls.identity, "https://vault.example.com/identity");
+                assert_eq!(urls.api, "https://vault.example.com/api");
+                assert_eq!(urls.web_vault, "https://vault.example.com");
+
+                assert!(ServerUrls::self_hosted("http://vault.example.com").is_err());
+                assert!(ServerUrls::self_hosted("vault.example.com").is_err());
+                assert!(ServerUrls::self_hosted("ftp://vault.example.com").is_err());
+        }
+
+        #[test]
+        fn self_hosted_allows_http_on_localhost() {
+                assert!(ServerUrls::self_hosted("http://localhost:8080").is_ok());
+                assert!(ServerUrls::self_hosted("http://127.0.0.1").is_ok());
+                assert!(ServerUrls::self_hosted("http://[::1]:8000").is_ok());
+                assert!(ServerUrls::self_hosted("http://localhost.example.com").is_err());
+        }
+
+        // As returned by /sync
+        const SECURE_NOTE: &str = r#"{
+                "object": "cipherDetails",
+                "id": "5d29fe4d-729a-4eea-b823-ed55d78c4c44",
+                "organizationId": null,
+                "folderId": null,
+                "type": 2,
+                "data": {
+                        "name": "2.EBqkTfvLd32Fus5+tNkfRA==|aHZCrjZDzUjxG+CSh7xJLCerAmA3ZMmedpmyUeUrgD0=|iaaJBa6KF3agC6jOtyOjLzOM4GG3r4Rwj3mRAW5ldaQ=",
+                        "notes": "2.Tlh8i9QZrIwXptK5uG7UOA==|uuXi8HQI1UlEsVhEK0sGFzWbURR4ysfD5n7DO8ghJ8k=|LLDVcHWnMaT5W/XbnrT/JYKVTM6bpfcH4OtBBPYWwzA=",
+                        "fields": null,
+                        "passwordHistory": null,
+                        "type": 0
+                },
+                "name": "2.EBqkTfvLd32Fus5+tNkfRA==|aHZCrjZDzUjxG+CSh7xJLCerAmA3ZMmedpmyUeUrgD0=|iaaJBa6KF3agC6jOtyOjLzOM4GG3r4Rwj3mRAW5ldaQ=",
+                "notes": "2.Tlh8i9QZrIwXptK5uG7UOA==|uuXi8HQI1UlEsVhEK0sGFzWbURR4ysfD5n7DO8ghJ8k=|LLDVcHWnMaT5W/XbnrT/JYKVTM6bpfcH4OtBBPYWwzA=",
+                "login": null,
+                "card": null,
+                "identity": null,
+                "secureNote": {
+                        "type": 0
+                },
+                "sshKey": null,
+                "fields": null,
+                "passwordHistory": null,
+                "attachments": null,
+                "organizationUseTotp": false,
+                "revisionDate": "2024-03-12T09:41:27.8466667Z",
+                "creationDate": "2024-03-12T09:41:27.8466667Z",
+                "deletedDate": null,
+                "archivedDate": null,
+                "favorite": false,
+                "edit": true,
+                "viewPassword": true,
+                "reprompt": 0,
+                "collectionIds": [],
+                "key": null
+        }"#;
+
+        #[test]
+        fn secure_note_deserializes() {
+                let entry: CipherEntry = serde_json::from_str(SECURE_NOTE).unwrap();
+
+                assert_eq!(entry.type_, CIPHER_SECURE_NOTE);
+                assert_eq!(entry.secure_note.map(|note| note.type_), Some(0));
+        }
+
+        #[test]
+        fn secure_note_survives_the_cache() {
+                let entry: CipherEntry = serde_json::from_str(SECURE_NOTE).unwrap();
+
+                let cached: CipherEntry = bincode::deserialize(&bincode::serialize(&entry).unwrap()).unwrap();
+                assert_eq!(cached.secure_note.map(|note| note.type_), Some(0));
+
+                let cached: CipherEntry = serde_json::from_str(&serde_json::to_string(&entry).unwrap()).unwrap();
+                assert_eq!(cached.secure_note.map(|note| note.type_), Some(0));
+        }
+}
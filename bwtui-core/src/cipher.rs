@@ -0,0 +1,811 @@
+// SPDX-License-Identifier: MIT
+
+use std::fmt;
+use std::ptr;
+use std::sync::atomic;
+
+use aes::Aes256;
+use argon2::{Config as Argon2Config, ThreadMode, Variant, Version};
+use block_modes::{Cbc, BlockMode, block_padding::Pkcs7};
+use hkdf::Hkdf;
+use hmac::{Hmac, Mac};
+use log::warn;
+use pbkdf2::pbkdf2;
+use rand::RngCore;
+use rand::rngs::OsRng;
+use rand::seq::SliceRandom;
+use rayon::prelude::*;
+use rsa::{PaddingScheme, PublicKey, RSAPrivateKey, RSAPublicKey};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde::de::{Error as _, Visitor};
+use serde_bytes::{ByteBuf, Bytes};
+use sha2::{Digest, Sha256};
+use sha1_oaep::Sha1;
+
+use crate::der;
+
+
+pub const KDF_PBKDF2: usize = 0;
+pub const KDF_ARGON2ID: usize = 1;
+
+// Used by the server for Argon2id accounts which don't say otherwise
+const ARGON2_MEMORY: usize = 64;
+const ARGON2_PARALLELISM: usize = 4;
+// Words of fingerprint phrases, at least 64 bits of entropy taken from the
+// EFF long word list like the official clients do
+const FINGERPRINT_WORDS: usize = 5;
+
+
+/// How the master key is derived from the master password, the account's
+/// settings as returned by the prelogin.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Kdf {
+        pub kdf: usize,
+        pub iterations: usize,
+        // Argon2id only, in MiB
+        pub memory: Option<usize>,
+        pub parallelism: Option<usize>,
+}
+
+impl Kdf {
+        pub fn pbkdf2(iterations: usize) -> Self {
+                Kdf {
+                        kdf: KDF_PBKDF2,
+                        iterations,
+                        memory: None,
+                        parallelism: None,
+                }
+        }
+}
+
+
+/// Keys of a vault. Derived from the master password first, which only
+/// decrypts the vault key, `set_decrypt_key` then makes it decrypt items.
+#[derive(Clone, Debug, Default)]
+pub struct CipherSuite {
+        master_key: Vec<u8>,
+        pub master_key_hash: String,
+        mac_key: Vec<u8>,
+
+        decrypt_key: Option<Vec<u8>>,
+}
+
+/// Key pair of a login approved by another device, which encrypts the keys
+/// of the vault with the public half.
+pub struct AuthRequestKeys {
+        private_key: RSAPrivateKey,
+        // DER encoded SPKI
+        public_key: Vec<u8>,
+}
+
+/// Keys of a newly created account, in the form the server stores them.
+pub struct AccountKeys {
+        pub master_key_hash: String,
+        // Key of the vault, encrypted with the master key
+        pub key: CipherString,
+        // DER encoded, base64
+        pub public_key: String,
+        // DER encoded, encrypted with the vault key
+        pub private_key: CipherString,
+}
+
+#[derive(Debug, failure::Fail)]
+pub enum CipherError {
+        #[fail(display = "failed to verify key")]
+        InvalidMac,
+
+        #[fail(display = "only type 2 ciphers are supported")]
+        InvalidKeyType,
+
+        #[fail(display = "key length must  be exactly 32 bytes")]
+        InvalidKeyLength,
+
+        #[fail(display = "block mode error")]
+        BlockModeError,
+
+        #[fail(display = "failed to set decrypt key: {:?}", 0)]
+        DecryptionKeyError(String),
+
+        #[fail(display = "failed to exchange key: {}", 0)]
+        SharedKeyError(String),
+
+        #[fail(display = "failed to derive the master key: {}", 0)]
+        KdfError(String),
+
+        #[fail(display = "only logins and notes without passkeys can be re-encrypted")]
+        UnsupportedItem,
+}
+
+impl CipherSuite {
+        /// Derives the master key with PBKDF2, `kdf_iterations` being the
+        /// account's setting returned by the prelogin.
+        pub fn from(email: &str, password: &str, kdf_iterations: usize) -> Self {
+                Self::from_derived(pbkdf2_master_key(email, password, kdf_iterations), password)
+        }
+
+        /// Derives the master key the way `kdf` says, PBKDF2 or Argon2id.
+        pub fn with_kdf(email: &str, password: &str, kdf: &Kdf) -> Result<Self, CipherError> {
+                let master_key = match kdf.kdf {
+                        KDF_ARGON2ID => argon2id(email, password, kdf)?,
+                        _ => pbkdf2_master_key(email, password, kdf.iterations),
+                };
+
+                Ok(Self::from_derived(master_key, password))
+        }
+
+        fn from_derived(master_key: Vec<u8>, password: &str) -> Self {
+                let (master_key, master_key_hash, mac_key) = stretch_derived_key(master_key, password);
+
+                Self {
+                        master_key,
+                        master_key_hash,
+                        mac_key,
+                        decrypt_key: None,
+                }
+        }
+
+        /// Builds a suite from the raw master key, as handed out by a device
+        /// approving a login. `set_decrypt_key` unlocks the vault as usual.
+        pub fn from_master_key(master_key: &[u8], master_key_hash: String) -> Result<CipherSuite, CipherError> {
+                if master_key.len() != 32 {
+                        return Err(CipherError::InvalidKeyLength);
+                }
+
+                let (master_key, mac_key) = stretch_master_key(master_key);
+
+                Ok(CipherSuite {
+                        master_key,
+                        master_key_hash,
+                        mac_key,
+                        decrypt_key: None,
+                })
+        }
+
+        /// Whether the vault key is known, either set with `set_decrypt_key`
+        /// or given right away.
+        pub fn is_unlocked(&self) -> bool {
+                self.decrypt_key.is_some()
+        }
+
+        /// Unlocks the vault with its key, as found in the synced profile.
+        /// Fails if the master password was wrong.
+        pub fn set_decrypt_key(&mut self, key: &CipherString) -> Result<(), CipherError> {
+                let key = key.decrypt_raw(&self.master_key, &self.mac_key)
+                        .map_err(|e| {
+                                warn!("failed to decrypt the vault key: {}", e);
+                                CipherError::DecryptionKeyError(e.to_string())
+                        })?;
+
+                scrub(&mut self.mac_key);
+                self.decrypt_key = Some(Vec::from(&key[0..32]));
+                self.mac_key = Vec::from(&key[32..64]);
+
+                Ok(())
+        }
+
+        /// Encrypts with the master key, for secrets needed before the vault
+        /// is unlocked. Only possible until `set_decrypt_key`.
+        pub fn encrypt_with_master_key(&self, plain: &str) -> Option<CipherString> {
+                if self.decrypt_key.is_some() {
+                        return None;
+                }
+
+                CipherString::encrypt_raw(plain.as_bytes(), &self.master_key, &self.mac_key).ok()
+        }
+
+        /// Decrypts what `encrypt_with_master_key` encrypted, `None` if the
+        /// master password differs.
+        pub fn decrypt_with_master_key(&self, encrypted: &CipherString) -> Option<String> {
+                if self.decrypt_key.is_some() {
+                        return None;
+                }
+
+                encrypted.decrypt_raw(&self.master_key, &self.mac_key)
+                        .ok()
+                        .and_then(|plain| String::from_utf8(plain).ok())
+        }
+
+        fn private_key(&self, private_key: &CipherString) -> Result<RSAPrivateKey, CipherError> {
+                let decrypt_key = self.decrypt_key.as_ref()
+                        .ok_or(CipherError::InvalidKeyLength)?;
+                let der = private_key.decrypt_raw(decrypt_key, &self.mac_key)?;
+
+                RSAPrivateKey::from_pkcs8(&der)
+                        .map_err(|e| CipherError::SharedKeyError(e.to_string()))
+        }
+
+        /// Derives a cipher suite from a key encrypted with the user's public
+        /// key, as used for organizations and emergency access.
+        pub fn shared_key(&self, private_key: &CipherString, encrypted: &str)
+                -> Result<CipherSuite, CipherError>
+        {
+                let private_key = self.private_key(private_key)?;
+                let key = rsa_decrypt(&private_key, encrypted)?;
+
+                CipherSuite::from_key(&key)
+        }
+
+        /// Fingerprint phrase of the account with the id `user_id`, from the
+        /// public key matching its `private_key`.
+        pub fn account_fingerprint(&self, user_id: &str, private_key: &CipherString)
+                -> Result<String, CipherError>
+        {
+                let private_key = self.private_key(private_key)?;
+
+                Ok(fingerprint_phrase(user_id, &der::public_key(&private_key)))
+        }
+
+        /// Encrypts the user's key with someone else's public key (DER encoded
+        /// SPKI), the counterpart of `shared_key()`.
+        pub fn share_key(&self, public_key: &[u8]) -> Result<String, CipherError> {
+                let public_key = RSAPublicKey::from_pkcs8(public_key)
+                        .map_err(|e| CipherError::SharedKeyError(e.to_string()))?;
+
+                let data = public_key.encrypt(&mut OsRng, PaddingScheme::new_oaep::<Sha1>(), &self.key()?)
+                        .map_err(|e| CipherError::SharedKeyError(e.to_string()))?;
+
+                Ok(format!("4.{}", base64::encode(&data)))
+        }
+
+        /// Encrypts the key of `other` with this suite's master key, e.g. to
+        /// store it as the protected key of an account.
+        pub fn protect_key(&self, other: &CipherSuite) -> Result<CipherString, CipherError> {
+                CipherString::encrypt_raw(&other.key()?, &self.master_key, &self.mac_key)
+        }
+
+        /// Derives the master key of `new_password` with the same `kdf` and
+        /// protects this suite's vault key with it, which stays the same so
+        /// that nothing needs to be encrypted again. Returns the hash of the
+        /// new master key along with the new protected key.
+        pub fn change_master_password(&self, email: &str, new_password: &str, kdf: &Kdf)
+                -> Result<(String, CipherString), CipherError>
+        {
+                let master = CipherSuite::with_kdf(email, new_password, kdf)?;
+                let key = master.protect_key(self)?;
+
+                Ok((master.master_key_hash.clone(), key))
+        }
+
+        /// Generates the vault key and key pair of a new account, protected by
+        /// the given master password.
+        pub fn generate_account(email: &str, password: &str, kdf_iterations: usize)
+                -> Result<AccountKeys, CipherError>
+        {
+                let master = CipherSuite::from(email, password, kdf_iterations);
+
+                let mut key = [0u8; 64];
+                OsRng.fill_bytes(&mut key);
+                let user = CipherSuite::from_key(&key)?;
+
+                let private_key = RSAPrivateKey::new(&mut OsRng, 2048)
+                        .map_err(|e| CipherError::SharedKeyError(e.to_string()))?;
+
+                let encrypted_private_key = CipherString::encrypt_raw(
+                        &der::private_key(&private_key),
+                        user.decrypt_key.as_ref().ok_or(CipherError::InvalidKeyLength)?,
+                        &user.mac_key,
+                )?;
+
+                Ok(AccountKeys {
+                        key: master.protect_key(&user)?,
+                        master_key_hash: master.master_key_hash,
+                        public_key: base64::encode(&der::public_key(&private_key)),
+                        private_key: encrypted_private_key,
+                })
+        }
+
+        /// Builds a suite from a raw 64 byte key, encryption key followed by
+        /// MAC key.
+        pub fn from_key(key: &[u8]) -> Result<CipherSuite, CipherError> {
+                if key.len() != 64 {
+                        return Err(CipherError::InvalidKeyLength);
+                }
+
+                Ok(CipherSuite {
+                        master_key: Vec::new(),
+                        master_key_hash: String::new(),
+                        mac_key: Vec::from(&key[32..64]),
+                        decrypt_key: Some(Vec::from(&key[0..32])),
+                })
+        }
+
+        /// Decrypts the key material of a send and derives the send's own
+        /// suite from it. The material is needed for the send's link.
+        pub fn send_key(&self, key: &CipherString) -> Result<(Vec<u8>, CipherSuite), CipherError> {
+                let decrypt_key = self.decrypt_key.as_ref()
+                        .ok_or(CipherError::InvalidKeyLength)?;
+                let material = key.decrypt_raw(decrypt_key, &self.mac_key)?;
+
+                let hkdf = Hkdf::<Sha256>::new(Some(&b"bitwarden-send"[..]), &material);
+                let mut send_key = [0u8; 64];
+                hkdf.expand(b"send", &mut send_key)
+                        .map_err(|_| CipherError::InvalidKeyLength)?;
+
+                Ok((material, CipherSuite::from_key(&send_key)?))
+        }
+
+        fn key(&self) -> Result<Vec<u8>, CipherError> {
+                let mut key = self.decrypt_key.clone()
+                        .ok_or(CipherError::InvalidKeyLength)?;
+                key.extend_from_slice(&self.mac_key);
+
+                Ok(key)
+        }
+
+        /// Decrypts all given cipher strings, spread across all available cores.
+        pub fn decrypt_all(&self, values: &[&CipherString]) -> Vec<Option<String>> {
+                values.par_iter()
+                        .map(|value| value.decrypt(self))
+                        .collect()
+        }
+}
+
+// Also runs while unwinding from a panic, so no keys are left behind in freed
+// memory.
+impl Drop for CipherSuite {
+        fn drop(&mut self) {
+                scrub(&mut self.master_key);
+                scrub(&mut self.mac_key);
+
+                if let Some(key) = self.decrypt_key.as_mut() {
+                        scrub(key);
+                }
+
+                // Safe as zeros are valid UTF-8
+                scrub(unsafe { self.master_key_hash.as_mut_vec() });
+        }
+}
+
+
+/// Overwrites key material with zeros, in a way the compiler can't optimize
+/// away.
+fn scrub(bytes: &mut Vec<u8>) {
+        for byte in bytes.iter_mut() {
+                unsafe { ptr::write_volatile(byte, 0) };
+        }
+
+        atomic::compiler_fence(atomic::Ordering::SeqCst);
+}
+
+
+/// Random password of `length` characters from letters and digits, plus
+/// symbols if `symbols` is set. Easily confused characters are left out.
+pub fn generate_password(length: usize, symbols: bool) -> String {
+        const ALPHANUMERIC: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz23456789";
+        const SYMBOLS: &[u8] = b"!@#$%^&*-_=+?";
+
+        let mut charset = ALPHANUMERIC.to_vec();
+        if symbols {
+                charset.extend_from_slice(SYMBOLS);
+        }
+
+        let mut rng = OsRng;
+        (0..length)
+                .filter_map(|_| charset.choose(&mut rng))
+                .map(|c| *c as char)
+                .collect()
+}
+
+
+fn argon2id(email: &str, password: &str, kdf: &Kdf) -> Result<Vec<u8>, CipherError> {
+        let lanes = kdf.parallelism.unwrap_or(ARGON2_PARALLELISM) as u32;
+        let config = Argon2Config {
+                variant: Variant::Argon2id,
+                version: Version::Version13,
+                mem_cost: (kdf.memory.unwrap_or(ARGON2_MEMORY) * 1024) as u32,
+                time_cost: kdf.iterations as u32,
+                lanes,
+                thread_mode: ThreadMode::from_threads(lanes),
+                hash_length: 32,
+                ..Argon2Config::default()
+        };
+
+        // The email is hashed to get a salt long enough
+        let salt = Sha256::digest(email.as_bytes());
+
+        argon2::hash_raw(password.as_bytes(), &salt, &config)
+                .map_err(|e| CipherError::KdfError(e.to_string()))
+}
+
+
+impl AuthRequestKeys {
+        pub fn generate() -> Result<Self, CipherError> {
+                let private_key = RSAPrivateKey::new(&mut OsRng, 2048)
+                        .map_err(|e| CipherError::SharedKeyError(e.to_string()))?;
+                let public_key = der::public_key(&private_key);
+
+                Ok(AuthRequestKeys { private_key, public_key })
+        }
+
+        /// Public key as sent along with the request, base64 encoded.
+        pub fn public_key(&self) -> String {
+                base64::encode(&self.public_key)
+        }
+
+        /// Decrypts what the approving device encrypted with the public key.
+        pub fn decrypt(&self, encrypted: &str) -> Result<Vec<u8>, CipherError> {
+                rsa_decrypt(&self.private_key, encrypted)
+        }
+
+        /// Fingerprint phrase the approving device shows for the request of
+        /// `email`.
+        pub fn fingerprint(&self, email: &str) -> String {
+                fingerprint_phrase(email, &self.public_key)
+        }
+}
+
+
+/// Phrase identifying `public_key` (DER encoded SPKI) along with `material`,
+/// the same as other clients show: the SHA-256 of the key is expanded with
+/// HKDF, then read as a big number picking words of the EFF long word list.
+pub fn fingerprint_phrase(material: &str, public_key: &[u8]) -> String {
+        let key_fingerprint = Sha256::digest(public_key);
+
+        let mut fingerprint = [0u8; 32];
+        Hkdf::<Sha256>::from_prk(&key_fingerprint)
+                .unwrap()
+                .expand(material.as_bytes(), &mut fingerprint)
+                .unwrap();
+
+        let words = &eff_wordlist::large::LIST;
+
+        (0..FINGERPRINT_WORDS)
+                .map(|_| words[divide(&mut fingerprint, words.len() as u32) as usize].1)
+                .collect::<Vec<&str>>()
+                .join("-")
+}
+
+
+/// Divides the big-endian number `number` by `divisor` in place, returning
+/// the remainder.
+fn divide(number: &mut [u8], divisor: u32) -> u32 {
+        let mut remainder = 0;
+
+        for byte in number.iter_mut() {
+                let value = (remainder << 8) | u32::from(*byte);
+                *byte = (value / divisor) as u8;
+                remainder = value % divisor;
+        }
+
+        remainder
+}
+
+
+fn rsa_decrypt(private_key: &RSAPrivateKey, encrypted: &str) -> Result<Vec<u8>, CipherError> {
+        // Either "4.<data>" or "6.<data>|<mac>", both RSA-OAEP with SHA-1
+        let mut parts = encrypted.splitn(2, '.');
+        let type_ = parts.next().ok_or(CipherError::InvalidKeyType)?;
+        let data = parts.next()
+                .and_then(|data| data.split('|').next())
+                .ok_or(CipherError::InvalidKeyType)?;
+
+        if type_ != "4" && type_ != "6" {
+                return Err(CipherError::InvalidKeyType);
+        }
+
+        let data = base64::decode(data)
+                .map_err(|e| CipherError::SharedKeyError(e.to_string()))?;
+
+        private_key.decrypt(PaddingScheme::new_oaep::<Sha1>(), &data)
+                .map_err(|e| CipherError::SharedKeyError(e.to_string()))
+}
+
+
+/// Expands the master key into the encryption and MAC keys.
+fn stretch_master_key(master_key: &[u8]) -> (Vec<u8>, Vec<u8>) {
+        let hkdf = Hkdf::<Sha256>::from_prk(master_key).unwrap();
+
+        let mut enc_key = vec![0u8; 32];
+        hkdf.expand("enc".as_bytes(), &mut enc_key).unwrap();
+
+        let mut mac_key = vec![0u8; 32];
+        hkdf.expand("mac".as_bytes(), &mut mac_key).unwrap();
+
+        (enc_key, mac_key)
+}
+
+
+fn pbkdf2_master_key(email: &str, password: &str, iterations: usize) -> Vec<u8> {
+        let mut master_key = vec![0u8; 32];
+        pbkdf2::<Hmac<Sha256>>(
+                password.as_bytes(), email.as_bytes(), iterations, &mut master_key
+        );
+
+        master_key
+}
+
+/// Hashes the master key for logging in and expands it into the encryption
+/// and MAC keys.
+fn stretch_derived_key(mut master_key: Vec<u8>, password: &str) -> (Vec<u8>, String, Vec<u8>) {
+        let mut master_key_hash = [0u8; 32];
+        pbkdf2::<Hmac<Sha256>>(
+                &master_key, password.as_bytes(), 1, &mut master_key_hash
+        );
+
+        let (enc_key, mac_key) = stretch_master_key(&master_key);
+        scrub(&mut master_key);
+
+        (enc_key, base64::encode(&master_key_hash), mac_key)
+}
+
+
+/// Parsed cipher string, IV, ciphertext and MAC are kept decoded in a single
+/// buffer (in that order).
+#[derive(Clone, Debug)]
+pub struct CipherString {
+        type_: usize,
+
+        data: Box<[u8]>,
+        ct_start: usize,
+        mac_start: usize,
+}
+
+
+impl CipherString {
+        fn from_str(text: &str) -> Option<CipherString> {
+                let type_end = text.find('.')?;
+                let type_ = text[0..type_end].parse::<usize>().ok()?;
+
+                let mut parts = text[type_end+1..].split('|');
+                let mut data = Vec::with_capacity(text.len() * 3 / 4);
+
+                base64::decode_config_buf(parts.next()?, base64::STANDARD, &mut data).ok()?;
+                let ct_start = data.len();
+                base64::decode_config_buf(parts.next()?, base64::STANDARD, &mut data).ok()?;
+                let mac_start = data.len();
+                base64::decode_config_buf(parts.next()?, base64::STANDARD, &mut data).ok()?;
+
+                Some(CipherString {
+                        type_,
+                        data: data.into_boxed_slice(),
+                        ct_start,
+                        mac_start,
+                })
+        }
+
+        fn as_str(&self) -> String {
+                format!("{}.{}|{}|{}",
+                        self.type_,
+                        base64::encode(self.iv()),
+                        base64::encode(self.ct()),
+                        base64::encode(self.mac()),
+                )
+        }
+
+        fn iv(&self) -> &[u8] {
+                &self.data[..self.ct_start]
+        }
+
+        fn ct(&self) -> &[u8] {
+                &self.data[self.ct_start..self.mac_start]
+        }
+
+        fn mac(&self) -> &[u8] {
+                &self.data[self.mac_start..]
+        }
+
+        fn is_valid_mac(&self, mac_key: &[u8]) -> bool {
+                if mac_key.len() != 32 {
+                        return false;
+                }
+
+                // IV and ciphertext are stored back to back, which is exactly
+                // the message the MAC is computed over.
+                let mut mac = Hmac::<Sha256>::new_varkey(mac_key).unwrap();
+                mac.input(&self.data[..self.mac_start]);
+
+                mac.verify(self.mac()).is_ok()
+        }
+
+        /// Checks the MAC only, without decrypting anything.
+        pub fn verify(&self, cipher: &CipherSuite) -> bool {
+                self.type_ == 2 && self.is_valid_mac(&cipher.mac_key)
+        }
+
+        pub fn decrypt_raw(&self, key: &[u8], mac: &[u8]) -> Result<Vec<u8>, CipherError> {
+                if self.type_ != 2 {
+                        return Err(CipherError::InvalidKeyType);
+                }
+
+                if !self.is_valid_mac(mac) {
+                        return Err(CipherError::InvalidMac);
+                }
+
+                // Currently only one cipher (type 2) is supported/used by bitwarden:
+                //   pbkdf2/aes-cbc-256/hmac-sha256
+
+                Cbc::<Aes256, Pkcs7>::new_var(key, self.iv())
+                        .map_err(|_| CipherError::InvalidKeyLength)?
+                        .decrypt_vec(self.ct())
+                        .map_err(|_| CipherError::BlockModeError)
+        }
+
+        /// Encrypts `plain` as a type 2 cipher string, using a random IV.
+        pub fn encrypt(plain: &str, cipher: &CipherSuite) -> Option<CipherString> {
+                Self::encrypt_raw(plain.as_bytes(), cipher.decrypt_key.as_ref()?, &cipher.mac_key).ok()
+        }
+
+        pub fn encrypt_raw(plain: &[u8], key: &[u8], mac_key: &[u8]) -> Result<CipherString, CipherError> {
+                let mut iv = [0u8; 16];
+                OsRng.fill_bytes(&mut iv);
+
+                let ct = Cbc::<Aes256, Pkcs7>::new_var(key, &iv)
+                        .map_err(|_| CipherError::InvalidKeyLength)?
+                        .encrypt_vec(plain);
+
+                let mut data = Vec::with_capacity(iv.len() + ct.len() + 32);
+                data.extend_from_slice(&iv);
+                data.extend_from_slice(&ct);
+
+                let mut mac = Hmac::<Sha256>::new_varkey(mac_key)
+                        .map_err(|_| CipherError::InvalidKeyLength)?;
+                mac.input(&data);
+
+                let mac_start = data.len();
+                data.extend_from_slice(&mac.result().code());
+
+                Ok(CipherString {
+                        type_: 2,
+                        data: data.into_boxed_slice(),
+                        ct_start: iv.len(),
+                        mac_start,
+                })
+        }
+
+        /// Decrypts with `from` and encrypts the result again with `to`, e.g.
+        /// to hand an item over to an organization.
+        pub fn reencrypt(&self, from: &CipherSuite, to: &CipherSuite) -> Result<CipherString, CipherError> {
+                let from_key = from.decrypt_key.as_ref().ok_or(CipherError::InvalidKeyLength)?;
+                let to_key = to.decrypt_key.as_ref().ok_or(CipherError::InvalidKeyLength)?;
+
+                let plain = self.decrypt_raw(from_key, &from.mac_key)?;
+                Self::encrypt_raw(&plain, to_key, &to.mac_key)
+        }
+
+        /// Decrypts to text, `None` if the MAC doesn't match or the vault
+        /// key isn't set yet.
+        pub fn decrypt(&self, cipher: &CipherSuite) -> Option<String> {
+                self.decrypt_raw(cipher.decrypt_key.as_ref()?, &cipher.mac_key)
+                        .ok()
+                        .and_then(|s| String::from_utf8(s).ok())
+        }
+}
+
+
+struct CipherStringVisitor;
+
+impl<'de> Visitor<'de> for CipherStringVisitor {
+        type Value = CipherString;
+
+        fn expecting(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+                fmt.write_str("valid cipher string")
+        }
+
+        fn visit_str<E: serde::de::Error>(self, value: &str) -> Result<CipherString, E> {
+                CipherString::from_str(value)
+                        .ok_or(E::custom("invalid cipher string"))
+        }
+}
+
+// Binary formats (i.e. the local cache) store the decoded buffer as-is, to
+// avoid base64 round-trips on every load.
+
+impl<'de> Deserialize<'de> for CipherString {
+        fn deserialize<D>(deserializer: D) -> Result<CipherString, D::Error>
+                where D: Deserializer<'de>
+        {
+                if deserializer.is_human_readable() {
+                        return deserializer.deserialize_str(CipherStringVisitor);
+                }
+
+                let (type_, ct_start, mac_start, data) =
+                        <(usize, usize, usize, ByteBuf)>::deserialize(deserializer)?;
+
+                if ct_start > mac_start || mac_start > data.len() {
+                        return Err(D::Error::custom("invalid cipher string"));
+                }
+
+                Ok(CipherString {
+                        type_,
+                        data: data.into_vec().into_boxed_slice(),
+                        ct_start,
+                        mac_start,
+                })
+        }
+}
+
+impl Serialize for CipherString {
+        fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+                where S: Serializer
+        {
+                if serializer.is_human_readable() {
+                        serializer.serialize_str(&self.as_str())
+                } else {
+                        (self.type_, self.ct_start, self.mac_start, Bytes::new(&self.data))
+                                .serialize(serializer)
+                }
+        }
+}
+
+
+#[cfg(test)]
+mod tests {
+        use super::*;
+
+        // Stands in for a DER encoded public key, only its hash matters
+        fn public_key() -> Vec<u8> {
+                (0..=255).collect()
+        }
+
+        fn phrase(indices: &[usize]) -> String {
+                indices.iter()
+                        .map(|i| eff_wordlist::large::LIST[*i].1)
+                        .collect::<Vec<&str>>()
+                        .join("-")
+        }
+
+        #[test]
+        fn divide_small_number() {
+                let mut number = [0x01, 0x00];
+
+                assert_eq!(divide(&mut number, 7), 4);
+                assert_eq!(number, [0x00, 36]);
+        }
+
+        #[test]
+        fn divide_hash_sized_number() {
+                let mut number = [0xff; 32];
+
+                assert_eq!(divide(&mut number, 7776), 7359);
+                assert_eq!(&number[..], &[
+                        0x00, 0x08, 0x6d, 0x90, 0x54, 0x47, 0xa3, 0x4a,
+                        0xcc, 0x60, 0xeb, 0xfb, 0xc9, 0x37, 0xd5, 0xdc,
+                        0x2e, 0x5a, 0x99, 0xcf, 0x8a, 0x02, 0x1b, 0x64,
+                        0x15, 0x11, 0xe8, 0xd2, 0xb3, 0x18, 0x3a, 0xfe,
+                ][..]);
+        }
+
+        #[test]
+        fn divide_zero() {
+                let mut number = [0u8; 32];
+
+                assert_eq!(divide(&mut number, 7776), 0);
+                assert_eq!(number, [0u8; 32]);
+        }
+
+        #[test]
+        fn wordlist_matches_bitwarden() {
+                // The EFF long list, as used by the official clients
+                assert_eq!(eff_wordlist::large::LIST.len(), 7776);
+                assert_eq!(eff_wordlist::large::LIST[0].1, "abacus");
+                assert_eq!(eff_wordlist::large::LIST[7775].1, "zoom");
+        }
+
+        // Expected word indices computed independently: HKDF-SHA256 expand of
+        // the material with SHA256(public key) as PRK, read as big-endian
+        // number and divided by 7776 five times.
+
+        #[test]
+        fn fingerprint_of_user_id() {
+                let material = "a09726a0-9590-49d1-a5f5-afe300b6a515";
+
+                assert_eq!(fingerprint_phrase(material, &public_key()),
+                           phrase(&[7062, 1942, 3469, 3641, 4096]));
+        }
+
+        #[test]
+        fn fingerprint_of_email() {
+                let material = "someone@example.com";
+
+                assert_eq!(fingerprint_phrase(material, &public_key()),
+                           phrase(&[6848, 6204, 822, 7651, 3751]));
+        }
+
+        #[test]
+        fn fingerprint_depends_on_key() {
+                let material = "someone@example.com";
+
+                assert_ne!(fingerprint_phrase(material, &public_key()),
+                           fingerprint_phrase(material, &public_key()[1..]));
+        }
+}
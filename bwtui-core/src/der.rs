@@ -0,0 +1,151 @@
+// SPDX-License-Identifier: MIT
+
+// Minimal DER encoding of RSA keys, as expected by the bitwarden servers and
+// clients (PKCS#8 private keys, SubjectPublicKeyInfo public keys).
+
+use rsa::{BigUint, PublicKey, RSAPrivateKey};
+
+
+// 1.2.840.113549.1.1.1
+const RSA_ENCRYPTION_OID: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x01];
+
+const TAG_INTEGER: u8 = 0x02;
+const TAG_BIT_STRING: u8 = 0x03;
+const TAG_OCTET_STRING: u8 = 0x04;
+const TAG_NULL: u8 = 0x05;
+const TAG_OID: u8 = 0x06;
+const TAG_SEQUENCE: u8 = 0x30;
+
+
+fn encode(tag: u8, content: &[u8]) -> Vec<u8> {
+        let mut out = vec![tag];
+        let len = content.len();
+
+        if len < 0x80 {
+                out.push(len as u8);
+        } else {
+                let bytes = len.to_be_bytes();
+                let skip = bytes.iter().take_while(|b| **b == 0).count();
+
+                out.push(0x80 | (bytes.len() - skip) as u8);
+                out.extend_from_slice(&bytes[skip..]);
+        }
+
+        out.extend_from_slice(content);
+        out
+}
+
+fn integer(value: &BigUint) -> Vec<u8> {
+        let mut bytes = value.to_bytes_be();
+
+        // Integers are signed, keep positive values positive
+        if bytes[0] & 0x80 != 0 {
+                bytes.insert(0, 0);
+        }
+
+        encode(TAG_INTEGER, &bytes)
+}
+
+fn sequence(items: &[Vec<u8>]) -> Vec<u8> {
+        encode(TAG_SEQUENCE, &items.concat())
+}
+
+fn algorithm_identifier() -> Vec<u8> {
+        sequence(&[encode(TAG_OID, RSA_ENCRYPTION_OID), encode(TAG_NULL, &[])])
+}
+
+/// Encodes the private key as PKCS#8 PrivateKeyInfo.
+pub fn private_key(key: &RSAPrivateKey) -> Vec<u8> {
+        let primes = key.primes();
+        let (p, q) = (&primes[0], &primes[1]);
+        let one = BigUint::from(1u32);
+
+        let dp = key.d() % (p - &one);
+        let dq = key.d() % (q - &one);
+        // p is prime, so q^(p-2) is the inverse of q mod p
+        let qinv = q.modpow(&(p - BigUint::from(2u32)), p);
+
+        let pkcs1 = sequence(&[
+                integer(&BigUint::from(0u32)),
+                integer(key.n()),
+                integer(key.e()),
+                integer(key.d()),
+                integer(p),
+                integer(q),
+                integer(&dp),
+                integer(&dq),
+                integer(&qinv),
+        ]);
+
+        sequence(&[
+                integer(&BigUint::from(0u32)),
+                algorithm_identifier(),
+                encode(TAG_OCTET_STRING, &pkcs1),
+        ])
+}
+
+/// Encodes the public part of the key as SubjectPublicKeyInfo.
+pub fn public_key<K>(key: &K) -> Vec<u8>
+        where K: PublicKey
+{
+        let pkcs1 = sequence(&[integer(key.n()), integer(key.e())]);
+
+        // No unused bits
+        let mut bits = vec![0];
+        bits.extend_from_slice(&pkcs1);
+
+        sequence(&[algorithm_identifier(), encode(TAG_BIT_STRING, &bits)])
+}
+
+
+#[cfg(test)]
+mod tests {
+        use super::*;
+
+        use rand::rngs::OsRng;
+        use rsa::RSAPublicKey;
+
+        fn key() -> RSAPrivateKey {
+                // Small keys are enough to check the encoding, and fast
+                RSAPrivateKey::new(&mut OsRng, 512).unwrap()
+        }
+
+        #[test]
+        fn encode_short_length() {
+                assert_eq!(encode(TAG_OCTET_STRING, &[]), vec![0x04, 0x00]);
+                assert_eq!(encode(TAG_OCTET_STRING, &[0xaa; 0x7f])[..2], [0x04, 0x7f]);
+        }
+
+        #[test]
+        fn encode_long_length() {
+                assert_eq!(encode(TAG_OCTET_STRING, &[0xaa; 0x80])[..3], [0x04, 0x81, 0x80]);
+                assert_eq!(encode(TAG_OCTET_STRING, &[0xaa; 0x1234])[..4], [0x04, 0x82, 0x12, 0x34]);
+        }
+
+        #[test]
+        fn integer_stays_positive() {
+                assert_eq!(integer(&BigUint::from(0x7fu32)), vec![0x02, 0x01, 0x7f]);
+                assert_eq!(integer(&BigUint::from(0x80u32)), vec![0x02, 0x02, 0x00, 0x80]);
+                assert_eq!(integer(&BigUint::from(0u32)), vec![0x02, 0x01, 0x00]);
+        }
+
+        #[test]
+        fn private_key_round_trip() {
+                let key = key();
+                let parsed = RSAPrivateKey::from_pkcs8(&private_key(&key)).unwrap();
+
+                assert_eq!(parsed.n(), key.n());
+                assert_eq!(parsed.e(), key.e());
+                assert_eq!(parsed.d(), key.d());
+                assert_eq!(parsed.primes(), key.primes());
+        }
+
+        #[test]
+        fn public_key_round_trip() {
+                let key = key();
+                let parsed = RSAPublicKey::from_pkcs8(&public_key(&key)).unwrap();
+
+                assert_eq!(parsed.n(), key.n());
+                assert_eq!(parsed.e(), key.e());
+        }
+}
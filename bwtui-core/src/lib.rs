@@ -0,0 +1,29 @@
+// SPDX-License-Identifier: MIT
+
+//! Bitwarden access without the TUI: logging in, syncing, decrypting and
+//! changing items, and the local cache in the data directory of bwtui.
+//!
+//! `api::Api` talks to the servers, `cipher::CipherSuite` holds the keys and
+//! `cipher::CipherString` the encrypted values:
+//!
+//! ```no_run
+//! use bwtui_core::api::Api;
+//!
+//! let api = Api::new();
+//! let mut auth = api.authenticate("user@example.com", "master password")?;
+//! let vault = api.sync(&auth)?;
+//! auth.cipher.set_decrypt_key(&vault.profile.key)?;
+//!
+//! for item in &vault.ciphers {
+//!         println!("{}", item.name.decrypt(&auth.cipher).unwrap_or_default());
+//! }
+//! # Ok::<(), failure::Error>(())
+//! ```
+//!
+//! Items are changed with `Api::create_cipher`, `Api::update_cipher` and
+//! `Api::delete_cipher`, values being encrypted with
+//! `CipherString::encrypt` beforehand.
+
+pub mod api;
+pub mod cipher;
+mod der;
@@ -0,0 +1,603 @@
+// SPDX-License-Identifier: MIT
+
+use std::sync::Arc;
+
+use chrono::Local;
+use cursive::traits::*;
+use cursive::views::{Dialog, DummyView, EditView, LinearLayout, SelectView, TextView};
+use cursive::Cursive;
+use log::{info, warn};
+
+use crate::api::{self, Api, ApiError, AuthData, AuthRequest, Device};
+use crate::cipher::CipherSuite;
+use crate::hooks::{self, Hook};
+use crate::login;
+use crate::policy;
+use crate::register::MIN_PASSWORD_LENGTH;
+use crate::vault::{self, Session, StartupView};
+use crate::worker;
+
+const MIN_PIN_LENGTH: usize = 4;
+
+/// Logs out of this device: the session is revoked on the server and all
+/// that's cached for the account is removed, back to the login form.
+pub fn logout(siv: &mut Cursive) {
+    siv.add_layer(
+        Dialog::text(
+            "Log out of this device? The cached vault is removed, along with \
+             changes not synced yet.",
+        )
+        .title("log out")
+        .button("Log out", confirm_logout)
+        .dismiss_button("Cancel")
+        .max_width(60),
+    );
+}
+
+fn confirm_logout(siv: &mut Cursive) {
+    let session = match siv.take_user_data::<Session>() {
+        Some(session) => session,
+        None => return,
+    };
+
+    info!("logging out");
+    hooks::run(Hook::Lock, &[]);
+
+    while siv.pop_layer().is_some() {}
+
+    let email = session.vault.profile.email.clone();
+    let auth = session.auth.clone();
+
+    worker::run(
+        move |api| {
+            // Nothing to revoke offline, the token expires on its own
+            if let Err(err) = api.revoke(&auth) {
+                warn!("failed to revoke the session: {}", err);
+            }
+
+            api::remove_account_data()
+        },
+        move |siv, removed| {
+            login::ask(siv, None, StartupView::default());
+            login::logged_out(siv, &email);
+
+            if let Err(err) = removed {
+                siv.add_layer(Dialog::info(format!(
+                    "Failed to remove the cached data: {}",
+                    err
+                )));
+            }
+        },
+    );
+}
+
+/// Sets a PIN to unlock the vault with on this device instead of the master
+/// password, or removes it.
+pub fn set_pin(siv: &mut Cursive) {
+    let email = match siv.user_data::<Session>() {
+        Some(session) => session.vault.profile.email.clone(),
+        None => return,
+    };
+
+    let layout = LinearLayout::vertical()
+        .child(TextView::new(
+            "The PIN unlocks the vault on this device instead of the master \
+             password. The vault key is stored encrypted with it, so prefer a \
+             long PIN. It's forgotten after 5 wrong attempts.",
+        ))
+        .child(DummyView)
+        .child(TextView::new("PIN:"))
+        .child(EditView::new().secret().with_name("new_pin"))
+        .child(TextView::new("PIN again:"))
+        .child(EditView::new().secret().with_name("new_pin_confirm"));
+
+    let mut dialog = Dialog::around(layout)
+        .title("unlock with PIN")
+        .button("Set", confirm_pin);
+
+    if api::has_pin_key(&email) {
+        dialog.add_button("Remove", |siv| {
+            siv.pop_layer();
+
+            let message = match api::remove_pin_key() {
+                Ok(()) => "The PIN was removed".to_owned(),
+                Err(err) => format!("Failed to remove the PIN: {}", err),
+            };
+            siv.add_layer(Dialog::info(message));
+        });
+    }
+
+    siv.add_layer(dialog.dismiss_button("Cancel").min_width(60));
+}
+
+fn confirm_pin(siv: &mut Cursive) {
+    let pin = siv
+        .call_on_name("new_pin", |view: &mut EditView| view.get_content())
+        .unwrap()
+        .to_string();
+    let confirmation = siv
+        .call_on_name("new_pin_confirm", |view: &mut EditView| view.get_content())
+        .unwrap()
+        .to_string();
+
+    if pin.chars().count() < MIN_PIN_LENGTH {
+        siv.add_layer(Dialog::info(format!(
+            "The PIN needs at least {} characters",
+            MIN_PIN_LENGTH
+        )));
+        return;
+    }
+
+    if pin != confirmation {
+        siv.add_layer(Dialog::info("The PINs don't match"));
+        return;
+    }
+
+    let (email, kdf, cipher) = match siv.user_data::<Session>() {
+        Some(session) => (
+            session.vault.profile.email.clone(),
+            session.auth.kdf(),
+            Arc::clone(&session.cipher),
+        ),
+        None => return,
+    };
+
+    siv.pop_layer();
+    siv.add_layer(Dialog::text("Setting the PIN ..."));
+
+    worker::run(
+        move |_| api::save_pin_key(&email, &pin, &kdf, &cipher),
+        |siv, result| {
+            siv.pop_layer();
+
+            let message = match result {
+                Ok(()) => "The PIN unlocks the vault from now on".to_owned(),
+                Err(err) => format!("Failed to set the PIN: {}", err),
+            };
+            siv.add_layer(Dialog::info(message));
+        },
+    );
+}
+
+fn content(siv: &mut Cursive, name: &str) -> String {
+    siv.call_on_name(name, |view: &mut EditView| view.get_content())
+        .unwrap()
+        .to_string()
+}
+
+/// Changes the master password. The vault key stays the same, only its
+/// protected copy on the server changes.
+pub fn change_password(siv: &mut Cursive) {
+    let hint = match siv.user_data::<Session>() {
+        Some(session) => session
+            .vault
+            .profile
+            .master_password_hint
+            .clone()
+            .unwrap_or_default(),
+        None => return,
+    };
+
+    let layout = LinearLayout::vertical()
+        .child(TextView::new(
+            "All devices are logged out afterwards, this one asks to log in \
+             with the new master password.",
+        ))
+        .child(DummyView)
+        .child(TextView::new("current master password:"))
+        .child(EditView::new().secret().with_name("change_current"))
+        .child(TextView::new("new master password:"))
+        .child(EditView::new().secret().with_name("change_password"))
+        .child(TextView::new("repeat new master password:"))
+        .child(EditView::new().secret().with_name("change_repeat"))
+        .child(TextView::new("password hint (optional):"))
+        .child(EditView::new().content(hint).with_name("change_hint"));
+
+    siv.add_layer(
+        Dialog::around(layout)
+            .title("change master password")
+            .button("Change", confirm_change_password)
+            .dismiss_button("Cancel")
+            .min_width(60),
+    );
+}
+
+fn confirm_change_password(siv: &mut Cursive) {
+    let current = content(siv, "change_current");
+    let password = content(siv, "change_password");
+    let repeat = content(siv, "change_repeat");
+    let hint = content(siv, "change_hint").trim().to_owned();
+
+    let session = match siv.user_data::<Session>() {
+        Some(session) => session,
+        None => return,
+    };
+    let email = session.vault.profile.email.clone();
+    let kdf = session.auth.kdf();
+
+    // Checked against the vault key, the hash is missing when unlocked with
+    // a PIN
+    let mut current_cipher = match CipherSuite::with_kdf(&email, &current, &kdf) {
+        Ok(cipher) => cipher,
+        Err(err) => return siv.add_layer(Dialog::info(err.to_string())),
+    };
+    let unmet = policy::unmet_requirements(&session.vault, &password);
+
+    let error = if current_cipher
+        .set_decrypt_key(&session.vault.profile.key)
+        .is_err()
+    {
+        Some("Wrong current master password".to_owned())
+    } else if password.chars().count() < MIN_PASSWORD_LENGTH {
+        Some(format!(
+            "The master password needs at least {} characters",
+            MIN_PASSWORD_LENGTH
+        ))
+    } else if password != repeat {
+        Some("The new master passwords don't match".to_owned())
+    } else if password == current {
+        Some("The new master password is the same as the current one".to_owned())
+    } else if !hint.is_empty() && hint.contains(&password) {
+        Some("The hint must not contain the master password".to_owned())
+    } else if !unmet.is_empty() {
+        Some(format!(
+            "Your organizations require the master password to have:\n\n- {}",
+            unmet.join("\n- ")
+        ))
+    } else {
+        None
+    };
+
+    if let Some(error) = error {
+        siv.add_layer(Dialog::info(error));
+        return;
+    }
+
+    let (hash, key) = match session
+        .cipher
+        .change_master_password(&email, &password, &kdf)
+    {
+        Ok(changed) => changed,
+        Err(err) => {
+            siv.add_layer(Dialog::info(err.to_string()));
+            return;
+        }
+    };
+    let current_hash = current_cipher.master_key_hash.clone();
+
+    siv.pop_layer();
+    siv.add_layer(Dialog::text("Changing the master password ..."));
+
+    vault::request(
+        siv,
+        move |api, auth| {
+            let hint = Some(hint.as_str()).filter(|hint| !hint.is_empty());
+
+            api.change_password(auth, &current_hash, &hash, hint, &key)
+        },
+        move |siv, result: Result<(), ApiError>| {
+            siv.pop_layer();
+
+            match result {
+                Ok(()) => password_changed(siv, &email),
+                Err(err) => siv.add_layer(Dialog::info(err.to_string())),
+            }
+        },
+    );
+}
+
+/// Closes the vault after the master password was changed. The cached vault
+/// holds the vault key protected by the old one and the tokens got revoked,
+/// so it's discarded and logging in again syncs afresh.
+fn password_changed(siv: &mut Cursive, email: &str) {
+    if siv.take_user_data::<Session>().is_none() {
+        return;
+    }
+
+    info!("master password changed, discarding the cache");
+    hooks::run(Hook::Lock, &[]);
+
+    let removed = api::remove_app_data();
+
+    while siv.pop_layer().is_some() {}
+
+    login::ask(siv, None, StartupView::default());
+    login::password_updated(siv, email);
+
+    if let Err(err) = removed {
+        siv.add_layer(Dialog::info(format!(
+            "Failed to remove the cached data: {}",
+            err
+        )));
+    }
+}
+
+/// Logs out all sessions of the account, after asking for the master
+/// password again.
+pub fn deauthorize(siv: &mut Cursive) {
+    let layout = LinearLayout::vertical()
+        .child(TextView::new(
+            "This logs out all devices, including this one. bwtui quits afterwards.",
+        ))
+        .child(TextView::new("master password:"))
+        .child(EditView::new().secret().with_name("deauthorize_password"));
+
+    siv.add_layer(
+        Dialog::around(layout)
+            .title("log out everywhere")
+            .button("Log out", confirm_deauthorize)
+            .dismiss_button("Cancel")
+            .min_width(60),
+    );
+}
+
+fn confirm_deauthorize(siv: &mut Cursive) {
+    let password = siv
+        .call_on_name("deauthorize_password", |view: &mut EditView| {
+            view.get_content()
+        })
+        .unwrap();
+
+    let hash = match siv.user_data::<Session>() {
+        Some(session) => {
+            let mut cipher = match CipherSuite::with_kdf(
+                &session.vault.profile.email,
+                &password,
+                &session.auth.kdf(),
+            ) {
+                Ok(cipher) => cipher,
+                Err(err) => return siv.add_layer(Dialog::info(err.to_string())),
+            };
+
+            // Don't bother the server with an obviously wrong password. The
+            // session has no hash to compare with after a PIN unlock, but the
+            // password has to decrypt the vault key either way
+            match cipher.set_decrypt_key(&session.vault.profile.key) {
+                Ok(()) => Some(cipher.master_key_hash.clone()),
+                Err(_) => None,
+            }
+        }
+        None => return,
+    };
+
+    let hash = match hash {
+        Some(hash) => hash,
+        None => {
+            siv.add_layer(Dialog::info("Wrong master password"));
+            return;
+        }
+    };
+
+    siv.pop_layer();
+
+    vault::request(
+        siv,
+        move |api, auth| api.deauthorize_sessions(auth, &hash),
+        |siv, result: Result<(), ApiError>| {
+            if let Err(err) = result {
+                siv.add_layer(Dialog::info(err.to_string()));
+                return;
+            }
+
+            // The cached token is useless now, the next start needs a full
+            // login anyway.
+            let message = match api::remove_auth_data() {
+                Ok(()) => "All sessions were logged out.".to_owned(),
+                Err(err) => format!("All sessions were logged out, but {}", err),
+            };
+
+            siv.add_layer(Dialog::text(message).button("Quit", |siv| siv.quit()));
+        },
+    );
+}
+
+fn device_type(type_: usize) -> &'static str {
+    match type_ {
+        0 => "Android",
+        1 => "iOS",
+        2 => "Chrome extension",
+        3 => "Firefox extension",
+        4 => "Opera extension",
+        5 => "Edge extension",
+        6 => "Windows desktop",
+        7 => "macOS desktop",
+        8 => "Linux desktop",
+        9 => "Chrome",
+        10 => "Firefox",
+        11 => "Opera",
+        12 => "Edge",
+        13 => "Internet Explorer",
+        14 => "browser",
+        15 => "Android (Amazon)",
+        16 => "UWP",
+        17 => "Safari",
+        18 => "Vivaldi",
+        19 => "Vivaldi extension",
+        20 => "Safari extension",
+        21 => "SDK",
+        22 => "server",
+        23 => "Windows CLI",
+        24 => "macOS CLI",
+        25 => "Linux CLI",
+        _ => "unknown",
+    }
+}
+
+/// Shows the account logged in to, along with its fingerprint phrase. It's
+/// compared with what other devices or emergency contacts are shown, to make
+/// sure they talk to this account.
+pub fn show(siv: &mut Cursive) {
+    let session = match siv.user_data::<Session>() {
+        Some(session) => session,
+        None => return,
+    };
+    let profile = &session.vault.profile;
+
+    let fingerprint = match &profile.private_key {
+        Some(private_key) => session
+            .cipher
+            .account_fingerprint(&profile.uuid.to_hyphenated().to_string(), private_key)
+            .unwrap_or_else(|err| format!("unknown, {}", err)),
+        None => "unknown, the account has no key pair".to_owned(),
+    };
+
+    let yes_no = |value| if value { "yes" } else { "no" };
+
+    let text = format!(
+        "name: {}\nemail address: {} (verified: {})\npremium: {}\n\
+         two-step login: {}\n\nfingerprint phrase:\n{}",
+        profile.name,
+        profile.email,
+        yes_no(profile.email_verified),
+        yes_no(profile.premium),
+        yes_no(profile.tfa_enabled),
+        fingerprint
+    );
+
+    siv.add_layer(
+        Dialog::around(TextView::new(text))
+            .title("account")
+            .dismiss_button("Close")
+            .max_width(70),
+    );
+}
+
+/// Lists the devices of the account as well as pending login requests of new
+/// devices.
+pub fn devices(siv: &mut Cursive) {
+    vault::request(
+        siv,
+        |api, auth| {
+            let devices = api.devices(auth)?;
+            // Older servers don't know about login requests at all
+            let requests = api.auth_requests(auth).unwrap_or_default();
+
+            Ok((devices, requests))
+        },
+        |siv, result: Result<(Vec<Device>, Vec<AuthRequest>), ApiError>| match result {
+            Ok((devices, requests)) => show_devices(siv, devices, requests),
+            Err(err) => siv.add_layer(Dialog::info(err.to_string())),
+        },
+    );
+}
+
+fn show_devices(siv: &mut Cursive, mut devices: Vec<Device>, requests: Vec<AuthRequest>) {
+    let current = api::device_identifier().to_hyphenated().to_string();
+    let email = match siv.user_data::<Session>() {
+        Some(session) => session.vault.profile.email.clone(),
+        None => return,
+    };
+
+    // Most recently used first
+    devices.sort_by(|a, b| b.last_activity_date.cmp(&a.last_activity_date));
+
+    let mut device_view = SelectView::new().on_submit(|siv, device: &Device| {
+        let id = device.uuid;
+
+        siv.add_layer(
+            Dialog::text(format!("Remove \"{}\" and log it out?", device.name))
+                .button("Remove", move |siv| {
+                    siv.pop_layer();
+                    change(siv, move |api, auth| api.delete_device(auth, id));
+                })
+                .dismiss_button("Cancel"),
+        );
+    });
+
+    for device in devices {
+        let last_active = device
+            .last_activity_date
+            .unwrap_or(device.creation_date)
+            .with_timezone(&Local)
+            .format("%Y-%m-%d %H:%M");
+
+        let mut label = format!(
+            "{} ({}), last active {}",
+            device.name,
+            device_type(device.type_),
+            last_active
+        );
+
+        if device.identifier == current {
+            label.push_str(" [this device]");
+        }
+
+        device_view.add_item(label, device);
+    }
+
+    let mut request_view = SelectView::new().on_submit(move |siv, request: &AuthRequest| {
+        let id = request.uuid;
+        let fingerprint = request
+            .fingerprint(&email)
+            .unwrap_or_else(|| "unknown".to_owned());
+
+        siv.add_layer(
+            Dialog::text(format!(
+                "Deny the login request of {} from {}? Its fingerprint phrase \
+                 is\n\n{}",
+                request.device_type, request.ip_address, fingerprint
+            ))
+            .button("Deny", move |siv| {
+                siv.pop_layer();
+                change(siv, move |api, auth| api.deny_auth_request(auth, id));
+            })
+            .dismiss_button("Cancel"),
+        );
+    });
+
+    for request in requests
+        .into_iter()
+        .filter(|request| request.approved.is_none())
+    {
+        let label = format!(
+            "{} from {}, {}",
+            request.device_type,
+            request.ip_address,
+            request
+                .creation_date
+                .with_timezone(&Local)
+                .format("%Y-%m-%d %H:%M")
+        );
+
+        request_view.add_item(label, request);
+    }
+
+    let mut layout = LinearLayout::vertical()
+        .child(TextView::new("Devices (<Enter>: remove):"))
+        .child(device_view);
+
+    if !request_view.is_empty() {
+        layout.add_child(DummyView);
+        layout.add_child(TextView::new("Pending login requests (<Enter>: deny):"));
+        layout.add_child(request_view);
+    }
+
+    siv.add_layer(
+        Dialog::around(layout.scrollable())
+            .title("devices")
+            .dismiss_button("Close")
+            .with_name("devices_dialog")
+            .min_width(70),
+    );
+}
+
+/// Runs a request changing the device list, showing the updated list after.
+fn change<F>(siv: &mut Cursive, request: F)
+where
+    F: Fn(&Api, &AuthData) -> Result<(), ApiError> + Send + 'static,
+{
+    vault::request(
+        siv,
+        request,
+        |siv, result: Result<(), ApiError>| match result {
+            Ok(()) => {
+                if siv.find_name::<Dialog>("devices_dialog").is_some() {
+                    siv.pop_layer();
+                }
+
+                devices(siv);
+            }
+            Err(err) => siv.add_layer(Dialog::info(err.to_string())),
+        },
+    );
+}
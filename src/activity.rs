@@ -0,0 +1,119 @@
+// SPDX-License-Identifier: MIT
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use chrono::{DateTime, Local, Utc};
+use cursive::traits::*;
+use cursive::views::{Dialog, TextView};
+use cursive::Cursive;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::api;
+use crate::cipher::CipherString;
+use crate::vault::Session;
+
+// Older entries are dropped
+const MAX_ENTRIES: usize = 1000;
+
+// Set from the command line, off by default
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Sensitive action, stored encrypted with the vault key next to the cache.
+#[derive(Deserialize, Serialize)]
+struct Entry {
+    at: DateTime<Utc>,
+    action: String,
+    item: Option<Uuid>,
+    // Name at the time, the item may be renamed or deleted later
+    name: Option<String>,
+}
+
+/// Starts recording sensitive actions.
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+/// Records `action`, e.g. "copied password", done on `item` if recording is
+/// enabled. Failing to record doesn't keep the action from happening.
+pub fn record(siv: &mut Cursive, action: &str, item: Option<Uuid>) {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
+
+    let session = match siv.user_data::<Session>() {
+        Some(session) => session,
+        None => return,
+    };
+
+    let name = item.and_then(|uuid| {
+        session
+            .vault
+            .ciphers
+            .iter()
+            .find(|entry| entry.uuid == uuid)
+            .and_then(|entry| entry.name.decrypt(&session.cipher))
+    });
+
+    let entry = Entry {
+        at: Utc::now(),
+        action: action.to_owned(),
+        item,
+        name,
+    };
+
+    let encrypted = serde_json::to_string(&entry)
+        .ok()
+        .and_then(|entry| CipherString::encrypt(&entry, &session.cipher));
+
+    if let Some(encrypted) = encrypted {
+        let mut entries = api::read_activity().unwrap_or_default();
+        entries.push(encrypted);
+
+        let excess = entries.len().saturating_sub(MAX_ENTRIES);
+        let _ = api::save_activity(&entries[excess..]);
+    }
+}
+
+/// Lists the recorded actions, latest first.
+pub fn show(siv: &mut Cursive) {
+    let entries = match siv.user_data::<Session>() {
+        Some(session) => api::read_activity()
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|entry| entry.decrypt(&session.cipher))
+            .filter_map(|entry| serde_json::from_str::<Entry>(&entry).ok())
+            .collect::<Vec<Entry>>(),
+        None => return,
+    };
+
+    let content = if entries.is_empty() {
+        if ENABLED.load(Ordering::Relaxed) {
+            "Nothing recorded yet".to_owned()
+        } else {
+            "Nothing recorded, start bwtui with --log-actions to record when \
+             passwords are copied, typed or exported"
+                .to_owned()
+        }
+    } else {
+        entries
+            .iter()
+            .rev()
+            .map(|entry| {
+                let at = entry.at.with_timezone(&Local).format("%Y-%m-%d %H:%M");
+                match &entry.name {
+                    Some(name) => format!("{}  {}: {}", at, entry.action, name),
+                    None => format!("{}  {}", at, entry.action),
+                }
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    };
+
+    siv.add_layer(
+        Dialog::around(TextView::new(content).scrollable())
+            .title("sensitive actions")
+            .dismiss_button("Close")
+            .min_width(60),
+    );
+}
@@ -0,0 +1,444 @@
+// SPDX-License-Identifier: MIT
+
+use std::collections::{BTreeMap, HashMap};
+use std::thread;
+
+use chrono::{Local, Utc};
+
+use cursive::traits::*;
+use cursive::views::{Dialog, SelectView};
+use cursive::Cursive;
+use rayon::prelude::*;
+use uuid::Uuid;
+
+use crate::activity;
+use crate::api::{ApiError, VaultData};
+use crate::cipher::{CipherString, CipherSuite};
+use crate::detail;
+use crate::merge;
+use crate::probe;
+use crate::rotation::{self, State};
+use crate::uri;
+use crate::vault::{self, Session};
+
+/// Items which look like the same account, and why.
+#[derive(Clone)]
+struct Cluster {
+    reason: String,
+    // Names along with the UUIDs
+    items: Vec<(String, Uuid)>,
+}
+
+/// Lists the available checks of the vault.
+pub fn show(siv: &mut Cursive) {
+    let mut select = SelectView::new().on_submit(|siv, check: &fn(&mut Cursive)| {
+        siv.pop_layer();
+        check(siv);
+    });
+
+    select.add_item("Duplicate items", duplicates as fn(&mut Cursive));
+    select.add_item("Unreachable sites", unreachable_sites);
+    select.add_item("Password rotation", rotations);
+    select.add_item("Sensitive actions", activity::show);
+
+    siv.add_layer(
+        Dialog::around(select)
+            .title("audit")
+            .dismiss_button("Cancel"),
+    );
+}
+
+fn decrypt(value: Option<&CipherString>, cipher: &CipherSuite) -> Option<String> {
+    value
+        .and_then(|value| value.decrypt(cipher))
+        .filter(|value| !value.is_empty())
+}
+
+fn add_to(groups: &mut HashMap<String, Vec<Uuid>>, key: String, uuid: Uuid) {
+    let group = groups.entry(key).or_default();
+    if !group.contains(&uuid) {
+        group.push(uuid);
+    }
+}
+
+/// What duplicates are told apart by, decrypted.
+struct Login {
+    uuid: Uuid,
+    name: String,
+    password: Option<String>,
+    username: Option<String>,
+    hosts: Vec<String>,
+}
+
+/// Groups the items sharing a username on the same host, or sharing a
+/// password.
+fn find_duplicates(vault: &VaultData, cipher: &CipherSuite) -> Vec<Cluster> {
+    let mut logins = HashMap::new();
+    let mut passwords = HashMap::new();
+    let mut names = HashMap::new();
+
+    // The whole vault gets decrypted, using all cores
+    let decrypted = vault
+        .ciphers
+        .par_iter()
+        .filter(|entry| entry.deleted_date.is_none())
+        .map(|entry| {
+            let data = &entry.data;
+
+            let hosts = data
+                .uris
+                .iter()
+                .flatten()
+                .filter_map(|uri| uri.uri.as_ref())
+                .chain(data.uri.iter())
+                .filter_map(|value| decrypt(Some(value), cipher))
+                .map(|value| uri::host(&value.to_lowercase()).to_owned())
+                .filter(|host| !host.is_empty())
+                .collect();
+
+            Login {
+                uuid: entry.uuid,
+                name: entry.name.decrypt(cipher).unwrap_or_default(),
+                password: decrypt(data.password.as_ref(), cipher),
+                username: decrypt(data.username.as_ref(), cipher),
+                hosts,
+            }
+        })
+        .collect::<Vec<Login>>();
+
+    for login in decrypted {
+        names.insert(login.uuid, login.name);
+
+        if let Some(password) = login.password {
+            add_to(&mut passwords, password, login.uuid);
+        }
+
+        let username = match login.username {
+            Some(username) => username.to_lowercase(),
+            None => continue,
+        };
+
+        for host in login.hosts {
+            add_to(&mut logins, format!("{} on {}", username, host), login.uuid);
+        }
+    }
+
+    let cluster = |reason: String, uuids: Vec<Uuid>| {
+        let mut items = uuids
+            .into_iter()
+            .map(|uuid| (names[&uuid].clone(), uuid))
+            .collect::<Vec<(String, Uuid)>>();
+        items.sort();
+
+        Cluster { reason, items }
+    };
+
+    let mut clusters = logins
+        .into_iter()
+        .filter(|(_, uuids)| uuids.len() > 1)
+        .map(|(login, uuids)| cluster(format!("same login {}", login), uuids))
+        .collect::<Vec<Cluster>>();
+    clusters.sort_by(|a, b| a.reason.cmp(&b.reason));
+
+    // Never show the shared password itself
+    let mut shared = passwords
+        .into_iter()
+        .filter(|(_, uuids)| uuids.len() > 1)
+        .map(|(_, uuids)| cluster("same password".to_owned(), uuids))
+        .collect::<Vec<Cluster>>();
+    shared.sort_by(|a, b| a.items.cmp(&b.items));
+
+    clusters.extend(shared);
+    clusters
+}
+
+/// Shows groups of items which are likely duplicates, e.g. left over from
+/// an import.
+fn duplicates(siv: &mut Cursive) {
+    let clusters = match siv.user_data::<Session>() {
+        Some(session) => find_duplicates(&session.vault, &session.cipher),
+        None => return,
+    };
+
+    if clusters.is_empty() {
+        siv.add_layer(Dialog::info("No duplicate items found"));
+        return;
+    }
+
+    let mut select = SelectView::new().on_submit(show_cluster);
+    for cluster in clusters {
+        let label = format!("{} items: {}", cluster.items.len(), cluster.reason);
+        select.add_item(label, cluster);
+    }
+
+    siv.add_layer(
+        Dialog::around(select.scrollable())
+            .title("duplicate items")
+            .dismiss_button("Close")
+            .with_name("duplicates_dialog")
+            .min_width(60),
+    );
+}
+
+fn show_cluster(siv: &mut Cursive, cluster: &Cluster) {
+    let items = cluster.items.clone();
+
+    let mut select = SelectView::new().on_submit(|siv, uuid: &Uuid| detail::show(siv, *uuid));
+    for (name, uuid) in &cluster.items {
+        select.add_item(name.clone(), *uuid);
+    }
+
+    siv.add_layer(
+        Dialog::around(select.with_name("cluster_items").scrollable())
+            .title(cluster.reason.clone())
+            .button("Open", |siv| {
+                if let Some(uuid) = selected(siv) {
+                    detail::show(siv, uuid);
+                }
+            })
+            .button("Merge", move |siv| {
+                if let Some(uuid) = selected(siv) {
+                    choose_merge(siv, uuid, &items);
+                }
+            })
+            .button("Delete", |siv| {
+                if let Some(uuid) = selected(siv) {
+                    confirm_delete(siv, uuid);
+                }
+            })
+            .dismiss_button("Close")
+            .with_name("cluster_dialog")
+            .min_width(50),
+    );
+}
+
+fn selected(siv: &mut Cursive) -> Option<Uuid> {
+    siv.call_on_name("cluster_items", |view: &mut SelectView<Uuid>| {
+        view.selection()
+    })
+    .and_then(|selection| selection)
+    .map(|uuid| *uuid)
+}
+
+/// Asks which other item of the group to merge into `kept`.
+fn choose_merge(siv: &mut Cursive, kept: Uuid, items: &[(String, Uuid)]) {
+    let others = items
+        .iter()
+        .filter(|(_, uuid)| *uuid != kept)
+        .cloned()
+        .collect::<Vec<(String, Uuid)>>();
+
+    if let [(_, other)] = others.as_slice() {
+        start_merge(siv, kept, *other);
+        return;
+    }
+
+    let mut select = SelectView::new().on_submit(move |siv, other: &Uuid| {
+        siv.pop_layer();
+        start_merge(siv, kept, *other);
+    });
+
+    for (name, uuid) in others {
+        select.add_item(name, uuid);
+    }
+
+    siv.add_layer(
+        Dialog::around(select.scrollable())
+            .title("merge into the selected item")
+            .dismiss_button("Cancel"),
+    );
+}
+
+fn start_merge(siv: &mut Cursive, kept: Uuid, other: Uuid) {
+    // The groups are outdated once the items are merged
+    for name in &["cluster_dialog", "duplicates_dialog"] {
+        if siv.find_name::<Dialog>(name).is_some() {
+            siv.pop_layer();
+        }
+    }
+
+    merge::show(siv, kept, other);
+}
+
+fn confirm_delete(siv: &mut Cursive, uuid: Uuid) {
+    siv.add_layer(
+        Dialog::text("Move this item to the trash?")
+            .button("Delete", move |siv| {
+                siv.pop_layer();
+
+                vault::request(
+                    siv,
+                    move |api, auth| api.delete_cipher(auth, uuid),
+                    |siv, result: Result<(), ApiError>| match result {
+                        Ok(()) => {
+                            // Both lists are outdated now
+                            for name in &["cluster_dialog", "duplicates_dialog"] {
+                                if siv.find_name::<Dialog>(name).is_some() {
+                                    siv.pop_layer();
+                                }
+                            }
+
+                            siv.add_layer(Dialog::info("Item moved to the trash"));
+                            vault::sync_in_background(siv);
+                        }
+                        Err(err) => siv.add_layer(Dialog::info(err.to_string())),
+                    },
+                );
+            })
+            .dismiss_button("Cancel"),
+    );
+}
+
+/// Items by the origins of their http(s) URIs.
+fn find_origins(vault: &VaultData, cipher: &CipherSuite) -> BTreeMap<String, Vec<(String, Uuid)>> {
+    let mut origins = BTreeMap::new();
+
+    let entries = vault
+        .ciphers
+        .iter()
+        .filter(|entry| entry.deleted_date.is_none());
+
+    for entry in entries {
+        let data = &entry.data;
+        let name = entry.name.decrypt(cipher).unwrap_or_default();
+
+        let uris = data
+            .uris
+            .iter()
+            .flatten()
+            .filter_map(|uri| uri.uri.as_ref())
+            .chain(data.uri.iter());
+
+        for value in uris {
+            if let Some(origin) = decrypt(Some(value), cipher).and_then(|uri| probe::origin(&uri)) {
+                let items: &mut Vec<(String, Uuid)> = origins.entry(origin).or_default();
+                if !items.iter().any(|(_, uuid)| *uuid == entry.uuid) {
+                    items.push((name.clone(), entry.uuid));
+                }
+            }
+        }
+    }
+
+    origins
+}
+
+/// Looks for items whose sites don't answer anymore. Every site gets
+/// contacted, so this only runs after asking.
+fn unreachable_sites(siv: &mut Cursive) {
+    let origins = match siv.user_data::<Session>() {
+        Some(session) => find_origins(&session.vault, &session.cipher),
+        None => return,
+    };
+
+    if origins.is_empty() {
+        siv.add_layer(Dialog::info("No items with web sites"));
+        return;
+    }
+
+    siv.add_layer(
+        Dialog::text(format!(
+            "This sends a request to each of the {} sites of your items, which \
+             tells them (and your network) which sites you have accounts on. \
+             Continue?",
+            origins.len()
+        ))
+        .title("unreachable sites")
+        .button("Check", move |siv| {
+            siv.pop_layer();
+            check_origins(siv, origins.clone());
+        })
+        .dismiss_button("Cancel"),
+    );
+}
+
+fn check_origins(siv: &mut Cursive, origins: BTreeMap<String, Vec<(String, Uuid)>>) {
+    siv.add_layer(
+        Dialog::text(format!("Checking {} sites ...", origins.len()))
+            .title("unreachable sites")
+            .with_name("probe_dialog"),
+    );
+
+    let cb_sink = siv.cb_sink().clone();
+
+    thread::spawn(move || {
+        let result = probe::unreachable(origins.keys().cloned().collect());
+
+        let _ = cb_sink.send(Box::new(move |siv: &mut Cursive| {
+            if siv.find_name::<Dialog>("probe_dialog").is_some() {
+                siv.pop_layer();
+            }
+
+            match result {
+                Ok(unreachable) => show_unreachable(siv, &origins, unreachable),
+                Err(err) => siv.add_layer(Dialog::info(err)),
+            }
+        }));
+    });
+}
+
+fn show_unreachable(
+    siv: &mut Cursive,
+    origins: &BTreeMap<String, Vec<(String, Uuid)>>,
+    mut unreachable: Vec<String>,
+) {
+    if unreachable.is_empty() {
+        siv.add_layer(Dialog::info("All sites answered"));
+        return;
+    }
+
+    unreachable.sort();
+
+    let mut select = SelectView::new().on_submit(|siv, uuid: &Uuid| detail::show(siv, *uuid));
+    for origin in &unreachable {
+        for (name, uuid) in &origins[origin] {
+            select.add_item(format!("{} ({})", name, origin), *uuid);
+        }
+    }
+
+    siv.add_layer(
+        Dialog::around(select.scrollable())
+            .title(format!("{} sites didn't answer", unreachable.len()))
+            .dismiss_button("Close")
+            .min_width(60),
+    );
+}
+
+/// Lists the items with a rotation policy, most urgent first.
+fn rotations(siv: &mut Cursive) {
+    let rotations = match siv.user_data::<Session>() {
+        Some(session) => rotation::rotations(&session.vault, &session.cipher, Utc::now()),
+        None => return,
+    };
+
+    if rotations.is_empty() {
+        siv.add_layer(Dialog::info(format!(
+            "No items have a rotation policy. Add a custom field \"{}\" with a \
+             period like \"90d\" to an item, or end a folder name with \
+             \"(rotate 90d)\".",
+            rotation::POLICY_FIELD
+        )));
+        return;
+    }
+
+    let mut select = SelectView::new().on_submit(|siv, uuid: &Uuid| detail::show(siv, *uuid));
+    for rotation in rotations {
+        let state = match rotation.state {
+            State::Overdue => "overdue since",
+            State::DueSoon => "due on",
+            State::Fine => "next on",
+        };
+
+        let due = rotation.due.with_timezone(&Local).format("%Y-%m-%d");
+        select.add_item(
+            format!("{} ({} {})", rotation.name, state, due),
+            rotation.uuid,
+        );
+    }
+
+    siv.add_layer(
+        Dialog::around(select.scrollable())
+            .title("password rotation")
+            .dismiss_button("Close")
+            .min_width(60),
+    );
+}
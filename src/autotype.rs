@@ -0,0 +1,295 @@
+// SPDX-License-Identifier: MIT
+
+// Talks to the window system, to type item values into other windows by
+// driving xdotool (X11) or wtype (Wayland) and to find the focused window.
+
+use std::env;
+use std::process::Command;
+use std::str::Chars;
+use std::thread;
+use std::time::Duration;
+
+use chrono::Utc;
+use serde_json::Value;
+
+use crate::api::CipherEntry;
+use crate::cipher::{CipherString, CipherSuite};
+use crate::totp::Totp;
+
+
+/// Name of the custom field holding an item's own sequence, e.g.
+/// `{USERNAME}{TAB}{TAB}{PASSWORD}{ENTER}` for awkward login forms.
+pub const SEQUENCE_FIELD: &str = "autotype";
+
+pub const DEFAULT_SEQUENCE: &str = "{USERNAME}{TAB}{PASSWORD}{ENTER}";
+
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Key {
+        Tab,
+        Enter,
+        Space,
+}
+
+/// Part of a parsed sequence.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Token {
+        Text(String),
+        Username,
+        Password,
+        Totp,
+        // {S:<name>}, value of a custom field
+        Field(String),
+        Key(Key),
+        Delay(Duration),
+}
+
+/// Step of a sequence with all values filled in.
+pub enum Action {
+        Type(String),
+        Key(Key),
+        Delay(Duration),
+}
+
+
+/// Reads the name of a placeholder, up to its closing brace. `{{}` and `{}}`
+/// are literal braces.
+fn placeholder(chars: &mut Chars) -> Result<String, String> {
+        let mut name = String::new();
+
+        while let Some(c) = chars.next() {
+                if c == '}' && !name.is_empty() {
+                        return Ok(name);
+                }
+
+                name.push(c);
+        }
+
+        Err(format!("unterminated placeholder \"{{{}\"", name))
+}
+
+fn token(name: &str) -> Result<Token, String> {
+        if name == "{" || name == "}" {
+                return Ok(Token::Text(name.to_owned()));
+        }
+
+        let is_field = name.get(..2).map_or(false, |prefix| prefix.eq_ignore_ascii_case("S:"));
+        if is_field {
+                return Ok(Token::Field(name[2..].to_owned()));
+        }
+
+        let mut parts = name.split_whitespace();
+        let keyword = parts.next().unwrap_or("").to_uppercase();
+
+        let token = match (keyword.as_str(), parts.next()) {
+                ("USERNAME", None) => Token::Username,
+                ("PASSWORD", None) => Token::Password,
+                ("TOTP", None) => Token::Totp,
+                ("TAB", None) => Token::Key(Key::Tab),
+                ("ENTER", None) => Token::Key(Key::Enter),
+                ("SPACE", None) => Token::Key(Key::Space),
+                ("DELAY", Some(millis)) => {
+                        let millis = millis.parse()
+                                .map_err(|_| format!("invalid delay \"{}\"", millis))?;
+
+                        Token::Delay(Duration::from_millis(millis))
+                }
+                _ => return Err(format!("unknown placeholder \"{{{}}}\"", name)),
+        };
+
+        Ok(token)
+}
+
+/// Parses a KeePass-like auto-type sequence.
+pub fn parse(sequence: &str) -> Result<Vec<Token>, String> {
+        let mut tokens = Vec::new();
+        let mut text = String::new();
+        let mut chars = sequence.chars();
+
+        while let Some(c) = chars.next() {
+                match c {
+                        '{' => match token(&placeholder(&mut chars)?)? {
+                                Token::Text(literal) => text.push_str(&literal),
+                                token => {
+                                        if !text.is_empty() {
+                                                tokens.push(Token::Text(text.split_off(0)));
+                                        }
+
+                                        tokens.push(token);
+                                }
+                        },
+                        '}' => return Err("unmatched \"}\"".to_owned()),
+                        c => text.push(c),
+                }
+        }
+
+        if !text.is_empty() {
+                tokens.push(Token::Text(text));
+        }
+
+        Ok(tokens)
+}
+
+
+fn decrypt(value: Option<&CipherString>, cipher: &CipherSuite) -> Option<String> {
+        value.and_then(|value| value.decrypt(cipher))
+}
+
+/// Builds the actions typing `entry`, following its own sequence if it has
+/// one. `skew` corrects the clock for TOTP codes.
+pub fn actions(entry: &CipherEntry, cipher: &CipherSuite, skew: chrono::Duration)
+        -> Result<Vec<Action>, String>
+{
+        let sequence = match entry.find_field(cipher, SEQUENCE_FIELD) {
+                Some(field) => decrypt(entry.field_value(field), cipher)
+                        .ok_or("the auto-type sequence can't be decrypted")?,
+                None => DEFAULT_SEQUENCE.to_owned(),
+        };
+
+        let value = |token: &Token| match token {
+                Token::Username => decrypt(entry.data.username.as_ref(), cipher),
+                Token::Password => decrypt(entry.data.password.as_ref(), cipher),
+                Token::Totp => decrypt(entry.data.totp.as_ref(), cipher)
+                        .and_then(|totp| Totp::parse(&totp))
+                        .map(|totp| totp.generate(Utc::now(), skew)),
+                Token::Field(name) => entry.find_field(cipher, name)
+                        .and_then(|field| decrypt(entry.field_value(field), cipher)),
+                _ => None,
+        };
+
+        parse(&sequence)?
+                .into_iter()
+                .map(|token| {
+                        let action = match token {
+                                Token::Text(text) => Action::Type(text),
+                                Token::Key(key) => Action::Key(key),
+                                Token::Delay(delay) => Action::Delay(delay),
+                                token => Action::Type(value(&token).ok_or_else(|| match token {
+                                        Token::Field(name) => format!("the item has no field \"{}\"", name),
+                                        Token::Totp => "the item has no valid TOTP secret".to_owned(),
+                                        _ => "the item has no username or password".to_owned(),
+                                })?),
+                        };
+
+                        Ok(action)
+                })
+                .collect()
+}
+
+
+/// Runs `program`, returning its standard output.
+fn output(program: &str, args: &[&str]) -> Result<String, String> {
+        // Capture all output, the terminal belongs to the TUI
+        let output = Command::new(program)
+                .args(args)
+                .output()
+                .map_err(|e| format!("failed to run {}: {}", program, e))?;
+
+        if output.status.success() {
+                Ok(String::from_utf8_lossy(&output.stdout).trim().to_owned())
+        } else {
+                Err(format!("{} failed: {}", program,
+                            String::from_utf8_lossy(&output.stderr).trim()))
+        }
+}
+
+
+#[derive(Clone, Copy)]
+enum Backend {
+        Xdotool,
+        Wtype,
+}
+
+impl Backend {
+        fn detect() -> Self {
+                if env::var_os("WAYLAND_DISPLAY").is_some() {
+                        Backend::Wtype
+                } else {
+                        Backend::Xdotool
+                }
+        }
+
+        fn exec(self, args: &[&str]) -> Result<(), String> {
+                let program = match self {
+                        Backend::Xdotool => "xdotool",
+                        Backend::Wtype => "wtype",
+                };
+
+                output(program, args).map(|_| ())
+        }
+
+        fn type_text(self, text: &str) -> Result<(), String> {
+                match self {
+                        Backend::Xdotool => self.exec(&["type", "--clearmodifiers", "--", text]),
+                        Backend::Wtype => self.exec(&["--", text]),
+                }
+        }
+
+        fn press(self, key: Key) -> Result<(), String> {
+                let name = match key {
+                        Key::Tab => "Tab",
+                        Key::Enter => "Return",
+                        Key::Space => "space",
+                };
+
+                match self {
+                        Backend::Xdotool => self.exec(&["key", "--clearmodifiers", name]),
+                        Backend::Wtype => self.exec(&["-k", name]),
+                }
+        }
+}
+
+
+/// Types the actions into the currently focused window.
+pub fn run(actions: &[Action]) -> Result<(), String> {
+        let backend = Backend::detect();
+
+        for action in actions {
+                match action {
+                        Action::Type(text) if text.is_empty() => {}
+                        Action::Type(text) => backend.type_text(text)?,
+                        Action::Key(key) => backend.press(*key)?,
+                        Action::Delay(delay) => thread::sleep(*delay),
+                }
+        }
+
+        Ok(())
+}
+
+
+fn focused_sway_node(node: &Value) -> Option<&str> {
+        if node["focused"].as_bool() == Some(true) {
+                return node["name"].as_str();
+        }
+
+        node["nodes"].as_array().into_iter()
+                .chain(node["floating_nodes"].as_array())
+                .flatten()
+                .filter_map(focused_sway_node)
+                .next()
+}
+
+/// Title of the currently focused window. Wayland has no common way to get
+/// it, only sway and Hyprland are supported there.
+pub fn active_window_title() -> Result<String, String> {
+        if let Backend::Xdotool = Backend::detect() {
+                return output("xdotool", &["getactivewindow", "getwindowname"]);
+        }
+
+        let sway = output("swaymsg", &["-t", "get_tree"]).and_then(|tree| {
+                let tree: Value = serde_json::from_str(&tree).map_err(|e| e.to_string())?;
+
+                focused_sway_node(&tree)
+                        .map(str::to_owned)
+                        .ok_or_else(|| "no focused window".to_owned())
+        });
+
+        sway.or_else(|_| {
+                let window: Value = serde_json::from_str(&output("hyprctl", &["activewindow", "-j"])?)
+                        .map_err(|e| e.to_string())?;
+
+                window["title"].as_str()
+                        .map(str::to_owned)
+                        .ok_or_else(|| "no focused window".to_owned())
+        })
+}
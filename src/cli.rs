@@ -0,0 +1,124 @@
+// SPDX-License-Identifier: MIT
+
+// Command line of bwtui: the TUI by default, plus subcommands working without
+// it. Completions for the shells are generated from the same definition.
+
+use std::io;
+
+use clap::{App, AppSettings, Arg, ArgMatches, Shell, SubCommand};
+
+
+/// Where the subcommands read the master password from instead of asking.
+fn password_file() -> Arg<'static, 'static> {
+        Arg::with_name("passwordfile")
+                .long("passwordfile")
+                .value_name("path")
+                .help("Reads the master password from the first line of the given file, or of \
+                       stdin for -, instead of asking (BW_PASSWORD is used if set)")
+}
+
+
+/// Definition of all arguments and subcommands.
+pub fn app() -> App<'static, 'static> {
+        App::new("bwtui")
+                .version(env!("CARGO_PKG_VERSION"))
+                .about("TUI for your bitwarden vault")
+                .setting(AppSettings::VersionlessSubcommands)
+                .arg(Arg::with_name("favorites")
+                        .long("favorites")
+                        .help("Only shows favorite items"))
+                .arg(Arg::with_name("folder")
+                        .long("folder")
+                        .value_name("name")
+                        .help("Only shows items in the given folder"))
+                .arg(Arg::with_name("search")
+                        .long("search")
+                        .value_name("query")
+                        .help("Starts with the given search"))
+                .arg(Arg::with_name("pick")
+                        .long("pick")
+                        .conflicts_with("window-title")
+                        .help("Only shows items matching the focused window"))
+                .arg(Arg::with_name("window-title")
+                        .long("window-title")
+                        .value_name("title")
+                        .help("Only shows items matching the given window title"))
+                .arg(Arg::with_name("lock-on-resume")
+                        .long("lock-on-resume")
+                        .help("Locks the vault when resumed after ctrl-z or system sleep, whatever \
+                               the lock settings say"))
+                .arg(Arg::with_name("verbose")
+                        .long("verbose")
+                        .help("Logs requests and other details for debugging"))
+                .arg(Arg::with_name("log-file")
+                        .long("log-file")
+                        .value_name("path")
+                        .help("Logs to the given file instead of bwtui.log in the data directory, \
+                               secrets are left out of logs"))
+                .arg(Arg::with_name("log-actions")
+                        .long("log-actions")
+                        .help("Records when passwords are copied, typed or exported, encrypted \
+                               with the vault key"))
+                .arg(Arg::with_name("server")
+                        .long("server")
+                        .value_name("url")
+                        .help("Talks to the self-hosted server (Vaultwarden or Bitwarden) at the \
+                               given URL instead of the one picked in the setup"))
+                .arg(Arg::with_name("pinentry")
+                        .long("pinentry")
+                        .value_name("program")
+                        .env("BWTUI_PINENTRY")
+                        .help("Asks for the master password and second factor codes with the \
+                               given pinentry program (e.g. pinentry-curses) instead of in the TUI"))
+                .arg(Arg::with_name("reset-device-id")
+                        .long("reset-device-id")
+                        .help("Logs in as a new device, the server lists bwtui as the same \
+                               device across logins otherwise"))
+                // Before the rpc subcommand existed, kept for existing setups
+                .arg(Arg::with_name("rpc")
+                        .long("rpc")
+                        .hidden(true))
+                .subcommand(SubCommand::with_name("get")
+                        .about("Copies the value of a custom field of an item, using only the \
+                                local cache (log in with bwtui once first, or set BW_EMAIL)")
+                        .arg(Arg::with_name("item")
+                                .required(true)
+                                .value_name("item name or id"))
+                        .arg(Arg::with_name("field")
+                                .long("field")
+                                .required(true)
+                                .value_name("custom field name"))
+                        .arg(Arg::with_name("print")
+                                .long("print")
+                                .help("Writes the value to stdout instead"))
+                        .arg(password_file()))
+                .subcommand(SubCommand::with_name("note")
+                        .about("Quickly captures secure notes")
+                        .setting(AppSettings::SubcommandRequiredElseHelp)
+                        .subcommand(SubCommand::with_name("add")
+                                .about("Creates a secure note from stdin, or written in $EDITOR if \
+                                        stdin is a terminal")
+                                .arg(Arg::with_name("title")
+                                        .long("title")
+                                        .value_name("title")
+                                        .help("Name of the note, its first line otherwise"))
+                                .arg(password_file())))
+                .subcommand(SubCommand::with_name("rpc")
+                        .about("Serves JSON-RPC requests on stdin, e.g. for editor integrations"))
+                .subcommand(SubCommand::with_name("completions")
+                        .about("Writes the completions for the given shell to stdout")
+                        .arg(Arg::with_name("shell")
+                                .required(true)
+                                .possible_values(&Shell::variants())))
+}
+
+
+/// Runs `bwtui completions`.
+pub fn completions(matches: &ArgMatches) {
+        // Validated by clap already
+        let shell = matches.value_of("shell")
+                .and_then(|shell| shell.parse::<Shell>().ok())
+                .unwrap();
+
+        app().gen_completions_to("bwtui", shell, &mut io::stdout());
+}
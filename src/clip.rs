@@ -0,0 +1,157 @@
+// SPDX-License-Identifier: MIT
+
+use std::env;
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread;
+use std::time::Duration;
+
+#[cfg(feature = "clipboard")]
+use clipboard::{ClipboardContext, ClipboardProvider};
+use log::warn;
+
+
+// Command taking the text to copy on stdin, e.g. "xclip -selection clipboard",
+// used instead of the built-in clipboard support if set.
+const COMMAND_VAR: &str = "BWTUI_CLIPBOARD_COMMAND";
+
+// Seconds after which copied text is cleared again, 0 to keep it
+static CLEAR_AFTER: AtomicU64 = AtomicU64::new(0);
+// Counts copies, only the last one gets cleared
+static COPIES: AtomicU64 = AtomicU64::new(0);
+
+
+/// Runs `command` through the shell of the platform, so wrappers and quoting
+/// work.
+pub fn shell(command: &str) -> Command {
+        let mut shell = if cfg!(windows) {
+                let mut shell = Command::new("cmd");
+                shell.arg("/C");
+                shell
+        } else {
+                let mut shell = Command::new("sh");
+                shell.arg("-c");
+                shell
+        };
+
+        shell.arg(command);
+        shell
+}
+
+
+fn command() -> Option<String> {
+        env::var(COMMAND_VAR).ok()
+                .filter(|command| !command.trim().is_empty())
+}
+
+
+/// Clears the clipboard `seconds` after each copy, `None` to leave it.
+pub fn set_clear_after(seconds: Option<u64>) {
+        CLEAR_AFTER.store(seconds.unwrap_or(0), Ordering::SeqCst);
+}
+
+
+/// Copies `text` to the system clipboard.
+pub fn copy(text: &str) -> Result<(), String> {
+        match command() {
+                Some(command) => copy_with(&command, text)?,
+                None => copy_builtin(text)?,
+        }
+
+        clear_later(text);
+        Ok(())
+}
+
+
+fn clear_later(text: &str) {
+        let copy = COPIES.fetch_add(1, Ordering::SeqCst) + 1;
+        let seconds = CLEAR_AFTER.load(Ordering::SeqCst);
+
+        if seconds == 0 || text.is_empty() {
+                return;
+        }
+
+        let text = text.to_owned();
+
+        thread::spawn(move || {
+                thread::sleep(Duration::from_secs(seconds));
+
+                // Something else was copied meanwhile
+                if COPIES.load(Ordering::SeqCst) != copy {
+                        return;
+                }
+
+                let cleared = match command() {
+                        Some(command) => copy_with(&command, ""),
+                        None => clear_builtin(&text),
+                };
+
+                if let Err(err) = cleared {
+                        warn!("failed to clear the clipboard: {}", err);
+                }
+        });
+}
+
+
+/// Runs `command`, passing the text only on stdin, never as an argument
+/// visible to other users.
+fn copy_with(command: &str, text: &str) -> Result<(), String> {
+        let mut child = shell(command)
+                .stdin(Stdio::piped())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .spawn()
+                .map_err(|e| format!("failed to run {}: {}", command, e))?;
+
+        if let Some(mut stdin) = child.stdin.take() {
+                stdin.write_all(text.as_bytes())
+                        .map_err(|e| format!("failed to write to {}: {}", command, e))?;
+        }
+
+        let status = child.wait()
+                .map_err(|e| format!("failed to run {}: {}", command, e))?;
+
+        if status.success() {
+                Ok(())
+        } else {
+                Err(format!("{} failed ({})", command, status))
+        }
+}
+
+
+#[cfg(feature = "clipboard")]
+fn copy_builtin(text: &str) -> Result<(), String> {
+        let mut clipboard: ClipboardContext = ClipboardProvider::new()
+                .map_err(|e| e.to_string())?;
+
+        clipboard.set_contents(text.to_owned())
+                .map_err(|e| e.to_string())
+}
+
+
+/// Empties the clipboard, unless something else was put there meanwhile.
+#[cfg(feature = "clipboard")]
+fn clear_builtin(copied: &str) -> Result<(), String> {
+        let mut clipboard: ClipboardContext = ClipboardProvider::new()
+                .map_err(|e| e.to_string())?;
+
+        if clipboard.get_contents().ok().as_ref().map(String::as_str) == Some(copied) {
+                clipboard.set_contents(String::new())
+                        .map_err(|e| e.to_string())?;
+        }
+
+        Ok(())
+}
+
+
+#[cfg(not(feature = "clipboard"))]
+fn clear_builtin(_copied: &str) -> Result<(), String> {
+        Ok(())
+}
+
+
+#[cfg(not(feature = "clipboard"))]
+fn copy_builtin(_text: &str) -> Result<(), String> {
+        Err(format!("bwtui was built without clipboard support, set {} to copy with a command", COMMAND_VAR))
+}
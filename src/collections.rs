@@ -0,0 +1,296 @@
+// SPDX-License-Identifier: MIT
+
+use std::sync::Arc;
+
+use cursive::traits::*;
+use cursive::views::{Dialog, EditView, SelectView};
+use cursive::Cursive;
+use uuid::Uuid;
+
+use crate::api::{ApiError, Collection, Organization};
+use crate::cipher::{CipherString, CipherSuite};
+use crate::vault::{self, Session};
+
+/// Organization whose collections are being managed, along with its key.
+#[derive(Clone)]
+struct Context {
+    organization: Uuid,
+    cipher: Arc<CipherSuite>,
+}
+
+/// Lets admins manage the collections of their organizations.
+pub fn show(siv: &mut Cursive) {
+    let organizations = match siv.user_data::<Session>() {
+        Some(session) => session
+            .vault
+            .profile
+            .organizations
+            .iter()
+            .filter(|org| org.enabled && org.is_admin() && org.key.is_some())
+            .cloned()
+            .collect::<Vec<Organization>>(),
+        None => return,
+    };
+
+    match organizations.len() {
+        0 => siv.add_layer(Dialog::info("You don't administer any organization")),
+        1 => open(siv, &organizations[0]),
+        _ => {
+            let mut select = SelectView::new().on_submit(|siv, org: &Organization| {
+                siv.pop_layer();
+                open(siv, org);
+            });
+
+            for org in organizations {
+                select.add_item(org.name.clone(), org);
+            }
+
+            siv.add_layer(
+                Dialog::around(select)
+                    .title("organization")
+                    .dismiss_button("Cancel"),
+            );
+        }
+    }
+}
+
+fn open(siv: &mut Cursive, organization: &Organization) {
+    let cipher = match siv.user_data::<Session>() {
+        Some(session) => match (&session.vault.profile.private_key, &organization.key) {
+            (Some(private_key), Some(key)) => session.cipher.shared_key(private_key, key),
+            _ => return,
+        },
+        None => return,
+    };
+
+    match cipher {
+        Ok(cipher) => refresh(
+            siv,
+            Context {
+                organization: organization.uuid,
+                cipher: Arc::new(cipher),
+            },
+        ),
+        Err(err) => siv.add_layer(Dialog::info(err.to_string())),
+    }
+}
+
+/// (Re-)fetches the collections and shows them, replacing the list if it is
+/// already shown.
+fn refresh(siv: &mut Cursive, context: Context) {
+    let organization = context.organization;
+
+    vault::request(
+        siv,
+        move |api, auth| api.organization_collections(auth, organization),
+        move |siv, result: Result<Vec<Collection>, ApiError>| match result {
+            Ok(collections) => {
+                if siv
+                    .find_name::<SelectView<Collection>>("collection_list")
+                    .is_some()
+                {
+                    siv.pop_layer();
+                }
+
+                show_collections(siv, context, collections);
+            }
+            Err(err) => siv.add_layer(Dialog::info(err.to_string())),
+        },
+    );
+}
+
+fn show_collections(siv: &mut Cursive, context: Context, collections: Vec<Collection>) {
+    let mut select = SelectView::new();
+
+    for collection in collections {
+        let name = collection
+            .name
+            .decrypt(&context.cipher)
+            .unwrap_or_else(|| collection.uuid.to_string());
+
+        select.add_item(name, collection);
+    }
+
+    select.sort_by_label();
+
+    let new = context.clone();
+    let rename = context.clone();
+    let delete = context.clone();
+    let assign = context;
+
+    siv.add_layer(
+        Dialog::around(select.with_name("collection_list").scrollable())
+            .title("collections")
+            .button("New", move |siv| ask_name(siv, new.clone(), None))
+            .button("Rename", move |siv| {
+                if let Some(collection) = selected(siv) {
+                    ask_name(siv, rename.clone(), Some(collection));
+                }
+            })
+            .button("Delete", move |siv| {
+                if let Some(collection) = selected(siv) {
+                    confirm_delete(siv, delete.clone(), collection);
+                }
+            })
+            .button("Assign item", move |siv| {
+                if let Some(collection) = selected(siv) {
+                    toggle_assignment(siv, assign.clone(), &collection);
+                }
+            })
+            .dismiss_button("Close")
+            .min_width(50),
+    );
+}
+
+fn selected(siv: &mut Cursive) -> Option<Collection> {
+    siv.call_on_name("collection_list", |view: &mut SelectView<Collection>| {
+        view.selection()
+    })
+    .and_then(|selection| selection)
+    .map(|collection| (*collection).clone())
+}
+
+/// Asks for the name of a new collection, or the new name of `collection`.
+fn ask_name(siv: &mut Cursive, context: Context, collection: Option<Collection>) {
+    let current = collection
+        .as_ref()
+        .and_then(|collection| collection.name.decrypt(&context.cipher))
+        .unwrap_or_default();
+
+    let title = if collection.is_some() {
+        "rename collection"
+    } else {
+        "new collection"
+    };
+
+    siv.add_layer(
+        Dialog::around(
+            EditView::new()
+                .content(current)
+                .with_name("collection_name"),
+        )
+        .title(title)
+        .button("Ok", move |siv| {
+            let name = siv
+                .call_on_name("collection_name", |view: &mut EditView| view.get_content())
+                .unwrap();
+
+            if name.trim().is_empty() {
+                return;
+            }
+
+            let name = match CipherString::encrypt(name.trim(), &context.cipher) {
+                Some(name) => name,
+                None => return,
+            };
+
+            siv.pop_layer();
+
+            let organization = context.organization;
+            let collection = collection.clone();
+            let context = context.clone();
+
+            vault::request(
+                siv,
+                move |api, auth| match &collection {
+                    Some(collection) => api.rename_collection(auth, collection, &name),
+                    None => api.create_collection(auth, organization, &name).map(|_| ()),
+                },
+                move |siv, result: Result<(), ApiError>| match result {
+                    Ok(()) => refresh(siv, context),
+                    Err(err) => siv.add_layer(Dialog::info(err.to_string())),
+                },
+            );
+        })
+        .dismiss_button("Cancel")
+        .min_width(40),
+    );
+}
+
+fn confirm_delete(siv: &mut Cursive, context: Context, collection: Collection) {
+    let name = collection
+        .name
+        .decrypt(&context.cipher)
+        .unwrap_or_else(|| collection.uuid.to_string());
+
+    siv.add_layer(
+        Dialog::text(format!(
+            "Delete collection \"{}\"? Its items stay in the organization.",
+            name
+        ))
+        .button("Delete", move |siv| {
+            siv.pop_layer();
+
+            let collection = collection.clone();
+            let context = context.clone();
+
+            vault::request(
+                siv,
+                move |api, auth| api.delete_collection(auth, &collection),
+                move |siv, result: Result<(), ApiError>| match result {
+                    Ok(()) => refresh(siv, context),
+                    Err(err) => siv.add_layer(Dialog::info(err.to_string())),
+                },
+            );
+        })
+        .dismiss_button("Cancel"),
+    );
+}
+
+/// Adds the item selected in the vault to `collection`, or removes it if it
+/// is already assigned to it.
+fn toggle_assignment(siv: &mut Cursive, context: Context, collection: &Collection) {
+    let item = vault::selected_item(siv);
+
+    let collection_ids = match (item, siv.user_data::<Session>()) {
+        (Some(uuid), Some(session)) => session
+            .vault
+            .ciphers
+            .iter()
+            .find(|entry| entry.uuid == uuid)
+            .filter(|entry| entry.organization_id == Some(context.organization))
+            .map(|entry| {
+                let mut ids = entry.collection_ids.clone();
+
+                match ids.iter().position(|id| *id == collection.uuid) {
+                    Some(index) => {
+                        ids.remove(index);
+                    }
+                    None => ids.push(collection.uuid),
+                }
+
+                (uuid, ids)
+            }),
+        _ => return,
+    };
+
+    let (item, collection_ids) = match collection_ids {
+        Some(assignment) => assignment,
+        None => {
+            siv.add_layer(Dialog::info(
+                "Only items of this organization can be assigned to its collections",
+            ));
+            return;
+        }
+    };
+
+    let assigned = collection_ids.contains(&collection.uuid);
+
+    vault::request(
+        siv,
+        move |api, auth| api.set_cipher_collections(auth, item, &collection_ids),
+        move |siv, result: Result<(), ApiError>| match result {
+            Ok(()) => {
+                let message = if assigned {
+                    "Item added to the collection"
+                } else {
+                    "Item removed from the collection"
+                };
+
+                siv.add_layer(Dialog::info(message));
+                vault::sync_in_background(siv);
+            }
+            Err(err) => siv.add_layer(Dialog::info(err.to_string())),
+        },
+    );
+}
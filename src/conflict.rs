@@ -0,0 +1,169 @@
+// SPDX-License-Identifier: MIT
+
+use std::sync::Arc;
+
+use chrono::Local;
+use cursive::traits::*;
+use cursive::views::{Dialog, TextView};
+use cursive::Cursive;
+
+use crate::api::{ApiError, CipherEntry, Update};
+use crate::cipher::{CipherString, CipherSuite};
+use crate::journal::{self, Operation};
+use crate::vault::{self, Session};
+
+type Saved = Arc<dyn Fn(&mut Cursive) + Send + Sync>;
+
+fn decrypt(value: Option<&CipherString>, cipher: &CipherSuite) -> String {
+    value
+        .and_then(|value| value.decrypt(cipher))
+        .unwrap_or_default()
+}
+
+/// Decrypted values of an item to compare, and whether they are secret.
+fn parts(entry: &CipherEntry, cipher: &CipherSuite) -> Vec<(&'static str, String, bool)> {
+    let data = &entry.data;
+
+    let uris = data
+        .uris
+        .iter()
+        .flatten()
+        .filter_map(|uri| uri.uri.as_ref())
+        .chain(data.uri.iter().filter(|_| data.uris.is_none()))
+        .map(|uri| decrypt(Some(uri), cipher))
+        .collect::<Vec<String>>()
+        .join(", ");
+
+    let fields = entry
+        .custom_fields()
+        .iter()
+        .map(|field| {
+            format!(
+                "{}={}",
+                decrypt(field.name.as_ref(), cipher),
+                decrypt(entry.field_value(field), cipher)
+            )
+        })
+        .collect::<Vec<String>>()
+        .join(", ");
+
+    vec![
+        ("name", decrypt(Some(&entry.name), cipher), false),
+        ("username", decrypt(data.username.as_ref(), cipher), false),
+        ("password", decrypt(data.password.as_ref(), cipher), true),
+        ("TOTP secret", decrypt(data.totp.as_ref(), cipher), true),
+        (
+            "notes",
+            decrypt(entry.notes.as_ref().or_else(|| data.notes.as_ref()), cipher),
+            false,
+        ),
+        ("URIs", uris, false),
+        ("custom fields", fields, true),
+    ]
+}
+
+/// Lists what differs between both versions, secrets are only said to differ.
+fn diff(mine: &CipherEntry, theirs: &CipherEntry, cipher: &CipherSuite) -> String {
+    let lines = parts(mine, cipher)
+        .into_iter()
+        .zip(parts(theirs, cipher))
+        .filter(|((_, mine, _), (_, theirs, _))| mine != theirs)
+        .map(|((label, mine, secret), (_, theirs, _))| {
+            if secret {
+                format!("{}: differs", label)
+            } else {
+                format!("{}:\n  mine:   {}\n  theirs: {}", label, mine, theirs)
+            }
+        })
+        .collect::<Vec<String>>();
+
+    if lines.is_empty() {
+        "Both versions have the same values".to_owned()
+    } else {
+        lines.join("\n")
+    }
+}
+
+/// Saves `entry`. Should it have been changed on another device since the
+/// last sync, the user chooses which version to keep instead of overwriting
+/// it. `saved` runs once the item is saved, offline the change is queued
+/// instead.
+pub fn save<F>(siv: &mut Cursive, entry: CipherEntry, saved: F)
+where
+    F: Fn(&mut Cursive) + Send + Sync + 'static,
+{
+    update(siv, entry, Arc::new(saved));
+}
+
+fn update(siv: &mut Cursive, entry: CipherEntry, saved: Saved) {
+    let mine = entry.clone();
+
+    vault::request(
+        siv,
+        move |api, auth| api.update_cipher(auth, &entry),
+        move |siv, result: Result<Update, ApiError>| match result {
+            Ok(Update::Saved) => saved(siv),
+            Ok(Update::Conflict(theirs)) => ask(siv, mine, theirs, saved),
+            Err(ApiError::Offline { .. }) => {
+                journal::queue(siv, Operation::Update(mine));
+                siv.add_layer(Dialog::info(
+                    "Offline, the change is saved here and will be sent on the next sync",
+                ));
+            }
+            Err(err) => siv.add_layer(Dialog::info(err.to_string())),
+        },
+    );
+}
+
+/// Lets the user choose between `mine`, which was queued while offline, and
+/// `theirs`, made on another device meanwhile.
+pub fn resolve(siv: &mut Cursive, mine: CipherEntry, theirs: CipherEntry) {
+    ask(siv, mine, theirs, Arc::new(vault::sync_in_background));
+}
+
+fn ask(siv: &mut Cursive, mine: CipherEntry, theirs: CipherEntry, saved: Saved) {
+    let message = format!(
+        "The item was changed on another device at {}, after it was last synced \
+         here. Saving would overwrite these changes.",
+        theirs
+            .last_changed
+            .with_timezone(&Local)
+            .format("%Y-%m-%d %H:%M")
+    );
+
+    let revision = theirs.last_changed;
+    let (keep_mine, keep_theirs) = (mine.clone(), theirs.clone());
+
+    siv.add_layer(
+        Dialog::around(TextView::new(message))
+            .title("edit conflict")
+            .button("Keep mine", move |siv| {
+                siv.pop_layer();
+
+                // Overwriting is fine now that the user knows
+                let mut mine = keep_mine.clone();
+                mine.last_changed = revision;
+                update(siv, mine, Arc::clone(&saved));
+            })
+            .button("Take theirs", move |siv| {
+                siv.pop_layer();
+                vault::replace_item(siv, keep_theirs.uuid, Some(keep_theirs.clone()));
+                siv.add_layer(Dialog::info("Kept the changes of the other device"));
+            })
+            .button("View diff", move |siv| {
+                let text = match siv.user_data::<Session>() {
+                    Some(session) => diff(&mine, &theirs, &session.cipher),
+                    None => return,
+                };
+
+                siv.add_layer(
+                    Dialog::around(TextView::new(text).scrollable())
+                        .title("differences")
+                        .dismiss_button("Close")
+                        .min_width(50),
+                );
+            })
+            .dismiss_button("Cancel")
+            .min_width(50),
+    );
+}
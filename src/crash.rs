@@ -0,0 +1,87 @@
+// SPDX-License-Identifier: MIT
+
+// Panic handling: the terminal is restored and a redacted crash report is
+// written, rather than leaving the terminal raw with secrets on screen.
+
+use std::any::Any;
+use std::io::Write;
+use std::panic::{self, Location};
+use std::path::PathBuf;
+use std::process;
+use std::thread;
+
+use chrono::Utc;
+use log::error;
+
+use crate::api;
+use crate::redact::redact;
+use crate::suspend;
+
+
+// Same exit code as an unhandled panic
+const EXIT_CODE: i32 = 101;
+
+
+fn message(payload: &(dyn Any + Send)) -> String {
+        let message = payload.downcast_ref::<&str>()
+                .map(|message| message.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "unknown panic".to_owned());
+
+        redact(&message)
+}
+
+fn report(payload: &(dyn Any + Send), location: Option<&Location>) -> String {
+        let location = location
+                .map(|location| format!("{}:{}", location.file(), location.line()))
+                .unwrap_or_default();
+
+        format!(
+                "bwtui {} crashed at {}\n\nthread: {}\nlocation: {}\nmessage: {}\n",
+                env!("CARGO_PKG_VERSION"),
+                Utc::now().to_rfc3339(),
+                thread::current().name().unwrap_or("unnamed"),
+                location,
+                message(payload),
+        )
+}
+
+fn write_report(report: &str) -> Result<PathBuf, String> {
+        let path = api::get_app_data_path()?
+                .join(format!("crash-{}.txt", Utc::now().format("%Y%m%d-%H%M%S")));
+
+        let mut file = api::private_file()
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .open(&path)
+                .map_err(|e| e.to_string())?;
+
+        file.write_all(report.as_bytes())
+                .map_err(|e| e.to_string())?;
+
+        Ok(path)
+}
+
+
+/// Installs the panic hook. A panic in any thread ends bwtui, as the TUI is
+/// gone once the terminal is restored.
+pub fn install() {
+        panic::set_hook(Box::new(|info| {
+                suspend::restore_terminal();
+
+                let report = report(info.payload(), info.location());
+                error!("{}", report);
+
+                match write_report(&report) {
+                        Ok(path) => eprintln!("{}\nA crash report was written to {}", report, path.display()),
+                        Err(_) => eprintln!("{}", report),
+                }
+
+                // Unwinding the main thread drops the vault, scrubbing its keys.
+                // Other threads would leave the UI running without a terminal.
+                if thread::current().name() != Some("main") {
+                        process::exit(EXIT_CODE);
+                }
+        }));
+}
@@ -0,0 +1,113 @@
+// SPDX-License-Identifier: MIT
+
+// Credentials of the subcommands, which run without the TUI: the master
+// password comes from `--passwordfile` (`-` being stdin), `BW_PASSWORD` or is
+// asked for on the terminal, and `BW_EMAIL` logs in when nothing is cached yet.
+// Neither is ever echoed or logged.
+
+use std::env;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::Path;
+
+use log::info;
+
+use crate::api::{self, Api, ApiError, AppData};
+
+
+const EMAIL_VAR: &str = "BW_EMAIL";
+const PASSWORD_VAR: &str = "BW_PASSWORD";
+
+
+/// Master password of `email`, read from `password_file` if given.
+pub fn password(email: &str, password_file: Option<&Path>) -> Result<String, String> {
+        if let Some(path) = password_file {
+                return read_password_file(path);
+        }
+
+        if let Some(password) = env::var_os(PASSWORD_VAR) {
+                // Programs started later on, like $EDITOR, don't need it
+                env::remove_var(PASSWORD_VAR);
+
+                return password.into_string()
+                        .map_err(|_| format!("{} isn't valid UTF-8", PASSWORD_VAR));
+        }
+
+        // On the terminal (the console on Windows), even if stdout is
+        // redirected
+        let prompt = format!("master password for {}: ", email);
+
+        rpassword::read_password_from_tty(Some(&prompt))
+                .map_err(|e| e.to_string())
+}
+
+
+/// First line of `path`, or of stdin for `-`. The rest of stdin is left for
+/// whatever reads it next, e.g. the text of a note.
+fn read_password_file(path: &Path) -> Result<String, String> {
+        let mut line = String::new();
+
+        let read = if path == Path::new("-") {
+                io::stdin().read_line(&mut line)
+        } else {
+                File::open(path)
+                        .and_then(|file| BufReader::new(file).read_line(&mut line))
+                        .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))
+        };
+        read.map_err(|e| e.to_string())?;
+
+        let password = line.trim_end_matches(&['\r', '\n'][..]);
+        if password.is_empty() {
+                return Err("the password file is empty".to_owned());
+        }
+
+        Ok(password.to_owned())
+}
+
+
+/// Cached data of the logged in account along with its master password. If
+/// nothing is cached, logs in as `BW_EMAIL` and syncs first.
+pub fn unlock(password_file: Option<&Path>) -> Result<(AppData, String), String> {
+        let email = env::var(EMAIL_VAR).ok().filter(|email| !email.is_empty());
+
+        match (api::read_app_data(), email) {
+                (Ok(data), Some(email)) if !data.vault.profile.email.eq_ignore_ascii_case(&email) => {
+                        Err(format!("the cached vault is of another account, log out with bwtui \
+                                     first to use {}", EMAIL_VAR))
+                }
+                (Ok(data), _) => {
+                        let password = password(&data.vault.profile.email, password_file)?;
+                        Ok((data, password))
+                }
+                (Err(_), Some(email)) => {
+                        let password = password(&email, password_file)?;
+                        let data = log_in(&email, &password)?;
+                        Ok((data, password))
+                }
+                (Err(_), None) => {
+                        Err(format!("no cached vault, log in with bwtui first or set {}", EMAIL_VAR))
+                }
+        }
+}
+
+
+fn log_in(email: &str, password: &str) -> Result<AppData, String> {
+        let api = Api::new();
+
+        let auth = api.authenticate(email, password).map_err(|err| match err {
+                ApiError::TwoFactorRequired { .. } => {
+                        "two-step login needs bwtui once, tick \"remember this device\" to skip it \
+                         afterwards".to_owned()
+                }
+                err => err.to_string(),
+        })?;
+        let vault = api.sync(&auth)
+                .map_err(|e| e.to_string())?;
+
+        info!("logged in without the TUI, {} items synced", vault.ciphers.len());
+
+        api::save_app_data(&auth, &vault)
+                .map_err(|e| e.to_string())?;
+
+        Ok(AppData { auth, vault })
+}
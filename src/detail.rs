@@ -0,0 +1,201 @@
+// SPDX-License-Identifier: MIT
+
+use std::cell::Cell;
+use std::rc::Rc;
+
+use chrono::{Duration, Utc};
+use cursive::traits::*;
+use cursive::utils::markup::StyledString;
+use cursive::views::{Dialog, DummyView, LinearLayout, TextView};
+use cursive::Cursive;
+use uuid::Uuid;
+
+use crate::api::{
+    CipherEntry, Domains, CIPHER_LOGIN, FIELD_BOOLEAN, FIELD_HIDDEN, FIELD_LINKED, LINKED_PASSWORD,
+    LINKED_USERNAME,
+};
+use crate::cipher::{CipherString, CipherSuite};
+use crate::markdown;
+use crate::totp::{Totp, MAX_CLOCK_SKEW};
+use crate::totp_edit;
+use crate::uri;
+use crate::vault::Session;
+
+fn decrypt_or_empty(value: Option<&CipherString>, cipher: &CipherSuite) -> String {
+    value
+        .and_then(|value| value.decrypt(cipher))
+        .unwrap_or_default()
+}
+
+/// Shows all details of the cipher with the given UUID, decrypting them only
+/// now.
+pub fn show(siv: &mut Cursive, uuid: Uuid) {
+    let dialog = match siv.user_data::<Session>() {
+        Some(session) => session
+            .vault
+            .ciphers
+            .iter()
+            .find(|entry| entry.uuid == uuid)
+            .map(|entry| {
+                let skew = session.vault.clock_skew.unwrap_or_else(Duration::zero);
+                build(entry, &session.cipher, skew, session.vault.domains.as_ref())
+            }),
+        None => return,
+    };
+
+    if let Some(dialog) = dialog {
+        siv.add_layer(dialog.min_width(60));
+    }
+}
+
+fn build(
+    entry: &CipherEntry,
+    cipher: &CipherSuite,
+    skew: Duration,
+    domains: Option<&Domains>,
+) -> Dialog {
+    let mut layout = LinearLayout::vertical();
+
+    if let Some(username) = &entry.data.username {
+        let username = decrypt_or_empty(Some(username), cipher);
+        layout.add_child(TextView::new(format!("Username: {}", username)));
+    }
+
+    if entry.data.password.is_some() {
+        layout.add_child(TextView::new("Password: ********"));
+    }
+
+    if let Some(totp) = &entry.data.totp {
+        let now = Utc::now();
+
+        let line = match Totp::parse(&decrypt_or_empty(Some(totp), cipher)) {
+            Some(totp) => format!(
+                "TOTP: {} ({}s left)",
+                totp.generate(now, skew),
+                totp.remaining(now, skew)
+            ),
+            None => "TOTP: (unsupported format)".to_owned(),
+        };
+        layout.add_child(TextView::new(line));
+
+        // Codes are corrected using the server time, but the user should
+        // still know why other authenticators might disagree.
+        if skew.num_seconds().abs() > MAX_CLOCK_SKEW {
+            layout.add_child(TextView::new(format!(
+                "  warning: local clock is off by {}s",
+                skew.num_seconds()
+            )));
+        }
+    }
+
+    let uris = entry
+        .data
+        .uris
+        .iter()
+        .flatten()
+        .filter_map(|uri| uri.uri.as_ref());
+
+    for uri in entry.data.uri.iter().chain(uris) {
+        let uri = decrypt_or_empty(Some(uri), cipher);
+        layout.add_child(TextView::new(format!("URI: {}", uri)));
+
+        let equivalent = domains
+            .map(|known| known.equivalent_to(uri::host(&uri)))
+            .unwrap_or_default();
+
+        if !equivalent.is_empty() {
+            layout.add_child(TextView::new(format!(
+                "  also matches: {}",
+                equivalent.join(", ")
+            )));
+        }
+    }
+
+    let fields = entry.custom_fields();
+    if !fields.is_empty() {
+        layout.add_child(DummyView);
+        layout.add_child(TextView::new("Custom fields:"));
+    }
+
+    for field in fields {
+        let name = decrypt_or_empty(field.name.as_ref(), cipher);
+
+        let value = match (field.type_, field.linked_id) {
+            (FIELD_HIDDEN, _) | (FIELD_LINKED, Some(LINKED_PASSWORD)) => "********".to_owned(),
+            _ => decrypt_or_empty(entry.field_value(field), cipher),
+        };
+
+        let value = match (field.type_, field.linked_id) {
+            // Boolean fields are stored as "true"/"false" strings
+            (FIELD_BOOLEAN, _) if value == "true" => "[x] yes".to_owned(),
+            (FIELD_BOOLEAN, _) => "[ ] no".to_owned(),
+            (FIELD_LINKED, Some(LINKED_USERNAME)) => format!("{} (linked to username)", value),
+            (FIELD_LINKED, Some(LINKED_PASSWORD)) => format!("{} (linked to password)", value),
+            (FIELD_LINKED, _) => "(linked to unsupported property)".to_owned(),
+            _ => value,
+        };
+
+        layout.add_child(TextView::new(format!("  {}: {}", name, value)));
+    }
+
+    if let Some(credentials) = &entry.data.fido2_credentials {
+        if !credentials.is_empty() {
+            layout.add_child(DummyView);
+            layout.add_child(TextView::new("Passkeys:"));
+        }
+
+        for credential in credentials {
+            let rp_id = decrypt_or_empty(Some(&credential.rp_id), cipher);
+            let username = decrypt_or_empty(credential.user_name.as_ref(), cipher);
+
+            layout.add_child(TextView::new(format!(
+                "  {} ({}), created {}",
+                rp_id,
+                username,
+                credential.creation_date.format("%Y-%m-%d")
+            )));
+        }
+    }
+
+    let notes = decrypt_or_empty(
+        entry.notes.as_ref().or_else(|| entry.data.notes.as_ref()),
+        cipher,
+    );
+    let has_notes = !notes.trim().is_empty();
+
+    if has_notes {
+        layout.add_child(DummyView);
+        layout.add_child(TextView::new("Notes:"));
+        layout.add_child(TextView::new(markdown::render(&notes)).with_name("detail_notes"));
+    }
+
+    let mut dialog =
+        Dialog::around(layout.scrollable()).title(decrypt_or_empty(Some(&entry.name), cipher));
+
+    if has_notes {
+        // Notes are kept as typed, e.g. to check what the markup looks like
+        let raw = Rc::new(Cell::new(false));
+
+        dialog.add_button("Raw notes", move |siv| {
+            raw.set(!raw.get());
+
+            let content = if raw.get() {
+                StyledString::plain(notes.clone())
+            } else {
+                markdown::render(&notes)
+            };
+
+            siv.call_on_name("detail_notes", |view: &mut TextView| {
+                view.set_content(content);
+            });
+        });
+    }
+
+    // Items of organizations would need their key
+    if entry.type_ == CIPHER_LOGIN && entry.organization_id.is_none() {
+        let uuid = entry.uuid;
+        dialog.add_button("Set TOTP", move |siv| totp_edit::show(siv, uuid));
+    }
+
+    dialog.dismiss_button("Close")
+}
@@ -0,0 +1,110 @@
+// SPDX-License-Identifier: MIT
+
+use cursive::traits::*;
+use cursive::views::{Dialog, EditView, LinearLayout, SelectView, TextView};
+use cursive::Cursive;
+
+use crate::api::{ApiError, Domains};
+use crate::uri;
+use crate::vault::{self, Session};
+
+/// Shows the custom equivalent domain groups of the account for editing.
+pub fn show(siv: &mut Cursive) {
+    let domains = match siv.user_data::<Session>() {
+        Some(session) => session.vault.domains.clone().unwrap_or_default(),
+        None => return,
+    };
+
+    let mut select = SelectView::new();
+    for (index, group) in domains.equivalent_domains.iter().enumerate() {
+        select.add_item(group.join(", "), index);
+    }
+
+    siv.add_layer(
+        Dialog::around(select.with_name("domain_groups").scrollable())
+            .title("equivalent domains")
+            .button("Add", ask_group)
+            .button("Remove", |siv| {
+                let index = siv
+                    .call_on_name("domain_groups", |view: &mut SelectView<usize>| {
+                        view.selection()
+                    })
+                    .unwrap();
+
+                if let Some(index) = index {
+                    let index = *index;
+                    update(siv, move |domains| {
+                        domains.equivalent_domains.remove(index);
+                    });
+                }
+            })
+            .dismiss_button("Close")
+            .with_name("domains_dialog")
+            .min_width(60),
+    );
+}
+
+fn ask_group(siv: &mut Cursive) {
+    let layout = LinearLayout::vertical()
+        .child(TextView::new("domains, separated by spaces or commas:"))
+        .child(EditView::new().with_name("domain_group"));
+
+    siv.add_layer(
+        Dialog::around(layout)
+            .title("add equivalent domains")
+            .button("Ok", |siv| {
+                let content = siv
+                    .call_on_name("domain_group", |view: &mut EditView| view.get_content())
+                    .unwrap();
+
+                let group = content
+                    .split(|c: char| c == ',' || c.is_whitespace())
+                    .filter(|domain| !domain.is_empty())
+                    .map(|domain| uri::host(domain).to_lowercase())
+                    .collect::<Vec<String>>();
+
+                if group.len() < 2 {
+                    siv.add_layer(Dialog::info("At least two domains are needed"));
+                    return;
+                }
+
+                siv.pop_layer();
+                update(siv, move |domains| domains.equivalent_domains.push(group));
+            })
+            .dismiss_button("Cancel")
+            .min_width(50),
+    );
+}
+
+/// Applies `change` to the domains of the account and pushes them to the
+/// server, the new domains are used right away.
+fn update<F>(siv: &mut Cursive, change: F)
+where
+    F: FnOnce(&mut Domains),
+{
+    let mut domains = match siv.user_data::<Session>() {
+        Some(session) => session.vault.domains.clone().unwrap_or_default(),
+        None => return,
+    };
+
+    change(&mut domains);
+
+    vault::request(
+        siv,
+        move |api, auth| api.update_domains(auth, &domains),
+        |siv, result: Result<Domains, ApiError>| match result {
+            Ok(domains) => {
+                if let Some(session) = siv.user_data::<Session>() {
+                    session.vault.domains = Some(domains);
+                }
+
+                if siv.find_name::<Dialog>("domains_dialog").is_some() {
+                    siv.pop_layer();
+                }
+
+                show(siv);
+            }
+            Err(err) => siv.add_layer(Dialog::info(err.to_string())),
+        },
+    );
+}
@@ -0,0 +1,448 @@
+// SPDX-License-Identifier: MIT
+
+use std::sync::Arc;
+
+use cursive::traits::*;
+use cursive::views::{Dialog, DummyView, EditView, LinearLayout, SelectView, TextView};
+use cursive::Cursive;
+use uuid::Uuid;
+
+use crate::activity;
+use crate::api::{
+    Api, ApiError, AuthData, EmergencyAccess, EmergencyAccessTakeover, EmergencyAccessView,
+    EMERGENCY_ACCEPTED, EMERGENCY_CONFIRMED, EMERGENCY_INVITED, EMERGENCY_RECOVERY_APPROVED,
+    EMERGENCY_RECOVERY_INITIATED, EMERGENCY_TAKEOVER, EMERGENCY_VIEW,
+};
+use crate::cipher::{self, CipherString, CipherSuite};
+use crate::clip;
+use crate::vault::{self, Session};
+
+const DEFAULT_WAIT_DAYS: usize = 7;
+
+fn describe(access: &EmergencyAccess) -> String {
+    let type_ = match access.type_ {
+        EMERGENCY_VIEW => "view",
+        EMERGENCY_TAKEOVER => "takeover",
+        _ => "unknown",
+    };
+
+    let status = match access.status {
+        EMERGENCY_INVITED => "invited",
+        EMERGENCY_ACCEPTED => "accepted, needs confirmation",
+        EMERGENCY_CONFIRMED => "confirmed",
+        EMERGENCY_RECOVERY_INITIATED => "access requested",
+        EMERGENCY_RECOVERY_APPROVED => "access granted",
+        _ => "unknown",
+    };
+
+    let name = if access.name.is_empty() {
+        &access.email
+    } else {
+        &access.name
+    };
+
+    format!(
+        "{} ({}, {}, {} days wait)",
+        name, type_, status, access.wait_time_days
+    )
+}
+
+/// Shows both the emergency contacts of the user and the grants others gave
+/// to the user.
+pub fn show(siv: &mut Cursive) {
+    vault::request(
+        siv,
+        |api, auth| {
+            let trusted = api.emergency_access_trusted(auth)?;
+            let granted = api.emergency_access_granted(auth)?;
+
+            Ok((trusted, granted))
+        },
+        |siv, result: Result<(Vec<EmergencyAccess>, Vec<EmergencyAccess>), ApiError>| match result {
+            Ok((trusted, granted)) => show_lists(siv, trusted, granted),
+            Err(err) => siv.add_layer(Dialog::info(err.to_string())),
+        },
+    );
+}
+
+fn refresh(siv: &mut Cursive) {
+    siv.pop_layer();
+    show(siv);
+}
+
+fn show_lists(siv: &mut Cursive, trusted: Vec<EmergencyAccess>, granted: Vec<EmergencyAccess>) {
+    let mut trusted_view = SelectView::new().on_submit(grantor_actions);
+    for access in trusted {
+        trusted_view.add_item(describe(&access), access);
+    }
+
+    let mut granted_view = SelectView::new().on_submit(grantee_actions);
+    for access in granted {
+        granted_view.add_item(describe(&access), access);
+    }
+
+    let layout = LinearLayout::vertical()
+        .child(TextView::new("Your emergency contacts:"))
+        .child(trusted_view)
+        .child(DummyView)
+        .child(TextView::new("Granted to you:"))
+        .child(granted_view);
+
+    siv.add_layer(
+        Dialog::around(layout.scrollable())
+            .title("emergency access")
+            .button("Invite", invite)
+            .dismiss_button("Close")
+            .min_width(60),
+    );
+}
+
+/// Runs a request which changes a grant, showing the updated lists after.
+fn change<F>(siv: &mut Cursive, request: F)
+where
+    F: Fn(&Api, &AuthData) -> Result<(), ApiError> + Send + 'static,
+{
+    vault::request(
+        siv,
+        request,
+        |siv, result: Result<(), ApiError>| match result {
+            Ok(()) => refresh(siv),
+            Err(err) => siv.add_layer(Dialog::info(err.to_string())),
+        },
+    );
+}
+
+fn invite(siv: &mut Cursive) {
+    let mut type_view = SelectView::new();
+    type_view.add_item("view vault", EMERGENCY_VIEW);
+    type_view.add_item("take over account", EMERGENCY_TAKEOVER);
+
+    let layout = LinearLayout::vertical()
+        .child(TextView::new("email address:"))
+        .child(EditView::new().with_name("emergency_email"))
+        .child(TextView::new("access:"))
+        .child(type_view.with_name("emergency_type"))
+        .child(TextView::new("days to wait before access is granted:"))
+        .child(
+            EditView::new()
+                .content(DEFAULT_WAIT_DAYS.to_string())
+                .with_name("emergency_wait"),
+        );
+
+    siv.add_layer(
+        Dialog::around(layout)
+            .title("invite emergency contact")
+            .button("Invite", |siv| {
+                let email = siv
+                    .call_on_name("emergency_email", |view: &mut EditView| view.get_content())
+                    .unwrap()
+                    .trim()
+                    .to_owned();
+
+                let type_ = siv
+                    .call_on_name("emergency_type", |view: &mut SelectView<usize>| {
+                        view.selection()
+                    })
+                    .unwrap()
+                    .map_or(EMERGENCY_VIEW, |type_| *type_);
+
+                let wait_time_days = siv
+                    .call_on_name("emergency_wait", |view: &mut EditView| view.get_content())
+                    .unwrap()
+                    .trim()
+                    .parse::<usize>();
+
+                let wait_time_days = match wait_time_days {
+                    Ok(days) if !email.is_empty() => days,
+                    _ => {
+                        siv.add_layer(Dialog::info("An email address and wait time are needed"));
+                        return;
+                    }
+                };
+
+                // Drop the invite form, the lists get refreshed after
+                siv.pop_layer();
+
+                change(siv, move |api, auth| {
+                    api.emergency_access_invite(auth, &email, type_, wait_time_days)
+                });
+            })
+            .dismiss_button("Cancel")
+            .min_width(50),
+    );
+}
+
+/// Actions on a grant the user gave to someone else.
+fn grantor_actions(siv: &mut Cursive, access: &EmergencyAccess) {
+    let mut dialog = Dialog::text(describe(access)).title(access.email.clone());
+    let id = access.uuid;
+
+    if access.status == EMERGENCY_ACCEPTED {
+        let access = access.clone();
+        dialog.add_button("Confirm", move |siv| {
+            siv.pop_layer();
+            confirm(siv, &access);
+        });
+    }
+
+    if access.status == EMERGENCY_RECOVERY_INITIATED {
+        dialog.add_button("Approve", move |siv| {
+            siv.pop_layer();
+            change(siv, move |api, auth| {
+                api.emergency_access_action(auth, id, "approve")
+            });
+        });
+        dialog.add_button("Reject", move |siv| {
+            siv.pop_layer();
+            change(siv, move |api, auth| {
+                api.emergency_access_action(auth, id, "reject")
+            });
+        });
+    }
+
+    dialog.add_button("Remove", move |siv| {
+        siv.pop_layer();
+        change(siv, move |api, auth| api.emergency_access_delete(auth, id));
+    });
+
+    siv.add_layer(dialog.dismiss_button("Cancel"));
+}
+
+/// Encrypts the user's key for the grantee, which is what actually enables
+/// the grant. The fingerprint phrase of the grantee's account is shown first,
+/// as the server could hand out someone else's key.
+fn confirm(siv: &mut Cursive, access: &EmergencyAccess) {
+    let (id, grantee) = match access.grantee_id {
+        Some(grantee) => (access.uuid, grantee),
+        None => return,
+    };
+    let email = access.email.clone();
+
+    let cipher = match siv.user_data::<Session>() {
+        Some(session) => Arc::clone(&session.cipher),
+        None => return,
+    };
+
+    vault::request(
+        siv,
+        move |api, auth| api.user_public_key(auth, grantee),
+        move |siv, result: Result<Vec<u8>, ApiError>| {
+            let public_key = match result {
+                Ok(public_key) => public_key,
+                Err(err) => return siv.add_layer(Dialog::info(err.to_string())),
+            };
+
+            let fingerprint =
+                cipher::fingerprint_phrase(&grantee.to_hyphenated().to_string(), &public_key);
+
+            siv.add_layer(
+                Dialog::text(format!(
+                    "Only confirm {} if their account shows the same \
+                     fingerprint phrase:\n\n{}",
+                    email, fingerprint
+                ))
+                .title("confirm emergency contact")
+                .button("Confirm", move |siv| {
+                    siv.pop_layer();
+
+                    let key = match cipher.share_key(&public_key) {
+                        Ok(key) => key,
+                        Err(err) => return siv.add_layer(Dialog::info(err.to_string())),
+                    };
+
+                    change(siv, move |api, auth| {
+                        api.emergency_access_confirm(auth, id, &key)
+                    });
+                })
+                .dismiss_button("Cancel")
+                .max_width(60),
+            );
+        },
+    );
+}
+
+/// Actions on a grant someone else gave to the user.
+fn grantee_actions(siv: &mut Cursive, access: &EmergencyAccess) {
+    let mut dialog = Dialog::text(describe(access)).title(access.email.clone());
+    let id = access.uuid;
+
+    if access.status == EMERGENCY_CONFIRMED {
+        dialog.add_button("Request access", move |siv| {
+            siv.pop_layer();
+            change(siv, move |api, auth| {
+                api.emergency_access_action(auth, id, "initiate")
+            });
+        });
+    }
+
+    if access.status == EMERGENCY_RECOVERY_APPROVED && access.type_ == EMERGENCY_VIEW {
+        dialog.add_button("View vault", move |siv| {
+            siv.pop_layer();
+            view(siv, id);
+        });
+    }
+
+    if access.status == EMERGENCY_RECOVERY_APPROVED && access.type_ == EMERGENCY_TAKEOVER {
+        let email = access.email.clone();
+        dialog.add_button("Take over", move |siv| {
+            siv.pop_layer();
+            ask_takeover_password(siv, id, email.clone());
+        });
+    }
+
+    siv.add_layer(dialog.dismiss_button("Cancel"));
+}
+
+/// Decrypts the key of the grantor, which the server hands out encrypted for
+/// the user once access is granted.
+fn grantor_cipher(siv: &mut Cursive, key_encrypted: &str) -> Result<CipherSuite, String> {
+    let session = siv
+        .user_data::<Session>()
+        .ok_or_else(|| "vault is locked".to_owned())?;
+
+    let private_key = session
+        .vault
+        .profile
+        .private_key
+        .as_ref()
+        .ok_or_else(|| "account has no key pair".to_owned())?;
+
+    session
+        .cipher
+        .shared_key(private_key, key_encrypted)
+        .map_err(|err| err.to_string())
+}
+
+fn view(siv: &mut Cursive, id: Uuid) {
+    vault::request(
+        siv,
+        move |api, auth| api.emergency_access_view(auth, id),
+        |siv, result: Result<EmergencyAccessView, ApiError>| {
+            let view = match result {
+                Ok(view) => view,
+                Err(err) => return siv.add_layer(Dialog::info(err.to_string())),
+            };
+
+            let cipher = match grantor_cipher(siv, &view.key_encrypted) {
+                Ok(cipher) => cipher,
+                Err(err) => return siv.add_layer(Dialog::info(err)),
+            };
+
+            let decrypt = |value: Option<&CipherString>| {
+                value
+                    .and_then(|value| value.decrypt(&cipher))
+                    .unwrap_or_default()
+            };
+
+            let mut select =
+                SelectView::new().on_submit(|siv, password: &String| match clip::copy(password) {
+                    Ok(()) => activity::record(siv, "copied emergency access password", None),
+                    Err(err) => siv.add_layer(Dialog::info(err)),
+                });
+
+            for entry in view
+                .ciphers
+                .iter()
+                .filter(|entry| entry.deleted_date.is_none())
+            {
+                let label = format!(
+                    "{}  {}",
+                    decrypt(Some(&entry.name)),
+                    decrypt(entry.data.username.as_ref())
+                );
+
+                select.add_item(label, decrypt(entry.data.password.as_ref()));
+            }
+
+            select.sort_by_label();
+
+            siv.add_layer(
+                Dialog::around(select.scrollable())
+                    .title("emergency access (<Enter>: copy password)")
+                    .dismiss_button("Close")
+                    .min_width(60),
+            );
+        },
+    );
+}
+
+fn ask_takeover_password(siv: &mut Cursive, id: Uuid, email: String) {
+    let layout = LinearLayout::vertical()
+        .child(TextView::new(format!("new master password for {}:", email)))
+        .child(EditView::new().secret().with_name("takeover_password"))
+        .child(TextView::new("repeat:"))
+        .child(
+            EditView::new()
+                .secret()
+                .with_name("takeover_password_repeat"),
+        );
+
+    siv.add_layer(
+        Dialog::around(layout)
+            .title("take over account")
+            .button("Ok", move |siv| {
+                let password = siv
+                    .call_on_name("takeover_password", |view: &mut EditView| {
+                        view.get_content()
+                    })
+                    .unwrap();
+                let repeat = siv
+                    .call_on_name("takeover_password_repeat", |view: &mut EditView| {
+                        view.get_content()
+                    })
+                    .unwrap();
+
+                if password.is_empty() || password != repeat {
+                    siv.add_layer(Dialog::info("Passwords are empty or don't match"));
+                    return;
+                }
+
+                siv.pop_layer();
+                takeover(siv, id, email.clone(), password.to_string());
+            })
+            .dismiss_button("Cancel")
+            .min_width(50),
+    );
+}
+
+/// Sets a new master password for the grantor, keeping their vault key so that
+/// all data stays readable.
+fn takeover(siv: &mut Cursive, id: Uuid, email: String, password: String) {
+    vault::request(
+        siv,
+        move |api, auth| api.emergency_access_takeover(auth, id),
+        move |siv, result: Result<EmergencyAccessTakeover, ApiError>| {
+            let takeover = match result {
+                Ok(takeover) => takeover,
+                Err(err) => return siv.add_layer(Dialog::info(err.to_string())),
+            };
+
+            // Only PBKDF2 is supported for deriving master keys
+            if takeover.kdf != 0 {
+                return siv.add_layer(Dialog::info("Unsupported key derivation function"));
+            }
+
+            let grantor = match grantor_cipher(siv, &takeover.key_encrypted) {
+                Ok(cipher) => cipher,
+                Err(err) => return siv.add_layer(Dialog::info(err)),
+            };
+
+            let master = CipherSuite::from(&email, &password, takeover.kdf_iterations);
+            let key = match master.protect_key(&grantor) {
+                Ok(key) => key,
+                Err(err) => return siv.add_layer(Dialog::info(err.to_string())),
+            };
+
+            vault::request(
+                siv,
+                move |api, auth| {
+                    api.emergency_access_password(auth, id, &master.master_key_hash, &key)
+                },
+                |siv, result: Result<(), ApiError>| match result {
+                    Ok(()) => siv.add_layer(Dialog::info("Master password changed")),
+                    Err(err) => siv.add_layer(Dialog::info(err.to_string())),
+                },
+            );
+        },
+    );
+}
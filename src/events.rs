@@ -0,0 +1,230 @@
+// SPDX-License-Identifier: MIT
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, Duration, Local, NaiveDate, Utc};
+use cursive::traits::*;
+use cursive::views::{Dialog, EditView, LinearLayout, SelectView, TextView};
+use cursive::Cursive;
+use uuid::Uuid;
+
+use crate::api::{ApiError, Event, Organization};
+use crate::vault::{self, Session};
+
+const DATE_FORMAT: &str = "%Y-%m-%d";
+const DEFAULT_DAYS: i64 = 7;
+
+fn describe(type_: usize) -> String {
+    let description = match type_ {
+        1000 => "logged in",
+        1001 => "changed password",
+        1002 => "updated two-step login",
+        1003 => "disabled two-step login",
+        1005 => "failed login",
+        1006 => "failed two-step login",
+        1007 => "exported vault",
+        1100 => "created item",
+        1101 => "edited item",
+        1102 => "deleted item",
+        1105 => "shared item",
+        1106 => "changed item collections",
+        1107 => "viewed item",
+        1108 => "viewed password",
+        1111 => "copied password",
+        1114 => "autofilled item",
+        1115 => "moved item to trash",
+        1116 => "restored item",
+        1300 => "created collection",
+        1301 => "edited collection",
+        1302 => "deleted collection",
+        1500 => "invited user",
+        1501 => "confirmed user",
+        1502 => "edited user",
+        1503 => "removed user",
+        1600 => "edited organization",
+        1602 => "exported organization vault",
+        _ => return format!("event {}", type_),
+    };
+
+    description.to_owned()
+}
+
+/// Shows the event log of an organization the user administers.
+pub fn show(siv: &mut Cursive) {
+    let organizations = match siv.user_data::<Session>() {
+        Some(session) => session
+            .vault
+            .profile
+            .organizations
+            .iter()
+            .filter(|org| org.enabled && org.use_events && org.is_admin())
+            .cloned()
+            .collect::<Vec<Organization>>(),
+        None => return,
+    };
+
+    if organizations.is_empty() {
+        siv.add_layer(Dialog::info(
+            "You don't administer any organization with event logs",
+        ));
+        return;
+    }
+
+    let mut select = SelectView::new();
+    for org in organizations {
+        select.add_item(org.name.clone(), org.uuid);
+    }
+
+    let today = Local::today().naive_local();
+    let start = today - Duration::days(DEFAULT_DAYS);
+
+    let layout = LinearLayout::vertical()
+        .child(TextView::new("organization:"))
+        .child(select.with_name("events_organization"))
+        .child(TextView::new("from (YYYY-MM-DD):"))
+        .child(
+            EditView::new()
+                .content(start.format(DATE_FORMAT).to_string())
+                .with_name("events_start"),
+        )
+        .child(TextView::new("to (YYYY-MM-DD):"))
+        .child(
+            EditView::new()
+                .content(today.format(DATE_FORMAT).to_string())
+                .with_name("events_end"),
+        );
+
+    siv.add_layer(
+        Dialog::around(layout)
+            .title("organization events")
+            .button("Show", fetch)
+            .dismiss_button("Cancel")
+            .min_width(40),
+    );
+}
+
+fn read_date(siv: &mut Cursive, name: &str) -> Option<NaiveDate> {
+    let content = siv
+        .call_on_name(name, |view: &mut EditView| view.get_content())
+        .unwrap();
+
+    NaiveDate::parse_from_str(content.trim(), DATE_FORMAT).ok()
+}
+
+fn fetch(siv: &mut Cursive) {
+    let organization = siv
+        .call_on_name("events_organization", |view: &mut SelectView<Uuid>| {
+            view.selection()
+        })
+        .unwrap();
+
+    let (organization, start, end) = match (
+        organization,
+        read_date(siv, "events_start"),
+        read_date(siv, "events_end"),
+    ) {
+        (Some(organization), Some(start), Some(end)) if start <= end => {
+            let start = DateTime::<Utc>::from_utc(start.and_hms(0, 0, 0), Utc);
+            // Include all events of the last day
+            let end = DateTime::<Utc>::from_utc((end + Duration::days(1)).and_hms(0, 0, 0), Utc);
+
+            (*organization, start, end)
+        }
+        _ => {
+            siv.add_layer(Dialog::info("Dates must be given as YYYY-MM-DD"));
+            return;
+        }
+    };
+
+    siv.add_layer(Dialog::text("Fetching events ..."));
+
+    vault::request(
+        siv,
+        move |api, auth| {
+            let events = api.organization_events(auth, organization, start, end)?;
+
+            // Only used to show names instead of ids, not worth failing for
+            let members = api
+                .organization_members(auth, organization)
+                .unwrap_or_default()
+                .into_iter()
+                .filter_map(|member| {
+                    let name = if member.name.is_empty() {
+                        member.email
+                    } else {
+                        member.name
+                    };
+
+                    member.user_id.map(|id| (id, name))
+                })
+                .collect::<HashMap<Uuid, String>>();
+
+            Ok((events, members))
+        },
+        |siv, result: Result<(Vec<Event>, HashMap<Uuid, String>), ApiError>| {
+            siv.pop_layer();
+
+            match result {
+                Ok((events, members)) => show_events(siv, &events, &members),
+                Err(err) => siv.add_layer(Dialog::info(err.to_string())),
+            }
+        },
+    );
+}
+
+fn show_events(siv: &mut Cursive, events: &[Event], members: &HashMap<Uuid, String>) {
+    let dialog = match siv.user_data::<Session>() {
+        Some(session) => build(session, events, members),
+        None => return,
+    };
+
+    siv.add_layer(dialog);
+}
+
+fn build(session: &Session, events: &[Event], members: &HashMap<Uuid, String>) -> Dialog {
+    let mut layout = LinearLayout::vertical();
+
+    if events.is_empty() {
+        layout.add_child(TextView::new("No events in this period"));
+    }
+
+    for event in events {
+        let user = event
+            .acting_user_id
+            .map(|id| members.get(&id).cloned().unwrap_or_else(|| id.to_string()))
+            .unwrap_or_default();
+
+        // Items of the organization itself can't be decrypted (yet)
+        let item = event.cipher_id.map(|id| {
+            session
+                .vault
+                .ciphers
+                .iter()
+                .find(|entry| entry.uuid == id)
+                .and_then(|entry| entry.name.decrypt(&session.cipher))
+                .unwrap_or_else(|| id.to_string())
+        });
+
+        let mut line = format!(
+            "{}  {}  {}",
+            event.date.with_timezone(&Local).format("%Y-%m-%d %H:%M"),
+            user,
+            describe(event.type_)
+        );
+
+        if let Some(item) = item {
+            line.push_str(&format!(" \"{}\"", item));
+        }
+
+        if let Some(ip_address) = &event.ip_address {
+            line.push_str(&format!(" from {}", ip_address));
+        }
+
+        layout.add_child(TextView::new(line));
+    }
+
+    Dialog::around(layout.scrollable())
+        .title(format!("events ({})", events.len()))
+        .dismiss_button("Close")
+        .min_width(80)
+}
@@ -0,0 +1,395 @@
+// SPDX-License-Identifier: MIT
+
+use std::collections::HashMap;
+use std::io::Write;
+
+use cursive::traits::*;
+use cursive::views::{Dialog, EditView, LinearLayout, TextView};
+use cursive::Cursive;
+use rayon::prelude::*;
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::activity;
+use crate::api::{self, CipherEntry, VaultData, CIPHER_LOGIN, CIPHER_SECURE_NOTE};
+use crate::cipher::{CipherString, CipherSuite};
+use crate::hooks::{self, Hook};
+use crate::policy;
+use crate::vault::{self, Session};
+
+#[derive(Clone, Copy)]
+enum Format {
+    Json,
+    Csv,
+}
+
+impl Format {
+    fn extension(self) -> &'static str {
+        match self {
+            Format::Json => "json",
+            Format::Csv => "csv",
+        }
+    }
+}
+
+// Same layout as the unencrypted JSON export of the official clients, so the
+// file can be imported there again.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Export {
+    encrypted: bool,
+    folders: Vec<ExportFolder>,
+    items: Vec<ExportItem>,
+}
+
+#[derive(Serialize)]
+struct ExportFolder {
+    id: Uuid,
+    name: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ExportItem {
+    id: Uuid,
+    folder_id: Option<Uuid>,
+    #[serde(rename = "type")]
+    type_: usize,
+    name: String,
+    notes: Option<String>,
+    favorite: bool,
+    fields: Vec<ExportField>,
+    login: Option<ExportLogin>,
+    secure_note: Option<ExportSecureNote>,
+}
+
+#[derive(Serialize)]
+struct ExportField {
+    name: String,
+    value: String,
+    #[serde(rename = "type")]
+    type_: usize,
+}
+
+#[derive(Serialize)]
+struct ExportLogin {
+    uris: Vec<ExportUri>,
+    username: Option<String>,
+    password: Option<String>,
+    totp: Option<String>,
+}
+
+#[derive(Serialize)]
+struct ExportUri {
+    #[serde(rename = "match")]
+    match_: Option<usize>,
+    uri: String,
+}
+
+#[derive(Serialize)]
+struct ExportSecureNote {
+    #[serde(rename = "type")]
+    type_: usize,
+}
+
+fn decrypt(value: Option<&CipherString>, cipher: &CipherSuite) -> Option<String> {
+    value.and_then(|value| value.decrypt(cipher))
+}
+
+fn export_item(entry: &CipherEntry, cipher: &CipherSuite) -> ExportItem {
+    let data = &entry.data;
+
+    let login = if entry.type_ == CIPHER_LOGIN {
+        let legacy = data
+            .uri
+            .iter()
+            .filter(|_| data.uris.is_none())
+            .map(|uri| (uri, None));
+
+        let uris = data
+            .uris
+            .iter()
+            .flatten()
+            .filter_map(|uri| uri.uri.as_ref().map(|value| (value, uri.match_)))
+            .chain(legacy)
+            .filter_map(|(uri, match_)| uri.decrypt(cipher).map(|uri| ExportUri { match_, uri }))
+            .collect();
+
+        Some(ExportLogin {
+            uris,
+            username: decrypt(data.username.as_ref(), cipher),
+            password: decrypt(data.password.as_ref(), cipher),
+            totp: decrypt(data.totp.as_ref(), cipher),
+        })
+    } else {
+        None
+    };
+
+    let secure_note = if entry.type_ == CIPHER_SECURE_NOTE {
+        Some(ExportSecureNote { type_: 0 })
+    } else {
+        None
+    };
+
+    let fields = entry
+        .custom_fields()
+        .iter()
+        .map(|field| ExportField {
+            name: decrypt(field.name.as_ref(), cipher).unwrap_or_default(),
+            value: decrypt(entry.field_value(field), cipher).unwrap_or_default(),
+            type_: field.type_,
+        })
+        .collect();
+
+    ExportItem {
+        id: entry.uuid,
+        folder_id: entry.folder_id,
+        type_: entry.type_,
+        name: entry.name.decrypt(cipher).unwrap_or_default(),
+        notes: decrypt(entry.notes.as_ref().or_else(|| data.notes.as_ref()), cipher),
+        favorite: entry.favorite,
+        fields,
+        login,
+        secure_note,
+    }
+}
+
+/// Quotes a CSV value if needed.
+fn csv_value(value: &str) -> String {
+    if value.contains(|c| c == ',' || c == '"' || c == '\n' || c == '\r') {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_owned()
+    }
+}
+
+/// CSV with the columns of the official clients' export.
+fn to_csv(export: &Export) -> String {
+    let folders = export
+        .folders
+        .iter()
+        .map(|folder| (folder.id, folder.name.as_str()))
+        .collect::<HashMap<Uuid, &str>>();
+
+    let mut csv = String::from(
+        "folder,favorite,type,name,notes,fields,reprompt,login_uri,login_username,login_password,login_totp\n",
+    );
+
+    for item in &export.items {
+        let login = item.login.as_ref();
+        let uris = login
+            .map(|login| {
+                login
+                    .uris
+                    .iter()
+                    .map(|uri| uri.uri.as_str())
+                    .collect::<Vec<&str>>()
+                    .join(",")
+            })
+            .unwrap_or_default();
+        let fields = item
+            .fields
+            .iter()
+            .map(|field| format!("{}: {}", field.name, field.value))
+            .collect::<Vec<String>>()
+            .join("\n");
+
+        let folder = item.folder_id.and_then(|id| folders.get(&id).cloned());
+        let type_ = if item.type_ == CIPHER_LOGIN {
+            "login"
+        } else {
+            "note"
+        };
+
+        let row: [&str; 11] = [
+            folder.unwrap_or(""),
+            if item.favorite { "1" } else { "" },
+            type_,
+            &item.name,
+            item.notes.as_deref().unwrap_or(""),
+            &fields,
+            "0",
+            &uris,
+            login
+                .and_then(|login| login.username.as_deref())
+                .unwrap_or(""),
+            login
+                .and_then(|login| login.password.as_deref())
+                .unwrap_or(""),
+            login.and_then(|login| login.totp.as_deref()).unwrap_or(""),
+        ];
+
+        let row = row
+            .iter()
+            .map(|value| csv_value(value))
+            .collect::<Vec<String>>();
+        csv.push_str(&row.join(","));
+        csv.push('\n');
+    }
+
+    csv
+}
+
+/// Decrypts the given items, skipping everything but logins and secure notes
+/// as their other data isn't known to bwtui. Returns the export and the
+/// number of skipped items.
+fn build(vault: &VaultData, cipher: &CipherSuite, uuids: &[Uuid]) -> (Export, usize) {
+    let entries = vault
+        .ciphers
+        .iter()
+        .filter(|entry| uuids.contains(&entry.uuid))
+        .collect::<Vec<&CipherEntry>>();
+
+    // Everything gets decrypted, using all cores
+    let items = entries
+        .par_iter()
+        .filter(|entry| entry.type_ == CIPHER_LOGIN || entry.type_ == CIPHER_SECURE_NOTE)
+        .map(|entry| export_item(entry, cipher))
+        .collect::<Vec<ExportItem>>();
+
+    // Only the folders of exported items
+    let folders = vault
+        .folders
+        .iter()
+        .filter(|folder| items.iter().any(|item| item.folder_id == Some(folder.uuid)))
+        .map(|folder| ExportFolder {
+            id: folder.uuid,
+            name: folder.name.decrypt(cipher).unwrap_or_default(),
+        })
+        .collect();
+
+    let skipped = entries.len() - items.len();
+
+    (
+        Export {
+            encrypted: false,
+            folders,
+            items,
+        },
+        skipped,
+    )
+}
+
+fn write(path: &str, contents: &str) -> Result<(), String> {
+    // The export is unencrypted, keep it private
+    let mut file = api::private_file()
+        .write(true)
+        .create_new(true)
+        .open(path)
+        .map_err(|e| format!("Failed to create {}: {}", path, e))?;
+
+    file.write_all(contents.as_bytes())
+        .map_err(|e| format!("Failed to write {}: {}", path, e))
+}
+
+/// Exports the marked items, or all items shown in the table if none are
+/// marked, e.g. a folder or search results.
+pub fn show(siv: &mut Cursive) {
+    let mut uuids = vault::selected_or_shown(siv);
+    if uuids.is_empty() {
+        siv.add_layer(Dialog::info("No items to export"));
+        return;
+    }
+
+    // Only items of organizations can be exported then
+    if policy::applies(siv, api::POLICY_DISABLE_PERSONAL_VAULT_EXPORT) {
+        if let Some(session) = siv.user_data::<Session>() {
+            let ciphers = &session.vault.ciphers;
+            uuids.retain(|uuid| {
+                ciphers
+                    .iter()
+                    .any(|entry| entry.uuid == *uuid && entry.organization_id.is_some())
+            });
+        }
+
+        if uuids.is_empty() {
+            siv.add_layer(Dialog::info(
+                "Exporting the personal vault is disabled by an organization policy",
+            ));
+            return;
+        }
+    }
+
+    let layout = LinearLayout::vertical()
+        .child(TextView::new(format!(
+            "Export {} items unencrypted to (a new file):",
+            uuids.len()
+        )))
+        .child(
+            EditView::new()
+                .content("bitwarden_export")
+                .with_name("export_path"),
+        );
+
+    let json = uuids.clone();
+    let csv = uuids;
+
+    siv.add_layer(
+        Dialog::around(layout)
+            .title("export")
+            .button("JSON", move |siv| export(siv, &json, Format::Json))
+            .button("CSV", move |siv| export(siv, &csv, Format::Csv))
+            .dismiss_button("Cancel")
+            .min_width(50),
+    );
+}
+
+fn export(siv: &mut Cursive, uuids: &[Uuid], format: Format) {
+    let path = siv
+        .call_on_name("export_path", |view: &mut EditView| view.get_content())
+        .unwrap();
+    let path = path.trim();
+
+    if path.is_empty() {
+        return;
+    }
+
+    // Add the extension of the chosen format, unless already given
+    let path = if path.ends_with(&format!(".{}", format.extension())) {
+        path.to_owned()
+    } else {
+        format!("{}.{}", path, format.extension())
+    };
+
+    let (export, skipped) = match siv.user_data::<Session>() {
+        Some(session) => build(&session.vault, &session.cipher, uuids),
+        None => return,
+    };
+
+    let items = export.items.len().to_string();
+    let vars = [
+        ("BWTUI_EXPORT_PATH", path.as_str()),
+        ("BWTUI_EXPORT_FORMAT", format.extension()),
+        ("BWTUI_ITEMS", items.as_str()),
+    ];
+
+    if let Err(err) = hooks::run_and_wait(Hook::PreExport, &vars) {
+        siv.add_layer(Dialog::info(format!("Export cancelled: {}", err)));
+        return;
+    }
+
+    let contents = match format {
+        Format::Json => serde_json::to_string_pretty(&export).map_err(|e| e.to_string()),
+        Format::Csv => Ok(to_csv(&export)),
+    };
+
+    match contents.and_then(|contents| write(&path, &contents)) {
+        Ok(()) => {
+            siv.pop_layer();
+
+            let mut message = format!("Exported {} items to {}", export.items.len(), path);
+            if skipped > 0 {
+                message.push_str(&format!(
+                    "\n{} items which aren't logins or notes were skipped",
+                    skipped
+                ));
+            }
+
+            siv.add_layer(Dialog::info(message));
+
+            let action = format!("exported {} items to {}", export.items.len(), path);
+            activity::record(siv, &action, None);
+        }
+        Err(err) => siv.add_layer(Dialog::info(err)),
+    }
+}
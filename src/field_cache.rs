@@ -0,0 +1,65 @@
+// SPDX-License-Identifier: MIT
+
+use std::rc::Rc;
+use std::sync::Arc;
+
+use lru::LruCache;
+use uuid::Uuid;
+
+use crate::cipher::{CipherString, CipherSuite};
+
+
+// Holds all fields of small vaults, larger ones get room for all of theirs
+// so that sorting the table doesn't decrypt everything over and over again.
+const MIN_CACHE_SIZE: usize = 8192;
+const FIELDS_PER_ITEM: usize = 3;
+
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Field {
+        Name,
+        Username,
+        Password,
+}
+
+
+/// Bounded cache of decrypted cipher fields, keyed by cipher UUID and field.
+pub struct FieldCache {
+        cipher: Arc<CipherSuite>,
+        entries: LruCache<(Uuid, Field), Rc<String>>,
+}
+
+impl FieldCache {
+        pub fn new(cipher: Arc<CipherSuite>) -> Self {
+                Self {
+                        cipher,
+                        entries: LruCache::new(MIN_CACHE_SIZE),
+                }
+        }
+
+        pub fn get(&mut self, uuid: Uuid, field: Field, value: &CipherString) -> Rc<String> {
+                if let Some(plain) = self.entries.get(&(uuid, field)) {
+                        return Rc::clone(plain);
+                }
+
+                let plain = Rc::new(value.decrypt(&self.cipher).unwrap_or_default());
+                self.entries.put((uuid, field), Rc::clone(&plain));
+
+                plain
+        }
+
+        /// Makes room for all fields of `items` items, e.g. after a sync.
+        pub fn fit(&mut self, items: usize) {
+                let size = MIN_CACHE_SIZE.max(items * FIELDS_PER_ITEM);
+
+                if size != self.entries.cap() {
+                        self.entries.resize(size);
+                }
+        }
+
+        /// Drops all decrypted values, e.g. when the vault gets locked or its
+        /// data is replaced.
+        pub fn purge(&mut self) {
+                self.entries.clear();
+        }
+}
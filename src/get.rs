@@ -0,0 +1,121 @@
+// SPDX-License-Identifier: MIT
+
+// Non-interactive `bwtui get`, working on the local cache only.
+
+use std::path::PathBuf;
+
+use chrono::{Duration, Utc};
+use clap::ArgMatches;
+use uuid::Uuid;
+
+use crate::api::CipherEntry;
+use crate::cipher::CipherSuite;
+use crate::clip;
+use crate::credentials;
+use crate::totp::Totp;
+
+
+struct Options {
+        item: String,
+        field: String,
+        print: bool,
+        password_file: Option<PathBuf>,
+}
+
+
+/// Looks up a single item by id or name, ignoring case.
+pub fn find_item<'a>(ciphers: &'a [CipherEntry], cipher: &CipherSuite, name: &str)
+        -> Result<&'a CipherEntry, String>
+{
+        let uuid = name.parse::<Uuid>().ok();
+
+        let items = ciphers.iter()
+                .filter(|entry| entry.deleted_date.is_none())
+                .filter(|entry| {
+                        Some(entry.uuid) == uuid
+                                || entry.name.decrypt(cipher)
+                                        .map_or(false, |entry| entry.eq_ignore_ascii_case(name))
+                })
+                .collect::<Vec<&CipherEntry>>();
+
+        match items.as_slice() {
+                [item] => Ok(*item),
+                [] => Err(format!("no item named \"{}\"", name)),
+                _ => Err(format!("{} items are named \"{}\", use the id instead", items.len(), name)),
+        }
+}
+
+/// Decrypted value of `field` of an item, either a login property (name,
+/// username, password, notes, uri or totp, the current code) or the name of
+/// a custom field.
+pub fn value(entry: &CipherEntry, field: &str, cipher: &CipherSuite, skew: Duration) -> Option<String> {
+        let data = &entry.data;
+
+        if field == "totp" {
+                return data.totp.as_ref()
+                        .and_then(|totp| totp.decrypt(cipher))
+                        .and_then(|totp| Totp::parse(&totp))
+                        .map(|totp| totp.generate(Utc::now(), skew));
+        }
+
+        let value = match field {
+                "name" => Some(&entry.name),
+                "username" => data.username.as_ref(),
+                "password" => data.password.as_ref(),
+                "notes" => entry.notes.as_ref().or_else(|| data.notes.as_ref()),
+                "uri" => data.uris.iter()
+                        .flatten()
+                        .filter_map(|uri| uri.uri.as_ref())
+                        .next()
+                        .or_else(|| data.uri.as_ref()),
+                _ => entry.find_field(cipher, field)
+                        .and_then(|found| entry.field_value(found)),
+        };
+
+        value.and_then(|value| value.decrypt(cipher))
+}
+
+fn get(options: Options) -> Result<(), String> {
+        let (data, password) = credentials::unlock(options.password_file.as_deref())?;
+        let email = &data.vault.profile.email;
+
+        let mut cipher = CipherSuite::with_kdf(email, &password, &data.auth.kdf())
+                .map_err(|e| e.to_string())?;
+        cipher.set_decrypt_key(&data.vault.profile.key)
+                .map_err(|_| "wrong master password".to_owned())?;
+
+        let item = find_item(&data.vault.ciphers, &cipher, &options.item)?;
+
+        let field = item.find_field(&cipher, &options.field)
+                .ok_or_else(|| format!("item has no field \"{}\"", options.field))?;
+        let value = item.field_value(field)
+                .and_then(|value| value.decrypt(&cipher))
+                .ok_or_else(|| format!("field \"{}\" has no value", options.field))?;
+
+        if options.print {
+                println!("{}", value);
+                Ok(())
+        } else {
+                clip::copy(&value)
+        }
+}
+
+
+/// Runs `bwtui get`, returning the exit code.
+pub fn run(matches: &ArgMatches) -> i32 {
+        // Both are required, clap makes sure they are there
+        let options = Options {
+                item: matches.value_of("item").unwrap_or_default().to_owned(),
+                field: matches.value_of("field").unwrap_or_default().to_owned(),
+                print: matches.is_present("print"),
+                password_file: matches.value_of_os("passwordfile").map(PathBuf::from),
+        };
+
+        match get(options) {
+                Ok(()) => 0,
+                Err(err) => {
+                        eprintln!("bwtui get: {}", err);
+                        1
+                }
+        }
+}
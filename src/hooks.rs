@@ -0,0 +1,124 @@
+// SPDX-License-Identifier: MIT
+
+use std::env;
+use std::process::{Child, Stdio};
+use std::thread;
+
+use log::{debug, warn};
+
+use crate::clip;
+
+
+// Passed on to hooks, anything else (e.g. BWTUI_CLIPBOARD_COMMAND or tokens
+// of other tools) is left out.
+const INHERITED_VARS: &[&str] = &[
+        "PATH", "HOME", "USER", "LOGNAME", "LANG", "LC_ALL", "TERM",
+        "DISPLAY", "WAYLAND_DISPLAY", "XDG_RUNTIME_DIR", "DBUS_SESSION_BUS_ADDRESS",
+        // Needed by cmd and most programs on Windows
+        "SYSTEMROOT", "COMSPEC", "PATHEXT", "USERPROFILE", "APPDATA", "LOCALAPPDATA", "TEMP",
+];
+
+
+/// Event a user script can be run on. Hooks are commands set in environment
+/// variables, run through the shell like the clipboard command. They never
+/// get any secrets, only what happened and to which item.
+#[derive(Clone, Copy, Debug)]
+pub enum Hook {
+        // After every successful sync
+        PostSync,
+        // A value of an item was copied
+        Copy,
+        // The vault was locked
+        Lock,
+        // Before exporting, a failing hook cancels the export
+        PreExport,
+}
+
+impl Hook {
+        fn var(self) -> &'static str {
+                match self {
+                        Hook::PostSync => "BWTUI_HOOK_POST_SYNC",
+                        Hook::Copy => "BWTUI_HOOK_ON_COPY",
+                        Hook::Lock => "BWTUI_HOOK_ON_LOCK",
+                        Hook::PreExport => "BWTUI_HOOK_PRE_EXPORT",
+                }
+        }
+
+        fn name(self) -> &'static str {
+                match self {
+                        Hook::PostSync => "post-sync",
+                        Hook::Copy => "copy",
+                        Hook::Lock => "lock",
+                        Hook::PreExport => "pre-export",
+                }
+        }
+}
+
+
+fn spawn(hook: Hook, vars: &[(&str, &str)]) -> Option<Result<Child, String>> {
+        let command = match env::var(hook.var()) {
+                Ok(command) if !command.trim().is_empty() => command,
+                _ => return None,
+        };
+
+        debug!("running the {} hook", hook.name());
+
+        let mut child = clip::shell(&command);
+        child.env_clear()
+                .stdin(Stdio::null())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null());
+
+        for name in INHERITED_VARS {
+                if let Some(value) = env::var_os(name) {
+                        child.env(name, value);
+                }
+        }
+
+        child.env("BWTUI_EVENT", hook.name());
+        for (name, value) in vars {
+                child.env(name, value);
+        }
+
+        Some(child.spawn()
+                .map_err(|e| format!("failed to run the {} hook: {}", hook.name(), e)))
+}
+
+
+/// Runs the command set for `hook` in the background, with `vars` in its
+/// environment. Does nothing if there is none.
+pub fn run(hook: Hook, vars: &[(&str, &str)]) {
+        match spawn(hook, vars) {
+                Some(Ok(mut child)) => {
+                        // Waited for elsewhere, so the UI doesn't block on it
+                        thread::spawn(move || {
+                                match child.wait() {
+                                        Ok(status) if !status.success() => {
+                                                warn!("the {} hook failed ({})", hook.name(), status);
+                                        }
+                                        Err(e) => warn!("the {} hook failed: {}", hook.name(), e),
+                                        _ => (),
+                                }
+                        });
+                }
+                Some(Err(err)) => warn!("{}", err),
+                None => (),
+        }
+}
+
+
+/// Runs the command set for `hook` and waits for it, an error means it
+/// failed and whatever it guards shouldn't happen.
+pub fn run_and_wait(hook: Hook, vars: &[(&str, &str)]) -> Result<(), String> {
+        let status = match spawn(hook, vars) {
+                Some(child) => child?.wait()
+                        .map_err(|e| format!("failed to run the {} hook: {}", hook.name(), e))?,
+                None => return Ok(()),
+        };
+
+        if status.success() {
+                Ok(())
+        } else {
+                Err(format!("the {} hook failed ({})", hook.name(), status))
+        }
+}
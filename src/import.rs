@@ -0,0 +1,934 @@
+// SPDX-License-Identifier: MIT
+
+use std::collections::HashMap;
+use std::fs;
+use std::mem;
+use std::rc::Rc;
+
+use cursive::traits::*;
+use cursive::views::{
+    Checkbox, Dialog, DummyView, EditView, LinearLayout, ListView, SelectView, TextView,
+};
+use cursive::Cursive;
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::api::{
+    self, ApiError, NewCipher, VaultData, CIPHER_LOGIN, CIPHER_SECURE_NOTE, FIELD_TEXT,
+};
+use crate::cipher::{CipherString, CipherSuite};
+use crate::journal::{self, Operation};
+use crate::policy;
+use crate::uri;
+use crate::vault::{self, Session};
+
+/// Item read from an import file, not encrypted yet.
+#[derive(Clone, Debug, Default)]
+struct Record {
+    // Secure note instead of a login
+    note: bool,
+    name: String,
+    folder: Option<String>,
+    favorite: bool,
+    notes: Option<String>,
+    uris: Vec<String>,
+    username: Option<String>,
+    password: Option<String>,
+    totp: Option<String>,
+    // Type, name and value of custom fields
+    fields: Vec<(usize, String, String)>,
+}
+
+/// Records of a file, along with how it was understood.
+struct Parsed {
+    records: Vec<Record>,
+    mapping: Vec<String>,
+}
+
+// Unencrypted JSON export of the official clients (and of bwtui)
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct JsonExport {
+    #[serde(default)]
+    encrypted: bool,
+    folders: Option<Vec<JsonFolder>>,
+    items: Vec<JsonItem>,
+}
+
+#[derive(Deserialize)]
+struct JsonFolder {
+    id: String,
+    name: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct JsonItem {
+    #[serde(rename = "type")]
+    type_: usize,
+    name: Option<String>,
+    notes: Option<String>,
+    favorite: Option<bool>,
+    folder_id: Option<String>,
+    fields: Option<Vec<JsonField>>,
+    login: Option<JsonLogin>,
+}
+
+#[derive(Deserialize)]
+struct JsonField {
+    name: Option<String>,
+    value: Option<String>,
+    #[serde(rename = "type")]
+    type_: Option<usize>,
+}
+
+#[derive(Deserialize)]
+struct JsonLogin {
+    uris: Option<Vec<JsonUri>>,
+    username: Option<String>,
+    password: Option<String>,
+    totp: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct JsonUri {
+    uri: Option<String>,
+}
+
+fn parse_json(text: &str) -> Result<Parsed, String> {
+    let export: JsonExport =
+        serde_json::from_str(text).map_err(|e| format!("Invalid JSON export: {}", e))?;
+
+    if export.encrypted {
+        return Err("Encrypted exports can't be imported".to_owned());
+    }
+
+    let folders = export
+        .folders
+        .unwrap_or_default()
+        .into_iter()
+        .map(|folder| (folder.id, folder.name))
+        .collect::<HashMap<String, String>>();
+
+    let total = export.items.len();
+
+    let records = export
+        .items
+        .into_iter()
+        .filter(|item| item.type_ == CIPHER_LOGIN || item.type_ == CIPHER_SECURE_NOTE)
+        .map(|item| {
+            let login = item.login;
+
+            Record {
+                note: item.type_ == CIPHER_SECURE_NOTE,
+                name: item.name.unwrap_or_default(),
+                folder: item.folder_id.and_then(|id| folders.get(&id).cloned()),
+                favorite: item.favorite.unwrap_or(false),
+                notes: item.notes,
+                uris: login
+                    .as_ref()
+                    .and_then(|login| login.uris.as_ref())
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|uri| uri.uri.clone())
+                    .collect(),
+                username: login.as_ref().and_then(|login| login.username.clone()),
+                password: login.as_ref().and_then(|login| login.password.clone()),
+                totp: login.as_ref().and_then(|login| login.totp.clone()),
+                fields: item
+                    .fields
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|field| {
+                        (
+                            field.type_.unwrap_or(FIELD_TEXT),
+                            field.name.unwrap_or_default(),
+                            field.value.unwrap_or_default(),
+                        )
+                    })
+                    .collect(),
+            }
+        })
+        .collect::<Vec<Record>>();
+
+    let mut mapping = vec!["Bitwarden JSON export".to_owned()];
+    if records.len() < total {
+        mapping.push(format!(
+            "{} items which aren't logins or notes are skipped",
+            total - records.len()
+        ));
+    }
+
+    Ok(Parsed { records, mapping })
+}
+
+/// Splits CSV into rows of values, following RFC 4180 quoting.
+fn split_csv(text: &str) -> Vec<Vec<String>> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut value = String::new();
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                value.push('"');
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => row.push(mem::take(&mut value)),
+            '\r' if !quoted => {}
+            '\n' if !quoted => {
+                row.push(mem::take(&mut value));
+                rows.push(mem::take(&mut row));
+            }
+            c => value.push(c),
+        }
+    }
+
+    if !value.is_empty() || !row.is_empty() {
+        row.push(value);
+        rows.push(row);
+    }
+
+    rows.retain(|row| row.iter().any(|value| !value.trim().is_empty()));
+    rows
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Column {
+    Folder,
+    Favorite,
+    Type,
+    Name,
+    Notes,
+    Fields,
+    Uri,
+    Username,
+    Password,
+    Totp,
+}
+
+// Headers of the official clients' CSV export and common ones of other
+// password managers
+const HEADERS: &[(&str, Column)] = &[
+    ("folder", Column::Folder),
+    ("grouping", Column::Folder),
+    ("group", Column::Folder),
+    ("favorite", Column::Favorite),
+    ("type", Column::Type),
+    ("name", Column::Name),
+    ("title", Column::Name),
+    ("notes", Column::Notes),
+    ("note", Column::Notes),
+    ("extra", Column::Notes),
+    ("fields", Column::Fields),
+    ("login_uri", Column::Uri),
+    ("url", Column::Uri),
+    ("uri", Column::Uri),
+    ("website", Column::Uri),
+    ("login_username", Column::Username),
+    ("username", Column::Username),
+    ("user", Column::Username),
+    ("login", Column::Username),
+    ("email", Column::Username),
+    ("login_password", Column::Password),
+    ("password", Column::Password),
+    ("login_totp", Column::Totp),
+    ("totp", Column::Totp),
+    ("otp", Column::Totp),
+];
+
+// Everything a column can be picked as when mapping them by hand
+const COLUMNS: &[Column] = &[
+    Column::Name,
+    Column::Username,
+    Column::Password,
+    Column::Uri,
+    Column::Notes,
+    Column::Totp,
+    Column::Folder,
+    Column::Favorite,
+    Column::Type,
+    Column::Fields,
+];
+
+// Items shown while mapping columns
+const PREVIEW_ROWS: usize = 3;
+
+/// CSV file split into rows, before its columns are known.
+struct Csv {
+    header: Vec<String>,
+    rows: Vec<Vec<String>>,
+}
+
+/// Content of an import file.
+enum Source {
+    Parsed(Parsed),
+    // CSV which the user should map the columns of, with the ones recognized
+    Unmapped(Csv, Vec<Option<Column>>),
+}
+
+impl Column {
+    fn label(self) -> &'static str {
+        match self {
+            Column::Folder => "folder",
+            Column::Favorite => "favorite",
+            Column::Type => "type",
+            Column::Name => "name",
+            Column::Notes => "notes",
+            Column::Fields => "custom fields",
+            Column::Uri => "URIs",
+            Column::Username => "username",
+            Column::Password => "password",
+            Column::Totp => "TOTP",
+        }
+    }
+}
+
+/// Guesses the meaning of each column from its header, each field is taken
+/// from the first column mapping to it.
+fn detect_columns(header: &[String]) -> Vec<Option<Column>> {
+    let mut columns: Vec<Option<Column>> = Vec::new();
+
+    for name in header {
+        let column = HEADERS
+            .iter()
+            .find(|(header, _)| name.trim().eq_ignore_ascii_case(header))
+            .map(|(_, column)| *column)
+            .filter(|column| !columns.contains(&Some(*column)));
+
+        columns.push(column);
+    }
+
+    columns
+}
+
+fn check_columns(columns: &[Option<Column>]) -> Result<(), String> {
+    if !columns.contains(&Some(Column::Name)) && !columns.contains(&Some(Column::Uri)) {
+        return Err("Neither a name nor a URL column is picked".to_owned());
+    }
+
+    for column in COLUMNS {
+        let picked = columns.iter().filter(|other| **other == Some(*column));
+        if picked.count() > 1 {
+            let label = column.label();
+            return Err(format!("More than one column is picked as {}", label));
+        }
+    }
+
+    Ok(())
+}
+
+fn describe_mapping(header: &[String], columns: &[Option<Column>]) -> Vec<String> {
+    header
+        .iter()
+        .zip(columns)
+        .map(|(name, column)| match column {
+            Some(column) => format!("{} -> {}", name, column.label()),
+            None => format!("{} -> ignored", name),
+        })
+        .collect()
+}
+
+fn csv_records(rows: &[Vec<String>], columns: &[Option<Column>]) -> Vec<Record> {
+    rows.iter()
+        .map(|row| {
+            let mut record = Record::default();
+
+            for (column, value) in columns.iter().zip(row) {
+                let value = value.trim().to_owned();
+                if value.is_empty() {
+                    continue;
+                }
+
+                match column {
+                    Some(Column::Folder) => record.folder = Some(value),
+                    Some(Column::Favorite) => {
+                        record.favorite = value == "1" || value.eq_ignore_ascii_case("true")
+                    }
+                    Some(Column::Type) => record.note = value.eq_ignore_ascii_case("note"),
+                    Some(Column::Name) => record.name = value,
+                    Some(Column::Notes) => record.notes = Some(value),
+                    Some(Column::Fields) => {
+                        record.fields = value
+                            .lines()
+                            .filter_map(|line| {
+                                let mut parts = line.splitn(2, ':');
+                                let name = parts.next()?.trim().to_owned();
+                                let value = parts.next()?.trim().to_owned();
+
+                                Some((FIELD_TEXT, name, value))
+                            })
+                            .collect()
+                    }
+                    Some(Column::Uri) => {
+                        record.uris = value.split(',').map(|uri| uri.trim().to_owned()).collect()
+                    }
+                    Some(Column::Username) => record.username = Some(value),
+                    Some(Column::Password) => record.password = Some(value),
+                    Some(Column::Totp) => record.totp = Some(value),
+                    None => {}
+                }
+            }
+
+            if record.name.is_empty() {
+                record.name = record
+                    .uris
+                    .first()
+                    .map(|uri| uri::host(uri).to_owned())
+                    .unwrap_or_else(|| "imported item".to_owned());
+            }
+
+            record
+        })
+        .collect()
+}
+
+impl Csv {
+    fn parse(&self, columns: &[Option<Column>]) -> Parsed {
+        Parsed {
+            records: csv_records(&self.rows, columns),
+            mapping: describe_mapping(&self.header, columns),
+        }
+    }
+}
+
+fn parse_csv(text: &str) -> Result<Source, String> {
+    let mut rows = split_csv(text);
+    if rows.is_empty() {
+        return Err("The CSV file is empty".to_owned());
+    }
+
+    let header = rows.remove(0);
+    let columns = detect_columns(&header);
+    let csv = Csv { header, rows };
+
+    // Likely from a password manager we don't know, ask instead of guessing
+    if check_columns(&columns).is_err() || !columns.contains(&Some(Column::Password)) {
+        return Ok(Source::Unmapped(csv, columns));
+    }
+
+    Ok(Source::Parsed(csv.parse(&columns)))
+}
+
+fn read(path: &str) -> Result<Source, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+
+    if path.to_lowercase().ends_with(".json") {
+        parse_json(&text).map(Source::Parsed)
+    } else {
+        parse_csv(&text)
+    }
+}
+
+/// Name of an existing item `record` likely duplicates, going by the name or
+/// a login on the same host.
+fn find_duplicate(
+    record: &Record,
+    existing: &[(String, Option<String>, Vec<String>)],
+) -> Option<String> {
+    let name = record.name.to_lowercase();
+    let username = record
+        .username
+        .as_ref()
+        .map(|username| username.to_lowercase());
+    let hosts = record
+        .uris
+        .iter()
+        .map(|value| uri::host(&value.to_lowercase()).to_owned())
+        .filter(|host| !host.is_empty())
+        .collect::<Vec<String>>();
+
+    existing
+        .iter()
+        .find(|(existing_name, existing_username, existing_hosts)| {
+            existing_name.to_lowercase() == name
+                || (*existing_username == username
+                    && existing_hosts.iter().any(|host| hosts.contains(host)))
+        })
+        .map(|(name, _, _)| name.clone())
+}
+
+/// Names, usernames and URI hosts of all items in the vault.
+fn existing_items(
+    vault: &VaultData,
+    cipher: &CipherSuite,
+) -> Vec<(String, Option<String>, Vec<String>)> {
+    vault
+        .ciphers
+        .iter()
+        .filter(|entry| entry.deleted_date.is_none())
+        .map(|entry| {
+            let data = &entry.data;
+            let decrypt = |value: &CipherString| value.decrypt(cipher);
+
+            let hosts = data
+                .uris
+                .iter()
+                .flatten()
+                .filter_map(|uri| uri.uri.as_ref())
+                .chain(data.uri.iter())
+                .filter_map(decrypt)
+                .map(|value| uri::host(&value.to_lowercase()).to_owned())
+                .collect();
+
+            (
+                entry.name.decrypt(cipher).unwrap_or_default(),
+                data.username
+                    .as_ref()
+                    .and_then(decrypt)
+                    .map(|value| value.to_lowercase()),
+                hosts,
+            )
+        })
+        .collect()
+}
+
+/// Asks for the file to import, CSV or the JSON export of the official
+/// clients.
+pub fn show(siv: &mut Cursive) {
+    // Imported items always end up in the personal vault
+    if policy::applies(siv, api::POLICY_PERSONAL_OWNERSHIP) {
+        siv.add_layer(Dialog::info(
+            "Items can't be imported, an organization policy doesn't allow \
+             items in the personal vault",
+        ));
+        return;
+    }
+
+    let layout = LinearLayout::vertical()
+        .child(TextView::new("Import from (.csv or .json):"))
+        .child(EditView::new().with_name("import_path"));
+
+    siv.add_layer(
+        Dialog::around(layout)
+            .title("import")
+            .button("Preview", |siv| {
+                let path = siv
+                    .call_on_name("import_path", |view: &mut EditView| view.get_content())
+                    .unwrap();
+
+                match read(path.trim()) {
+                    Ok(Source::Parsed(parsed)) => {
+                        siv.pop_layer();
+                        preview(siv, parsed);
+                    }
+                    Ok(Source::Unmapped(csv, columns)) => {
+                        siv.pop_layer();
+                        map_columns(siv, csv, columns);
+                    }
+                    Err(err) => siv.add_layer(Dialog::info(err)),
+                }
+            })
+            .dismiss_button("Cancel")
+            .min_width(50),
+    );
+}
+
+fn selected_columns(siv: &mut Cursive, count: usize) -> Vec<Option<Column>> {
+    (0..count)
+        .map(|index| {
+            siv.call_on_name(
+                &format!("csv_column_{}", index),
+                |view: &mut SelectView<Option<Column>>| view.selection().map(|column| *column),
+            )
+            .flatten()
+            .flatten()
+        })
+        .collect()
+}
+
+/// The first rows of `csv` as they would be imported with `columns`.
+fn preview_rows(csv: &Csv, columns: &[Option<Column>]) -> String {
+    let rows = &csv.rows[..csv.rows.len().min(PREVIEW_ROWS)];
+
+    csv_records(rows, columns)
+        .iter()
+        .map(|record| {
+            let mut lines = vec![format!("name: {}", record.name)];
+
+            if let Some(username) = &record.username {
+                lines.push(format!("username: {}", username));
+            }
+            if record.password.is_some() {
+                lines.push("password: (hidden)".to_owned());
+            }
+            if !record.uris.is_empty() {
+                lines.push(format!("URIs: {}", record.uris.join(", ")));
+            }
+            if let Some(notes) = &record.notes {
+                lines.push(format!(
+                    "notes: {}",
+                    notes.lines().next().unwrap_or_default()
+                ));
+            }
+            if let Some(folder) = &record.folder {
+                lines.push(format!("folder: {}", folder));
+            }
+
+            lines.join("\n")
+        })
+        .collect::<Vec<String>>()
+        .join("\n\n")
+}
+
+fn update_preview(siv: &mut Cursive, csv: &Csv) {
+    let columns = selected_columns(siv, csv.header.len());
+    let rows = preview_rows(csv, &columns);
+
+    siv.call_on_name("csv_preview", |view: &mut TextView| view.set_content(rows));
+}
+
+/// Lets the user pick what each column of a CSV file holds, for files of
+/// password managers whose headers aren't known. Starts from the columns
+/// which were recognized.
+fn map_columns(siv: &mut Cursive, csv: Csv, columns: Vec<Option<Column>>) {
+    let csv = Rc::new(csv);
+    let mut list = ListView::new();
+
+    for (index, (name, column)) in csv.header.iter().zip(&columns).enumerate() {
+        let selected = column
+            .and_then(|column| COLUMNS.iter().position(|other| *other == column))
+            .map_or(0, |position| position + 1);
+
+        let mut select = SelectView::new().popup();
+        select.add_item("ignored", None);
+        for column in COLUMNS {
+            select.add_item(column.label(), Some(*column));
+        }
+
+        let shown = Rc::clone(&csv);
+        let select = select
+            .selected(selected)
+            .on_submit(move |siv, _: &Option<Column>| update_preview(siv, &shown));
+
+        list.add_child(name, select.with_name(format!("csv_column_{}", index)));
+    }
+
+    let layout = LinearLayout::vertical()
+        .child(TextView::new("Pick what each column holds:"))
+        .child(list.scrollable().max_height(12))
+        .child(DummyView)
+        .child(TextView::new(format!("first {} items:", PREVIEW_ROWS)))
+        .child(TextView::new(preview_rows(&csv, &columns)).with_name("csv_preview"));
+
+    siv.add_layer(
+        Dialog::around(layout.scrollable())
+            .title("import columns")
+            .button("Continue", move |siv| {
+                let columns = selected_columns(siv, csv.header.len());
+
+                if let Err(err) = check_columns(&columns) {
+                    siv.add_layer(Dialog::info(err));
+                    return;
+                }
+
+                siv.pop_layer();
+                preview(siv, csv.parse(&columns));
+            })
+            .dismiss_button("Cancel")
+            .min_width(60),
+    );
+}
+
+/// Shows what would be imported, letting the user leave out single items.
+/// Likely duplicates are left out by default.
+fn preview(siv: &mut Cursive, parsed: Parsed) {
+    let existing = match siv.user_data::<Session>() {
+        Some(session) => existing_items(&session.vault, &session.cipher),
+        None => return,
+    };
+
+    if parsed.records.is_empty() {
+        siv.add_layer(Dialog::info("The file contains no items"));
+        return;
+    }
+
+    let mut list = ListView::new();
+    let mut duplicates = 0;
+
+    for (index, record) in parsed.records.iter().enumerate() {
+        let duplicate = find_duplicate(record, &existing);
+
+        let mut label = record.name.clone();
+        if let Some(username) = &record.username {
+            label.push_str(&format!(" ({})", username));
+        }
+        if let Some(duplicate) = &duplicate {
+            duplicates += 1;
+            label.push_str(&format!(" - like \"{}\"", duplicate));
+        }
+
+        let mut checkbox = Checkbox::new();
+        checkbox.set_checked(duplicate.is_none());
+        list.add_child(&label, checkbox.with_name(format!("import_{}", index)));
+    }
+
+    let summary = format!(
+        "{} new items, {} potential duplicates (not selected)\n\ncolumns:\n{}",
+        parsed.records.len() - duplicates,
+        duplicates,
+        parsed.mapping.join("\n")
+    );
+
+    let records = parsed.records;
+
+    siv.add_layer(
+        Dialog::around(
+            LinearLayout::vertical()
+                .child(TextView::new(summary))
+                .child(DummyView)
+                .child(list.scrollable()),
+        )
+        .title("import preview")
+        .button("Import", move |siv| {
+            let selected = records
+                .iter()
+                .enumerate()
+                .filter(|(index, _)| {
+                    siv.call_on_name(&format!("import_{}", index), |view: &mut Checkbox| {
+                        view.is_checked()
+                    })
+                    .unwrap_or(false)
+                })
+                .map(|(_, record)| record.clone())
+                .collect::<Vec<Record>>();
+
+            if selected.is_empty() {
+                siv.add_layer(Dialog::info("No items selected"));
+                return;
+            }
+
+            siv.pop_layer();
+            import(siv, selected);
+        })
+        .dismiss_button("Cancel")
+        .min_width(60),
+    );
+}
+
+fn encrypt_record(record: &Record, cipher: &CipherSuite) -> Option<NewCipher> {
+    let encrypt = |value: &String| CipherString::encrypt(value, cipher);
+    let optional = |value: &Option<String>| match value {
+        Some(value) => encrypt(value).map(Some),
+        None => Some(None),
+    };
+
+    Some(NewCipher {
+        type_: if record.note {
+            CIPHER_SECURE_NOTE
+        } else {
+            CIPHER_LOGIN
+        },
+        folder_id: None,
+        favorite: record.favorite,
+        name: encrypt(&record.name)?,
+        notes: optional(&record.notes)?,
+        uris: record.uris.iter().map(encrypt).collect::<Option<_>>()?,
+        username: optional(&record.username)?,
+        password: optional(&record.password)?,
+        totp: optional(&record.totp)?,
+        fields: record
+            .fields
+            .iter()
+            .map(|(type_, name, value)| Some((*type_, encrypt(name)?, encrypt(value)?)))
+            .collect::<Option<_>>()?,
+    })
+}
+
+/// Outcome of creating the imported items.
+struct Imported {
+    created: usize,
+    // Folders and items rejected by the server, they would be rejected again
+    failed: Vec<String>,
+    // Set if the server couldn't be reached or the token was rejected, the
+    // items not created yet are queued for the next sync
+    stopped: Option<ApiError>,
+    queued: Vec<NewCipher>,
+    // Queued items in a folder which couldn't be created
+    without_folder: usize,
+}
+
+fn with_folder(
+    folder: &Option<String>,
+    cipher: &NewCipher,
+    folders: &HashMap<String, Uuid>,
+) -> NewCipher {
+    let mut cipher = cipher.clone();
+    cipher.folder_id = folder.as_ref().and_then(|key| folders.get(key).cloned());
+    cipher
+}
+
+/// Creates the selected items, and any of their folders which don't exist
+/// yet. Offline, they are queued to be created on the next sync.
+fn import(siv: &mut Cursive, records: Vec<Record>) {
+    let prepared = match siv.user_data::<Session>() {
+        Some(session) => {
+            let cipher = &session.cipher;
+
+            let folders = session
+                .vault
+                .folders
+                .iter()
+                .filter_map(|folder| {
+                    folder
+                        .name
+                        .decrypt(cipher)
+                        .map(|name| (name.to_lowercase(), folder.uuid))
+                })
+                .collect::<HashMap<String, Uuid>>();
+
+            let mut new_folders = Vec::new();
+            for name in records.iter().filter_map(|record| record.folder.as_ref()) {
+                let key = name.to_lowercase();
+                if !folders.contains_key(&key)
+                    && !new_folders.iter().any(|(other, _)| *other == key)
+                {
+                    new_folders.push((key, CipherString::encrypt(name, cipher)));
+                }
+            }
+
+            let new_folders = new_folders
+                .into_iter()
+                .map(|(key, name)| name.map(|name| (key, name)))
+                .collect::<Option<Vec<(String, CipherString)>>>();
+
+            let ciphers = records
+                .iter()
+                .map(|record| {
+                    encrypt_record(record, cipher)
+                        .map(|new| (record.folder.as_ref().map(|name| name.to_lowercase()), new))
+                })
+                .collect::<Option<Vec<(Option<String>, NewCipher)>>>();
+
+            new_folders
+                .and_then(|new_folders| ciphers.map(|ciphers| (folders, new_folders, ciphers)))
+        }
+        None => return,
+    };
+
+    let (folders, new_folders, ciphers) = match prepared {
+        Some(prepared) => prepared,
+        None => {
+            siv.add_layer(Dialog::info("Failed to encrypt the items"));
+            return;
+        }
+    };
+
+    let count = ciphers.len();
+
+    vault::request(
+        siv,
+        move |api, auth| {
+            let mut folders = folders.clone();
+            let mut imported = Imported {
+                created: 0,
+                failed: Vec::new(),
+                stopped: None,
+                queued: Vec::new(),
+                without_folder: 0,
+            };
+
+            // Errors are collected rather than returned, so that a retry
+            // never creates the same folder or item twice
+            for (key, name) in &new_folders {
+                match api.create_folder(auth, name) {
+                    Ok(folder) => {
+                        folders.insert(key.clone(), folder.uuid);
+                    }
+                    Err(err @ ApiError::Offline { .. })
+                    | Err(err @ ApiError::Unauthorized { .. }) => {
+                        imported.stopped = Some(err);
+                        break;
+                    }
+                    Err(err) => imported.failed.push(err.to_string()),
+                }
+            }
+
+            let mut sent = 0;
+            if imported.stopped.is_none() {
+                for (folder, cipher) in &ciphers {
+                    match api.create_cipher(auth, &with_folder(folder, cipher, &folders)) {
+                        Ok(()) => imported.created += 1,
+                        Err(err @ ApiError::Offline { .. })
+                        | Err(err @ ApiError::Unauthorized { .. }) => {
+                            imported.stopped = Some(err);
+                            break;
+                        }
+                        Err(err) => imported.failed.push(err.to_string()),
+                    }
+
+                    sent += 1;
+                }
+            }
+
+            let left = &ciphers[sent..];
+            imported.queued = left
+                .iter()
+                .map(|(folder, cipher)| with_folder(folder, cipher, &folders))
+                .collect();
+            imported.without_folder = left
+                .iter()
+                .filter(|(folder, _)| {
+                    folder
+                        .as_ref()
+                        .map_or(false, |key| !folders.contains_key(key))
+                })
+                .count();
+
+            Ok(imported)
+        },
+        move |siv, result: Result<Imported, ApiError>| {
+            match result {
+                Ok(imported) => done(siv, imported, count),
+                Err(err) => siv.add_layer(Dialog::info(format!("Import failed: {}", err))),
+            }
+
+            vault::sync_in_background(siv);
+        },
+    );
+}
+
+fn done(siv: &mut Cursive, imported: Imported, count: usize) {
+    let mut lines = vec![format!("Imported {} of {} items", imported.created, count)];
+
+    if !imported.failed.is_empty() {
+        lines.push(format!(
+            "{} folders or items were rejected:\n{}",
+            imported.failed.len(),
+            imported.failed.join("\n")
+        ));
+    }
+
+    if let Some(err) = &imported.stopped {
+        let mut message = format!(
+            "{} items will be created on the next sync: {}",
+            imported.queued.len(),
+            err
+        );
+
+        if imported.without_folder > 0 {
+            message.push_str(&format!(
+                ". Folders can only be created online, {} of them are queued without their \
+                 folder",
+                imported.without_folder
+            ));
+        }
+
+        lines.push(message);
+    }
+
+    if !imported.queued.is_empty() {
+        let operations = imported.queued.into_iter().map(Operation::Create).collect();
+        journal::queue_all(siv, operations);
+    }
+
+    siv.add_layer(
+        Dialog::around(TextView::new(lines.join("\n\n")).scrollable())
+            .title("import")
+            .dismiss_button("Close")
+            .min_width(50),
+    );
+}
@@ -0,0 +1,221 @@
+// SPDX-License-Identifier: MIT
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use cursive::traits::*;
+use cursive::views::{Dialog, TextView};
+use cursive::Cursive;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::api::{self, ApiError, CipherEntry, NewCipher, Update};
+use crate::cipher::{CipherString, CipherSuite};
+use crate::conflict;
+use crate::vault::{self, Session};
+
+// Set while queued operations are sent, so that syncs finishing meanwhile
+// don't send them twice
+static REPLAYING: AtomicBool = AtomicBool::new(false);
+
+/// Change made while offline, stored encrypted with the vault key next to
+/// the cache until it can be sent.
+#[derive(Clone, Deserialize, Serialize)]
+pub enum Operation {
+    Create(NewCipher),
+    Update(CipherEntry),
+}
+
+/// Outcome of sending the queued operations.
+struct Replayed {
+    // Operations done, from the start of the journal
+    done: usize,
+    conflicts: Vec<(CipherEntry, CipherEntry)>,
+    // Rejected by the server, they would be rejected again
+    failed: Vec<String>,
+    // Set if the server couldn't be reached or the token was rejected, the
+    // rest stays queued
+    stopped: Option<ApiError>,
+}
+
+fn read(siv: &mut Cursive) -> Option<Vec<Operation>> {
+    let session = siv.user_data::<Session>()?;
+
+    api::read_journal()
+        .unwrap_or_default()
+        .iter()
+        .map(|operation| {
+            operation
+                .decrypt(&session.cipher)
+                .and_then(|operation| serde_json::from_str(&operation).ok())
+        })
+        .collect()
+}
+
+fn superseded(later: &[Operation], entry: &CipherEntry) -> bool {
+    later.iter().any(|operation| match operation {
+        Operation::Update(later) => later.uuid == entry.uuid,
+        Operation::Create(_) => false,
+    })
+}
+
+/// Adds `operation` to the journal, encrypted with the vault key of
+/// `cipher`. Also used outside of the TUI, see `queue` otherwise.
+pub fn append(operation: &Operation, cipher: &CipherSuite) -> Result<(), String> {
+    let encrypted = serde_json::to_string(operation)
+        .ok()
+        .and_then(|operation| CipherString::encrypt(&operation, cipher))
+        .ok_or_else(|| "Failed to encrypt the change".to_owned())?;
+
+    let mut operations = api::read_journal().unwrap_or_default();
+    operations.push(encrypted);
+    api::save_journal(&operations).map_err(|err| err.to_string())
+}
+
+/// Queues `operation` to be sent on the next successful sync. Updates are
+/// applied to the local copy right away.
+pub fn queue(siv: &mut Cursive, operation: Operation) {
+    let queued = match siv.user_data::<Session>() {
+        Some(session) => append(&operation, &session.cipher),
+        None => return,
+    };
+
+    if let Err(err) = queued {
+        siv.add_layer(Dialog::info(format!("Failed to queue the change: {}", err)));
+        return;
+    }
+
+    if let Operation::Update(entry) = operation {
+        vault::replace_item(siv, entry.uuid, Some(entry));
+    }
+}
+
+/// Queues `operations` as with `queue`, used when a batch couldn't be sent.
+pub fn queue_all(siv: &mut Cursive, operations: Vec<Operation>) {
+    for operation in operations {
+        queue(siv, operation);
+    }
+}
+
+/// Sends the queued operations, in the order they were made. Called after
+/// every successful sync, it does nothing if the journal is empty.
+pub fn replay(siv: &mut Cursive) {
+    let operations = match read(siv) {
+        Some(operations) => operations,
+        None => {
+            warn!("offline changes can't be decrypted, keeping them");
+            return;
+        }
+    };
+
+    if operations.is_empty() || REPLAYING.swap(true, Ordering::SeqCst) {
+        return;
+    }
+
+    info!("sending {} changes made offline", operations.len());
+
+    vault::request(
+        siv,
+        move |api, auth| {
+            let mut replayed = Replayed {
+                done: 0,
+                conflicts: Vec::new(),
+                failed: Vec::new(),
+                stopped: None,
+            };
+
+            // Errors are collected rather than returned, so that a retry
+            // never sends the same operation twice
+            for (index, operation) in operations.iter().enumerate() {
+                let result = match operation {
+                    Operation::Create(cipher) => api.create_cipher(auth, cipher).map(|()| None),
+                    // Only the last edit of an item is sent, earlier ones would
+                    // make it look changed elsewhere
+                    Operation::Update(entry) if superseded(&operations[index + 1..], entry) => {
+                        Ok(None)
+                    }
+                    Operation::Update(entry) => {
+                        api.update_cipher(auth, entry).map(|update| match update {
+                            Update::Saved => None,
+                            Update::Conflict(theirs) => Some((entry.clone(), theirs)),
+                        })
+                    }
+                };
+
+                match result {
+                    Ok(conflict) => replayed.conflicts.extend(conflict),
+                    Err(err @ ApiError::Offline { .. })
+                    | Err(err @ ApiError::Unauthorized { .. }) => {
+                        replayed.stopped = Some(err);
+                        break;
+                    }
+                    Err(err) => replayed.failed.push(err.to_string()),
+                }
+
+                replayed.done += 1;
+            }
+
+            Ok(replayed)
+        },
+        |siv, result: Result<Replayed, ApiError>| {
+            REPLAYING.store(false, Ordering::SeqCst);
+
+            match result {
+                Ok(replayed) => done(siv, replayed),
+                Err(err) => warn!("failed to send offline changes: {}", err),
+            }
+        },
+    );
+}
+
+fn done(siv: &mut Cursive, replayed: Replayed) {
+    // Changes may have been queued meanwhile, they come after the sent ones
+    let mut operations = api::read_journal().unwrap_or_default();
+    operations.drain(..replayed.done.min(operations.len()));
+
+    if let Err(err) = api::save_journal(&operations) {
+        siv.add_layer(Dialog::info(format!(
+            "Failed to update the offline changes, they might be sent again: {}",
+            err
+        )));
+    }
+
+    let mut lines = Vec::new();
+    let sent = replayed.done - replayed.failed.len();
+
+    if sent > 0 {
+        lines.push(format!("Sent {} changes made offline", sent));
+    }
+
+    if !replayed.failed.is_empty() {
+        lines.push(format!(
+            "{} changes were rejected and dropped:\n{}",
+            replayed.failed.len(),
+            replayed.failed.join("\n")
+        ));
+    }
+
+    if let Some(err) = &replayed.stopped {
+        lines.push(format!(
+            "{} changes are still waiting: {}",
+            operations.len(),
+            err
+        ));
+    }
+
+    if replayed.done > 0 {
+        vault::sync_in_background(siv);
+    }
+
+    if !lines.is_empty() {
+        siv.add_layer(
+            Dialog::around(TextView::new(lines.join("\n\n")).scrollable())
+                .title("offline changes")
+                .dismiss_button("Close")
+                .min_width(50),
+        );
+    }
+
+    for (mine, theirs) in replayed.conflicts {
+        conflict::resolve(siv, mine, theirs);
+    }
+}
@@ -0,0 +1,84 @@
+// SPDX-License-Identifier: MIT
+
+// Logging to a file, for debugging login and sync problems. Every message is
+// redacted before being written, so logs can be shared.
+
+use std::fs::File;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use chrono::Local;
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+use crate::api;
+use crate::redact::redact;
+
+
+const LOG_FILE: &str = "bwtui.log";
+
+
+struct Logger {
+        level: Level,
+        file: Mutex<File>,
+}
+
+impl Log for Logger {
+        fn enabled(&self, metadata: &Metadata) -> bool {
+                // Dependencies (e.g. the HTTP client) log request details,
+                // only their warnings are of interest.
+                if metadata.target().starts_with(env!("CARGO_PKG_NAME")) {
+                        metadata.level() <= self.level
+                } else {
+                        metadata.level() <= Level::Warn
+                }
+        }
+
+        fn log(&self, record: &Record) {
+                if !self.enabled(record.metadata()) {
+                        return;
+                }
+
+                let line = format!(
+                        "{} {:5} {}: {}\n",
+                        Local::now().format("%Y-%m-%d %H:%M:%S%.3f"),
+                        record.level(),
+                        record.target(),
+                        redact(&record.args().to_string()),
+                );
+
+                if let Ok(mut file) = self.file.lock() {
+                        let _ = file.write_all(line.as_bytes());
+                }
+        }
+
+        fn flush(&self) {
+                if let Ok(mut file) = self.file.lock() {
+                        let _ = file.flush();
+                }
+        }
+}
+
+
+/// Starts logging to `path`, or bwtui.log in the data directory. Only
+/// warnings and important events are logged, unless `verbose` is set.
+pub fn init(verbose: bool, path: Option<PathBuf>) -> Result<(), String> {
+        let path = match path {
+                Some(path) => path,
+                None => api::get_app_data_path()?.join(LOG_FILE),
+        };
+
+        let file = api::private_file()
+                .append(true)
+                .create(true)
+                .open(&path)
+                .map_err(|e| format!("failed to open log file {}: {}", path.display(), e))?;
+
+        let level = if verbose { Level::Debug } else { Level::Info };
+
+        log::set_boxed_logger(Box::new(Logger { level, file: Mutex::new(file) }))
+                .map_err(|e| e.to_string())?;
+        log::set_max_level(LevelFilter::Debug);
+
+        Ok(())
+}
@@ -1,19 +1,118 @@
 // SPDX-License-Identifier: MIT
 
+use std::mem;
+use std::net::TcpListener;
+use std::thread;
+use std::time::{Duration, Instant};
+
 use cursive::direction::Orientation;
 use cursive::event::Event;
 use cursive::traits::*;
-use cursive::views::{Dialog, EditView, LinearLayout, OnEventView, TextView};
+use cursive::views::{
+    Checkbox, Dialog, DummyView, EditView, LinearLayout, OnEventView, SelectView, TextView,
+};
 use cursive::Cursive;
+use log::{info, warn};
 
-use crate::api::{self, ApiError, AppData, AuthData, VaultData};
+use crate::api::{
+    self, ApiError, AppData, AuthData, DeviceLogin, SsoLogin, VaultData, WebAuthnChallenge,
+};
 use crate::cipher::CipherSuite;
-use crate::vault;
+use crate::clip;
+use crate::pinentry;
+use crate::policy;
+use crate::register;
+use crate::sso;
+use crate::vault::{self, StartupView};
+use crate::verify;
+use crate::webauthn;
+use crate::worker;
+
+// Second factors which work by entering a code, in order of preference
+const TWO_FACTOR_PROVIDERS: &[(usize, &str, &str)] = &[
+    (0, "authenticator app", "code of your authenticator app:"),
+    (
+        3,
+        "YubiKey",
+        "focus the field below and touch your YubiKey:",
+    ),
+    (
+        7,
+        "security key (FIDO2)",
+        "PIN of your security key if it has one, touch the key after Ok:",
+    ),
+    (1, "email", "code sent to your email address:"),
+];
+
+// Duo, for the user and the organization, is approved in a browser
+const DUO_PROVIDERS: &[usize] = &[2, 6];
+const EMAIL_PROVIDER: usize = 1;
+const AUTHENTICATOR_PROVIDER: usize = 0;
+const AUTHENTICATOR_DIGITS: usize = 6;
+const YUBIKEY_PROVIDER: usize = 3;
+const WEBAUTHN_PROVIDER: usize = 7;
+// Yubico OTPs are made of 44 characters of modhex, which types the same on
+// most keyboard layouts
+const YUBIKEY_OTP_LENGTH: usize = 44;
+const MODHEX: &str = "cbdefghijklnrtuv";
+
+// Failed attempts after which further attempts get delayed
+const THROTTLE_AFTER: u32 = 3;
+const MAX_THROTTLE: Duration = Duration::from_secs(60);
+// Failed attempts after which the master password hint is offered
+const HINT_AFTER: u32 = 2;
+// Wrong PINs after which the PIN is forgotten, the master password has to be
+// used then
+const MAX_PIN_ATTEMPTS: u32 = 5;
+
+// How often to check whether another device approved the login, and for how
+// long the server keeps the request
+const AUTH_REQUEST_POLL_INTERVAL: Duration = Duration::from_secs(3);
+const AUTH_REQUEST_TIMEOUT: Duration = Duration::from_secs(15 * 60);
+
+/// State of the login screen, kept as cursive user data until the vault is
+/// unlocked.
+pub struct LoginState {
+    /// Cached data of a previous session, if any
+    pub app_data: Option<AppData>,
+    failed_attempts: u32,
+    retry_at: Option<Instant>,
+    pin_attempts: u32,
+    hint_offered: bool,
+    view: StartupView,
+    // Challenge for a security key and Duo link, if the server asked for a
+    // second factor
+    webauthn: Option<WebAuthnChallenge>,
+    duo: Option<String>,
+}
+
+/// Shows the login form. With the cached `app_data` of a session, which is
+/// still logged in but locked, it only asks for the master password to unlock
+/// the vault, or offers to log out.
+pub fn ask(siv: &mut Cursive, app_data: Option<AppData>, view: StartupView) {
+    let default_email = app_data
+        .as_ref()
+        .map(|data| data.vault.profile.email.clone());
+    let locked = default_email.is_some();
+    let has_pin = default_email.as_deref().map_or(false, api::has_pin_key);
+
+    siv.set_user_data(LoginState {
+        app_data,
+        failed_attempts: 0,
+        retry_at: None,
+        pin_attempts: 0,
+        hint_offered: false,
+        view,
+        webauthn: None,
+        duo: None,
+    });
 
-pub fn ask(siv: &mut Cursive, default_email: Option<String>) {
-    let email_edit = EditView::new()
-        .content(default_email.clone().unwrap_or("".to_owned()))
-        .with_name("email");
+    let mut email_edit = EditView::new().content(default_email.clone().unwrap_or("".to_owned()));
+    // Another account needs logging out first
+    if locked {
+        email_edit.disable();
+    }
+    let email_edit = email_edit.with_name("email");
 
     let email_view = OnEventView::new(email_edit).on_event(Event::CtrlChar('u'), |siv| {
         if let Some(mut view) = siv.find_name::<EditView>("email") {
@@ -21,88 +120,1317 @@ pub fn ask(siv: &mut Cursive, default_email: Option<String>) {
         }
     });
 
-    let password_edit = EditView::new().secret().with_name("master_password");
+    let mut layout = LinearLayout::new(Orientation::Vertical)
+        .child(TextView::new("email address:"))
+        .child(email_view);
+
+    // Otherwise pinentry asks for it once the form is submitted
+    if !pinentry::enabled() {
+        let password_edit = EditView::new().secret().with_name("master_password");
 
-    let password_view = OnEventView::new(password_edit).on_event(Event::CtrlChar('u'), |siv| {
-        if let Some(mut view) = siv.find_name::<EditView>("master_password") {
-            view.set_content("")(siv);
+        let password_view = OnEventView::new(password_edit).on_event(Event::CtrlChar('u'), |siv| {
+            if let Some(mut view) = siv.find_name::<EditView>("master_password") {
+                view.set_content("")(siv);
+            }
+        });
+
+        layout.add_child(TextView::new("master password:"));
+        layout.add_child(password_view);
+    }
+
+    layout.add_child(TextView::new("").with_name("login_status"));
+
+    let dialog = Dialog::around(layout)
+        .title(if locked {
+            "bitwarden vault locked"
+        } else {
+            "bitwarden vault login"
+        })
+        .button(if locked { "Unlock" } else { "Ok" }, |siv| {
+            let email = siv
+                .call_on_name("email", |view: &mut EditView| view.get_content())
+                .unwrap()
+                .to_string();
+
+            if let Some(password) = master_password(siv, &email) {
+                check_master_password(siv, email, &password);
+            }
+        });
+
+    let dialog = if has_pin {
+        dialog
+            .button("PIN", ask_pin)
+            .button("Log out", confirm_logout)
+    } else if locked {
+        dialog.button("Log out", confirm_logout)
+    } else {
+        dialog
+            .button("Register", register::ask)
+            .button("SSO", ask_sso)
+            .button("Device", request_device_login)
+    };
+
+    siv.add_layer(dialog.with_name("login_dialog").min_width(60));
+
+    if locked {
+        focus_password(siv);
+    }
+}
+
+/// Unlocks with the PIN set in the vault instead of the master password.
+fn ask_pin(siv: &mut Cursive) {
+    let layout = LinearLayout::vertical().child(TextView::new("PIN:")).child(
+        EditView::new()
+            .secret()
+            .on_submit(check_pin)
+            .with_name("unlock_pin"),
+    );
+
+    siv.add_layer(
+        Dialog::around(layout)
+            .title("unlock with PIN")
+            .button("Unlock", |siv| {
+                let pin = siv
+                    .call_on_name("unlock_pin", |view: &mut EditView| view.get_content())
+                    .unwrap();
+
+                check_pin(siv, &pin);
+            })
+            .dismiss_button("Cancel")
+            .min_width(40),
+    );
+}
+
+fn check_pin(siv: &mut Cursive, pin: &str) {
+    if pin.is_empty() {
+        return;
+    }
+
+    let account = siv
+        .user_data::<LoginState>()
+        .and_then(|state| state.app_data.as_ref())
+        .map(|data| (data.vault.profile.email.clone(), data.auth.kdf()));
+
+    let (email, kdf) = match account {
+        Some(account) => account,
+        None => return,
+    };
+
+    let pin = pin.to_owned();
+
+    siv.pop_layer();
+    siv.add_layer(Dialog::text("Unlocking ..."));
+
+    worker::run(
+        move |_| api::unlock_with_pin(&email, &pin, &kdf),
+        |siv, cipher| {
+            siv.pop_layer();
+
+            let cipher = match cipher {
+                Some(cipher) => cipher,
+                None => {
+                    wrong_pin(siv);
+                    return;
+                }
+            };
+
+            let app_data = siv
+                .user_data::<LoginState>()
+                .and_then(|state| state.app_data.take());
+
+            if let Some(AppData { mut auth, vault }) = app_data {
+                info!("unlocked the cached vault with the PIN");
+                auth.cipher = cipher;
+
+                let view = take_view(siv);
+                vault::show(siv, auth, vault, view);
+                vault::sync_in_background(siv);
+            }
+        },
+    );
+}
+
+fn wrong_pin(siv: &mut Cursive) {
+    let attempts = match siv.user_data::<LoginState>() {
+        Some(state) => {
+            state.pin_attempts += 1;
+            state.pin_attempts
         }
-    });
+        None => return,
+    };
 
-    let layout = LinearLayout::new(Orientation::Vertical)
-        .child(TextView::new("email address:"))
-        .child(email_view)
-        .child(TextView::new("master password:"))
-        .child(password_view);
+    if attempts < MAX_PIN_ATTEMPTS {
+        login_failed(siv, "Wrong PIN");
+        return;
+    }
+
+    warn!("too many wrong PINs, forgetting the PIN");
+    if let Err(err) = api::remove_pin_key() {
+        warn!("failed to remove the PIN: {}", err);
+    }
+
+    login_failed(siv, "Too many wrong PINs, unlock with the master password");
+}
+
+fn confirm_logout(siv: &mut Cursive) {
+    siv.add_layer(
+        Dialog::text(
+            "Log out of this device? The cached vault is removed, along with \
+             changes not synced yet.",
+        )
+        .title("log out")
+        .button("Log out", logout)
+        .dismiss_button("Cancel")
+        .max_width(60),
+    );
+}
+
+/// Logs the locked session out, without unlocking it first.
+fn logout(siv: &mut Cursive) {
+    let app_data = siv
+        .user_data::<LoginState>()
+        .and_then(|state| state.app_data.take());
+
+    let AppData { auth, vault } = match app_data {
+        Some(app_data) => app_data,
+        None => return,
+    };
+
+    info!("logging out");
+    siv.pop_layer();
+    siv.add_layer(Dialog::text("Logging out ..."));
+
+    let view = take_view(siv);
+    let email = vault.profile.email.clone();
+
+    worker::run(
+        move |api| {
+            // Nothing to revoke offline, the token expires on its own
+            if let Err(err) = api.revoke(&auth) {
+                warn!("failed to revoke the session: {}", err);
+            }
+
+            api::remove_account_data()
+        },
+        move |siv, removed| {
+            while siv.pop_layer().is_some() {}
+
+            ask(siv, None, view);
+            logged_out(siv, &email);
+
+            if let Err(err) = removed {
+                siv.add_layer(Dialog::info(format!(
+                    "Failed to remove the cached data: {}",
+                    err
+                )));
+            }
+        },
+    );
+}
+
+/// Has another device of the account, e.g. the mobile app, approve the login
+/// instead of entering the master password.
+fn request_device_login(siv: &mut Cursive) {
+    let email = siv
+        .call_on_name("email", |view: &mut EditView| view.get_content())
+        .unwrap()
+        .to_string();
+
+    if email.is_empty() {
+        set_status(siv, "Enter your email address first");
+        return;
+    }
+
+    set_status(siv, "");
+    siv.add_layer(Dialog::text("Requesting the login ..."));
+
+    worker::run(
+        move |api| api.start_device_login(&email),
+        |siv, result| {
+            siv.pop_layer();
+
+            let request = match result {
+                Ok(request) => request,
+                Err(err) => {
+                    login_failed(siv, &err.to_string());
+                    return;
+                }
+            };
+
+            siv.add_layer(
+                Dialog::around(TextView::new(format!(
+                    "Approve the login request in the Bitwarden app on another \
+                     device, if it shows the same fingerprint phrase:\n\n{}",
+                    request.fingerprint()
+                )))
+                .title("log in with device")
+                .dismiss_button("Cancel")
+                .with_name("auth_request_waiting")
+                .max_width(60),
+            );
+
+            poll_device_login(siv, request, Instant::now());
+        },
+    );
+}
+
+fn poll_device_login(siv: &mut Cursive, request: DeviceLogin, started: Instant) {
+    // Cancelled meanwhile
+    if siv.find_name::<Dialog>("auth_request_waiting").is_none() {
+        return;
+    }
+
+    if started.elapsed() >= AUTH_REQUEST_TIMEOUT {
+        siv.pop_layer();
+        login_failed(siv, "The login request expired");
+        return;
+    }
+
+    worker::run(
+        move |api| {
+            let result = api.check_device_login(&request);
+            (request, result)
+        },
+        move |siv, (request, result)| {
+            if siv.find_name::<Dialog>("auth_request_waiting").is_none() {
+                return;
+            }
+
+            match result {
+                Ok(Some(auth_data)) => {
+                    siv.pop_layer();
+                    sync_vault_data(siv, auth_data, None);
+                }
+                Ok(None) => {
+                    let cb_sink = siv.cb_sink().clone();
+
+                    thread::spawn(move || {
+                        thread::sleep(AUTH_REQUEST_POLL_INTERVAL);
+
+                        let _ = cb_sink.send(Box::new(move |siv: &mut Cursive| {
+                            poll_device_login(siv, request, started)
+                        }));
+                    });
+                }
+                Err(err) => {
+                    siv.pop_layer();
+                    login_failed(siv, &err.to_string());
+                }
+            }
+        },
+    );
+}
+
+/// Asks for the SSO identifier of the organization to log in with. The
+/// master password is still needed to decrypt the vault.
+fn ask_sso(siv: &mut Cursive) {
+    let email = siv
+        .call_on_name("email", |view: &mut EditView| view.get_content())
+        .unwrap()
+        .to_string();
+
+    if email.is_empty() {
+        set_status(siv, "Enter your email address and master password first");
+        return;
+    }
+
+    let master_password = match master_password(siv, &email) {
+        Some(password) if !password.is_empty() => password,
+        Some(_) => {
+            set_status(siv, "Enter your email address and master password first");
+            return;
+        }
+        None => return,
+    };
+
+    let layout = LinearLayout::vertical()
+        .child(TextView::new("SSO identifier of your organization:"))
+        .child(EditView::new().with_name("sso_identifier"));
 
     siv.add_layer(
         Dialog::around(layout)
-            .title("bitwarden vault login")
-            .button("Ok", |siv| {
-                let email = siv
-                    .call_on_name("email", |view: &mut EditView| view.get_content())
+            .title("single sign-on")
+            .button("Ok", move |siv| {
+                let identifier = siv
+                    .call_on_name("sso_identifier", |view: &mut EditView| view.get_content())
                     .unwrap()
-                    .to_string();
+                    .trim()
+                    .to_owned();
 
-                let password = siv
-                    .call_on_name("master_password", |view: &mut EditView| view.get_content())
-                    .unwrap();
+                if identifier.is_empty() {
+                    return;
+                }
 
-                check_master_password(siv, email, &password);
+                siv.pop_layer();
+                start_sso(siv, email.clone(), master_password.clone(), identifier);
             })
-            .min_width(60),
+            .dismiss_button("Cancel")
+            .min_width(50),
+    );
+}
+
+fn start_sso(siv: &mut Cursive, email: String, master_password: String, identifier: String) {
+    let (listener, redirect_uri) = match sso::listen() {
+        Ok(listening) => listening,
+        Err(err) => {
+            login_failed(siv, &format!("SSO login failed: {}", err));
+            return;
+        }
+    };
+
+    siv.add_layer(Dialog::text("Starting the SSO login ..."));
+
+    worker::run(
+        move |api| api.start_sso(&identifier, &redirect_uri),
+        move |siv, result| {
+            siv.pop_layer();
+
+            match result {
+                Ok(login) => wait_for_sso(siv, email, master_password, login, listener),
+                Err(err) => login_failed(siv, &err.to_string()),
+            }
+        },
+    );
+}
+
+/// Sends the user to the identity provider, the browser comes back with the
+/// code on `listener`.
+fn wait_for_sso(
+    siv: &mut Cursive,
+    email: String,
+    master_password: String,
+    login: SsoLogin,
+    listener: TcpListener,
+) {
+    sso::open_browser(&login.url);
+
+    let url = login.url.clone();
+    let message = format!(
+        "Log in with your organization in the browser. If none opened, open\n\n{}",
+        url
+    );
+
+    siv.add_layer(
+        Dialog::around(TextView::new(message))
+            .title("single sign-on")
+            .button("Copy link", move |siv| {
+                if let Err(err) = clip::copy(&url) {
+                    siv.add_layer(Dialog::info(err));
+                }
+            })
+            .dismiss_button("Cancel")
+            .with_name("sso_waiting"),
+    );
+
+    let cb_sink = siv.cb_sink().clone();
+
+    thread::spawn(move || {
+        let code = sso::wait_for_code(listener, &login.state);
+
+        let _ = cb_sink.send(Box::new(move |siv: &mut Cursive| {
+            sso_redirected(siv, email, master_password, login, code)
+        }));
+    });
+}
+
+fn sso_redirected(
+    siv: &mut Cursive,
+    email: String,
+    master_password: String,
+    login: SsoLogin,
+    code: Result<String, String>,
+) {
+    // Cancelled meanwhile
+    if siv.find_name::<Dialog>("sso_waiting").is_none() {
+        return;
+    }
+
+    siv.pop_layer();
+
+    let code = match code {
+        Ok(code) => code,
+        Err(err) => {
+            login_failed(siv, &format!("SSO login failed: {}", err));
+            return;
+        }
+    };
+
+    siv.add_layer(Dialog::text("Logging in ..."));
+
+    worker::run(
+        move |api| {
+            let auth_data = api.finish_sso(&email, &master_password, &login, &code);
+            (auth_data, master_password)
+        },
+        |siv, (auth_data, master_password)| {
+            siv.pop_layer();
+
+            match auth_data {
+                Ok(auth_data) => sync_vault_data(siv, auth_data, Some(master_password)),
+                Err(err) => login_failed(siv, &err.to_string()),
+            }
+        },
     );
+}
+
+/// Prefills the login form after a new account was created.
+pub fn registered(siv: &mut Cursive, email: &str) {
+    siv.call_on_name("email", |view: &mut EditView| {
+        view.set_content(email);
+    });
+
+    set_status(siv, "Account created, log in with your new master password");
+    focus_password(siv);
+}
+
+/// Prefills the login form after logging out, to make logging in again easy.
+pub fn logged_out(siv: &mut Cursive, email: &str) {
+    siv.call_on_name("email", |view: &mut EditView| {
+        view.set_content(email);
+    });
+
+    set_status(siv, "Logged out, the cached vault was removed");
+}
+
+/// Prefills the login form after the master password was changed from the
+/// vault.
+pub fn password_updated(siv: &mut Cursive, email: &str) {
+    siv.call_on_name("email", |view: &mut EditView| {
+        view.set_content(email);
+    });
 
-    if default_email.is_some() {
+    set_status(siv, "Master password changed, log in with the new one");
+    focus_password(siv);
+}
+
+/// Prefills the login form after the master password was changed on another
+/// device.
+pub fn password_changed(siv: &mut Cursive, email: &str) {
+    siv.call_on_name("email", |view: &mut EditView| {
+        view.set_content(email);
+    });
+
+    set_status(
+        siv,
+        "Master password changed elsewhere, log in with the new one",
+    );
+    focus_password(siv);
+}
+
+/// Prefills the login form after the security stamp of the account changed
+/// while the vault was open, e.g. all its sessions were revoked.
+pub fn security_stamp_changed(siv: &mut Cursive, email: &str) {
+    siv.call_on_name("email", |view: &mut EditView| {
+        view.set_content(email);
+    });
+
+    set_status(
+        siv,
+        "Account security changed elsewhere (password changed or sessions \
+         revoked), log in again",
+    );
+    focus_password(siv);
+}
+
+/// Prefills the login form after the session expired while the vault was
+/// open.
+pub fn session_expired(siv: &mut Cursive, email: &str) {
+    siv.call_on_name("email", |view: &mut EditView| {
+        view.set_content(email);
+    });
+
+    set_status(siv, "Session expired, log in again to keep syncing");
+    focus_password(siv);
+}
+
+/// Master password of `email`, entered in the form or asked with pinentry.
+/// `None` if pinentry was cancelled or failed, the reason is shown then.
+fn master_password(siv: &mut Cursive, email: &str) -> Option<String> {
+    if !pinentry::enabled() {
+        return siv
+            .call_on_name("master_password", |view: &mut EditView| view.get_content())
+            .map(|password| password.to_string());
+    }
+
+    let description = format!("Master password of the bitwarden account {}", email);
+
+    match pinentry::ask(siv, &description, "Master password:") {
+        Ok(password) => password,
+        Err(err) => {
+            set_status(siv, &format!("pinentry failed: {}", err));
+            None
+        }
+    }
+}
+
+/// Focuses the master password field, unless pinentry asks for it.
+fn focus_password(siv: &mut Cursive) {
+    if !pinentry::enabled() {
         siv.focus_name("master_password").unwrap();
     }
 }
 
+/// Hands the startup view over to the vault, it only applies once.
+fn take_view(siv: &mut Cursive) -> StartupView {
+    siv.user_data::<LoginState>()
+        .map(|state| mem::take(&mut state.view))
+        .unwrap_or_default()
+}
+
+fn set_status(siv: &mut Cursive, message: &str) {
+    siv.call_on_name("login_status", |view: &mut TextView| {
+        view.set_content(message);
+    });
+}
+
+/// Time left until the next login attempt is allowed, if any.
+fn throttled(siv: &mut Cursive) -> Option<Duration> {
+    let retry_at = siv.user_data::<LoginState>()?.retry_at?;
+    let now = Instant::now();
+
+    if retry_at > now {
+        Some(retry_at - now)
+    } else {
+        None
+    }
+}
+
+fn login_failed(siv: &mut Cursive, message: &str) {
+    warn!("login failed: {}", message);
+
+    let attempts = match siv.user_data::<LoginState>() {
+        Some(state) => {
+            state.failed_attempts += 1;
+
+            if state.failed_attempts >= THROTTLE_AFTER {
+                // 5s, 10s, 20s, ... up to a minute
+                let exponent = (state.failed_attempts - THROTTLE_AFTER).min(4);
+                let delay = Duration::from_secs(5 * 2u64.pow(exponent)).min(MAX_THROTTLE);
+
+                state.retry_at = Some(Instant::now() + delay);
+            }
+
+            state.failed_attempts
+        }
+        None => 0,
+    };
+
+    set_status(siv, &format!("{} (failed attempts: {})", message, attempts));
+
+    if attempts >= HINT_AFTER {
+        offer_hint(siv);
+    }
+
+    siv.call_on_name("master_password", |view: &mut EditView| {
+        view.set_content("");
+    });
+    focus_password(siv);
+}
+
+/// Adds a button showing the master password hint to the login form, once.
+fn offer_hint(siv: &mut Cursive) {
+    let offered = match siv.user_data::<LoginState>() {
+        Some(state) => mem::replace(&mut state.hint_offered, true),
+        None => return,
+    };
+
+    if !offered {
+        siv.call_on_name("login_dialog", |view: &mut Dialog| {
+            view.add_button("Hint", show_hint);
+        });
+    }
+}
+
+/// Shows the hint of the cached account, or has the server email it when
+/// nothing is cached.
+fn show_hint(siv: &mut Cursive) {
+    let cached = siv
+        .user_data::<LoginState>()
+        .and_then(|state| state.app_data.as_ref())
+        .map(|data| data.vault.profile.master_password_hint.clone());
+
+    if let Some(hint) = cached {
+        let message = match hint.filter(|hint| !hint.is_empty()) {
+            Some(hint) => format!("Your master password hint:\n\n{}", hint),
+            None => "No master password hint is set for this account".to_owned(),
+        };
+
+        siv.add_layer(Dialog::info(message).title("master password hint"));
+        return;
+    }
+
+    let email = siv
+        .call_on_name("email", |view: &mut EditView| view.get_content())
+        .unwrap()
+        .trim()
+        .to_owned();
+
+    if email.is_empty() {
+        set_status(siv, "Enter your email address first");
+        return;
+    }
+
+    siv.add_layer(Dialog::text("Requesting the hint ..."));
+
+    worker::run(
+        move |api| api.request_password_hint(&email).map(|()| email),
+        |siv, result: Result<String, ApiError>| {
+            siv.pop_layer();
+
+            let message = match result {
+                Ok(email) => format!(
+                    "If the account exists and has a hint, it was emailed to {}",
+                    email
+                ),
+                Err(err) => err.to_string(),
+            };
+
+            siv.add_layer(Dialog::info(message).title("master password hint"));
+        },
+    );
+}
+
 fn check_master_password(siv: &mut Cursive, email: String, master_password: &str) {
-    if let Some(app_data) = siv.take_user_data::<AppData>() {
+    if let Some(wait) = throttled(siv) {
+        let message = format!("Too many failed attempts, retry in {}s", wait.as_secs() + 1);
+        set_status(siv, &message);
+        return;
+    }
+
+    let cached = siv
+        .user_data::<LoginState>()
+        .and_then(|state| state.app_data.take());
+
+    if let Some(app_data) = cached {
         let AppData { mut auth, vault } = app_data;
 
-        auth.cipher = CipherSuite::from(&email, master_password, auth.kdf_iterations);
+        let cipher = match CipherSuite::with_kdf(&email, master_password, &auth.kdf()) {
+            Ok(cipher) => cipher,
+            Err(err) => {
+                if let Some(state) = siv.user_data::<LoginState>() {
+                    state.app_data = Some(AppData { auth, vault });
+                }
+                set_status(siv, &err.to_string());
+                return;
+            }
+        };
+        auth.cipher = cipher;
+        auth.read_two_factor_token(&email);
 
         if let Err(_) = auth.cipher.set_decrypt_key(&vault.profile.key) {
-            siv.add_layer(Dialog::info("Wrong vault password"));
+            // Either a typo, or the master password was changed on another
+            // device, which only the server can tell.
+            check_changed_password(
+                siv,
+                email,
+                master_password.to_owned(),
+                AppData { auth, vault },
+            );
         } else {
-            vault::show(siv, auth, vault);
+            info!("unlocked the cached vault");
+
+            let view = take_view(siv);
+            vault::show(siv, auth, vault, view);
+            policy::check_master_password(siv, master_password);
+
+            // The cache is shown right away, fetch any changes while the user
+            // is already browsing.
+            vault::sync_in_background(siv);
         }
 
         return;
     }
 
-    let auth_data = api::authenticate(&email, &master_password);
+    let master_password = master_password.to_owned();
 
-    match auth_data {
-        Ok(mut auth_data) => {
+    set_status(siv, "");
+    siv.add_layer(Dialog::text("Logging in ..."));
+
+    worker::run(
+        move |api| {
+            let auth_data = api.authenticate(&email, &master_password);
+            (auth_data, email, master_password)
+        },
+        |siv, (auth_data, email, master_password)| {
             siv.pop_layer();
 
-            let vault = sync_vault_data(siv, &auth_data).unwrap();
+            match auth_data {
+                Ok(auth_data) => sync_vault_data(siv, auth_data, Some(master_password)),
+                Err(ApiError::TwoFactorRequired {
+                    providers,
+                    webauthn,
+                    duo,
+                }) => {
+                    if let Some(state) = siv.user_data::<LoginState>() {
+                        state.webauthn = webauthn;
+                        state.duo = duo;
+                    }
 
-            if let Err(_) = auth_data.cipher.set_decrypt_key(&vault.profile.key) {
-                siv.add_layer(Dialog::info("Wrong vault password"));
-            } else {
-                vault::show(siv, auth_data, vault);
+                    ask_two_factor(siv, email, master_password, &providers)
+                }
+                Err(ApiError::CaptchaRequired) => ask_captcha(siv, email, master_password),
+                Err(ApiError::DeviceVerificationRequired) => {
+                    ask_new_device_otp(siv, email, master_password)
+                }
+                Err(err) => login_failed(siv, &err.to_string()),
             }
-        }
-        Err(_) => siv.add_layer(Dialog::info("Wrong vault password")),
-    }
+        },
+    );
 }
 
-fn sync_vault_data(siv: &mut Cursive, auth_data: &AuthData) -> Result<VaultData, ApiError> {
-    match api::sync(&auth_data) {
-        Ok(vault_data) => {
-            if let Err(err) = api::save_app_data(&auth_data, &vault_data) {
-                siv.add_layer(Dialog::info(err.to_string()));
+/// Asks for the code the server emailed to verify a device it doesn't know,
+/// then logs in again with it.
+fn ask_new_device_otp(siv: &mut Cursive, email: String, master_password: String) {
+    let layout = LinearLayout::vertical()
+        .child(TextView::new(format!(
+            "The server doesn't know this device yet and emailed a \
+             verification code to {}.",
+            email
+        )))
+        .child(DummyView)
+        .child(TextView::new("verification code:"))
+        .child(EditView::new().with_name("new_device_otp"));
+
+    let resend_email = email.clone();
+    let resend_password = master_password.clone();
+
+    siv.add_layer(
+        Dialog::around(layout)
+            .title("new device")
+            .button("Ok", move |siv| {
+                let code = siv
+                    .call_on_name("new_device_otp", |view: &mut EditView| view.get_content())
+                    .unwrap()
+                    .trim()
+                    .to_owned();
+
+                if code.is_empty() {
+                    return;
+                }
+
+                siv.pop_layer();
+
+                let email = email.clone();
+                let master_password = master_password.clone();
+
+                worker::run(
+                    move |api| api.set_new_device_otp(Some(code)),
+                    move |siv, ()| check_master_password(siv, email, &master_password),
+                );
+            })
+            .button("Resend code", move |siv| {
+                let email = resend_email.clone();
+                let master_password = resend_password.clone();
+
+                worker::run(
+                    move |api| api.resend_new_device_otp(&email, &master_password),
+                    |siv, result| {
+                        let message = match result {
+                            Ok(()) => "A new code was sent".to_owned(),
+                            Err(err) => format!("Failed to send a new code: {}", err),
+                        };
+                        siv.add_layer(Dialog::info(message));
+                    },
+                );
+            })
+            .dismiss_button("Cancel")
+            .min_width(60),
+    );
+}
+
+/// Explains the captcha the server asks for on logins from unknown devices,
+/// which bwtui can't show, and has the user pass it with their API key.
+fn ask_captcha(siv: &mut Cursive, email: String, master_password: String) {
+    let layout = LinearLayout::vertical()
+        .child(TextView::new(
+            "The server doesn't know this device yet and asks for a captcha, \
+             which bwtui can't show. Enter the client_secret of your personal \
+             API key instead, found in the web vault under Account settings, \
+             Security, Keys, \"View API key\".",
+        ))
+        .child(DummyView)
+        .child(TextView::new("client_secret:"))
+        .child(EditView::new().secret().with_name("captcha_client_secret"));
+
+    siv.add_layer(
+        Dialog::around(layout)
+            .title("captcha")
+            .button("Ok", move |siv| {
+                let client_secret = siv
+                    .call_on_name("captcha_client_secret", |view: &mut EditView| {
+                        view.get_content()
+                    })
+                    .unwrap()
+                    .trim()
+                    .to_owned();
+
+                if client_secret.is_empty() {
+                    return;
+                }
+
+                siv.pop_layer();
+
+                let email = email.clone();
+                let master_password = master_password.clone();
+
+                worker::run(
+                    move |api| api.set_captcha_response(Some(client_secret)),
+                    move |siv, ()| check_master_password(siv, email, &master_password),
+                );
+            })
+            .dismiss_button("Cancel")
+            .min_width(60),
+    );
+}
+
+/// Logs in online with a master password which doesn't unlock the cache. If
+/// the server accepts it, the password or KDF were changed elsewhere and the
+/// stale cache is replaced by a fresh sync under the new keys.
+fn check_changed_password(
+    siv: &mut Cursive,
+    email: String,
+    master_password: String,
+    app_data: AppData,
+) {
+    set_status(siv, "");
+    siv.add_layer(Dialog::text("Checking the master password ..."));
+
+    worker::run(
+        move |api| {
+            let auth_data = api.authenticate(&email, &master_password);
+            (auth_data, email, master_password)
+        },
+        move |siv, (auth_data, email, master_password)| {
+            siv.pop_layer();
+
+            match auth_data {
+                Ok(auth_data) => {
+                    discard_cache();
+                    sync_vault_data(siv, auth_data, Some(master_password));
+                }
+                Err(ApiError::TwoFactorRequired {
+                    providers,
+                    webauthn,
+                    duo,
+                }) => {
+                    if let Some(state) = siv.user_data::<LoginState>() {
+                        state.webauthn = webauthn;
+                        state.duo = duo;
+                    }
+
+                    discard_cache();
+                    ask_two_factor(siv, email, master_password, &providers);
+                }
+                Err(ApiError::CaptchaRequired) => {
+                    // Asked again once the captcha is passed
+                    if let Some(state) = siv.user_data::<LoginState>() {
+                        state.app_data = Some(app_data);
+                    }
+
+                    ask_captcha(siv, email, master_password);
+                }
+                Err(ApiError::DeviceVerificationRequired) => {
+                    if let Some(state) = siv.user_data::<LoginState>() {
+                        state.app_data = Some(app_data);
+                    }
+
+                    ask_new_device_otp(siv, email, master_password);
+                }
+                Err(err) => {
+                    info!("master password not accepted online either: {}", err);
+
+                    // Keep using the cache for the next attempt
+                    if let Some(state) = siv.user_data::<LoginState>() {
+                        state.app_data = Some(app_data);
+                    }
+
+                    login_failed(siv, "Wrong vault password");
+                }
             }
+        },
+    );
+}
+
+fn discard_cache() {
+    info!("master password changed on another device, discarding the cache");
+
+    if let Err(err) = api::remove_app_data() {
+        warn!("failed to remove the stale cache: {}", err);
+    }
+}
+
+/// Names of the second factors of the account that can be picked from, by
+/// preference.
+fn two_factor_choices(providers: &[usize]) -> Vec<(&'static str, usize)> {
+    let mut choices: Vec<(&str, usize)> = TWO_FACTOR_PROVIDERS
+        .iter()
+        .filter(|(provider, _, _)| providers.contains(provider))
+        .map(|(provider, name, _)| (*name, *provider))
+        .collect();
+
+    if let Some(duo) = DUO_PROVIDERS.iter().find(|duo| providers.contains(duo)) {
+        choices.push(("Duo", *duo));
+    }
+
+    choices
+}
+
+/// Asks which second factor to use if the account has several, the code of
+/// the only one otherwise.
+fn ask_two_factor(siv: &mut Cursive, email: String, master_password: String, providers: &[usize]) {
+    let choices = two_factor_choices(providers);
 
-            Ok(vault_data)
+    match choices.as_slice() {
+        [] => {
+            login_failed(
+                siv,
+                "The second factors of this account aren't supported, \
+                 add an authenticator app, email or YubiKey",
+            );
+            return;
         }
-        Err(err) => {
-            siv.add_layer(Dialog::info(err.to_string()));
-            Err(err)
+        [(_, provider)] => {
+            let provider = *provider;
+            ask_two_factor_code(siv, email, master_password, provider, providers.to_vec());
+            return;
+        }
+        _ => (),
+    }
+
+    let providers = providers.to_vec();
+    let mut picker = SelectView::new();
+    for (name, provider) in choices {
+        picker.add_item(name, provider);
+    }
+
+    picker.set_on_submit(move |siv, provider: &usize| {
+        siv.pop_layer();
+        ask_two_factor_code(
+            siv,
+            email.clone(),
+            master_password.clone(),
+            *provider,
+            providers.clone(),
+        );
+    });
+
+    siv.add_layer(
+        Dialog::around(
+            LinearLayout::vertical()
+                .child(TextView::new("log in with:"))
+                .child(picker),
+        )
+        .title("two-step login")
+        .dismiss_button("Cancel")
+        .min_width(50),
+    );
+}
+
+/// Asks for the code of the second factor of type `provider`.
+fn ask_two_factor_code(
+    siv: &mut Cursive,
+    email: String,
+    master_password: String,
+    provider: usize,
+    providers: Vec<usize>,
+) {
+    let is_duo = DUO_PROVIDERS.contains(&provider);
+    let duo = siv
+        .user_data::<LoginState>()
+        .and_then(|state| state.duo.clone());
+
+    let prompt = match (is_duo, &duo) {
+        (true, Some(url)) => format!(
+            "Approve the login with Duo in a browser at\n\n{}\n\nthen paste the \
+             address of the page it ends on:",
+            url
+        ),
+        (true, None) => {
+            siv.add_layer(Dialog::info(
+                "The server sent no Duo link, pick another method or update \
+                 the server to use Duo's Universal Prompt",
+            ));
+            return;
         }
+        (false, _) => TWO_FACTOR_PROVIDERS
+            .iter()
+            .find(|(known, _, _)| *known == provider)
+            .map_or("code:", |(_, _, prompt)| *prompt)
+            .to_owned(),
+    };
+
+    // Duo's address is pasted, pinentry asks for anything typed
+    let use_pinentry = pinentry::enabled() && !is_duo;
+
+    let mut layout = LinearLayout::vertical().child(TextView::new(prompt.clone()));
+
+    if !use_pinentry {
+        // The PIN of a security key is entered instead of a code
+        let code_edit = if provider == WEBAUTHN_PROVIDER {
+            EditView::new().secret()
+        } else {
+            EditView::new()
+        };
+
+        layout.add_child(code_edit.with_name("two_factor_code"));
+    }
+
+    let layout = layout.child(DummyView).child(
+        LinearLayout::horizontal()
+            .child(Checkbox::new().with_name("two_factor_remember"))
+            .child(TextView::new(" remember this device")),
+    );
+
+    let (ok_email, ok_password) = (email.clone(), master_password.clone());
+
+    let mut dialog = Dialog::around(layout)
+        .title("two-step login")
+        .button("Ok", move |siv| {
+            let content = if use_pinentry {
+                let label = if provider == WEBAUTHN_PROVIDER {
+                    "PIN:"
+                } else {
+                    "Code:"
+                };
+
+                match pinentry::ask(siv, &prompt, label) {
+                    Ok(Some(content)) => content,
+                    Ok(None) => return,
+                    Err(err) => {
+                        siv.add_layer(Dialog::info(format!("pinentry failed: {}", err)));
+                        return;
+                    }
+                }
+            } else {
+                siv.call_on_name("two_factor_code", |view: &mut EditView| view.get_content())
+                    .unwrap()
+                    .to_string()
+            };
+            let code = if is_duo {
+                match duo_token(content.trim()) {
+                    Some(token) => token,
+                    None => {
+                        siv.add_layer(Dialog::info(
+                            "Paste the whole address of the page Duo ended on",
+                        ));
+                        return;
+                    }
+                }
+            } else {
+                content.trim().replace(' ', "")
+            };
+
+            let webauthn = if provider == WEBAUTHN_PROVIDER {
+                match siv
+                    .user_data::<LoginState>()
+                    .and_then(|state| state.webauthn.clone())
+                {
+                    Some(challenge) => Some(challenge),
+                    None => {
+                        siv.add_layer(Dialog::info("The server sent no challenge to sign"));
+                        return;
+                    }
+                }
+            } else {
+                None
+            };
+
+            if code.is_empty() && webauthn.is_none() {
+                return;
+            }
+
+            if provider == AUTHENTICATOR_PROVIDER
+                && (code.len() != AUTHENTICATOR_DIGITS || !code.chars().all(|c| c.is_ascii_digit()))
+            {
+                siv.add_layer(Dialog::info(format!(
+                    "Codes of authenticator apps are {} digits",
+                    AUTHENTICATOR_DIGITS
+                )));
+                return;
+            }
+
+            if provider == YUBIKEY_PROVIDER
+                && (code.len() != YUBIKEY_OTP_LENGTH || !code.chars().all(|c| MODHEX.contains(c)))
+            {
+                siv.add_layer(Dialog::info(format!(
+                    "That isn't a YubiKey OTP, touching the key types {} characters",
+                    YUBIKEY_OTP_LENGTH
+                )));
+                return;
+            }
+
+            let remember = siv
+                .call_on_name("two_factor_remember", |view: &mut Checkbox| {
+                    view.is_checked()
+                })
+                .unwrap_or(false);
+
+            let email = ok_email.clone();
+            let master_password = ok_password.clone();
+
+            siv.add_layer(Dialog::text(if webauthn.is_some() {
+                "Touch your security key ..."
+            } else {
+                "Logging in ..."
+            }));
+
+            worker::run(
+                move |api| {
+                    let token = match &webauthn {
+                        Some(challenge) => {
+                            let pin = Some(content.as_str()).filter(|pin| !pin.is_empty());
+
+                            webauthn::sign(challenge, &api.web_vault_url(), pin)
+                                .map_err(|error| ApiError::LoginFailed { error })
+                        }
+                        None => Ok(code),
+                    };
+
+                    let auth_data = token.and_then(|token| {
+                        api.authenticate_two_factor(
+                            &email,
+                            &master_password,
+                            provider,
+                            &token,
+                            remember,
+                        )
+                    });
+                    (auth_data, master_password)
+                },
+                |siv, (auth_data, master_password)| {
+                    siv.pop_layer();
+
+                    match auth_data {
+                        Ok(auth_data) => {
+                            // Drop the code dialog
+                            siv.pop_layer();
+                            sync_vault_data(siv, auth_data, Some(master_password));
+                        }
+                        Err(err) => siv.add_layer(Dialog::info(err.to_string())),
+                    }
+                },
+            );
+        });
+
+    if let Some(url) = duo {
+        dialog.add_button("Copy link", move |siv| {
+            if let Err(err) = clip::copy(&url) {
+                siv.add_layer(Dialog::info(err));
+            }
+        });
+    }
+
+    let several = two_factor_choices(&providers).len() > 1;
+    // The server only emails a code by itself if there is no other second
+    // factor to pick
+    let send_code = provider == EMAIL_PROVIDER && several;
+
+    if provider == EMAIL_PROVIDER {
+        let (email, master_password) = (email.clone(), master_password.clone());
+
+        dialog.add_button("Resend code", move |siv| {
+            send_two_factor_email(siv, email.clone(), master_password.clone());
+        });
+    }
+
+    if several {
+        let (email, master_password) = (email.clone(), master_password.clone());
+
+        dialog.add_button("Other method", move |siv| {
+            siv.pop_layer();
+            ask_two_factor(siv, email.clone(), master_password.clone(), &providers);
+        });
+    }
+
+    siv.add_layer(dialog.dismiss_button("Cancel").min_width(50));
+
+    if send_code {
+        send_two_factor_email(siv, email, master_password);
+    }
+}
+
+/// Token of an approved Duo login, from the address of the page Duo
+/// redirected to, or its code and state as `code|state` already.
+fn duo_token(input: &str) -> Option<String> {
+    if !input.contains("code=") {
+        return Some(input)
+            .filter(|token| token.contains('|'))
+            .map(str::to_owned);
     }
+
+    let code = sso::query_param(input, "code")?;
+    let state = sso::query_param(input, "state")?;
+
+    Some(format!("{}|{}", code, state))
+}
+
+/// Has the server email a (new) login code.
+fn send_two_factor_email(siv: &mut Cursive, email: String, master_password: String) {
+    siv.add_layer(Dialog::text("Sending the code ..."));
+
+    worker::run(
+        move |api| api.send_two_factor_email(&email, &master_password),
+        |siv, result| {
+            siv.pop_layer();
+
+            let message = match result {
+                Ok(()) => "A code was sent to your email address".to_owned(),
+                Err(err) => err.to_string(),
+            };
+
+            siv.add_layer(Dialog::info(message));
+        },
+    );
+}
+
+/// Syncs after logging in and shows the vault. `master_password` is checked
+/// against the policies, unless the login was approved by another device.
+fn sync_vault_data(siv: &mut Cursive, auth_data: AuthData, master_password: Option<String>) {
+    siv.add_layer(Dialog::text("Syncing vault ..."));
+
+    worker::run(
+        move |api| {
+            let vault_data = api.sync(&auth_data)?;
+            let saved = api::save_app_data(&auth_data, &vault_data);
+
+            Ok((auth_data, vault_data, saved))
+        },
+        |siv, result: Result<(AuthData, VaultData, Result<(), ApiError>), ApiError>| {
+            siv.pop_layer();
+
+            let (mut auth_data, vault_data, saved) = match result {
+                Ok(result) => result,
+                Err(err) => {
+                    set_status(siv, &err.to_string());
+                    return;
+                }
+            };
+
+            // Unless another device handed out the vault key right away
+            if !auth_data.cipher.is_unlocked() {
+                if let Err(_) = auth_data.cipher.set_decrypt_key(&vault_data.profile.key) {
+                    login_failed(siv, "Wrong vault password");
+                    return;
+                }
+            }
+
+            let report = verify::verify(&vault_data, &auth_data.cipher);
+
+            // Drop the login dialog
+            siv.pop_layer();
+            let view = take_view(siv);
+            vault::show(siv, auth_data, vault_data, view);
+            if let Some(master_password) = master_password {
+                policy::check_master_password(siv, &master_password);
+            }
+
+            if let Err(err) = saved {
+                siv.add_layer(Dialog::info(err.to_string()));
+            }
+
+            if !report.is_clean() {
+                siv.add_layer(Dialog::info(report.summary()).title("vault check"));
+            }
+        },
+    );
 }
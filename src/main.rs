@@ -1,31 +1,212 @@
 // SPDX-License-Identifier: MIT
 
-use cursive::backend::termion::Backend;
+use std::path::PathBuf;
+use std::process;
+
+use clap::ArgMatches;
+use cursive::backend::Backend;
+#[cfg(unix)]
+use cursive::event::Event;
 use cursive::Cursive;
+#[cfg(feature = "termion-backend")]
 use cursive_buffered_backend::BufferedBackend;
 
-mod api;
-mod cipher;
+use bwtui_core::{api, cipher};
+
+mod account;
+mod activity;
+mod audit;
+mod autotype;
+mod cli;
+mod clip;
+mod collections;
+mod conflict;
+mod crash;
+mod credentials;
+mod detail;
+mod domains;
+mod emergency;
+mod events;
+mod export;
+mod field_cache;
+mod get;
+mod hooks;
+mod import;
+mod journal;
+mod logger;
 mod login;
+mod markdown;
+mod merge;
+mod note;
+mod pinentry;
+mod policy;
+mod probe;
+#[cfg(feature = "qr")]
+mod qr;
+mod redact;
+mod register;
+mod rotation;
+mod rpc;
+#[cfg(feature = "scripts")]
+mod scripts;
+mod search;
+mod sends;
+mod setup;
+mod share;
+mod sso;
+mod suspend;
+mod totp;
+mod totp_edit;
+mod uri;
 mod vault;
+mod verify;
+#[cfg(feature = "watch")]
+mod watch;
+mod webauthn;
+mod worker;
 
 
-fn main() {
+struct Args {
+        view: vault::StartupView,
+        lock_on_resume: bool,
+        verbose: bool,
+        log_file: Option<PathBuf>,
+        log_actions: bool,
+        reset_device_id: bool,
+        server: Option<api::ServerUrls>,
+        pinentry: Option<String>,
+}
+
+
+fn parse_args(matches: &ArgMatches) -> Result<Args, String> {
+        // Read right away, before the terminal possibly takes the focus.
+        let window_title = if matches.is_present("pick") {
+                let title = autotype::active_window_title()
+                        .map_err(|e| format!("--pick: {}", e))?;
+                Some(title)
+        } else {
+                matches.value_of("window-title").map(str::to_owned)
+        };
+
+        let view = vault::StartupView {
+                favorites: matches.is_present("favorites"),
+                folder: matches.value_of("folder").map(str::to_owned),
+                search: matches.value_of("search").map(str::to_owned),
+                window_title,
+        };
+
+        Ok(Args {
+                view,
+                lock_on_resume: matches.is_present("lock-on-resume"),
+                verbose: matches.is_present("verbose"),
+                log_file: matches.value_of_os("log-file").map(PathBuf::from),
+                log_actions: matches.is_present("log-actions"),
+                reset_device_id: matches.is_present("reset-device-id"),
+                server: matches.value_of("server")
+                        .map(api::ServerUrls::self_hosted)
+                        .transpose()
+                        .map_err(|e| format!("--server: {}", e))?,
+                pinentry: matches.value_of("pinentry").map(str::to_owned),
+        })
+}
+
+
+#[cfg(feature = "termion-backend")]
+fn backend() -> Box<dyn Backend> {
         // We need to use a buffered backend due to flickering with termion.
-        let mut siv = Cursive::new(|| {
-                let backend = Backend::init().unwrap();
-                let buffered = BufferedBackend::new(backend);
+        let backend = cursive::backend::termion::Backend::init().unwrap();
+
+        Box::new(BufferedBackend::new(backend))
+}
+
+#[cfg(all(feature = "crossterm-backend", not(feature = "termion-backend")))]
+fn backend() -> Box<dyn Backend> {
+        cursive::backend::crossterm::Backend::init().unwrap()
+}
 
-                Box::new(buffered)
-        });
+#[cfg(not(any(feature = "termion-backend", feature = "crossterm-backend")))]
+compile_error!("either the termion-backend or the crossterm-backend feature is needed");
+
+
+fn main() {
+        let matches = cli::app().get_matches();
+
+        match matches.subcommand() {
+                ("get", Some(matches)) => process::exit(get::run(matches)),
+                ("note", Some(matches)) => process::exit(note::run(matches)),
+                ("rpc", _) => process::exit(rpc::run()),
+                ("completions", Some(matches)) => return cli::completions(matches),
+                _ => (),
+        }
 
-        let mut email = None;
-        if let Ok(data) = api::read_app_data() {
-                email = Some(data.vault.profile.email.clone());
-                siv.set_user_data(data);
+        if matches.is_present("rpc") {
+                process::exit(rpc::run());
         }
 
-        login::ask(&mut siv, email);
+        let args = match parse_args(&matches) {
+                Ok(args) => args,
+                Err(err) => {
+                        eprintln!("{}", err);
+                        process::exit(2);
+                }
+        };
+
+        crash::install();
+
+        // Nothing is logged unless asked for
+        if args.verbose || args.log_file.is_some() {
+                if let Err(err) = logger::init(args.verbose, args.log_file.clone()) {
+                        eprintln!("{}", err);
+                        process::exit(2);
+                }
+        }
+
+        if args.log_actions {
+                activity::enable();
+        }
+
+        if let Some(program) = args.pinentry.clone() {
+                pinentry::set_program(program);
+        }
+
+        if args.reset_device_id {
+                if let Err(err) = api::reset_device_identifier() {
+                        eprintln!("failed to reset the device identifier: {}", err);
+                        process::exit(1);
+                }
+        }
+
+        let mut siv = Cursive::new(|| policy::IdleBackend::wrap(backend()));
+
+        worker::spawn(siv.cb_sink().clone());
+
+        let app_data = api::read_app_data().ok();
+
+        // A cached session keeps talking to the servers it was logged in to
+        let server = args.server.clone()
+                .or_else(|| app_data.as_ref().and_then(|data| data.auth.server().cloned()));
+        if let Some(urls) = server {
+                worker::run(move |api| api.set_server(urls), |_, ()| {});
+        }
+
+        // Nothing cached and nothing set up yet, this is the first launch
+        match api::read_settings() {
+                Err(_) if app_data.is_none() => setup::show(&mut siv, args.view, args.server),
+                settings => {
+                        setup::apply(&settings.unwrap_or_default());
+                        login::ask(&mut siv, app_data, args.view);
+                }
+        }
+
+        #[cfg(unix)]
+        siv.add_global_callback(Event::CtrlChar('z'), suspend::suspend);
+        suspend::spawn(siv.cb_sink().clone(), args.lock_on_resume);
+        policy::spawn(siv.cb_sink().clone());
+
+        #[cfg(feature = "watch")]
+        watch::spawn(siv.cb_sink().clone());
 
         siv.run();
+
+        vault::save_view_state(&mut siv);
 }
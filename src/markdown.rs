@@ -0,0 +1,139 @@
+// SPDX-License-Identifier: MIT
+
+// Lightweight markdown for notes: headings, lists, quotes, code, emphasis
+// and links. Anything else is shown as it is.
+
+use cursive::theme::{BaseColor, Color, Effect, Style};
+use cursive::utils::markup::StyledString;
+
+fn code_style() -> Style {
+    Style::from(Color::Dark(BaseColor::Cyan))
+}
+
+/// Position of `marker` closing a span which starts at `start`, if the span
+/// isn't empty.
+fn closing(text: &str, start: usize, marker: &str) -> Option<usize> {
+    text[start..]
+        .find(marker)
+        .filter(|length| *length > 0)
+        .map(|length| start + length)
+}
+
+/// Whether `*` or `_` at `index` opens emphasis, rather than being part of a
+/// word like snake_case or a lone asterisk.
+fn opens_emphasis(text: &str, index: usize) -> bool {
+    let before = text[..index].chars().next_back();
+    let after = text[index + 1..].chars().next();
+
+    before.map_or(true, |c| !c.is_alphanumeric()) && after.map_or(false, |c| !c.is_whitespace())
+}
+
+fn render_inline(text: &str, base: Style) -> StyledString {
+    let mut styled = StyledString::new();
+    let mut plain_start = 0;
+    let mut index = 0;
+
+    let flush = |styled: &mut StyledString, from: usize, to: usize| {
+        if from < to {
+            styled.append_styled(&text[from..to], base);
+        }
+    };
+
+    while index < text.len() {
+        let rest = &text[index..];
+
+        let span = if rest.starts_with('`') {
+            closing(text, index + 1, "`").map(|end| {
+                let code = StyledString::styled(&text[index + 1..end], code_style());
+                (code, end + 1)
+            })
+        } else if rest.starts_with("**") || rest.starts_with("__") {
+            closing(text, index + 2, &rest[..2]).map(|end| {
+                let style = base.combine(Effect::Bold);
+                (render_inline(&text[index + 2..end], style), end + 2)
+            })
+        } else if (rest.starts_with('*') || rest.starts_with('_')) && opens_emphasis(text, index) {
+            closing(text, index + 1, &rest[..1]).map(|end| {
+                let style = base.combine(Effect::Italic);
+                (render_inline(&text[index + 1..end], style), end + 1)
+            })
+        } else if rest.starts_with('[') {
+            // [text](url), shown as the text followed by the URL
+            closing(text, index + 1, "](").and_then(|middle| {
+                closing(text, middle + 2, ")").map(|end| {
+                    let mut link =
+                        render_inline(&text[index + 1..middle], base.combine(Effect::Underline));
+                    link.append_styled(format!(" <{}>", &text[middle + 2..end]), base);
+                    (link, end + 1)
+                })
+            })
+        } else {
+            None
+        };
+
+        match span {
+            Some((span, end)) => {
+                flush(&mut styled, plain_start, index);
+                styled.append(span);
+                index = end;
+                plain_start = end;
+            }
+            None => index += rest.chars().next().map_or(1, char::len_utf8),
+        }
+    }
+
+    flush(&mut styled, plain_start, text.len());
+    styled
+}
+
+/// Renders `text` as markdown.
+pub fn render(text: &str) -> StyledString {
+    let mut styled = StyledString::new();
+    let mut in_code_block = false;
+
+    for line in text.lines() {
+        let trimmed = line.trim_start();
+        let indent = &line[..line.len() - trimmed.len()];
+
+        if trimmed.starts_with("```") {
+            in_code_block = !in_code_block;
+            continue;
+        }
+
+        if in_code_block {
+            styled.append_styled(line, code_style());
+        } else if trimmed.starts_with('#') {
+            let level = trimmed.chars().take_while(|c| *c == '#').count();
+            let heading = &trimmed[level..];
+
+            if level <= 6 && (heading.is_empty() || heading.starts_with(' ')) {
+                let mut style = Style::from(Effect::Bold);
+                if level == 1 {
+                    style = style.combine(Effect::Underline);
+                }
+
+                styled.append(render_inline(heading.trim(), style));
+            } else {
+                styled.append(render_inline(line, Style::none()));
+            }
+        } else if trimmed.starts_with("- ")
+            || trimmed.starts_with("* ")
+            || trimmed.starts_with("+ ")
+        {
+            styled.append_plain(format!("{}\u{2022} ", indent));
+            styled.append(render_inline(&trimmed[2..], Style::none()));
+        } else if trimmed.starts_with('>') {
+            styled.append_plain(format!("{}\u{2502} ", indent));
+            styled.append(render_inline(
+                trimmed[1..].trim_start(),
+                Style::from(Effect::Italic),
+            ));
+        } else {
+            styled.append(render_inline(line, Style::none()));
+        }
+
+        styled.append_plain("\n");
+    }
+
+    styled
+}
@@ -0,0 +1,337 @@
+// SPDX-License-Identifier: MIT
+
+use chrono::Utc;
+use cursive::traits::*;
+use cursive::views::{Dialog, LinearLayout, RadioGroup, TextView};
+use cursive::Cursive;
+use uuid::Uuid;
+
+use crate::api::{
+    ApiError, CipherEntry, CipherEntryHistory, CipherEntryUriMatch, CIPHER_LOGIN,
+    CIPHER_SECURE_NOTE,
+};
+use crate::cipher::{CipherString, CipherSuite};
+use crate::conflict;
+use crate::vault::{self, Session};
+
+/// Value which can be taken from either item.
+#[derive(Clone, Copy, PartialEq)]
+enum Part {
+    Name,
+    Username,
+    Password,
+    Totp,
+    Notes,
+}
+
+const PARTS: &[Part] = &[
+    Part::Name,
+    Part::Username,
+    Part::Password,
+    Part::Totp,
+    Part::Notes,
+];
+
+impl Part {
+    fn label(self) -> &'static str {
+        match self {
+            Part::Name => "name",
+            Part::Username => "username",
+            Part::Password => "password",
+            Part::Totp => "TOTP secret",
+            Part::Notes => "notes",
+        }
+    }
+
+    fn get(self, entry: &CipherEntry) -> Option<&CipherString> {
+        match self {
+            Part::Name => Some(&entry.name),
+            Part::Username => entry.data.username.as_ref(),
+            Part::Password => entry.data.password.as_ref(),
+            Part::Totp => entry.data.totp.as_ref(),
+            Part::Notes => entry.notes.as_ref().or_else(|| entry.data.notes.as_ref()),
+        }
+    }
+
+    fn set(self, entry: &mut CipherEntry, value: Option<CipherString>) {
+        match self {
+            Part::Name => {
+                if let Some(value) = value {
+                    entry.data.name = value.clone();
+                    entry.name = value;
+                }
+            }
+            Part::Username => entry.data.username = value,
+            Part::Password => {
+                entry.data.password = value;
+                entry.data.assword_last_changed = Some(Utc::now());
+            }
+            Part::Totp => entry.data.totp = value,
+            Part::Notes => {
+                entry.data.notes = value.clone();
+                entry.notes = value;
+            }
+        }
+    }
+
+    /// How a value is shown when choosing, secrets are never shown.
+    fn describe(self, entry: &CipherEntry, value: &str) -> String {
+        match self {
+            Part::Password => match entry.data.assword_last_changed {
+                Some(date) => format!("******** (changed {})", date.format("%Y-%m-%d")),
+                None => "********".to_owned(),
+            },
+            Part::Totp => "(secret)".to_owned(),
+            _ => value.lines().next().unwrap_or("").to_owned(),
+        }
+    }
+}
+
+fn decrypt(value: Option<&CipherString>, cipher: &CipherSuite) -> String {
+    value
+        .and_then(|value| value.decrypt(cipher))
+        .unwrap_or_default()
+}
+
+/// Combines `other` into `kept`, taking the chosen parts from `other`. URIs
+/// and custom fields missing in `kept` are added, password histories of both
+/// are kept along with the password which isn't used anymore.
+fn merge(
+    kept: &CipherEntry,
+    other: &CipherEntry,
+    from_other: &[Part],
+    cipher: &CipherSuite,
+) -> CipherEntry {
+    let mut merged = kept.clone();
+
+    for part in from_other {
+        part.set(&mut merged, part.get(other).cloned());
+    }
+
+    // URIs are encrypted with random IVs, so compare them decrypted
+    let uris = |entry: &CipherEntry| {
+        let legacy = entry
+            .data
+            .uri
+            .iter()
+            .filter(|_| entry.data.uris.is_none())
+            .map(|uri| CipherEntryUriMatch {
+                uri: Some(uri.clone()),
+                match_: None,
+            });
+
+        entry
+            .data
+            .uris
+            .iter()
+            .flatten()
+            .cloned()
+            .chain(legacy)
+            .collect::<Vec<CipherEntryUriMatch>>()
+    };
+
+    let mut merged_uris = uris(kept);
+    let mut known = merged_uris
+        .iter()
+        .map(|uri| decrypt(uri.uri.as_ref(), cipher))
+        .collect::<Vec<String>>();
+
+    for uri in uris(other) {
+        let value = decrypt(uri.uri.as_ref(), cipher);
+        if !known.contains(&value) {
+            known.push(value);
+            merged_uris.push(uri);
+        }
+    }
+
+    merged.data.uri = None;
+    merged.data.uris = Some(merged_uris);
+
+    let mut fields = kept.custom_fields().to_vec();
+    let names = fields
+        .iter()
+        .map(|field| decrypt(field.name.as_ref(), cipher))
+        .collect::<Vec<String>>();
+
+    fields.extend(
+        other
+            .custom_fields()
+            .iter()
+            .filter(|field| !names.contains(&decrypt(field.name.as_ref(), cipher)))
+            .cloned(),
+    );
+
+    merged.data.fields = Some(fields.clone());
+    merged.fields = Some(fields);
+
+    let history = |entry: &CipherEntry| {
+        entry
+            .password_history
+            .as_ref()
+            .or_else(|| entry.data.password_history.as_ref())
+            .cloned()
+            .unwrap_or_default()
+    };
+
+    let mut merged_history = history(kept);
+    merged_history.extend(history(other));
+
+    // The password which isn't used anymore goes to the history as well
+    let dropped = if from_other.contains(&Part::Password) {
+        kept.data.password.as_ref()
+    } else {
+        other.data.password.as_ref()
+    };
+
+    if let Some(dropped) = dropped {
+        let current = decrypt(merged.data.password.as_ref(), cipher);
+        if decrypt(Some(dropped), cipher) != current {
+            merged_history.push(CipherEntryHistory {
+                password: dropped.clone(),
+                last_used_date: Utc::now(),
+            });
+        }
+    }
+
+    merged_history.sort_by(|a, b| b.last_used_date.cmp(&a.last_used_date));
+
+    merged.data.password_history = Some(merged_history.clone());
+    merged.password_history = Some(merged_history);
+
+    merged
+}
+
+/// Lets the user merge `other` into `kept`, choosing which values to keep
+/// where the two differ. `other` gets moved to the trash afterwards.
+pub fn show(siv: &mut Cursive, kept: Uuid, other: Uuid) {
+    let entries = match siv.user_data::<Session>() {
+        Some(session) => {
+            let find = |uuid| {
+                session
+                    .vault
+                    .ciphers
+                    .iter()
+                    .find(|entry| entry.uuid == uuid)
+            };
+
+            match (find(kept), find(other)) {
+                (Some(kept), Some(other)) => {
+                    let choices = PARTS
+                        .iter()
+                        .filter_map(|part| {
+                            let a = decrypt(part.get(kept), &session.cipher);
+                            let b = decrypt(part.get(other), &session.cipher);
+
+                            // Nothing to choose if they agree or one is empty
+                            if a == b || b.is_empty() {
+                                return None;
+                            }
+
+                            Some((
+                                *part,
+                                part.describe(kept, &a),
+                                part.describe(other, &b),
+                                a.is_empty(),
+                            ))
+                        })
+                        .collect::<Vec<(Part, String, String, bool)>>();
+
+                    Some((kept.clone(), other.clone(), choices))
+                }
+                _ => None,
+            }
+        }
+        None => return,
+    };
+
+    let (kept, other, choices) = match entries {
+        Some(entries) => entries,
+        None => return,
+    };
+
+    let supported = |entry: &CipherEntry| {
+        entry.organization_id.is_none()
+            && (entry.type_ == CIPHER_LOGIN || entry.type_ == CIPHER_SECURE_NOTE)
+    };
+
+    if !supported(&kept) || !supported(&other) || kept.type_ != other.type_ {
+        siv.add_layer(Dialog::info(
+            "Only two personal logins or two personal notes can be merged",
+        ));
+        return;
+    }
+
+    let mut layout = LinearLayout::vertical().child(TextView::new(
+        "URIs, custom fields and password histories of both items are kept. \
+         Where they differ, choose the value to keep:",
+    ));
+
+    let mut groups = Vec::new();
+
+    for (part, a, b, a_empty) in choices {
+        let mut group = RadioGroup::new();
+
+        layout.add_child(TextView::new(format!("\n{}:", part.label())));
+
+        let mut first = group.button(false, format!("this item: {}", a));
+        let mut second = group.button(true, format!("other item: {}", b));
+
+        // Filling in what is missing is the sensible default
+        if a_empty {
+            second.select();
+        } else {
+            first.select();
+        }
+
+        layout.add_child(first);
+        layout.add_child(second);
+        groups.push((part, group));
+    }
+
+    siv.add_layer(
+        Dialog::around(layout.scrollable())
+            .title("merge items")
+            .button("Merge", move |siv| {
+                let from_other = groups
+                    .iter()
+                    .filter(|(_, group)| *group.selection())
+                    .map(|(part, _)| *part)
+                    .collect::<Vec<Part>>();
+
+                let merged = match siv.user_data::<Session>() {
+                    Some(session) => merge(&kept, &other, &from_other, &session.cipher),
+                    None => return,
+                };
+
+                siv.pop_layer();
+                confirm(siv, merged, other.uuid);
+            })
+            .dismiss_button("Cancel")
+            .min_width(60),
+    );
+}
+
+fn confirm(siv: &mut Cursive, merged: CipherEntry, other: Uuid) {
+    siv.add_layer(
+        Dialog::text("Save the merged item and move the other one to the trash?")
+            .button("Merge", move |siv| {
+                siv.pop_layer();
+
+                // The other item is only deleted once the merged one is saved
+                conflict::save(siv, merged.clone(), move |siv| {
+                    vault::request(
+                        siv,
+                        move |api, auth| api.delete_cipher(auth, other),
+                        |siv, result: Result<(), ApiError>| match result {
+                            Ok(()) => {
+                                siv.add_layer(Dialog::info("Items merged"));
+                                vault::sync_in_background(siv);
+                            }
+                            Err(err) => siv.add_layer(Dialog::info(err.to_string())),
+                        },
+                    );
+                });
+            })
+            .dismiss_button("Cancel"),
+    );
+}
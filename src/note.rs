@@ -0,0 +1,255 @@
+// SPDX-License-Identifier: MIT
+
+// Quick capture of secure notes, e.g. recovery codes or server details, with
+// `bwtui note add` or from the vault, without going through a full entry form.
+
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Read};
+use std::path::Path;
+use std::process::Command;
+
+use clap::ArgMatches;
+use cursive::traits::*;
+use cursive::views::{Dialog, EditView, LinearLayout, TextArea, TextView};
+use cursive::Cursive;
+use uuid::Uuid;
+
+use crate::api::{self, Api, ApiError, NewCipher, CIPHER_SECURE_NOTE};
+use crate::cipher::{CipherString, CipherSuite};
+use crate::credentials;
+use crate::journal::{self, Operation};
+use crate::vault::{self, Session};
+
+
+// Longest name taken from the first line of a note without a title
+const MAX_TITLE_LENGTH: usize = 64;
+
+#[cfg(windows)]
+const DEFAULT_EDITOR: &str = "notepad";
+#[cfg(not(windows))]
+const DEFAULT_EDITOR: &str = "vi";
+
+
+/// Title given, or the first line of the note otherwise.
+fn title(title: &str, text: &str) -> String {
+        let title = title.trim();
+
+        if !title.is_empty() {
+                return title.to_owned();
+        }
+
+        text.lines()
+                .map(str::trim)
+                .find(|line| !line.is_empty())
+                .unwrap_or("note")
+                .chars()
+                .take(MAX_TITLE_LENGTH)
+                .collect()
+}
+
+
+fn encrypt(title: &str, text: &str, cipher: &CipherSuite) -> Option<NewCipher> {
+        Some(NewCipher {
+                type_: CIPHER_SECURE_NOTE,
+                folder_id: None,
+                favorite: false,
+                name: CipherString::encrypt(title, cipher)?,
+                notes: Some(CipherString::encrypt(text, cipher)?),
+                uris: Vec::new(),
+                username: None,
+                password: None,
+                totp: None,
+                fields: Vec::new(),
+        })
+}
+
+
+/// Popup of the vault to jot down a note.
+pub fn show(siv: &mut Cursive) {
+        let layout = LinearLayout::vertical()
+                .child(TextView::new("title (first line if empty):"))
+                .child(EditView::new().with_name("note_title"))
+                .child(TextView::new("note:"))
+                .child(TextArea::new().with_name("note_text").min_height(8));
+
+        siv.add_layer(Dialog::around(layout)
+                .title("new secure note")
+                .button("Save", save)
+                .dismiss_button("Cancel")
+                .min_width(60));
+}
+
+
+fn save(siv: &mut Cursive) {
+        let title_text = siv.call_on_name("note_title", |view: &mut EditView| view.get_content())
+                .map(|title| title.to_string())
+                .unwrap_or_default();
+        let text = siv.call_on_name("note_text", |view: &mut TextArea| view.get_content().to_owned())
+                .unwrap_or_default();
+
+        if text.trim().is_empty() {
+                return;
+        }
+
+        let note = match siv.user_data::<Session>() {
+                Some(session) => encrypt(&title(&title_text, &text), &text, &session.cipher),
+                None => return,
+        };
+
+        let note = match note {
+                Some(note) => note,
+                None => {
+                        siv.add_layer(Dialog::info("Failed to encrypt the note"));
+                        return;
+                }
+        };
+
+        siv.pop_layer();
+
+        let queued = note.clone();
+
+        vault::request(
+                siv,
+                move |api, auth| api.create_cipher(auth, &note),
+                move |siv, result| {
+                        match result {
+                                Ok(()) => vault::sync_in_background(siv),
+                                Err(ApiError::Offline { .. }) => {
+                                        journal::queue(siv, Operation::Create(queued));
+                                        siv.add_layer(Dialog::info(
+                                                "Offline, the note will be created on the next sync"));
+                                }
+                                Err(err) => siv.add_layer(Dialog::info(format!(
+                                        "Failed to create the note: {}", err))),
+                        }
+                });
+}
+
+
+#[cfg(unix)]
+fn stdin_is_terminal() -> bool {
+        unsafe { libc::isatty(libc::STDIN_FILENO) == 1 }
+}
+
+#[cfg(not(unix))]
+fn stdin_is_terminal() -> bool {
+        false
+}
+
+
+/// Lets the user write the note in $VISUAL or $EDITOR, in a file only they can
+/// read which is removed right after.
+fn edit() -> Result<String, String> {
+        let editor = env::var("VISUAL")
+                .or_else(|_| env::var("EDITOR"))
+                .unwrap_or_else(|_| DEFAULT_EDITOR.to_owned());
+        let mut words = editor.split_whitespace();
+        let program = words.next().unwrap_or(DEFAULT_EDITOR);
+
+        let path = api::get_app_data_path()?
+                .join(format!("note-{}.txt", Uuid::new_v4()));
+
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        {
+                use std::os::unix::fs::OpenOptionsExt;
+                options.mode(0o600);
+        }
+        options.open(&path).map_err(|e| e.to_string())?;
+
+        let status = Command::new(program)
+                .args(words)
+                .arg(&path)
+                .status();
+        let text = fs::read_to_string(&path);
+        let _ = fs::remove_file(&path);
+
+        match status {
+                Ok(status) if status.success() => text.map_err(|e| e.to_string()),
+                Ok(status) => Err(format!("{} exited with {}", program, status)),
+                Err(e) => Err(format!("failed to run {}: {}", program, e)),
+        }
+}
+
+
+fn read_text() -> Result<String, String> {
+        if stdin_is_terminal() {
+                return edit();
+        }
+
+        let mut text = String::new();
+        io::stdin().read_to_string(&mut text)
+                .map_err(|e| e.to_string())?;
+
+        Ok(text)
+}
+
+
+fn add(title_arg: &str, password_file: Option<&Path>) -> Result<String, String> {
+        // Asked first, so that nothing written is lost to a typo
+        let (data, password) = credentials::unlock(password_file)?;
+        let email = data.vault.profile.email.clone();
+
+        let mut auth = data.auth;
+        auth.cipher = CipherSuite::with_kdf(&email, &password, &auth.kdf())
+                .map_err(|e| e.to_string())?;
+        auth.read_two_factor_token(&email);
+        auth.cipher.set_decrypt_key(&data.vault.profile.key)
+                .map_err(|_| "wrong master password".to_owned())?;
+        let cipher = auth.cipher.clone();
+
+        let text = read_text()?;
+        if text.trim().is_empty() {
+                return Err("empty note, nothing saved".to_owned());
+        }
+
+        let title = title(title_arg, &text);
+        let note = encrypt(&title, &text, &cipher)
+                .ok_or_else(|| "failed to encrypt the note".to_owned())?;
+
+        let api = Api::new();
+        if let Some(urls) = auth.server() {
+                api.set_server(urls.clone());
+        }
+        let created = api.retry_unauthorized(&email, &mut auth, &cipher, |api, auth| {
+                api.create_cipher(auth, &note)
+        });
+
+        match created {
+                Ok(()) => {
+                        let _ = api::save_auth_data(&auth);
+                        Ok(format!("created \"{}\"", title))
+                }
+                Err(ApiError::Offline { .. }) => {
+                        journal::append(&Operation::Create(note), &cipher)?;
+                        Ok(format!("offline, \"{}\" will be created on the next sync", title))
+                }
+                Err(err) => Err(err.to_string()),
+        }
+}
+
+
+/// Runs `bwtui note`, returning the exit code.
+pub fn run(matches: &ArgMatches) -> i32 {
+        let result = match matches.subcommand() {
+                ("add", Some(matches)) => add(
+                        matches.value_of("title").unwrap_or_default(),
+                        matches.value_of_os("passwordfile").map(Path::new),
+                ),
+                // clap asks for a subcommand
+                _ => return 2,
+        };
+
+        match result {
+                Ok(message) => {
+                        println!("{}", message);
+                        0
+                }
+                Err(err) => {
+                        eprintln!("bwtui note: {}", err);
+                        1
+                }
+        }
+}
@@ -0,0 +1,191 @@
+// SPDX-License-Identifier: MIT
+
+// Asks for the master password and second factor codes with an external
+// pinentry program, as GnuPG does, instead of reading them in the TUI. The
+// program is talked to with the Assuan protocol over its stdin and stdout.
+
+use std::cell::RefCell;
+#[cfg(unix)]
+use std::env;
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Command, Stdio};
+
+use cursive::Cursive;
+use log::{info, warn};
+
+use crate::suspend;
+
+// Error code pinentry answers with when the prompt was cancelled
+const CANCELLED: &str = "83886179";
+const TITLE: &str = "bwtui";
+
+
+thread_local! {
+        // Set up once at startup, prompts only come from the UI thread
+        static PROGRAM: RefCell<Option<String>> = RefCell::new(None);
+}
+
+
+/// Uses `program` for the prompts from now on.
+pub fn set_program(program: String) {
+        info!("using {} for the prompts", program);
+
+        PROGRAM.with(|cell| *cell.borrow_mut() = Some(program));
+}
+
+
+/// Whether the prompts are up to the pinentry program.
+pub fn enabled() -> bool {
+        PROGRAM.with(|cell| cell.borrow().is_some())
+}
+
+
+/// Asks for a secret with pinentry, showing `description` above the field
+/// labelled `prompt`. The TUI is left meanwhile, as the program may prompt in
+/// the terminal. `None` if the prompt was cancelled.
+pub fn ask(siv: &mut Cursive, description: &str, prompt: &str) -> Result<Option<String>, String> {
+        let program = PROGRAM.with(|cell| cell.borrow().clone())
+                .ok_or_else(|| "no pinentry program set up".to_owned())?;
+
+        suspend::outside_tui(siv, || get_pin(&program, description, prompt))
+}
+
+
+fn get_pin(program: &str, description: &str, prompt: &str) -> Result<Option<String>, String> {
+        let mut child = Command::new(program)
+                .stdin(Stdio::piped())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::null())
+                .spawn()
+                .map_err(|err| format!("failed to run {}: {}", program, err))?;
+
+        let mut input = child.stdin.take().unwrap();
+        let mut output = BufReader::new(child.stdout.take().unwrap());
+
+        let pin = converse(&mut input, &mut output, description, prompt);
+
+        let _ = writeln!(input, "BYE");
+        drop(input);
+        let _ = child.wait();
+
+        pin.map_err(|err| format!("{}: {}", program, err))
+}
+
+
+fn converse(
+        input: &mut impl Write,
+        output: &mut impl BufRead,
+        description: &str,
+        prompt: &str,
+) -> Result<Option<String>, String> {
+        // Greeting
+        response(output)?;
+
+        let mut commands = Vec::new();
+
+        // Where to prompt, for the programs doing so in the terminal
+        #[cfg(unix)]
+        {
+                let tty = env::var("GPG_TTY").unwrap_or_else(|_| "/dev/tty".to_owned());
+                commands.push(format!("OPTION ttyname={}", escape(&tty)));
+
+                if let Ok(term) = env::var("TERM") {
+                        commands.push(format!("OPTION ttytype={}", escape(&term)));
+                }
+        }
+
+        commands.push(format!("SETTITLE {}", TITLE));
+        commands.push(format!("SETDESC {}", escape(description)));
+        commands.push(format!("SETPROMPT {}", escape(prompt)));
+
+        for command in commands {
+                request(input, &command)?;
+
+                if response(output)?.is_none() {
+                        warn!("pinentry refused {}", command.split(' ').next().unwrap_or(""));
+                }
+        }
+
+        request(input, "GETPIN")?;
+        response(output)
+}
+
+
+fn request(input: &mut impl Write, command: &str) -> Result<(), String> {
+        writeln!(input, "{}", command)
+                .and_then(|_| input.flush())
+                .map_err(|err| err.to_string())
+}
+
+
+/// Reads lines up to the final OK, returning the data sent along, or up to
+/// an ERR, which is `None` if the user cancelled.
+fn response(output: &mut impl BufRead) -> Result<Option<String>, String> {
+        let mut data = Vec::new();
+
+        loop {
+                let mut line = String::new();
+                match output.read_line(&mut line) {
+                        Ok(0) => return Err("exited unexpectedly".to_owned()),
+                        Ok(_) => (),
+                        Err(err) => return Err(err.to_string()),
+                }
+
+                let line = line.trim_end_matches(&['\r', '\n'][..]);
+
+                if line == "OK" || line.starts_with("OK ") {
+                        let data = String::from_utf8(data)
+                                .map_err(|_| "sent invalid UTF-8".to_owned())?;
+
+                        return Ok(Some(data));
+                } else if line.starts_with("ERR ") {
+                        let error = &line[4..];
+                        if error.split(' ').next() == Some(CANCELLED) {
+                                return Ok(None);
+                        }
+
+                        return Err(error.to_owned());
+                } else if line.starts_with("D ") {
+                        data.extend(unescape(&line[2..]));
+                }
+
+                // Status lines and comments are of no interest
+        }
+}
+
+
+/// Percent-escapes what can't be sent as is in an Assuan command.
+fn escape(value: &str) -> String {
+        value.replace('%', "%25")
+                .replace('\r', "%0D")
+                .replace('\n', "%0A")
+}
+
+
+fn unescape(chunk: &str) -> Vec<u8> {
+        let bytes = chunk.as_bytes();
+        let mut data = Vec::with_capacity(bytes.len());
+        let mut i = 0;
+
+        while i < bytes.len() {
+                let byte = if bytes[i] == b'%' {
+                        chunk.get(i + 1..i + 3)
+                                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                } else {
+                        None
+                };
+
+                match byte {
+                        Some(byte) => {
+                                data.push(byte);
+                                i += 3;
+                        }
+                        None => {
+                                data.push(bytes[i]);
+                                i += 1;
+                        }
+                }
+        }
+
+        data
+}
@@ -0,0 +1,225 @@
+// SPDX-License-Identifier: MIT
+
+// Organization policies enforced locally, like the official clients do: the
+// maximum vault timeout, master password requirements, personal ownership and
+// disabled exports of the personal vault. Owners and admins are exempt.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use cursive::backend::Backend;
+use cursive::event::Event;
+use cursive::theme::{Color, ColorPair, Effect};
+use cursive::views::Dialog;
+use cursive::{CbSink, Cursive, Vec2};
+use log::info;
+
+use crate::api::{self, VaultData};
+use crate::vault::{self, Session};
+
+
+const IDLE_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+// Special characters a master password may be required to contain, the same
+// as checked by the official clients
+const SPECIAL_CHARS: &str = "!@#$%^&*";
+
+// Minutes without input the user picked to lock after, 0 for never
+static LOCK_TIMEOUT: AtomicU64 = AtomicU64::new(0);
+
+// When the user last pressed a key or used the mouse, in seconds since the
+// epoch. Wall clock time, so that time spent asleep counts as idle.
+static LAST_INPUT: AtomicU64 = AtomicU64::new(0);
+
+
+fn now() -> u64 {
+        SystemTime::now().duration_since(UNIX_EPOCH)
+                .map(|now| now.as_secs())
+                .unwrap_or(0)
+}
+
+
+/// Backend noting down when the user last did anything, everything else is
+/// passed on as is.
+pub struct IdleBackend {
+        inner: Box<dyn Backend>,
+}
+
+impl IdleBackend {
+        pub fn wrap(inner: Box<dyn Backend>) -> Box<dyn Backend> {
+                LAST_INPUT.store(now(), Ordering::SeqCst);
+
+                Box::new(Self { inner })
+        }
+}
+
+impl Backend for IdleBackend {
+        fn poll_event(&mut self) -> Option<Event> {
+                let event = self.inner.poll_event();
+
+                match event {
+                        None | Some(Event::Refresh) | Some(Event::WindowResize) => (),
+                        Some(_) => LAST_INPUT.store(now(), Ordering::SeqCst),
+                }
+
+                event
+        }
+
+        fn finish(&mut self) {
+                self.inner.finish()
+        }
+
+        fn refresh(&mut self) {
+                self.inner.refresh()
+        }
+
+        fn has_colors(&self) -> bool {
+                self.inner.has_colors()
+        }
+
+        fn screen_size(&self) -> Vec2 {
+                self.inner.screen_size()
+        }
+
+        fn print_at(&self, pos: Vec2, text: &str) {
+                self.inner.print_at(pos, text)
+        }
+
+        fn print_at_rep(&self, pos: Vec2, repetitions: usize, text: &str) {
+                self.inner.print_at_rep(pos, repetitions, text)
+        }
+
+        fn clear(&self, color: Color) {
+                self.inner.clear(color)
+        }
+
+        fn set_color(&self, colors: ColorPair) -> ColorPair {
+                self.inner.set_color(colors)
+        }
+
+        fn set_effect(&self, effect: Effect) {
+                self.inner.set_effect(effect)
+        }
+
+        fn unset_effect(&self, effect: Effect) {
+                self.inner.unset_effect(effect)
+        }
+}
+
+
+/// Whether a policy of type `type_` applies to the user of the open vault.
+pub fn applies(siv: &mut Cursive, type_: usize) -> bool {
+        siv.user_data::<Session>()
+                .map_or(false, |session| session.vault.applying_policies(type_).next().is_some())
+}
+
+
+/// Shortest maximum vault timeout set by the organizations.
+pub fn vault_timeout(vault: &VaultData) -> Option<Duration> {
+        vault.applying_policies(api::POLICY_MAXIMUM_VAULT_TIMEOUT)
+                .filter_map(|policy| policy.data.minutes)
+                .min()
+                .map(|minutes| Duration::from_secs(minutes * 60))
+}
+
+
+/// Locks the vault after `minutes` without input, `None` for never. Policies
+/// can still set a shorter timeout.
+pub fn set_lock_timeout(minutes: Option<u64>) {
+        LOCK_TIMEOUT.store(minutes.unwrap_or(0), Ordering::SeqCst);
+}
+
+
+/// Locks the vault once it was left alone longer than the user picked or a
+/// policy allows.
+pub fn spawn(cb_sink: CbSink) {
+        thread::spawn(move || {
+                loop {
+                        thread::sleep(IDLE_CHECK_INTERVAL);
+
+                        if cb_sink.send(Box::new(lock_if_idle)).is_err() {
+                                break;
+                        }
+                }
+        });
+}
+
+
+fn lock_if_idle(siv: &mut Cursive) {
+        let session = match siv.user_data::<Session>() {
+                Some(session) => session,
+                None => return,
+        };
+
+        let own = match LOCK_TIMEOUT.load(Ordering::SeqCst) {
+                0 => None,
+                minutes => Some(Duration::from_secs(minutes * 60)),
+        };
+
+        let timeout = match own.into_iter().chain(vault_timeout(&session.vault)).min() {
+                Some(timeout) => timeout,
+                None => return,
+        };
+
+        let idle = now().saturating_sub(LAST_INPUT.load(Ordering::SeqCst));
+
+        if idle >= timeout.as_secs() {
+                info!("idle for {}s, locking the vault", idle);
+                vault::lock(siv);
+        }
+}
+
+
+/// Requirements of the master password policies which `password` doesn't
+/// meet. The minimum complexity can't be checked, as bwtui doesn't estimate
+/// password strength.
+pub fn unmet_requirements(vault: &VaultData, password: &str) -> Vec<String> {
+        let mut unmet = Vec::new();
+
+        for policy in vault.applying_policies(api::POLICY_MASTER_PASSWORD) {
+                let data = &policy.data;
+
+                if let Some(min_length) = data.min_length {
+                        if password.chars().count() < min_length {
+                                unmet.push(format!("at least {} characters", min_length));
+                        }
+                }
+
+                let checks = [
+                        (data.require_upper, "an uppercase letter", password.chars().any(char::is_uppercase)),
+                        (data.require_lower, "a lowercase letter", password.chars().any(char::is_lowercase)),
+                        (data.require_numbers, "a number", password.chars().any(|c| c.is_ascii_digit())),
+                        (data.require_special, "one of !@#$%^&*", password.chars().any(|c| SPECIAL_CHARS.contains(c))),
+                ];
+
+                for (required, requirement, met) in checks.iter() {
+                        if *required && !met {
+                                unmet.push(requirement.to_string());
+                        }
+                }
+        }
+
+        unmet.sort();
+        unmet.dedup();
+        unmet
+}
+
+
+/// Tells the user if the master password they unlocked the vault with
+/// doesn't meet the requirements of their organizations. It can only be
+/// changed in the web vault.
+pub fn check_master_password(siv: &mut Cursive, password: &str) {
+        let unmet = match siv.user_data::<Session>() {
+                Some(session) => unmet_requirements(&session.vault, password),
+                None => return,
+        };
+
+        if unmet.is_empty() {
+                return;
+        }
+
+        siv.add_layer(Dialog::info(format!(
+                "Your master password doesn't meet the requirements of your \
+                 organization, please change it in the web vault. It needs:\n\n- {}",
+                unmet.join("\n- "))).title("master password policy"));
+}
@@ -0,0 +1,70 @@
+// SPDX-License-Identifier: MIT
+
+// Checks whether the sites of items are still around, for the audit of
+// unreachable sites.
+
+use std::time::Duration;
+
+use rayon::prelude::*;
+use rayon::ThreadPoolBuilder;
+use reqwest::{Client, RedirectPolicy};
+
+
+// Sites contacted at the same time
+const CONCURRENCY: usize = 8;
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+
+/// Scheme and host (with port) of an http(s) URI, e.g. "https://example.com".
+pub fn origin(uri: &str) -> Option<String> {
+        let uri = uri.trim().to_lowercase();
+
+        let index = uri.find("://")?;
+        let scheme = &uri[..index];
+        if scheme != "http" && scheme != "https" {
+                return None;
+        }
+
+        let rest = &uri[index + 3..];
+        let end = rest.find(|c| c == '/' || c == '?' || c == '#');
+        let authority = &rest[..end.unwrap_or(rest.len())];
+
+        // Drop any credentials
+        let host = authority.rsplit('@').next().unwrap_or(authority);
+        if host.is_empty() {
+                return None;
+        }
+
+        Some(format!("{}://{}", scheme, host))
+}
+
+
+/// Whether anything answers at `origin`. Any status counts, plenty of sites
+/// reject HEAD requests but are still around.
+fn answers(client: &Client, origin: &str) -> bool {
+        client.head(origin).send().is_ok()
+}
+
+/// Sends a HEAD request to each of `origins`, returning those which fail,
+/// e.g. since their domain doesn't resolve anymore or nothing answers in
+/// time.
+pub fn unreachable(origins: Vec<String>) -> Result<Vec<String>, String> {
+        let client = Client::builder()
+                .timeout(TIMEOUT)
+                .redirect(RedirectPolicy::none())
+                .build()
+                .map_err(|e| e.to_string())?;
+
+        let pool = ThreadPoolBuilder::new()
+                .num_threads(CONCURRENCY)
+                .build()
+                .map_err(|e| e.to_string())?;
+
+        let unreachable = pool.install(|| {
+                origins.into_par_iter()
+                        .filter(|origin| !answers(&client, origin))
+                        .collect()
+        });
+
+        Ok(unreachable)
+}
@@ -0,0 +1,193 @@
+// SPDX-License-Identifier: MIT
+
+use cursive::traits::*;
+use cursive::views::{Dialog, SelectView, TextView};
+use cursive::Cursive;
+use qrcode::{Color, QrCode};
+
+use crate::activity;
+use crate::api::CipherEntry;
+use crate::cipher::{CipherString, CipherSuite};
+use crate::vault::{self, Session};
+
+// Custom field with the network name of WiFi items, the item name otherwise
+const SSID_FIELD: &str = "ssid";
+// Light modules around the code, scanners need some
+const QUIET_ZONE: usize = 2;
+
+fn decrypt(value: Option<&CipherString>, cipher: &CipherSuite) -> Option<String> {
+    value
+        .and_then(|value| value.decrypt(cipher))
+        .filter(|value| !value.is_empty())
+}
+
+fn percent_encode(text: &str) -> String {
+    text.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+/// Escapes the special characters of the WiFi QR code format.
+fn wifi_escape(text: &str) -> String {
+    text.chars()
+        .flat_map(|c| match c {
+            '\\' | ';' | ',' | ':' | '"' => vec!['\\', c],
+            _ => vec![c],
+        })
+        .collect()
+}
+
+/// Values of an item which can be shown as QR code, with their labels.
+fn choices(entry: &CipherEntry, cipher: &CipherSuite) -> Vec<(String, String)> {
+    let mut choices = Vec::new();
+    let name = entry.name.decrypt(cipher).unwrap_or_default();
+    let password = decrypt(entry.data.password.as_ref(), cipher);
+
+    if let Some(password) = &password {
+        choices.push(("Password".to_owned(), password.clone()));
+    }
+
+    if let Some(username) = decrypt(entry.data.username.as_ref(), cipher) {
+        choices.push(("Username".to_owned(), username));
+    }
+
+    if let Some(totp) = decrypt(entry.data.totp.as_ref(), cipher) {
+        // Bare secrets are turned into a URI authenticator apps understand
+        let uri = if totp.trim().starts_with("otpauth://") {
+            totp.trim().to_owned()
+        } else {
+            format!(
+                "otpauth://totp/{}?secret={}",
+                percent_encode(&name),
+                totp.replace(' ', "").to_uppercase()
+            )
+        };
+
+        choices.push(("TOTP (for an authenticator app)".to_owned(), uri));
+    }
+
+    if let Some(password) = password {
+        let ssid = entry
+            .find_field(cipher, SSID_FIELD)
+            .and_then(|field| decrypt(entry.field_value(field), cipher))
+            .unwrap_or_else(|| name.clone());
+
+        choices.push((
+            format!("WiFi network {}", ssid),
+            format!(
+                "WIFI:T:WPA;S:{};P:{};;",
+                wifi_escape(&ssid),
+                wifi_escape(&password)
+            ),
+        ));
+    }
+
+    choices
+}
+
+/// Draws `code` with half blocks, two modules per line. Light modules are
+/// drawn as blocks, which suits terminals with a dark background, `inverted`
+/// swaps that.
+fn render(code: &QrCode, inverted: bool) -> String {
+    let width = code.width();
+    let colors = code.to_colors();
+    let size = width + 2 * QUIET_ZONE;
+
+    let light = |x: usize, y: usize| {
+        if x < QUIET_ZONE || y < QUIET_ZONE || x >= width + QUIET_ZONE || y >= width + QUIET_ZONE {
+            return !inverted;
+        }
+
+        let dark = colors[(y - QUIET_ZONE) * width + x - QUIET_ZONE] == Color::Dark;
+        dark == inverted
+    };
+
+    let mut text = String::new();
+
+    for y in (0..size).step_by(2) {
+        for x in 0..size {
+            let bottom = y + 1 < size && light(x, y + 1);
+
+            text.push(match (light(x, y), bottom) {
+                (true, true) => '\u{2588}',
+                (true, false) => '\u{2580}',
+                (false, true) => '\u{2584}',
+                (false, false) => ' ',
+            });
+        }
+
+        text.push('\n');
+    }
+
+    text
+}
+
+/// Lets the user pick a value of the selected item and shows it as QR code,
+/// e.g. to get a password onto a phone without any clipboard or network.
+pub fn show(siv: &mut Cursive) {
+    let uuid = match vault::selected_item(siv) {
+        Some(uuid) => uuid,
+        None => return,
+    };
+
+    let choices = match siv.user_data::<Session>() {
+        Some(session) => session
+            .vault
+            .ciphers
+            .iter()
+            .find(|entry| entry.uuid == uuid)
+            .map(|entry| choices(entry, &session.cipher))
+            .unwrap_or_default(),
+        None => return,
+    };
+
+    if choices.is_empty() {
+        siv.add_layer(Dialog::info("The item has nothing to show as QR code"));
+        return;
+    }
+
+    let mut select = SelectView::new().on_submit(move |siv, (label, value): &(String, String)| {
+        siv.pop_layer();
+        show_code(siv, value, false);
+
+        activity::record(siv, &format!("showed QR code ({})", label), Some(uuid));
+    });
+
+    for (label, value) in choices {
+        select.add_item(label.clone(), (label, value));
+    }
+
+    siv.add_layer(
+        Dialog::around(select)
+            .title("show as QR code")
+            .dismiss_button("Cancel"),
+    );
+}
+
+fn show_code(siv: &mut Cursive, value: &str, inverted: bool) {
+    let code = match QrCode::new(value.as_bytes()) {
+        Ok(code) => code,
+        Err(err) => {
+            siv.add_layer(Dialog::info(format!("Can't show as QR code: {}", err)));
+            return;
+        }
+    };
+
+    let value = value.to_owned();
+
+    siv.add_layer(
+        Dialog::around(TextView::new(render(&code, inverted)).scrollable())
+            .title("QR code")
+            // Depends on the colors of the terminal
+            .button("Invert", move |siv| {
+                siv.pop_layer();
+                show_code(siv, &value, !inverted);
+            })
+            .dismiss_button("Close"),
+    );
+}
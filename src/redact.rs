@@ -0,0 +1,77 @@
+// SPDX-License-Identifier: MIT
+
+// Removes anything looking like a secret from text which leaves bwtui, like
+// crash reports and logs.
+
+
+const REDACTED: &str = "<redacted>";
+
+// Words this long are most likely tokens, keys or cipher strings
+const MAX_WORD_LENGTH: usize = 20;
+
+
+fn is_word_char(c: char) -> bool {
+        c.is_ascii_alphanumeric() || "+/=_-.|".contains(c)
+}
+
+fn redact_word(word: &str, redacted: &mut String) {
+        if word.chars().count() < MAX_WORD_LENGTH {
+                redacted.push_str(word);
+        } else if word.starts_with('/') {
+                // URLs (after their scheme) and paths stay readable, only long
+                // segments like IDs are dropped.
+                let segments = word.split('/')
+                        .map(|segment| if segment.chars().count() < MAX_WORD_LENGTH { segment } else { REDACTED })
+                        .collect::<Vec<&str>>();
+
+                redacted.push_str(&segments.join("/"));
+        } else {
+                redacted.push_str(REDACTED);
+        }
+}
+
+/// Replaces quoted values and long words with a placeholder. Quoted values
+/// are usually data (names, decrypted values, server responses), while
+/// messages themselves don't contain long words.
+pub fn redact(text: &str) -> String {
+        let mut redacted = String::with_capacity(text.len());
+        let mut word = String::new();
+        let mut quoted = false;
+        let mut escaped = false;
+
+        let flush = |word: &mut String, redacted: &mut String| {
+                redact_word(word, redacted);
+                word.clear();
+        };
+
+        for c in text.chars() {
+                // Debug output escapes quotes within quoted values
+                if quoted && (escaped || c == '\\') {
+                        escaped = !escaped;
+                        continue;
+                }
+
+                if c == '"' {
+                        flush(&mut word, &mut redacted);
+
+                        if !quoted {
+                                redacted.push('"');
+                                redacted.push_str(REDACTED);
+                                redacted.push('"');
+                        }
+
+                        quoted = !quoted;
+                } else if quoted {
+                        continue;
+                } else if is_word_char(c) {
+                        word.push(c);
+                } else {
+                        flush(&mut word, &mut redacted);
+                        redacted.push(c);
+                }
+        }
+
+        flush(&mut word, &mut redacted);
+
+        redacted
+}
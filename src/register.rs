@@ -0,0 +1,108 @@
+// SPDX-License-Identifier: MIT
+
+use cursive::traits::*;
+use cursive::views::{Dialog, EditView, LinearLayout, TextView};
+use cursive::Cursive;
+
+use crate::api::ApiError;
+use crate::cipher::CipherSuite;
+use crate::login;
+use crate::worker;
+
+// Current default of the official clients
+const KDF_ITERATIONS: usize = 600_000;
+pub const MIN_PASSWORD_LENGTH: usize = 12;
+
+fn content(siv: &mut Cursive, name: &str) -> String {
+    siv.call_on_name(name, |view: &mut EditView| view.get_content())
+        .unwrap()
+        .to_string()
+}
+
+/// Asks for the details of a new account and creates it.
+pub fn ask(siv: &mut Cursive) {
+    let layout = LinearLayout::vertical()
+        .child(TextView::new("email address:"))
+        .child(EditView::new().with_name("register_email"))
+        .child(TextView::new("name:"))
+        .child(EditView::new().with_name("register_name"))
+        .child(TextView::new("master password:"))
+        .child(EditView::new().secret().with_name("register_password"))
+        .child(TextView::new("repeat master password:"))
+        .child(EditView::new().secret().with_name("register_repeat"))
+        .child(TextView::new("password hint (optional):"))
+        .child(EditView::new().with_name("register_hint"));
+
+    siv.add_layer(
+        Dialog::around(layout)
+            .title("create bitwarden account")
+            .button("Create", submit)
+            .dismiss_button("Cancel")
+            .min_width(60),
+    );
+}
+
+fn submit(siv: &mut Cursive) {
+    // The email is the salt of the master key, the official clients always
+    // use it in lowercase.
+    let email = content(siv, "register_email").trim().to_lowercase();
+    let name = content(siv, "register_name").trim().to_owned();
+    let password = content(siv, "register_password");
+    let repeat = content(siv, "register_repeat");
+    let hint = content(siv, "register_hint").trim().to_owned();
+
+    let error = if email.is_empty() || !email.contains('@') {
+        Some("A valid email address is needed".to_owned())
+    } else if password.chars().count() < MIN_PASSWORD_LENGTH {
+        Some(format!(
+            "The master password needs at least {} characters",
+            MIN_PASSWORD_LENGTH
+        ))
+    } else if password != repeat {
+        Some("The master passwords don't match".to_owned())
+    } else if !hint.is_empty() && hint.contains(&password) {
+        Some("The hint must not contain the master password".to_owned())
+    } else {
+        None
+    };
+
+    if let Some(error) = error {
+        siv.add_layer(Dialog::info(error));
+        return;
+    }
+
+    siv.add_layer(Dialog::text("Creating account ..."));
+
+    worker::run(
+        move |api| {
+            let keys =
+                CipherSuite::generate_account(&email, &password, KDF_ITERATIONS).map_err(|e| {
+                    ApiError::RegistrationFailed {
+                        error: e.to_string(),
+                    }
+                })?;
+
+            let hint = if hint.is_empty() {
+                None
+            } else {
+                Some(hint.as_str())
+            };
+
+            api.register(&email, &name, hint, KDF_ITERATIONS, &keys)?;
+
+            Ok(email)
+        },
+        |siv, result: Result<String, ApiError>| {
+            siv.pop_layer();
+
+            match result {
+                Ok(email) => {
+                    // Drop the registration form
+                    siv.pop_layer();
+                    login::registered(siv, &email);
+                }
+                Err(err) => siv.add_layer(Dialog::info(err.to_string())),
+            }
+        },
+    );
+}
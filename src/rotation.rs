@@ -0,0 +1,140 @@
+// SPDX-License-Identifier: MIT
+
+// Password rotation policies, set per item in a custom field or per folder
+// in its name, e.g. "banking (rotate 90d)".
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, Duration, Utc};
+use uuid::Uuid;
+
+use crate::api::{CipherEntry, VaultData};
+use crate::cipher::CipherSuite;
+
+
+/// Name of the custom field holding an item's rotation period, like "90d",
+/// "12w", "6m" or "1y".
+pub const POLICY_FIELD: &str = "rotate";
+
+// Rotations due within this are reported as soon due
+const DUE_SOON_DAYS: i64 = 14;
+
+
+/// Parses a period like "90d", a plain number counts days.
+pub fn parse_period(text: &str) -> Option<Duration> {
+        let text = text.trim().to_lowercase();
+        let (number, unit) = match text.find(|c: char| !c.is_ascii_digit()) {
+                Some(index) => text.split_at(index),
+                None => (text.as_str(), "d"),
+        };
+
+        let number = number.parse::<i64>().ok().filter(|number| *number > 0)?;
+
+        let days = match unit.trim() {
+                "d" | "day" | "days" => number,
+                "w" | "week" | "weeks" => number * 7,
+                "m" | "month" | "months" => number * 30,
+                "y" | "year" | "years" => number * 365,
+                _ => return None,
+        };
+
+        Some(Duration::days(days))
+}
+
+/// Period given in a folder name, as "(rotate <period>)" at its end.
+fn folder_period(name: &str) -> Option<Duration> {
+        let name = name.trim_end();
+        if !name.ends_with(')') {
+                return None;
+        }
+
+        let name = &name[..name.len() - 1];
+        let start = name.rfind('(')?;
+        let policy = name[start + 1..].trim();
+
+        let period = policy.get(..6)
+                .filter(|prefix| prefix.eq_ignore_ascii_case("rotate"))
+                .map(|_| &policy[6..])?;
+
+        parse_period(period)
+}
+
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum State {
+        Overdue,
+        DueSoon,
+        Fine,
+}
+
+/// Item with a rotation policy.
+#[derive(Clone, Debug)]
+pub struct Rotation {
+        pub uuid: Uuid,
+        pub name: String,
+        pub due: DateTime<Utc>,
+        pub state: State,
+}
+
+
+fn item_period(entry: &CipherEntry, cipher: &CipherSuite) -> Option<Duration> {
+        let field = entry.find_field(cipher, POLICY_FIELD)?;
+
+        entry.field_value(field)
+                .and_then(|value| value.decrypt(cipher))
+                .and_then(|value| parse_period(&value))
+}
+
+/// All items with a password and a rotation policy, the item's own policy
+/// taking precedence over the one of its folder. Sorted by due date.
+pub fn rotations(vault: &VaultData, cipher: &CipherSuite, now: DateTime<Utc>) -> Vec<Rotation> {
+        let folders = vault.folders.iter()
+                .filter_map(|folder| {
+                        let period = folder.name.decrypt(cipher).and_then(|name| folder_period(&name))?;
+                        Some((folder.uuid, period))
+                })
+                .collect::<HashMap<Uuid, Duration>>();
+
+        let mut rotations = vault.ciphers.iter()
+                .filter(|entry| entry.deleted_date.is_none() && entry.data.password.is_some())
+                .filter_map(|entry| {
+                        let period = item_period(entry, cipher)
+                                .or_else(|| entry.folder_id.and_then(|id| folders.get(&id).cloned()))?;
+
+                        // Passwords never changed are as old as the item
+                        let changed = entry.data.assword_last_changed.unwrap_or(entry.last_changed);
+                        let due = changed + period;
+
+                        let state = if due < now {
+                                State::Overdue
+                        } else if due < now + Duration::days(DUE_SOON_DAYS) {
+                                State::DueSoon
+                        } else {
+                                State::Fine
+                        };
+
+                        Some(Rotation {
+                                uuid: entry.uuid,
+                                name: entry.name.decrypt(cipher).unwrap_or_default(),
+                                due,
+                                state,
+                        })
+                })
+                .collect::<Vec<Rotation>>();
+
+        rotations.sort_by(|a, b| a.due.cmp(&b.due));
+        rotations
+}
+
+/// Short summary of rotations needing attention, empty if there are none.
+pub fn summary(rotations: &[Rotation]) -> String {
+        let count = |state: State| rotations.iter().filter(|rotation| rotation.state == state).count();
+        let (overdue, soon) = (count(State::Overdue), count(State::DueSoon));
+
+        match (overdue, soon) {
+                (0, 0) => String::new(),
+                (overdue, 0) => format!("{} passwords overdue for rotation", overdue),
+                (0, soon) => format!("{} password rotations due soon", soon),
+                (overdue, soon) => format!("{} passwords overdue, {} due soon", overdue, soon),
+        }
+}
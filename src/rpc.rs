@@ -0,0 +1,234 @@
+// SPDX-License-Identifier: MIT
+
+// `bwtui rpc`: JSON-RPC 2.0 over stdin/stdout, one message per line, so
+// that editors and other tools can use bwtui as secrets backend. Works on
+// the local cache only, like `bwtui get`.
+
+use std::io::{self, BufRead, Write};
+
+use chrono::Duration;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::api::{self, AppData, CipherEntry};
+use crate::cipher::{self, CipherSuite};
+use crate::get;
+use crate::search::SearchIndex;
+
+
+// Error codes defined by JSON-RPC
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+// Valid request which couldn't be done, e.g. for a missing item
+const REQUEST_FAILED: i64 = -32000;
+
+const DEFAULT_PASSWORD_LENGTH: u64 = 20;
+const MAX_PASSWORD_LENGTH: u64 = 128;
+
+
+#[derive(Deserialize)]
+struct Request {
+        // Missing for notifications, which aren't answered
+        #[serde(default)]
+        id: Option<Value>,
+        method: String,
+        #[serde(default)]
+        params: Value,
+}
+
+struct Error {
+        code: i64,
+        message: String,
+}
+
+impl Error {
+        fn new<S: Into<String>>(code: i64, message: S) -> Self {
+                Self { code, message: message.into() }
+        }
+}
+
+
+struct Unlocked {
+        cipher: CipherSuite,
+        index: SearchIndex,
+}
+
+struct Server {
+        data: AppData,
+        // Set by the unlock method, cleared by lock
+        unlocked: Option<Unlocked>,
+}
+
+
+fn string_param<'a>(params: &'a Value, name: &str) -> Result<&'a str, Error> {
+        params.get(name)
+                .and_then(Value::as_str)
+                .ok_or_else(|| Error::new(INVALID_PARAMS, format!("missing string parameter \"{}\"", name)))
+}
+
+
+/// What's listed of an item, leaving out any secrets.
+fn summary(entry: &CipherEntry, cipher: &CipherSuite) -> Value {
+        json!({
+                "id": entry.uuid,
+                "name": entry.name.decrypt(cipher).unwrap_or_default(),
+                "username": entry.data.username.as_ref().and_then(|username| username.decrypt(cipher)),
+        })
+}
+
+
+fn generate(params: &Value) -> Result<Value, Error> {
+        let length = match params.get("length") {
+                Some(length) => length.as_u64()
+                        .filter(|length| *length > 0 && *length <= MAX_PASSWORD_LENGTH)
+                        .ok_or_else(|| Error::new(INVALID_PARAMS,
+                                format!("length must be between 1 and {}", MAX_PASSWORD_LENGTH)))?,
+                None => DEFAULT_PASSWORD_LENGTH,
+        };
+
+        let symbols = params.get("symbols")
+                .and_then(Value::as_bool)
+                .unwrap_or(true);
+
+        Ok(Value::String(cipher::generate_password(length as usize, symbols)))
+}
+
+
+impl Server {
+        fn unlocked(&self) -> Result<&Unlocked, Error> {
+                self.unlocked.as_ref()
+                        .ok_or_else(|| Error::new(REQUEST_FAILED, "the vault is locked, call unlock first"))
+        }
+
+        fn unlock(&mut self, params: &Value) -> Result<Value, Error> {
+                let password = string_param(params, "password")?;
+                let vault = &self.data.vault;
+
+                let mut cipher = CipherSuite::with_kdf(&vault.profile.email, password, &self.data.auth.kdf())
+                        .map_err(|e| Error::new(REQUEST_FAILED, e.to_string()))?;
+                cipher.set_decrypt_key(&vault.profile.key)
+                        .map_err(|_| Error::new(REQUEST_FAILED, "wrong master password"))?;
+
+                let index = SearchIndex::build(vault, &cipher);
+                self.unlocked = Some(Unlocked { cipher, index });
+
+                Ok(Value::Bool(true))
+        }
+
+        fn list(&self) -> Result<Value, Error> {
+                let unlocked = self.unlocked()?;
+
+                Ok(self.data.vault.ciphers.iter()
+                        .filter(|entry| entry.deleted_date.is_none())
+                        .map(|entry| summary(entry, &unlocked.cipher))
+                        .collect())
+        }
+
+        fn search(&self, params: &Value) -> Result<Value, Error> {
+                let query = string_param(params, "query")?;
+                let unlocked = self.unlocked()?;
+
+                Ok(unlocked.index.search(query).into_iter()
+                        .filter_map(|pos| self.data.vault.ciphers.get(pos))
+                        .filter(|entry| entry.deleted_date.is_none())
+                        .map(|entry| summary(entry, &unlocked.cipher))
+                        .collect())
+        }
+
+        fn get_field(&self, params: &Value) -> Result<Value, Error> {
+                let item = string_param(params, "item")?;
+                let field = string_param(params, "field")?;
+                let unlocked = self.unlocked()?;
+
+                let entry = get::find_item(&self.data.vault.ciphers, &unlocked.cipher, item)
+                        .map_err(|e| Error::new(REQUEST_FAILED, e))?;
+
+                get::value(entry, field, &unlocked.cipher, Duration::zero())
+                        .map(Value::String)
+                        .ok_or_else(|| Error::new(REQUEST_FAILED, format!("item has no {}", field)))
+        }
+
+        fn handle(&mut self, method: &str, params: &Value) -> Result<Value, Error> {
+                match method {
+                        "unlock" => self.unlock(params),
+                        "lock" => {
+                                self.unlocked = None;
+                                Ok(Value::Null)
+                        }
+                        "list" => self.list(),
+                        "search" => self.search(params),
+                        "get-field" => self.get_field(params),
+                        "generate" => generate(params),
+                        _ => Err(Error::new(METHOD_NOT_FOUND, format!("unknown method {}", method))),
+                }
+        }
+
+        /// Answers a single message, `None` for notifications.
+        fn respond(&mut self, line: &str) -> Option<Value> {
+                let request = serde_json::from_str::<Value>(line)
+                        .map_err(|e| Error::new(PARSE_ERROR, e.to_string()))
+                        .and_then(|request| serde_json::from_value::<Request>(request)
+                                .map_err(|e| Error::new(INVALID_REQUEST, e.to_string())));
+
+                let request = match request {
+                        Ok(request) => request,
+                        Err(err) => return Some(error_response(Value::Null, err)),
+                };
+
+                let result = self.handle(&request.method, &request.params);
+                let id = request.id?;
+
+                Some(match result {
+                        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+                        Err(err) => error_response(id, err),
+                })
+        }
+}
+
+
+fn error_response(id: Value, err: Error) -> Value {
+        json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "error": { "code": err.code, "message": err.message },
+        })
+}
+
+
+/// Serves requests until stdin is closed, returning the exit code.
+pub fn run() -> i32 {
+        let data = match api::read_app_data() {
+                Ok(data) => data,
+                Err(_) => {
+                        eprintln!("bwtui rpc: no cached vault, log in with bwtui first");
+                        return 1;
+                }
+        };
+
+        let mut server = Server { data, unlocked: None };
+
+        let stdin = io::stdin();
+        let stdout = io::stdout();
+        let mut stdout = stdout.lock();
+
+        for line in stdin.lock().lines() {
+                let line = match line {
+                        Ok(line) => line,
+                        Err(_) => break,
+                };
+
+                if line.trim().is_empty() {
+                        continue;
+                }
+
+                if let Some(response) = server.respond(&line) {
+                        if writeln!(stdout, "{}", response).and_then(|()| stdout.flush()).is_err() {
+                                break;
+                        }
+                }
+        }
+
+        0
+}
@@ -0,0 +1,296 @@
+// SPDX-License-Identifier: MIT
+
+// User defined commands, written in rhai. The script registers them at its
+// top level and gets a small read-only view of the vault when one runs:
+//
+//     command("DB connection string", "db_url");
+//     bind("d", "db_url");
+//
+//     fn db_url() {
+//         copy("postgres://" + username() + ":" + password() + "@" + field("host"));
+//     }
+
+use std::cell::RefCell;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::sync::Arc;
+
+use chrono::Duration;
+use cursive::event::Event;
+use cursive::traits::*;
+use cursive::views::{Dialog, OnEventView, SelectView};
+use cursive::Cursive;
+use rhai::{Dynamic, Engine, ImmutableString, Scope, AST};
+use unicase::UniCase;
+use uuid::Uuid;
+
+use crate::activity;
+use crate::api::{self, CipherEntry};
+use crate::cipher::CipherSuite;
+use crate::clip;
+use crate::get;
+use crate::vault::{self, Session};
+
+const SCRIPT_FILE: &str = "commands.rhai";
+// Keeps a runaway script from hanging the UI
+const MAX_OPERATIONS: u64 = 1_000_000;
+
+struct Script {
+    ast: AST,
+    // Label and function of each command
+    commands: Vec<(String, String)>,
+    // Key (used with alt) and function
+    bindings: Vec<(char, String)>,
+}
+
+/// What a script sees of the vault.
+struct Context {
+    ciphers: Vec<CipherEntry>,
+    selected: Option<Uuid>,
+    cipher: Arc<CipherSuite>,
+    skew: Duration,
+}
+
+enum Action {
+    Copy(String),
+    Show(String),
+}
+
+impl Context {
+    fn find(&self, uuid: Option<Uuid>) -> Option<&CipherEntry> {
+        self.ciphers.iter().find(|entry| Some(entry.uuid) == uuid)
+    }
+
+    fn find_by_name(&self, name: &str) -> Option<&CipherEntry> {
+        self.ciphers.iter().find(|entry| {
+            entry.name.decrypt(&self.cipher).map_or(false, |other| {
+                UniCase::new(other) == UniCase::new(name.to_owned())
+            })
+        })
+    }
+
+    /// Value of `field`, either a login property or the name of a custom
+    /// field. Empty if the item doesn't have it.
+    fn value(&self, entry: Option<&CipherEntry>, field: &str) -> String {
+        entry
+            .and_then(|entry| get::value(entry, field, &self.cipher, self.skew))
+            .unwrap_or_default()
+    }
+}
+
+fn path() -> Result<PathBuf, String> {
+    api::get_app_data_path().map(|dir| dir.join(SCRIPT_FILE))
+}
+
+/// Reads the script and runs its top level to collect the commands, `None`
+/// if there is no script.
+fn load() -> Result<Option<Script>, String> {
+    let path = path()?;
+
+    let source = match fs::read_to_string(&path) {
+        Ok(source) => source,
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(format!("failed to read {}: {}", path.display(), e)),
+    };
+
+    let commands = Rc::new(RefCell::new(Vec::new()));
+    let bindings = Rc::new(RefCell::new(Vec::new()));
+    let mut engine = Engine::new();
+    engine.set_max_operations(MAX_OPERATIONS);
+
+    let registered = Rc::clone(&commands);
+    engine.register_fn(
+        "command",
+        move |label: ImmutableString, function: ImmutableString| {
+            registered
+                .borrow_mut()
+                .push((label.to_string(), function.to_string()));
+        },
+    );
+
+    let bound = Rc::clone(&bindings);
+    engine.register_fn(
+        "bind",
+        move |key: ImmutableString, function: ImmutableString| {
+            bound
+                .borrow_mut()
+                .push((key.to_string(), function.to_string()));
+        },
+    );
+
+    let ast = engine
+        .compile(&source)
+        .map_err(|e| format!("{}: {}", path.display(), e))?;
+    engine
+        .consume_ast(&ast)
+        .map_err(|e| format!("{}: {}", path.display(), e))?;
+
+    let mut keys = Vec::new();
+    for (key, function) in bindings.borrow().iter() {
+        let mut chars = key.chars();
+        match (chars.next(), chars.next()) {
+            (Some(key), None) => keys.push((key, function.clone())),
+            _ => {
+                return Err(format!(
+                    "can't bind \"{}\", keys are single characters",
+                    key
+                ))
+            }
+        }
+    }
+
+    let commands = commands.borrow().clone();
+
+    Ok(Some(Script {
+        ast,
+        commands,
+        bindings: keys,
+    }))
+}
+
+/// Engine for running a command, with the vault functions and the actions
+/// scripts can take.
+fn engine(context: Context, actions: &Rc<RefCell<Vec<Action>>>) -> Engine {
+    let mut engine = Engine::new();
+    engine.set_max_operations(MAX_OPERATIONS);
+
+    let context = Rc::new(context);
+
+    for field in &["name", "username", "password", "totp", "notes", "uri"] {
+        let context = Rc::clone(&context);
+        engine.register_fn(field, move || {
+            context.value(context.find(context.selected), field)
+        });
+    }
+
+    let selected = Rc::clone(&context);
+    engine.register_fn("field", move |name: ImmutableString| {
+        selected.value(selected.find(selected.selected), &name)
+    });
+
+    let other = Rc::clone(&context);
+    engine.register_fn(
+        "item_field",
+        move |item: ImmutableString, name: ImmutableString| {
+            other.value(other.find_by_name(&item), &name)
+        },
+    );
+
+    let copied = Rc::clone(actions);
+    engine.register_fn("copy", move |text: ImmutableString| {
+        copied.borrow_mut().push(Action::Copy(text.to_string()));
+    });
+
+    let shown = Rc::clone(actions);
+    engine.register_fn("show", move |text: ImmutableString| {
+        shown.borrow_mut().push(Action::Show(text.to_string()));
+    });
+
+    engine
+}
+
+/// Runs the script function `function` on the selected item.
+fn run(siv: &mut Cursive, function: &str) {
+    let script = match load() {
+        Ok(Some(script)) => script,
+        Ok(None) => return,
+        Err(err) => return siv.add_layer(Dialog::info(err)),
+    };
+
+    let selected = vault::selected_item(siv);
+
+    let context = match siv.user_data::<Session>() {
+        Some(session) => Context {
+            ciphers: session.vault.ciphers.clone(),
+            selected,
+            cipher: Arc::clone(&session.cipher),
+            skew: session.vault.clock_skew.unwrap_or_else(Duration::zero),
+        },
+        None => return,
+    };
+
+    let actions = Rc::new(RefCell::new(Vec::new()));
+    let engine = engine(context, &actions);
+
+    let result: Result<Dynamic, _> = engine.call_fn(&mut Scope::new(), &script.ast, function, ());
+
+    if let Err(err) = result {
+        siv.add_layer(Dialog::info(format!("{} failed: {}", function, err)));
+        return;
+    }
+
+    let actions = actions.replace(Vec::new());
+
+    for action in actions {
+        match action {
+            Action::Copy(text) => match clip::copy(&text) {
+                Ok(()) => {
+                    let action = format!("copied from command {}", function);
+                    activity::record(siv, &action, selected);
+                }
+                Err(err) => siv.add_layer(Dialog::info(err)),
+            },
+            Action::Show(text) => siv.add_layer(Dialog::info(text).title(function)),
+        }
+    }
+}
+
+/// Binds the keys the script asks for, with alt, on `view`. Returns why the
+/// script couldn't be loaded, if it couldn't.
+pub fn bind<V: View>(view: &mut OnEventView<V>) -> Option<String> {
+    let script = match load() {
+        Ok(Some(script)) => script,
+        Ok(None) => return None,
+        Err(err) => return Some(err),
+    };
+
+    for (key, function) in script.bindings {
+        view.set_on_event(Event::AltChar(key), move |siv| run(siv, &function));
+    }
+
+    None
+}
+
+/// Lists the commands of the script to pick one.
+pub fn show(siv: &mut Cursive) {
+    let script = match load() {
+        Ok(Some(script)) if !script.commands.is_empty() => script,
+        Ok(_) => {
+            let path = path()
+                .map(|path| path.display().to_string())
+                .unwrap_or_else(|_| SCRIPT_FILE.to_owned());
+
+            siv.add_layer(Dialog::info(format!(
+                "No commands yet, they can be defined in {}",
+                path
+            )));
+            return;
+        }
+        Err(err) => return siv.add_layer(Dialog::info(err)),
+    };
+
+    let mut select = SelectView::new().on_submit(|siv, function: &String| {
+        siv.pop_layer();
+        run(siv, function);
+    });
+
+    for (label, function) in script.commands {
+        let key = script
+            .bindings
+            .iter()
+            .find(|(_, bound)| *bound == function)
+            .map(|(key, _)| format!(" (alt-{})", key))
+            .unwrap_or_default();
+
+        select.add_item(format!("{}{}", label, key), function);
+    }
+
+    siv.add_layer(
+        Dialog::around(select.scrollable())
+            .title("commands")
+            .dismiss_button("Cancel")
+            .min_width(40),
+    );
+}
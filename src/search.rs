@@ -0,0 +1,134 @@
+// SPDX-License-Identifier: MIT
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use fuzzy_matcher::{skim::SkimMatcherV2, FuzzyMatcher};
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+use uuid::Uuid;
+
+use crate::api::{CipherEntry, VaultData};
+use crate::cipher::{CipherString, CipherSuite};
+
+
+struct IndexEntry {
+        uuid: Uuid,
+        last_changed: DateTime<Utc>,
+        fields: Vec<String>,
+}
+
+
+/// Normalized names, usernames and URIs of all ciphers, kept in the same order
+/// as `VaultData::ciphers`.
+#[derive(Default)]
+pub struct SearchIndex {
+        entries: Vec<IndexEntry>,
+}
+
+
+/// Lowercases `text` and strips all diacritics, e.g. "Ärztekammer" becomes
+/// "arztekammer".
+pub fn normalize(text: &str) -> String {
+        text.nfd()
+                .filter(|c| !is_combining_mark(*c))
+                .flat_map(char::to_lowercase)
+                .collect()
+}
+
+
+fn searchable_fields(entry: &CipherEntry) -> Vec<&CipherString> {
+        let mut fields = vec![&entry.name];
+        fields.extend(&entry.data.username);
+        fields.extend(&entry.data.uri);
+
+        if let Some(uris) = &entry.data.uris {
+                fields.extend(uris.iter().filter_map(|u| u.uri.as_ref()));
+        }
+
+        fields
+}
+
+
+impl SearchIndex {
+        pub fn build(vault: &VaultData, cipher: &CipherSuite) -> Self {
+                let mut index = Self::default();
+                index.update(vault, cipher);
+
+                index
+        }
+
+        /// Brings the index in line with `vault`, only decrypting ciphers which
+        /// were added or changed since the last update.
+        pub fn update(&mut self, vault: &VaultData, cipher: &CipherSuite) {
+                let mut known = self.entries.drain(..)
+                        .map(|entry| (entry.uuid, entry))
+                        .collect::<HashMap<Uuid, IndexEntry>>();
+
+                let stale = vault.ciphers.iter()
+                        .filter(|c| {
+                                known.get(&c.uuid)
+                                        .map_or(true, |entry| entry.last_changed != c.last_changed)
+                        })
+                        .collect::<Vec<&CipherEntry>>();
+
+                let fields = stale.iter()
+                        .map(|c| searchable_fields(c))
+                        .collect::<Vec<Vec<&CipherString>>>();
+
+                let values = fields.iter()
+                        .flatten()
+                        .cloned()
+                        .collect::<Vec<&CipherString>>();
+
+                let mut plain = cipher.decrypt_all(&values).into_iter();
+
+                for (entry, fields) in stale.iter().zip(&fields) {
+                        let fields = plain.by_ref()
+                                .take(fields.len())
+                                .map(|value| normalize(&value.unwrap_or_default()))
+                                .collect();
+
+                        known.insert(entry.uuid, IndexEntry {
+                                uuid: entry.uuid,
+                                last_changed: entry.last_changed,
+                                fields,
+                        });
+                }
+
+                self.entries = vault.ciphers.iter()
+                        .filter_map(|c| known.remove(&c.uuid))
+                        .collect();
+        }
+
+        /// Returns the positions of all ciphers matching `query`, best match
+        /// first.
+        pub fn search(&self, query: &str) -> Vec<usize> {
+                let all = (0..self.entries.len()).collect::<Vec<usize>>();
+
+                self.search_within(query, &all)
+        }
+
+        /// Like `search()`, but only considers the ciphers at `candidates`,
+        /// e.g. the results of a search for a prefix of `query`.
+        pub fn search_within(&self, query: &str, candidates: &[usize]) -> Vec<usize> {
+                let matcher = SkimMatcherV2::default();
+                let query = normalize(query);
+
+                let mut matches = candidates.iter()
+                        .filter_map(|&pos| {
+                                self.entries.get(pos)?
+                                        .fields.iter()
+                                        .filter_map(|field| matcher.fuzzy_match(field, &query))
+                                        .max()
+                                        .map(|score| (score, pos))
+                        })
+                        .collect::<Vec<(i64, usize)>>();
+
+                matches.sort_by(|a, b| a.0.cmp(&b.0).reverse());
+
+                matches.into_iter()
+                        .map(|(_, pos)| pos)
+                        .collect()
+        }
+}
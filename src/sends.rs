@@ -0,0 +1,236 @@
+// SPDX-License-Identifier: MIT
+
+use chrono::{DateTime, Local, NaiveDateTime, TimeZone, Utc};
+use cursive::traits::*;
+use cursive::views::{Dialog, EditView, LinearLayout, SelectView, TextView};
+use cursive::Cursive;
+
+use crate::api::{Api, ApiError, AuthData, SendEntry, SEND_FILE, SEND_TEXT};
+use crate::cipher::CipherSuite;
+use crate::clip;
+use crate::vault::{self, Session};
+
+const DATE_FORMAT: &str = "%Y-%m-%d %H:%M";
+
+/// Send along with what could be decrypted of it.
+struct Item {
+    send: SendEntry,
+    name: String,
+    link: Option<String>,
+}
+
+fn decrypt(send: SendEntry, cipher: &CipherSuite, web_vault: &str) -> Item {
+    match cipher.send_key(&send.key) {
+        Ok((material, key)) => Item {
+            name: send.name.decrypt(&key).unwrap_or_default(),
+            link: Some(send.link(web_vault, &material)),
+            send,
+        },
+        Err(err) => Item {
+            name: format!("(unreadable: {})", err),
+            link: None,
+            send,
+        },
+    }
+}
+
+fn format_date(date: DateTime<Utc>) -> String {
+    date.with_timezone(&Local).format(DATE_FORMAT).to_string()
+}
+
+fn describe(item: &Item) -> String {
+    let send = &item.send;
+
+    let type_ = match send.type_ {
+        SEND_TEXT => "text",
+        SEND_FILE => "file",
+        _ => "unknown",
+    };
+
+    let expiry = match send.expiration_date {
+        Some(date) => format!("expires {}", format_date(date)),
+        None => "never expires".to_owned(),
+    };
+
+    let accesses = match send.max_access_count {
+        Some(max) => format!("{}/{} accesses", send.access_count, max),
+        None => format!("{} accesses", send.access_count),
+    };
+
+    let mut label = format!("{} ({}), {}, {}", item.name, type_, expiry, accesses);
+
+    if send.disabled {
+        label.push_str(" [disabled]");
+    }
+
+    label
+}
+
+/// Lists the sends of the account.
+pub fn show(siv: &mut Cursive) {
+    vault::request(
+        siv,
+        |api, auth| api.sends(auth).map(|sends| (sends, api.web_vault_url())),
+        |siv, result: Result<(Vec<SendEntry>, String), ApiError>| match result {
+            Ok((sends, web_vault)) => show_list(siv, sends, &web_vault),
+            Err(err) => siv.add_layer(Dialog::info(err.to_string())),
+        },
+    );
+}
+
+fn show_list(siv: &mut Cursive, mut sends: Vec<SendEntry>, web_vault: &str) {
+    let items = match siv.user_data::<Session>() {
+        Some(session) => {
+            sends.sort_by(|a, b| a.deletion_date.cmp(&b.deletion_date));
+
+            sends
+                .into_iter()
+                .map(|send| decrypt(send, &session.cipher, web_vault))
+                .collect::<Vec<Item>>()
+        }
+        None => return,
+    };
+
+    let mut select = SelectView::new().on_submit(actions);
+    for item in items {
+        select.add_item(describe(&item), item);
+    }
+
+    let content = if select.is_empty() {
+        LinearLayout::vertical().child(TextView::new("No sends."))
+    } else {
+        LinearLayout::vertical().child(select.scrollable())
+    };
+
+    siv.add_layer(
+        Dialog::around(content)
+            .title("sends")
+            .dismiss_button("Close")
+            .with_name("sends_dialog")
+            .min_width(70),
+    );
+}
+
+fn actions(siv: &mut Cursive, item: &Item) {
+    let link = item.link.clone();
+    let send = item.send.clone();
+    let id = send.uuid;
+
+    siv.add_layer(
+        Dialog::text(format!(
+            "{}\n\ndeleted on {}",
+            item.name,
+            format_date(send.deletion_date)
+        ))
+        .title("send")
+        .button("Copy link", move |siv| {
+            siv.pop_layer();
+
+            let result = match &link {
+                Some(link) => clip::copy(link),
+                None => Err("The key of this send can't be decrypted".to_owned()),
+            };
+
+            if let Err(err) = result {
+                siv.add_layer(Dialog::info(err));
+            }
+        })
+        .button("Edit expiry", move |siv| {
+            siv.pop_layer();
+            ask_expiry(siv, send.clone());
+        })
+        .button("Delete", move |siv| {
+            siv.pop_layer();
+            siv.add_layer(
+                Dialog::text("Delete this send? Its link stops working right away.")
+                    .button("Delete", move |siv| {
+                        siv.pop_layer();
+                        change(siv, move |api, auth| api.delete_send(auth, id));
+                    })
+                    .dismiss_button("Cancel"),
+            );
+        })
+        .dismiss_button("Cancel"),
+    );
+}
+
+fn ask_expiry(siv: &mut Cursive, send: SendEntry) {
+    let current = send.expiration_date.map(format_date).unwrap_or_default();
+
+    let layout = LinearLayout::vertical()
+        .child(TextView::new(
+            "expiry (YYYY-MM-DD HH:MM, local time, empty for none):",
+        ))
+        .child(EditView::new().content(current).with_name("send_expiry"));
+
+    siv.add_layer(
+        Dialog::around(layout)
+            .title("edit expiry")
+            .button("Ok", move |siv| {
+                let content = siv
+                    .call_on_name("send_expiry", |view: &mut EditView| view.get_content())
+                    .unwrap();
+                let content = content.trim();
+
+                let expiry = if content.is_empty() {
+                    None
+                } else {
+                    let date = NaiveDateTime::parse_from_str(content, DATE_FORMAT)
+                        .ok()
+                        .and_then(|date| Local.from_local_datetime(&date).single());
+
+                    match date {
+                        Some(date) => Some(date.with_timezone(&Utc)),
+                        None => {
+                            siv.add_layer(Dialog::info("Invalid date"));
+                            return;
+                        }
+                    }
+                };
+
+                // The server deletes the send anyway at that point
+                if let Some(expiry) = expiry {
+                    if expiry > send.deletion_date {
+                        siv.add_layer(Dialog::info(format!(
+                            "The send is deleted on {}, it can't expire later",
+                            format_date(send.deletion_date)
+                        )));
+                        return;
+                    }
+                }
+
+                siv.pop_layer();
+
+                let mut send = send.clone();
+                send.expiration_date = expiry;
+
+                change(siv, move |api, auth| {
+                    api.update_send(auth, &send)?;
+                    Ok(())
+                });
+            })
+            .dismiss_button("Cancel")
+            .min_width(50),
+    );
+}
+
+/// Runs a request changing a send, showing the updated list after.
+fn change<F>(siv: &mut Cursive, request: F)
+where
+    F: Fn(&Api, &AuthData) -> Result<(), ApiError> + Send + 'static,
+{
+    vault::request(
+        siv,
+        request,
+        |siv, result: Result<(), ApiError>| match result {
+            Ok(()) => {
+                if siv.find_name::<Dialog>("sends_dialog").is_some() {
+                    siv.pop_layer();
+                }
+
+                show(siv);
+            }
+            Err(err) => siv.add_layer(Dialog::info(err.to_string())),
+        },
+    );
+}
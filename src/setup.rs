@@ -0,0 +1,202 @@
+// SPDX-License-Identifier: MIT
+
+// Setup on the very first launch, before anything is cached: picks the server
+// region, when to lock and how long copied values stay in the clipboard, then
+// continues with the usual login and initial sync. When to lock can be changed
+// later from the vault.
+
+use cursive::traits::*;
+use cursive::views::{Checkbox, Dialog, DummyView, EditView, LinearLayout, SelectView, TextView};
+use cursive::Cursive;
+
+use crate::api::{self, Region, ServerUrls, Settings};
+use crate::clip;
+use crate::login;
+use crate::policy;
+use crate::suspend;
+use crate::vault::StartupView;
+use crate::worker;
+
+const REGIONS: &[(&str, Region)] = &[
+    ("bitwarden.com (US)", Region::Us),
+    ("bitwarden.eu (EU)", Region::Eu),
+];
+
+const LOCK_TIMEOUTS: &[(&str, Option<u64>)] = &[
+    ("never", None),
+    ("after 5 minutes", Some(5)),
+    ("after 15 minutes", Some(15)),
+    ("after 30 minutes", Some(30)),
+    ("after 1 hour", Some(60)),
+];
+
+const LOCK_ON_SUSPEND: &str = "also lock when suspended (ctrl-z) or the system slept";
+
+const CLIPBOARD_TIMEOUTS: &[(&str, Option<u64>)] = &[
+    ("never", None),
+    ("after 10 seconds", Some(10)),
+    ("after 30 seconds", Some(30)),
+    ("after 1 minute", Some(60)),
+    ("after 2 minutes", Some(120)),
+];
+
+fn select<T>(items: &[(&str, T)], selected: usize) -> SelectView<T>
+where
+    T: Clone + 'static,
+{
+    let mut view = SelectView::new().popup();
+    for (label, value) in items {
+        view.add_item(*label, value.clone());
+    }
+
+    view.selected(selected)
+}
+
+fn selection<T>(siv: &mut Cursive, name: &str) -> Option<T>
+where
+    T: Clone + 'static,
+{
+    siv.call_on_name(name, |view: &mut SelectView<T>| view.selection())
+        .flatten()
+        .map(|value| (*value).clone())
+}
+
+fn checkbox(checked: bool, name: &str, label: &str) -> LinearLayout {
+    LinearLayout::horizontal()
+        .child(Checkbox::new().with_checked(checked).with_name(name))
+        .child(TextView::new(format!(" {}", label)))
+}
+
+fn checked(siv: &mut Cursive, name: &str) -> bool {
+    siv.call_on_name(name, |view: &mut Checkbox| view.is_checked())
+        .unwrap_or(false)
+}
+
+/// Walks through the setup, ending at the login form. `server` prefills the
+/// self-hosted server, e.g. given with `--server`.
+pub fn show(siv: &mut Cursive, view: StartupView, server: Option<ServerUrls>) {
+    let server = server.map(|urls| urls.web_vault).unwrap_or_default();
+
+    let layout = LinearLayout::vertical()
+        .child(TextView::new(
+            "Welcome to bwtui! A few choices before logging in, they are kept \
+             in the data directory.",
+        ))
+        .child(DummyView)
+        .child(TextView::new("server your account is on:"))
+        .child(select(REGIONS, 0).with_name("setup_region"))
+        .child(TextView::new("or the URL of your self-hosted server:"))
+        .child(EditView::new().content(server).with_name("setup_server"))
+        .child(TextView::new("lock the vault without input:"))
+        .child(select(LOCK_TIMEOUTS, 2).with_name("setup_lock"))
+        .child(checkbox(false, "setup_lock_suspend", LOCK_ON_SUSPEND))
+        .child(TextView::new("clear copied passwords from the clipboard:"))
+        .child(select(CLIPBOARD_TIMEOUTS, 2).with_name("setup_clipboard"))
+        .child(DummyView)
+        .child(TextView::new(
+            "Next, log in with your email address and master password. If \
+             your account uses two-step login, the code is asked for \
+             afterwards.",
+        ));
+
+    siv.add_layer(
+        Dialog::around(layout)
+            .title("bwtui setup")
+            .button("Continue", move |siv| {
+                let server = siv
+                    .call_on_name("setup_server", |view: &mut EditView| view.get_content())
+                    .unwrap()
+                    .trim()
+                    .to_owned();
+
+                let server = if server.is_empty() {
+                    None
+                } else {
+                    match ServerUrls::self_hosted(&server) {
+                        Ok(urls) => Some(urls),
+                        Err(err) => {
+                            siv.add_layer(Dialog::info(err));
+                            return;
+                        }
+                    }
+                };
+
+                let settings = Settings {
+                    region: selection::<Region>(siv, "setup_region").unwrap_or_default(),
+                    server,
+                    lock_timeout: selection::<Option<u64>>(siv, "setup_lock").flatten(),
+                    lock_on_suspend: checked(siv, "setup_lock_suspend"),
+                    clear_clipboard: selection::<Option<u64>>(siv, "setup_clipboard").flatten(),
+                };
+
+                if let Err(err) = api::save_settings(&settings) {
+                    siv.add_layer(Dialog::info(format!(
+                        "Failed to save the settings: {}",
+                        err
+                    )));
+                    return;
+                }
+
+                apply(&settings);
+
+                let urls = settings.urls();
+                worker::run(move |api| api.set_server(urls), |_, ()| {});
+
+                siv.pop_layer();
+                login::ask(siv, None, view.clone());
+            })
+            .min_width(60),
+    );
+}
+
+/// Changes when the vault is locked, as picked in the setup.
+pub fn lock_settings(siv: &mut Cursive) {
+    let settings = api::read_settings().unwrap_or_default();
+    let selected = LOCK_TIMEOUTS
+        .iter()
+        .position(|(_, minutes)| *minutes == settings.lock_timeout)
+        .unwrap_or(0);
+
+    let layout = LinearLayout::vertical()
+        .child(TextView::new("lock the vault without input:"))
+        .child(select(LOCK_TIMEOUTS, selected).with_name("settings_lock"))
+        .child(checkbox(
+            settings.lock_on_suspend,
+            "settings_lock_suspend",
+            LOCK_ON_SUSPEND,
+        ))
+        .child(DummyView)
+        .child(TextView::new(
+            "Policies of your organizations can still lock it sooner.",
+        ));
+
+    siv.add_layer(
+        Dialog::around(layout)
+            .title("lock")
+            .button("Ok", |siv| {
+                let mut settings = api::read_settings().unwrap_or_default();
+                settings.lock_timeout = selection::<Option<u64>>(siv, "settings_lock").flatten();
+                settings.lock_on_suspend = checked(siv, "settings_lock_suspend");
+
+                if let Err(err) = api::save_settings(&settings) {
+                    siv.add_layer(Dialog::info(format!(
+                        "Failed to save the settings: {}",
+                        err
+                    )));
+                    return;
+                }
+
+                apply(&settings);
+                siv.pop_layer();
+            })
+            .dismiss_button("Cancel")
+            .min_width(50),
+    );
+}
+
+/// Makes the choices of the setup take effect.
+pub fn apply(settings: &Settings) {
+    policy::set_lock_timeout(settings.lock_timeout);
+    suspend::set_lock_on_resume(settings.lock_on_suspend);
+    clip::set_clear_after(settings.clear_clipboard);
+}
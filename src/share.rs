@@ -0,0 +1,215 @@
+// SPDX-License-Identifier: MIT
+
+use cursive::traits::*;
+use cursive::views::{Checkbox, Dialog, ListView, SelectView};
+use cursive::Cursive;
+use uuid::Uuid;
+
+use crate::api::{ApiError, Organization};
+use crate::vault::{self, Session};
+
+/// Moves the selected personal item into one of the organizations of the
+/// user.
+pub fn show(siv: &mut Cursive) {
+    let item = match vault::selected_item(siv) {
+        Some(item) => item,
+        None => return,
+    };
+
+    let organizations = match siv.user_data::<Session>() {
+        Some(session) => {
+            let entry = session
+                .vault
+                .ciphers
+                .iter()
+                .find(|entry| entry.uuid == item);
+
+            if entry.map_or(true, |entry| entry.organization_id.is_some()) {
+                siv.add_layer(Dialog::info("This item already belongs to an organization"));
+                return;
+            }
+
+            session
+                .vault
+                .profile
+                .organizations
+                .iter()
+                .filter(|org| org.enabled && org.key.is_some())
+                .cloned()
+                .collect::<Vec<Organization>>()
+        }
+        None => return,
+    };
+
+    match organizations.len() {
+        0 => siv.add_layer(Dialog::info("You aren't a member of any organization")),
+        1 => ask_collections(siv, item, &organizations[0]),
+        _ => {
+            let mut select = SelectView::new().on_submit(move |siv, org: &Organization| {
+                siv.pop_layer();
+                ask_collections(siv, item, org);
+            });
+
+            for org in organizations {
+                select.add_item(org.name.clone(), org);
+            }
+
+            siv.add_layer(
+                Dialog::around(select)
+                    .title("move to organization")
+                    .dismiss_button("Cancel"),
+            );
+        }
+    }
+}
+
+/// Asks which collections of `organization` the item goes into, the server
+/// needs at least one.
+fn ask_collections(siv: &mut Cursive, item: Uuid, organization: &Organization) {
+    let collections = match siv.user_data::<Session>() {
+        Some(session) => {
+            let cipher = match (&session.vault.profile.private_key, &organization.key) {
+                (Some(private_key), Some(key)) => session.cipher.shared_key(private_key, key),
+                _ => return,
+            };
+
+            let cipher = match cipher {
+                Ok(cipher) => cipher,
+                Err(err) => {
+                    siv.add_layer(Dialog::info(err.to_string()));
+                    return;
+                }
+            };
+
+            let mut collections = session
+                .vault
+                .collections
+                .iter()
+                .filter(|collection| collection.organization_id == organization.uuid)
+                .map(|collection| {
+                    let name = collection
+                        .name
+                        .decrypt(&cipher)
+                        .unwrap_or_else(|| collection.uuid.to_string());
+
+                    (name, collection.uuid)
+                })
+                .collect::<Vec<(String, Uuid)>>();
+
+            collections.sort();
+            collections
+        }
+        None => return,
+    };
+
+    if collections.is_empty() {
+        siv.add_layer(Dialog::info(
+            "You can't add items to any collection of this organization",
+        ));
+        return;
+    }
+
+    let mut list = ListView::new();
+    for (name, uuid) in &collections {
+        list.add_child(name, Checkbox::new().with_name(uuid.to_string()));
+    }
+
+    let organization = organization.uuid;
+
+    siv.add_layer(
+        Dialog::around(list.scrollable())
+            .title("collections")
+            .button("Move", move |siv| {
+                let chosen = collections
+                    .iter()
+                    .map(|(_, uuid)| *uuid)
+                    .filter(|uuid| {
+                        siv.call_on_name(&uuid.to_string(), |view: &mut Checkbox| view.is_checked())
+                            .unwrap_or(false)
+                    })
+                    .collect::<Vec<Uuid>>();
+
+                if chosen.is_empty() {
+                    siv.add_layer(Dialog::info("Choose at least one collection"));
+                    return;
+                }
+
+                siv.pop_layer();
+                confirm(siv, item, organization, chosen);
+            })
+            .dismiss_button("Cancel")
+            .min_width(40),
+    );
+}
+
+fn confirm(siv: &mut Cursive, item: Uuid, organization: Uuid, collections: Vec<Uuid>) {
+    siv.add_layer(
+        Dialog::text(
+            "Move this item into the organization? Its members get access to \
+             it and it can't be made personal again.",
+        )
+        .button("Move", move |siv| {
+            siv.pop_layer();
+            share(siv, item, organization, collections.clone());
+        })
+        .dismiss_button("Cancel"),
+    );
+}
+
+/// Re-encrypts the item with the organization key and hands it over.
+fn share(siv: &mut Cursive, item: Uuid, organization: Uuid, collections: Vec<Uuid>) {
+    let shared = match siv.user_data::<Session>() {
+        Some(session) => {
+            let org = session
+                .vault
+                .profile
+                .organizations
+                .iter()
+                .find(|org| org.uuid == organization);
+
+            let cipher = match (
+                &session.vault.profile.private_key,
+                org.and_then(|org| org.key.as_ref()),
+            ) {
+                (Some(private_key), Some(key)) => session.cipher.shared_key(private_key, key),
+                _ => return,
+            };
+
+            let entry = match session
+                .vault
+                .ciphers
+                .iter()
+                .find(|entry| entry.uuid == item)
+            {
+                Some(entry) => entry,
+                None => return,
+            };
+
+            cipher.and_then(|cipher| entry.reencrypt(&session.cipher, &cipher))
+        }
+        None => return,
+    };
+
+    let shared = match shared {
+        Ok(mut shared) => {
+            shared.organization_id = Some(organization);
+            shared
+        }
+        Err(err) => {
+            siv.add_layer(Dialog::info(err.to_string()));
+            return;
+        }
+    };
+
+    vault::request(
+        siv,
+        move |api, auth| api.share_cipher(auth, &shared, &collections),
+        |siv, result: Result<(), ApiError>| match result {
+            Ok(()) => {
+                siv.add_layer(Dialog::info("Item moved to the organization"));
+                vault::sync_in_background(siv);
+            }
+            Err(err) => siv.add_layer(Dialog::info(err.to_string())),
+        },
+    );
+}
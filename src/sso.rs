@@ -0,0 +1,133 @@
+// SPDX-License-Identifier: MIT
+
+// Single sign-on through the identity provider of an organization: the
+// browser logs in there and is sent back to a port on localhost with the
+// authorization code.
+
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use log::{debug, warn};
+use reqwest::Url;
+
+
+// Redirects to these are accepted by the identity server for its CLI client
+const PORTS: &[u16] = &[8065, 8066, 8067, 8068, 8069, 8070];
+const TIMEOUT: Duration = Duration::from_secs(5 * 60);
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+const DONE_PAGE: &str = "<html><body><p>Logged in, you can close this window and go back to \
+                         bwtui.</p></body></html>";
+const FAILED_PAGE: &str = "<html><body><p>The login failed, go back to bwtui to try \
+                           again.</p></body></html>";
+
+
+/// Value of the query parameter `name` of `url`, decoded.
+pub fn query_param(url: &str, name: &str) -> Option<String> {
+        Url::parse(url).ok()?
+                .query_pairs()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.into_owned())
+}
+
+
+/// Listens on the first free port the browser may be redirected to.
+pub fn listen() -> Result<(TcpListener, String), String> {
+        for port in PORTS {
+                if let Ok(listener) = TcpListener::bind(("127.0.0.1", *port)) {
+                        return Ok((listener, format!("http://localhost:{}", port)));
+                }
+        }
+
+        Err(format!("ports {} to {} are all taken", PORTS[0], PORTS[PORTS.len() - 1]))
+}
+
+
+/// Tries to open `url` in the default browser, it's shown to be opened by
+/// hand as well.
+pub fn open_browser(url: &str) {
+        #[cfg(target_os = "macos")]
+        let mut command = Command::new("open");
+        #[cfg(windows)]
+        let mut command = {
+                let mut command = Command::new("cmd");
+                command.args(&["/C", "start", ""]);
+                command
+        };
+        #[cfg(not(any(target_os = "macos", windows)))]
+        let mut command = Command::new("xdg-open");
+
+        let opened = command.arg(url)
+                .stdin(Stdio::null())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .spawn();
+
+        if let Err(err) = opened {
+                debug!("failed to open a browser: {}", err);
+        }
+}
+
+
+/// Code the browser is redirected with, `None` for other requests.
+fn read_code(stream: &mut TcpStream, state: &str) -> Option<Result<String, String>> {
+        let mut request_line = String::new();
+        BufReader::new(&*stream).read_line(&mut request_line).ok()?;
+
+        // GET /?code=...&state=... HTTP/1.1
+        let target = request_line.split_whitespace().nth(1)?;
+        let url = format!("http://localhost{}", target);
+
+        if let Some(error) = query_param(&url, "error") {
+                return Some(Err(error));
+        }
+
+        let code = query_param(&url, "code")?;
+
+        if query_param(&url, "state").as_deref() != Some(state) {
+                return Some(Err("the login was started elsewhere".to_owned()));
+        }
+
+        Some(Ok(code))
+}
+
+
+/// Waits on `listener` for the browser to come back with the authorization
+/// code for the login with `state`, for up to 5 minutes.
+pub fn wait_for_code(listener: TcpListener, state: &str) -> Result<String, String> {
+        listener.set_nonblocking(true).map_err(|e| e.to_string())?;
+        let deadline = Instant::now() + TIMEOUT;
+
+        while Instant::now() < deadline {
+                let mut stream = match listener.accept() {
+                        Ok((stream, _)) => stream,
+                        Err(_) => {
+                                thread::sleep(POLL_INTERVAL);
+                                continue;
+                        }
+                };
+
+                let _ = stream.set_nonblocking(false);
+
+                let result = match read_code(&mut stream, state) {
+                        Some(result) => result,
+                        // e.g. the favicon
+                        None => continue,
+                };
+
+                let page = if result.is_ok() { DONE_PAGE } else { FAILED_PAGE };
+                let response = format!("HTTP/1.1 200 OK\r\nContent-Type: text/html\r\n\
+                                        Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+                                       page.len(), page);
+                if let Err(err) = stream.write_all(response.as_bytes()) {
+                        warn!("failed to answer the browser: {}", err);
+                }
+
+                return result;
+        }
+
+        Err("timed out waiting for the browser".to_owned())
+}
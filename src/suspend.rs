@@ -0,0 +1,242 @@
+// SPDX-License-Identifier: MIT
+
+// Suspending to the shell (ctrl-z or SIGTSTP) and resuming, optionally
+// locking the vault on resume and after the system slept. Windows has no job
+// control, only the sleep detection works there. Also hands the terminal over
+// to other programs for a while.
+
+#[cfg(unix)]
+use std::fs::{File, OpenOptions};
+#[cfg(not(unix))]
+use std::io;
+use std::io::Write;
+#[cfg(unix)]
+use std::mem;
+#[cfg(unix)]
+use std::os::unix::io::AsRawFd;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+use cursive::CbSink;
+use cursive::Cursive;
+use log::info;
+#[cfg(unix)]
+use signal_hook::iterator::Signals;
+
+use crate::vault;
+
+
+// Leaves the alternate screen, turns off mouse reporting and shows the cursor
+const LEAVE_SCREEN: &str = "\x1b[?1000l\x1b[?1002l\x1b[?1015l\x1b[?1006l\x1b[?25h\x1b[?1049l";
+// Undoes the above, as set up by the termion backend
+#[cfg(unix)]
+const ENTER_SCREEN: &str = "\x1b[?1049h\x1b[?1000h\x1b[?1002h\x1b[?1015h\x1b[?1006h\x1b[?25l";
+const CLEAR_SCREEN: &str = "\x1b[2J\x1b[H";
+
+const SLEEP_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+// The wall clock running ahead of the monotonic clock by more than this
+// means the system was asleep, the monotonic clock stops meanwhile.
+const SLEEP_THRESHOLD: Duration = Duration::from_secs(30);
+
+// Whether the settings lock on resume, and whether --lock-on-resume does
+// regardless of them
+static LOCK_ON_RESUME: AtomicBool = AtomicBool::new(false);
+static ALWAYS_LOCK_ON_RESUME: AtomicBool = AtomicBool::new(false);
+
+
+/// Handles SIGTSTP and SIGCONT sent to bwtui, e.g. by `kill` or a terminal
+/// which doesn't pass ctrl-z through, and watches for system sleep.
+/// `lock_on_resume` locks on resume whatever the settings say.
+pub fn spawn(cb_sink: CbSink, lock_on_resume: bool) {
+        ALWAYS_LOCK_ON_RESUME.store(lock_on_resume, Ordering::SeqCst);
+
+        #[cfg(unix)]
+        if let Ok(signals) = Signals::new(&[libc::SIGTSTP, libc::SIGCONT]) {
+                let cb_sink = cb_sink.clone();
+
+                thread::spawn(move || {
+                        for signal in signals.forever() {
+                                let sent = if signal == libc::SIGTSTP {
+                                        cb_sink.send(Box::new(suspend))
+                                } else {
+                                        cb_sink.send(Box::new(resumed))
+                                };
+
+                                if sent.is_err() {
+                                        break;
+                                }
+                        }
+                });
+        }
+
+        thread::spawn(move || watch_sleep(cb_sink));
+}
+
+
+/// Locks the vault when resumed after a suspend or system sleep, as picked
+/// in the settings.
+pub fn set_lock_on_resume(lock: bool) {
+        LOCK_ON_RESUME.store(lock, Ordering::SeqCst);
+}
+
+
+fn lock_on_resume() -> bool {
+        LOCK_ON_RESUME.load(Ordering::SeqCst) || ALWAYS_LOCK_ON_RESUME.load(Ordering::SeqCst)
+}
+
+
+fn watch_sleep(cb_sink: CbSink) {
+        let mut instant = Instant::now();
+        let mut time = SystemTime::now();
+
+        loop {
+                thread::sleep(SLEEP_CHECK_INTERVAL);
+
+                let (now_instant, now_time) = (Instant::now(), SystemTime::now());
+                let monotonic = now_instant - instant;
+                let wall = now_time.duration_since(time).unwrap_or_default();
+
+                instant = now_instant;
+                time = now_time;
+
+                if wall > monotonic + SLEEP_THRESHOLD && lock_on_resume() {
+                        info!("system slept for about {}s, locking", (wall - monotonic).as_secs());
+
+                        if cb_sink.send(Box::new(vault::lock)).is_err() {
+                                break;
+                        }
+                }
+        }
+}
+
+
+/// Switches the terminal at `tty` back to its normal state, returning the
+/// settings of the TUI to restore them later.
+#[cfg(unix)]
+fn leave_tui(tty: &mut File) -> Option<libc::termios> {
+        let fd = tty.as_raw_fd();
+
+        let raw = unsafe {
+                let mut termios = mem::zeroed::<libc::termios>();
+                if libc::tcgetattr(fd, &mut termios) != 0 {
+                        return None;
+                }
+
+                termios
+        };
+
+        let mut cooked = raw;
+        cooked.c_lflag |= libc::ICANON | libc::ECHO | libc::ISIG | libc::IEXTEN;
+        cooked.c_iflag |= libc::ICRNL | libc::IXON;
+        cooked.c_oflag |= libc::OPOST;
+
+        let _ = tty.write_all(LEAVE_SCREEN.as_bytes());
+        let _ = tty.flush();
+
+        unsafe { libc::tcsetattr(fd, libc::TCSADRAIN, &cooked) };
+
+        Some(raw)
+}
+
+/// Undoes `leave_tui`, restoring the settings `raw` of the TUI.
+#[cfg(unix)]
+fn enter_tui(tty: &mut File, raw: &libc::termios) {
+        unsafe { libc::tcsetattr(tty.as_raw_fd(), libc::TCSADRAIN, raw) };
+
+        let _ = tty.write_all(ENTER_SCREEN.as_bytes());
+        let _ = tty.flush();
+}
+
+#[cfg(unix)]
+fn open_tty() -> Option<File> {
+        OpenOptions::new().read(true).write(true).open("/dev/tty").ok()
+}
+
+
+/// Clears the screen and puts the terminal back into its normal state for
+/// good, when bwtui can't do so itself anymore (e.g. after a panic).
+#[cfg(unix)]
+pub fn restore_terminal() {
+        if let Some(mut tty) = open_tty() {
+                let _ = tty.write_all(CLEAR_SCREEN.as_bytes());
+                leave_tui(&mut tty);
+        }
+}
+
+/// Like on Unix, without any tty to reset. Windows Terminal and ConPTY
+/// understand the escape sequences.
+#[cfg(not(unix))]
+pub fn restore_terminal() {
+        let mut stdout = io::stdout();
+        let _ = stdout.write_all(CLEAR_SCREEN.as_bytes());
+        let _ = stdout.write_all(LEAVE_SCREEN.as_bytes());
+        let _ = stdout.flush();
+}
+
+/// Puts the terminal back into its normal state and stops bwtui, like ctrl-z
+/// does for other programs. Once continued, the TUI is set up again.
+#[cfg(unix)]
+pub fn suspend(siv: &mut Cursive) {
+        let mut tty = match open_tty() {
+                Some(tty) => tty,
+                None => return,
+        };
+
+        let raw = match leave_tui(&mut tty) {
+                Some(raw) => raw,
+                None => return,
+        };
+
+        info!("suspending");
+
+        // SIGTSTP is handled by us, so stop with a signal which can't be.
+        // Execution continues here on SIGCONT.
+        unsafe { libc::kill(libc::getpid(), libc::SIGSTOP) };
+
+        enter_tui(&mut tty, &raw);
+
+        siv.clear();
+}
+
+/// Runs `f` with the terminal in its normal state, e.g. for a program
+/// prompting in it, and sets up the TUI again afterwards.
+#[cfg(unix)]
+pub fn outside_tui<T>(siv: &mut Cursive, f: impl FnOnce() -> T) -> T {
+        let mut tty = match open_tty() {
+                Some(tty) => tty,
+                None => return f(),
+        };
+
+        let raw = leave_tui(&mut tty);
+        let result = f();
+
+        if let Some(raw) = raw {
+                enter_tui(&mut tty, &raw);
+        }
+
+        siv.clear();
+
+        result
+}
+
+/// Like on Unix, programs run meanwhile have their own window on Windows.
+#[cfg(not(unix))]
+pub fn outside_tui<T>(siv: &mut Cursive, f: impl FnOnce() -> T) -> T {
+        let result = f();
+        siv.clear();
+
+        result
+}
+
+#[cfg(unix)]
+fn resumed(siv: &mut Cursive) {
+        info!("resumed");
+
+        // The terminal may have been used by something else meanwhile
+        siv.clear();
+
+        if lock_on_resume() {
+                vault::lock(siv);
+        }
+}
@@ -0,0 +1,280 @@
+// SPDX-License-Identifier: MIT
+
+use chrono::{DateTime, Duration, Utc};
+use hmac::{Hmac, Mac};
+use sha1::Sha1;
+use sha2::{Sha256, Sha512};
+
+
+// Codes are only accepted for a period or so, warn well before the skew
+// starts producing invalid ones.
+pub const MAX_CLOCK_SKEW: i64 = 15;
+
+const BASE32_ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Algorithm {
+        Sha1,
+        Sha256,
+        Sha512,
+}
+
+/// TOTP parameters of a login item, as defined by RFC 6238.
+#[derive(Clone, Debug)]
+pub struct Totp {
+        secret: Vec<u8>,
+        digits: u32,
+        period: i64,
+        algorithm: Algorithm,
+}
+
+impl Totp {
+        /// Parses either an `otpauth://totp/...` URI or a bare base32 secret.
+        pub fn parse(value: &str) -> Option<Totp> {
+                let value = value.trim();
+
+                let mut totp = Totp {
+                        secret: Vec::new(),
+                        digits: 6,
+                        period: 30,
+                        algorithm: Algorithm::Sha1,
+                };
+
+                if !value.starts_with("otpauth://") {
+                        totp.secret = base32_decode(value)?;
+                        return Some(totp);
+                }
+
+                let query = value.splitn(2, '?').nth(1)?;
+
+                for param in query.split('&') {
+                        let mut parts = param.splitn(2, '=');
+                        let key = parts.next()?;
+                        let value = parts.next().unwrap_or("");
+
+                        match key.to_ascii_lowercase().as_str() {
+                                "secret" => totp.secret = base32_decode(value)?,
+                                "digits" => totp.digits = value.parse().ok()?,
+                                "period" => totp.period = value.parse().ok()?,
+                                "algorithm" => totp.algorithm = match value.to_ascii_uppercase().as_str() {
+                                        "SHA1" => Algorithm::Sha1,
+                                        "SHA256" => Algorithm::Sha256,
+                                        "SHA512" => Algorithm::Sha512,
+                                        _ => return None,
+                                },
+                                _ => (),
+                        }
+                }
+
+                if totp.secret.is_empty() || totp.period <= 0 || totp.digits == 0 || totp.digits > 9 {
+                        return None;
+                }
+
+                Some(totp)
+        }
+
+        /// Generates the code valid at `now` on a clock that is `skew` behind
+        /// the server.
+        pub fn generate(&self, now: DateTime<Utc>, skew: Duration) -> String {
+                let counter = ((now + skew).timestamp() / self.period) as u64;
+                let message = counter.to_be_bytes();
+
+                let hash = match self.algorithm {
+                        Algorithm::Sha1 => hmac::<Hmac<Sha1>>(&self.secret, &message),
+                        Algorithm::Sha256 => hmac::<Hmac<Sha256>>(&self.secret, &message),
+                        Algorithm::Sha512 => hmac::<Hmac<Sha512>>(&self.secret, &message),
+                };
+
+                // Dynamic truncation
+                let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+                let code = u32::from_be_bytes([
+                        hash[offset] & 0x7f,
+                        hash[offset + 1],
+                        hash[offset + 2],
+                        hash[offset + 3],
+                ]);
+
+                format!("{:0width$}", code % 10u32.pow(self.digits), width = self.digits as usize)
+        }
+
+        /// Seconds until the code generated for `now` expires.
+        pub fn remaining(&self, now: DateTime<Utc>, skew: Duration) -> i64 {
+                self.period - (now + skew).timestamp().rem_euclid(self.period)
+        }
+}
+
+
+fn hmac<M>(key: &[u8], message: &[u8]) -> Vec<u8>
+        where M: Mac
+{
+        let mut mac = M::new_varkey(key).unwrap();
+        mac.input(message);
+
+        mac.result().code().to_vec()
+}
+
+fn base32_decode(value: &str) -> Option<Vec<u8>> {
+        let mut bytes = Vec::with_capacity(value.len() * 5 / 8);
+        let mut buffer = 0u32;
+        let mut bits = 0;
+
+        // Secrets are often shown in groups and in lowercase, be lenient
+        for c in value.bytes().filter(|c| *c != b' ' && *c != b'-' && *c != b'=') {
+                let index = BASE32_ALPHABET.iter()
+                        .position(|a| *a == c.to_ascii_uppercase())?;
+
+                buffer = (buffer << 5) | index as u32;
+                bits += 5;
+
+                if bits >= 8 {
+                        bits -= 8;
+                        bytes.push((buffer >> bits) as u8);
+                        buffer &= (1 << bits) - 1;
+                }
+        }
+
+        if bytes.is_empty() {
+                None
+        } else {
+                Some(bytes)
+        }
+}
+
+
+#[cfg(test)]
+mod tests {
+        use chrono::TimeZone;
+
+        use super::*;
+
+        // Secrets of RFC 6238 appendix B, base32 encoded
+        const SHA1_SECRET: &str = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+        const SHA256_SECRET: &str = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZA";
+        const SHA512_SECRET: &str = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ\
+                                     GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZDGNA";
+
+        // Time, then the SHA1, SHA256 and SHA512 codes
+        const RFC_6238: &[(i64, &str, &str, &str)] = &[
+                (59, "94287082", "46119246", "90693936"),
+                (1111111109, "07081804", "68084774", "25091201"),
+                (1111111111, "14050471", "67062674", "99943326"),
+                (1234567890, "89005924", "91819424", "93441116"),
+                (2000000000, "69279037", "90698825", "38618901"),
+                (20000000000, "65353130", "77737706", "47863826"),
+        ];
+
+        fn uri(secret: &str, algorithm: &str) -> String {
+                format!("otpauth://totp/Example:alice@example.com?secret={}&algorithm={}&digits=8",
+                        secret, algorithm)
+        }
+
+        fn code(totp: &Totp, time: i64) -> String {
+                totp.generate(Utc.timestamp(time, 0), Duration::zero())
+        }
+
+        #[test]
+        fn rfc_6238_sha1() {
+                let totp = Totp::parse(&uri(SHA1_SECRET, "SHA1")).unwrap();
+
+                for (time, expected, _, _) in RFC_6238 {
+                        assert_eq!(code(&totp, *time), *expected, "at {}", time);
+                }
+        }
+
+        #[test]
+        fn rfc_6238_sha256() {
+                let totp = Totp::parse(&uri(SHA256_SECRET, "SHA256")).unwrap();
+
+                for (time, _, expected, _) in RFC_6238 {
+                        assert_eq!(code(&totp, *time), *expected, "at {}", time);
+                }
+        }
+
+        #[test]
+        fn rfc_6238_sha512() {
+                let totp = Totp::parse(&uri(SHA512_SECRET, "SHA512")).unwrap();
+
+                for (time, _, _, expected) in RFC_6238 {
+                        assert_eq!(code(&totp, *time), *expected, "at {}", time);
+                }
+        }
+
+        #[test]
+        fn bare_secret_defaults() {
+                let totp = Totp::parse(SHA1_SECRET).unwrap();
+
+                assert_eq!(totp.digits, 6);
+                assert_eq!(totp.period, 30);
+                assert_eq!(totp.algorithm, Algorithm::Sha1);
+                assert_eq!(code(&totp, 59), "287082");
+        }
+
+        #[test]
+        fn lenient_secret() {
+                let grouped = Totp::parse(" gezd gnbv gy3t qojq-gezd gnbv gy3t qojq== ").unwrap();
+                let plain = Totp::parse(SHA1_SECRET).unwrap();
+
+                assert_eq!(grouped.secret, plain.secret);
+                assert_eq!(grouped.secret, b"12345678901234567890");
+        }
+
+        #[test]
+        fn skew_and_remaining() {
+                let totp = Totp::parse(SHA1_SECRET).unwrap();
+                let now = Utc.timestamp(50, 0);
+
+                // 9 seconds ahead is the period of time 59
+                assert_eq!(totp.generate(now, Duration::seconds(9)), code(&totp, 59));
+                assert_eq!(totp.remaining(now, Duration::zero()), 10);
+                assert_eq!(totp.remaining(now, Duration::seconds(10)), 30);
+        }
+
+        #[test]
+        fn uri_parameters() {
+                let totp = Totp::parse(&format!(
+                        "otpauth://totp/Example?Secret={}&ALGORITHM=sha256&digits=7&period=60",
+                        SHA256_SECRET)).unwrap();
+
+                assert_eq!(totp.algorithm, Algorithm::Sha256);
+                assert_eq!(totp.digits, 7);
+                assert_eq!(totp.period, 60);
+        }
+
+        #[test]
+        fn uri_defaults() {
+                let totp = Totp::parse(&format!("otpauth://totp/Example?secret={}", SHA1_SECRET)).unwrap();
+
+                assert_eq!(totp.digits, 6);
+                assert_eq!(totp.period, 30);
+                assert_eq!(totp.algorithm, Algorithm::Sha1);
+        }
+
+        #[test]
+        fn invalid_uris() {
+                let invalid = [
+                        // No secret at all, or an empty one
+                        "otpauth://totp/Example",
+                        "otpauth://totp/Example?issuer=Example",
+                        "otpauth://totp/Example?secret=",
+                        // Not base32
+                        "otpauth://totp/Example?secret=GEZD1890",
+                        "otpauth://totp/Example?secret=GEZDGNBV&algorithm=MD5",
+                        "otpauth://totp/Example?secret=GEZDGNBV&digits=0",
+                        "otpauth://totp/Example?secret=GEZDGNBV&digits=10",
+                        "otpauth://totp/Example?secret=GEZDGNBV&digits=six",
+                        "otpauth://totp/Example?secret=GEZDGNBV&period=0",
+                        "otpauth://totp/Example?secret=GEZDGNBV&period=-30",
+                ];
+
+                for uri in &invalid {
+                        assert!(Totp::parse(uri).is_none(), "{}", uri);
+                }
+        }
+
+        #[test]
+        fn invalid_secrets() {
+                assert!(Totp::parse("").is_none());
+                assert!(Totp::parse("not base32!").is_none());
+        }
+}
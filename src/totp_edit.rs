@@ -0,0 +1,134 @@
+// SPDX-License-Identifier: MIT
+
+use chrono::{Duration, Utc};
+use cursive::traits::*;
+use cursive::views::{Dialog, EditView, LinearLayout, TextView};
+use cursive::Cursive;
+use uuid::Uuid;
+
+use crate::cipher::CipherString;
+use crate::conflict;
+use crate::totp::Totp;
+use crate::vault::{self, Session};
+
+/// Code the entered secret generates right now, so it can be compared with
+/// the site or another authenticator before saving.
+fn preview(secret: &str, skew: Duration) -> String {
+    if secret.trim().is_empty() {
+        return "Enter a base32 secret or an otpauth:// URI".to_owned();
+    }
+
+    match Totp::parse(secret) {
+        Some(totp) => {
+            let now = Utc::now();
+            format!(
+                "Current code: {} ({}s left)",
+                totp.generate(now, skew),
+                totp.remaining(now, skew)
+            )
+        }
+        None => "Not a valid base32 secret or otpauth:// URI".to_owned(),
+    }
+}
+
+fn skew(siv: &mut Cursive) -> Duration {
+    siv.user_data::<Session>()
+        .and_then(|session| session.vault.clock_skew)
+        .unwrap_or_else(Duration::zero)
+}
+
+/// Lets the user set or replace the TOTP secret of a personal login.
+pub fn show(siv: &mut Cursive, uuid: Uuid) {
+    let has_secret = match siv.user_data::<Session>() {
+        Some(session) => session
+            .vault
+            .ciphers
+            .iter()
+            .find(|entry| entry.uuid == uuid)
+            .map(|entry| entry.data.totp.is_some()),
+        None => return,
+    };
+
+    let has_secret = match has_secret {
+        Some(has_secret) => has_secret,
+        None => return,
+    };
+
+    let skew = skew(siv);
+
+    let secret = EditView::new()
+        .secret()
+        .on_edit(move |siv, content, _| {
+            let line = preview(content, skew);
+            siv.call_on_name("totp_preview", |view: &mut TextView| {
+                view.set_content(line);
+            });
+        })
+        .with_name("totp_secret");
+
+    let hint = if has_secret {
+        "New secret, replacing the current one:"
+    } else {
+        "Secret:"
+    };
+
+    let layout = LinearLayout::vertical()
+        .child(TextView::new(hint))
+        .child(secret)
+        .child(TextView::new(preview("", skew)).with_name("totp_preview"));
+
+    let mut dialog = Dialog::around(layout)
+        .title("set TOTP")
+        .button("Save", move |siv| {
+            let secret = siv
+                .call_on_name("totp_secret", |view: &mut EditView| view.get_content())
+                .unwrap();
+
+            if Totp::parse(&secret).is_none() {
+                siv.add_layer(Dialog::info("Not a valid base32 secret or otpauth:// URI"));
+                return;
+            }
+
+            save(siv, uuid, Some(secret.trim()));
+        });
+
+    if has_secret {
+        dialog.add_button("Remove", move |siv| save(siv, uuid, None));
+    }
+
+    siv.add_layer(dialog.dismiss_button("Cancel").min_width(50));
+}
+
+fn save(siv: &mut Cursive, uuid: Uuid, secret: Option<&str>) {
+    let updated = match siv.user_data::<Session>() {
+        Some(session) => session
+            .vault
+            .ciphers
+            .iter()
+            .find(|entry| entry.uuid == uuid)
+            .and_then(|entry| {
+                let mut entry = entry.clone();
+                entry.data.totp = match secret {
+                    Some(secret) => Some(CipherString::encrypt(secret, &session.cipher)?),
+                    None => None,
+                };
+
+                Some(entry)
+            }),
+        None => return,
+    };
+
+    let updated = match updated {
+        Some(updated) => updated,
+        None => return,
+    };
+
+    // Both the editor and the detail view, which is outdated now
+    siv.pop_layer();
+    siv.pop_layer();
+
+    conflict::save(siv, updated, |siv| {
+        siv.add_layer(Dialog::info("TOTP saved"));
+        vault::sync_in_background(siv);
+    });
+}
@@ -0,0 +1,144 @@
+// SPDX-License-Identifier: MIT
+
+// Matching of item URIs against a page, following the match types of the
+// official clients.
+
+use std::net::IpAddr;
+
+use crate::api::{
+        CipherEntry, Domains, URI_MATCH_DOMAIN, URI_MATCH_EXACT, URI_MATCH_HOST,
+        URI_MATCH_NEVER, URI_MATCH_REGEX, URI_MATCH_STARTS_WITH,
+};
+use crate::cipher::CipherSuite;
+
+
+/// Host part of a URI, without port and a leading "www.".
+pub fn host(uri: &str) -> &str {
+        let rest = match uri.find("://") {
+                Some(index) => &uri[index + 3..],
+                None => uri,
+        };
+
+        let end = rest.find(|c| c == '/' || c == ':' || c == '?' || c == '#');
+        let host = &rest[..end.unwrap_or(rest.len())];
+
+        host.trim_start_matches("www.")
+}
+
+/// Registrable part of a host. Without a public suffix list this is a guess:
+/// the last two labels, or three for suffixes like "co.uk".
+fn base_domain(host: &str) -> &str {
+        if host.parse::<IpAddr>().is_ok() {
+                return host;
+        }
+
+        let labels = host.split('.').collect::<Vec<&str>>();
+        if labels.len() <= 2 {
+                return host;
+        }
+
+        let tld = labels[labels.len() - 1];
+        let second = labels[labels.len() - 2];
+        let count = if tld.len() == 2 && second.len() <= 3 { 3 } else { 2 };
+
+        let skip = labels[..labels.len() - count]
+                .iter()
+                .map(|label| label.len() + 1)
+                .sum::<usize>();
+
+        &host[skip..]
+}
+
+fn looks_like_domain(text: &str) -> bool {
+        let tld = match text.rsplit('.').next() {
+                Some(tld) => tld,
+                None => return false,
+        };
+
+        text.contains('.')
+                && !text.starts_with('.')
+                && tld.len() >= 2
+                && tld.chars().all(|c| c.is_ascii_alphabetic())
+                && text.chars().all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-')
+}
+
+
+/// Page to find items for, known by its URL or only its domain.
+#[derive(Clone, Debug)]
+pub struct Page {
+        url: String,
+        host: String,
+}
+
+impl Page {
+        pub fn parse(text: &str) -> Option<Page> {
+                let text = text.trim().to_lowercase();
+
+                let url = if text.contains("://") {
+                        text
+                } else if looks_like_domain(&text) {
+                        format!("https://{}/", text)
+                } else {
+                        return None;
+                };
+
+                let host = host(&url).to_owned();
+                if host.is_empty() {
+                        return None;
+                }
+
+                Some(Page { url, host })
+        }
+
+        /// Finds the first URL or domain in a window title, browsers often
+        /// only show the page title though.
+        pub fn from_title(title: &str) -> Option<Page> {
+                title.split_whitespace()
+                        .map(|word| word.trim_matches(|c: char| "()[]<>\"',;:|".contains(c)))
+                        .filter_map(Page::parse)
+                        .next()
+        }
+
+        pub fn host(&self) -> &str {
+                &self.host
+        }
+
+        /// Whether an item URI applies to this page. Items without an explicit
+        /// match type are matched by domain.
+        pub fn matches(&self, uri: &str, match_: Option<usize>, domains: Option<&Domains>) -> bool {
+                let uri = uri.trim().to_lowercase();
+
+                match match_.unwrap_or(URI_MATCH_DOMAIN) {
+                        URI_MATCH_HOST => host(&uri) == self.host,
+                        URI_MATCH_STARTS_WITH => self.url.starts_with(&uri),
+                        URI_MATCH_EXACT => self.url == uri,
+                        // No regex support, better not to guess
+                        URI_MATCH_REGEX | URI_MATCH_NEVER => false,
+                        _ => {
+                                let base = base_domain(host(&uri));
+                                let page_base = base_domain(&self.host);
+
+                                base == page_base || domains.map_or(false, |domains| {
+                                        domains.equivalent_to(page_base)
+                                                .iter()
+                                                .any(|domain| base_domain(domain) == base)
+                                })
+                        }
+                }
+        }
+
+        /// Whether any URI of `entry` applies to this page.
+        pub fn matches_entry(&self, entry: &CipherEntry, cipher: &CipherSuite,
+                             domains: Option<&Domains>) -> bool
+        {
+                let uris = entry.data.uris.iter()
+                        .flatten()
+                        .filter_map(|uri| uri.uri.as_ref().map(|value| (value, uri.match_)));
+
+                entry.data.uri.iter()
+                        .map(|value| (value, None))
+                        .chain(uris)
+                        .filter_map(|(value, match_)| value.decrypt(cipher).map(|value| (value, match_)))
+                        .any(|(value, match_)| self.matches(&value, match_, domains))
+        }
+}
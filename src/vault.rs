@@ -1,63 +1,290 @@
 // SPDX-License-Identifier: MIT
 
+use std::cell::RefCell;
 use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::mem;
+use std::rc::Rc;
+use std::sync::atomic::{self, AtomicUsize};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
 
-use clipboard::ClipboardContext;
-use clipboard::ClipboardProvider;
-
-use fuzzy_matcher::{skim::SkimMatcherV2, FuzzyMatcher};
-
+use chrono::{Local, Utc};
 use cursive::event::{Event, Key};
 use cursive::traits::*;
-use cursive::views::{Dialog, DummyView, EditView, LinearLayout, OnEventView, TextView};
+use cursive::views::{
+    Dialog, DummyView, EditView, LinearLayout, OnEventView, SelectView, TextView,
+};
 use cursive::Cursive;
 use cursive_table_view::{TableView, TableViewItem};
+use log::{info, warn};
 
 use unicase::UniCase;
+use uuid::Uuid;
+
+use crate::account;
+use crate::activity;
+use crate::api::{self, Api, ApiError, AuthData, CipherEntry, VaultData};
+use crate::audit;
+use crate::autotype;
+use crate::cipher::{CipherString, CipherSuite};
+use crate::clip;
+use crate::collections;
+use crate::detail;
+use crate::domains;
+use crate::emergency;
+use crate::events;
+use crate::export;
+use crate::field_cache::{Field, FieldCache};
+use crate::hooks::{self, Hook};
+use crate::import;
+use crate::journal;
+use crate::login;
+use crate::note;
+#[cfg(feature = "qr")]
+use crate::qr;
+use crate::rotation;
+#[cfg(feature = "scripts")]
+use crate::scripts;
+use crate::search::SearchIndex;
+use crate::sends;
+use crate::setup;
+use crate::share;
+use crate::totp::Totp;
+use crate::uri::Page;
+use crate::verify;
+use crate::worker;
+
+const SEARCH_DEBOUNCE: Duration = Duration::from_millis(150);
+const TOKEN_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+const OFFLINE_RETRY_INTERVAL: Duration = Duration::from_secs(30);
+// Time to switch to the target window before auto-type starts
+const AUTOTYPE_DELAY: Duration = Duration::from_secs(3);
+
+// Bumped when the vault is shown again or closed, so that only the token
+// refresh thread of the current session keeps running
+static TOKEN_REFRESH: AtomicUsize = AtomicUsize::new(0);
+
+/// State of an unlocked vault, kept as cursive user data.
+///
+/// The cipher suite is moved out of `auth` on unlock, so it can be shared with
+/// the decryption cache used by the table entries.
+pub struct Session {
+    pub auth: AuthData,
+    pub vault: VaultData,
+    pub cipher: Arc<CipherSuite>,
+    cache: Rc<RefCell<FieldCache>>,
+    index: SearchIndex,
+    items: Vec<VaultEntry>,
+    // Bumped on every edit of the search field, pending searches for older
+    // generations are dropped.
+    search_generation: usize,
+    last_search: Option<(String, Vec<usize>)>,
+    filter: Filter,
+    // Marked items, which are exported instead of all shown ones
+    marked: HashSet<Uuid>,
+}
+
+impl Session {
+    fn with_marks(&self, mut items: Vec<VaultEntry>) -> Vec<VaultEntry> {
+        for entry in &mut items {
+            entry.marked = self.marked.contains(&entry.uuid);
+        }
+
+        items
+    }
+}
+
+/// What to show right after unlocking, as given on the command line.
+#[derive(Clone, Debug, Default)]
+pub struct StartupView {
+    pub favorites: bool,
+    pub folder: Option<String>,
+    pub search: Option<String>,
+    // Title of the window to pick an item for
+    pub window_title: Option<String>,
+}
+
+impl StartupView {
+    fn is_empty(&self) -> bool {
+        !self.favorites
+            && self.folder.is_none()
+            && self.search.is_none()
+            && self.window_title.is_none()
+    }
+}
+
+/// Restricts the table to a part of the vault, on top of any search.
+#[derive(Default)]
+struct Filter {
+    favorites: bool,
+    // Requested name and all folders with it
+    folders: Option<(String, Vec<Uuid>)>,
+    // Picked window and the items with URIs matching it
+    matching: Option<(Page, Vec<Uuid>)>,
+    // Only archived items are shown when set, none otherwise
+    archived: bool,
+}
+
+impl Filter {
+    fn matches(&self, entry: &VaultEntry) -> bool {
+        let in_folder = match &self.folders {
+            Some((_, folders)) => entry.folder.map_or(false, |id| folders.contains(&id)),
+            None => true,
+        };
+
+        let matching = match &self.matching {
+            Some((_, matching)) => matching.contains(&entry.uuid),
+            None => true,
+        };
+
+        !entry.deleted
+            && entry.archived == self.archived
+            && in_folder
+            && matching
+            && (!self.favorites || entry.is_favorite)
+    }
+
+    fn apply(&self, items: &[VaultEntry]) -> Vec<VaultEntry> {
+        items
+            .iter()
+            .filter(|entry| self.matches(entry))
+            .cloned()
+            .collect()
+    }
+
+    fn describe(&self) -> String {
+        let mut parts = Vec::new();
+
+        if self.archived {
+            parts.push("archived".to_owned());
+        }
 
-use crate::api::{AuthData, CipherEntry, VaultData};
-use crate::cipher::CipherSuite;
+        if self.favorites {
+            parts.push("favorites".to_owned());
+        }
+
+        if let Some((folder, _)) = &self.folders {
+            parts.push(format!("folder {}", folder));
+        }
+
+        if let Some((page, _)) = &self.matching {
+            parts.push(format!("matching {}", page.host()));
+        }
+
+        parts.join(", ")
+    }
+
+    fn title(&self) -> String {
+        match self.describe() {
+            description if description.is_empty() => "bitwarden vault".to_owned(),
+            description => format!("bitwarden vault ({})", description),
+        }
+    }
+}
 
 #[derive(Copy, Clone, PartialEq, Eq, Hash)]
 enum VaultColumn {
+    Marked,
     Favorite,
     Name,
     Username,
 }
 
-#[derive(Clone, Debug)]
+impl VaultColumn {
+    const ALL: [VaultColumn; 4] = [
+        VaultColumn::Marked,
+        VaultColumn::Favorite,
+        VaultColumn::Name,
+        VaultColumn::Username,
+    ];
+
+    // Stored in the view state
+    fn name(self) -> &'static str {
+        match self {
+            VaultColumn::Marked => "marked",
+            VaultColumn::Favorite => "favorite",
+            VaultColumn::Name => "name",
+            VaultColumn::Username => "username",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .cloned()
+            .find(|column| column.name() == name)
+    }
+}
+
+#[derive(Clone)]
 struct VaultEntry {
-    name: UniCase<String>,
-    username: UniCase<String>,
-    password: String,
+    uuid: Uuid,
+    cache: Rc<RefCell<FieldCache>>,
+    name: CipherString,
+    username: Option<CipherString>,
+    password: Option<CipherString>,
     favorite: String,
+    is_favorite: bool,
+    folder: Option<Uuid>,
+    deleted: bool,
+    archived: bool,
+    marked: bool,
 }
 
 type VaultTableView = TableView<VaultEntry, VaultColumn>;
 
 impl VaultEntry {
-    fn from_cipher_entry(entry: &CipherEntry, cipher: &CipherSuite) -> Option<VaultEntry> {
+    fn from_cipher_entry(entry: &CipherEntry, cache: &Rc<RefCell<FieldCache>>) -> VaultEntry {
         let favorite = if entry.favorite {
             "\u{2605}"
         } else {
             "\u{2606}"
         };
 
-        Some(Self {
-            name: UniCase::new(entry.name.decrypt(cipher)?),
-            username: UniCase::new(entry.data.username.decrypt(cipher)?),
-            password: entry.data.password.decrypt(cipher)?,
+        Self {
+            uuid: entry.uuid,
+            cache: Rc::clone(cache),
+            name: entry.name.clone(),
+            username: entry.data.username.clone(),
+            password: entry.data.password.clone(),
             favorite: favorite.to_owned(),
-        })
+            is_favorite: entry.favorite,
+            folder: entry.folder_id,
+            deleted: entry.deleted_date.is_some(),
+            archived: entry.archived_date.is_some(),
+            marked: false,
+        }
+    }
+
+    fn field(&self, field: Field, value: Option<&CipherString>) -> Rc<String> {
+        match value {
+            Some(value) => self.cache.borrow_mut().get(self.uuid, field, value),
+            None => Rc::default(),
+        }
+    }
+
+    fn name(&self) -> Rc<String> {
+        self.field(Field::Name, Some(&self.name))
+    }
+
+    fn username(&self) -> Rc<String> {
+        self.field(Field::Username, self.username.as_ref())
+    }
+
+    fn password(&self) -> Rc<String> {
+        self.field(Field::Password, self.password.as_ref())
     }
 }
 
 impl TableViewItem<VaultColumn> for VaultEntry {
     fn to_column(&self, column: VaultColumn) -> String {
         match column {
+            VaultColumn::Marked => if self.marked { "+" } else { "" }.to_owned(),
             VaultColumn::Favorite => self.favorite.clone(),
-            VaultColumn::Name => self.name.to_string(),
-            VaultColumn::Username => self.username.to_string(),
+            VaultColumn::Name => self.name().to_string(),
+            VaultColumn::Username => self.username().to_string(),
         }
     }
 
@@ -66,28 +293,167 @@ impl TableViewItem<VaultColumn> for VaultEntry {
         Self: Sized,
     {
         match column {
+            VaultColumn::Marked => self.marked.cmp(&other.marked),
             VaultColumn::Favorite => self.favorite.cmp(&other.favorite),
-            VaultColumn::Name => self.name.cmp(&other.name),
-            VaultColumn::Username => self.username.cmp(&other.username),
+            VaultColumn::Name => {
+                UniCase::new(self.name().as_str()).cmp(&UniCase::new(other.name().as_str()))
+            }
+            VaultColumn::Username => UniCase::new(self.username().as_str())
+                .cmp(&UniCase::new(other.username().as_str())),
         }
     }
 }
 
-pub fn show(siv: &mut Cursive, auth_data: AuthData, vault_data: VaultData) {
-    let items = vault_data
+fn build_items(vault_data: &VaultData, cache: &Rc<RefCell<FieldCache>>) -> Vec<VaultEntry> {
+    cache.borrow_mut().fit(vault_data.ciphers.len());
+
+    vault_data
         .ciphers
         .iter()
-        .map(|c| VaultEntry::from_cipher_entry(&c, &auth_data.cipher).unwrap())
-        .collect::<Vec<VaultEntry>>();
+        .map(|c| VaultEntry::from_cipher_entry(&c, cache))
+        .collect()
+}
+
+/// Folders of a restored view which still exist, along with the name of one.
+fn restore_folders(
+    vault_data: &VaultData,
+    cipher: &CipherSuite,
+    uuids: &[Uuid],
+) -> Option<(String, Vec<Uuid>)> {
+    let folders = vault_data
+        .folders
+        .iter()
+        .filter(|folder| uuids.contains(&folder.uuid))
+        .collect::<Vec<_>>();
+
+    let name = folders.first()?.name.decrypt(cipher)?;
+    Some((name, folders.iter().map(|folder| folder.uuid).collect()))
+}
+
+/// Looks up all folders called `name`, ignoring case.
+fn find_folders(vault_data: &VaultData, cipher: &CipherSuite, name: &str) -> Vec<Uuid> {
+    let name = UniCase::new(name);
+
+    vault_data
+        .folders
+        .iter()
+        .filter(|folder| {
+            folder
+                .name
+                .decrypt(cipher)
+                .map_or(false, |folder| UniCase::new(folder.as_str()) == name)
+        })
+        .map(|folder| folder.uuid)
+        .collect()
+}
+
+pub fn show(siv: &mut Cursive, mut auth_data: AuthData, vault_data: VaultData, view: StartupView) {
+    let cipher = Arc::new(mem::take(&mut auth_data.cipher));
+    let cache = Rc::new(RefCell::new(FieldCache::new(Arc::clone(&cipher))));
+    let items = build_items(&vault_data, &cache);
+    let index = SearchIndex::build(&vault_data, &cipher);
+
+    // Filters which can't be applied are dropped, rather than showing nothing
+    let mut notices = Vec::new();
+
+    // Without command line flags, the view is reopened the way it was left
+    let restore = view.is_empty();
+    let state = api::read_view_state().unwrap_or_default();
+
+    let folders = match &view.folder {
+        Some(name) => {
+            let folders = find_folders(&vault_data, &cipher, name);
+            if folders.is_empty() {
+                notices.push(format!("No folder named \"{}\", showing all folders", name));
+                None
+            } else {
+                Some((name.clone(), folders))
+            }
+        }
+        None if restore => restore_folders(&vault_data, &cipher, &state.folders),
+        None => None,
+    };
+
+    let page = view.window_title.as_ref().and_then(|title| {
+        let page = Page::from_title(title);
+        if page.is_none() {
+            notices.push(format!("No domain in window title \"{}\"", title));
+        }
+
+        page
+    });
+
+    let matching = page.and_then(|page| {
+        let matching = vault_data
+            .ciphers
+            .iter()
+            .filter(|entry| page.matches_entry(entry, &cipher, vault_data.domains.as_ref()))
+            .map(|entry| entry.uuid)
+            .collect::<Vec<Uuid>>();
+
+        if matching.is_empty() {
+            notices.push(format!("No items match {}, showing all items", page.host()));
+            return None;
+        }
+
+        Some((page, matching))
+    });
+
+    let filter = Filter {
+        favorites: view.favorites || (restore && state.favorites),
+        folders,
+        matching,
+        archived: restore && state.archived,
+    };
+
+    let restored_search = Some(state.search).filter(|search| restore && !search.is_empty());
+    let search = view.search.or(restored_search);
+
+    let title = filter.title();
 
     let mut table = VaultTableView::new()
+        .column(VaultColumn::Marked, "", |c| c.width(1))
         .column(VaultColumn::Favorite, "", |c| c.width(1))
         .column(VaultColumn::Name, "Name", |c| c.width_percent(25))
         .column(VaultColumn::Username, "Username", |c| c)
-        .items(items.clone());
+        .items(filter.apply(&items))
+        .on_submit(|siv, _row, index| {
+            let uuid = siv
+                .call_on_name("password_table", |view: &mut VaultTableView| {
+                    view.borrow_item(index).map(|entry| entry.uuid)
+                })
+                .unwrap();
+
+            if let Some(uuid) = uuid {
+                detail::show(siv, uuid);
+            }
+        });
+
+    siv.set_user_data(Session {
+        auth: auth_data,
+        vault: vault_data,
+        cipher,
+        cache,
+        index,
+        items,
+        search_generation: 0,
+        last_search: None,
+        filter,
+        marked: HashSet::new(),
+    });
+
+    let sort = state.sort.as_ref().and_then(|(name, ascending)| {
+        VaultColumn::from_name(name).map(|column| (column, *ascending))
+    });
 
-    table.sort_by(VaultColumn::Name, Ordering::Less);
-    table.sort_by(VaultColumn::Favorite, Ordering::Less);
+    match sort {
+        Some((column, true)) => table.sort_by(column, Ordering::Less),
+        Some((column, false)) => table.sort_by(column, Ordering::Greater),
+        None => {
+            table.sort_by(VaultColumn::Name, Ordering::Less);
+            table.sort_by(VaultColumn::Favorite, Ordering::Less);
+        }
+    }
 
     let table_view = OnEventView::new(table.with_name("password_table").full_screen())
         .on_event('j', |siv| {
@@ -122,37 +488,60 @@ pub fn show(siv: &mut Cursive, auth_data: AuthData, vault_data: VaultData) {
             })
             .unwrap();
         })
+        .on_event('m', toggle_mark)
+        .on_event('n', note::show)
+        .on_event('l', lock)
+        .on_event('L', account::logout)
+        .on_event('P', account::set_pin)
+        .on_event('A', account::show)
+        .on_event('M', account::change_password)
+        .on_event('T', setup::lock_settings)
+        .on_event('r', refresh_item)
         .on_event(Event::CtrlChar('u'), |siv| {
-            siv.call_on_name("password_table", |view: &mut VaultTableView| {
-                if let Some(row) = view.item() {
-                    if let Some(entry) = view.borrow_item(row) {
-                        let mut clipboard: ClipboardContext = ClipboardProvider::new().unwrap();
-
-                        clipboard.set_contents(entry.username.to_string()).unwrap();
-                    }
-                }
-            })
-            .unwrap();
+            if copy_field(siv, VaultEntry::username) {
+                copy_hook("username", selected_item(siv));
+            }
         })
         .on_event(Event::CtrlChar('p'), |siv| {
-            siv.call_on_name("password_table", |view: &mut VaultTableView| {
-                if let Some(row) = view.item() {
-                    if let Some(entry) = view.borrow_item(row) {
-                        let mut clipboard: ClipboardContext = ClipboardProvider::new().unwrap();
-
-                        clipboard.set_contents(entry.password.clone()).unwrap();
-                    }
-                }
-            })
-            .unwrap();
+            if copy_field(siv, VaultEntry::password) {
+                let uuid = selected_item(siv);
+                activity::record(siv, "copied password", uuid);
+                copy_hook("password", uuid);
+            }
         })
+        .on_event(Event::CtrlChar('t'), copy_totp)
+        .on_event(Event::CtrlChar('a'), auto_type)
+        .on_event(Event::CtrlChar('k'), copy_custom_field)
+        .on_event(Event::CtrlChar('e'), events::show)
+        .on_event(Event::CtrlChar('o'), collections::show)
+        .on_event(Event::CtrlChar('g'), emergency::show)
+        .on_event(Event::CtrlChar('d'), account::deauthorize)
+        .on_event(Event::CtrlChar('v'), account::devices)
+        .on_event(Event::CtrlChar('n'), domains::show)
+        .on_event(Event::CtrlChar('s'), sends::show)
+        .on_event(Event::CtrlChar('x'), share::show)
+        .on_event(Event::CtrlChar('r'), toggle_archived)
+        .on_event(Event::CtrlChar('w'), toggle_archived_view)
+        .on_event(Event::CtrlChar('l'), audit::show)
+        .on_event(Event::CtrlChar('b'), export::show)
+        .on_event(Event::CtrlChar('y'), import::show)
         .on_event(Event::CtrlChar('f'), |siv| {
             siv.focus_name("search_field").unwrap();
         });
 
+    #[cfg(feature = "qr")]
+    let table_view = table_view.on_event('q', qr::show);
+
+    #[cfg(feature = "scripts")]
+    let mut table_view = table_view.on_event(':', scripts::show);
+    #[cfg(feature = "scripts")]
+    let script_error = scripts::bind(&mut table_view);
+    #[cfg(not(feature = "scripts"))]
+    let script_error: Option<String> = None;
+
     let search_field = EditView::new()
-        .on_edit(move |siv, content, _| {
-            fuzzy_match_on_edit(siv, &items, content);
+        .on_edit(|siv, content, _| {
+            schedule_search(siv, content);
         })
         .with_name("search_field")
         .full_width();
@@ -168,6 +557,10 @@ pub fn show(siv: &mut Cursive, auth_data: AuthData, vault_data: VaultData) {
                     siv.focus_name("password_table").unwrap();
                 })
                 .on_event(Key::Enter, |siv| {
+                    // Don't wait for the debounce when the user is done typing
+                    let content = search_content(siv);
+                    fuzzy_match_on_edit(siv, &content);
+
                     siv.focus_name("password_table").unwrap();
                 })
                 .on_event(Event::CtrlChar('u'), |siv| {
@@ -183,46 +576,876 @@ pub fn show(siv: &mut Cursive, auth_data: AuthData, vault_data: VaultData) {
         .child(table_view);
 
     let layout = LinearLayout::vertical()
+        .child(TextView::new("").with_name("offline_banner"))
         .child(
             Dialog::around(main_view)
-                .title("bitwarden vault")
-                .padding_top(1),
+                .title(title)
+                .padding_top(1)
+                .with_name("vault_dialog"),
         )
         .child(
             LinearLayout::horizontal()
-                .child(TextView::new("^U: Copy username  ^P: Copy password").full_width())
+                .child(
+                    TextView::new(
+                        "^U: Copy username  ^P: Copy password  ^T: Copy TOTP  <Enter>: Details",
+                    )
+                    .full_width(),
+                )
+                .child(TextView::new("").with_name("rotation_status"))
                 .child(TextView::new("^F: fuzzy-search")),
         );
 
     siv.add_layer(layout);
     siv.focus_name("password_table").unwrap();
+
+    if let Some(err) = script_error {
+        siv.add_layer(Dialog::info(err).title("commands"));
+    }
+
+    if let Some(query) = search {
+        siv.call_on_name("search_field", |field: &mut EditView| {
+            field.set_content(query.as_str());
+        });
+        fuzzy_match_on_edit(siv, &query);
+    }
+
+    if let Some(uuid) = state.selected.filter(|_| restore) {
+        siv.call_on_name("password_table", |view: &mut VaultTableView| {
+            let index = view
+                .borrow_items()
+                .iter()
+                .position(|entry| entry.uuid == uuid);
+
+            if let Some(index) = index {
+                view.set_selected_item(index);
+            }
+        });
+    }
+
+    if !notices.is_empty() {
+        siv.add_layer(Dialog::info(notices.join("\n")));
+    }
+
+    update_rotation_status(siv);
+    spawn_token_refresh(siv);
 }
 
-fn fuzzy_match_on_edit(siv: &mut Cursive, items: &Vec<VaultEntry>, content: &str) {
-    let mut table = siv.find_name::<VaultTableView>("password_table").unwrap();
+/// Shows how many passwords are due for rotation in the status bar.
+fn update_rotation_status(siv: &mut Cursive) {
+    let summary = match siv.user_data::<Session>() {
+        Some(session) => {
+            let rotations = rotation::rotations(&session.vault, &session.cipher, Utc::now());
+            rotation::summary(&rotations)
+        }
+        None => return,
+    };
 
-    // If no search term is present, sort by name and favorite by default
-    if content.len() == 0 {
-        table.set_items(items.clone());
+    siv.call_on_name("rotation_status", |view: &mut TextView| {
+        if summary.is_empty() {
+            view.set_content("");
+        } else {
+            view.set_content(format!("{} (^L)  ", summary));
+        }
+    });
+}
 
-        table.sort_by(VaultColumn::Name, Ordering::Less);
-        table.sort_by(VaultColumn::Favorite, Ordering::Less);
+/// Copies a value of the selected entry, returns whether it got copied.
+fn copy_field<F>(siv: &mut Cursive, field: F) -> bool
+where
+    F: FnOnce(&VaultEntry) -> Rc<String>,
+{
+    let value = siv
+        .call_on_name("password_table", |view: &mut VaultTableView| {
+            view.item().and_then(|row| view.borrow_item(row)).map(field)
+        })
+        .unwrap();
+
+    match value.map(|value| clip::copy(&value)) {
+        Some(Ok(())) => true,
+        Some(Err(err)) => {
+            siv.add_layer(Dialog::info(err));
+            false
+        }
+        None => false,
+    }
+}
+
+/// Tells the copy hook, if any, which value of which item was copied.
+fn copy_hook(field: &str, uuid: Option<Uuid>) {
+    let uuid = uuid.map(|uuid| uuid.to_string()).unwrap_or_default();
+    hooks::run(
+        Hook::Copy,
+        &[("BWTUI_FIELD", field), ("BWTUI_ITEM_ID", uuid.as_str())],
+    );
+}
+
+/// Remembers how the vault is shown, so the next start reopens it that way.
+pub fn save_view_state(siv: &mut Cursive) {
+    let table = siv.call_on_name("password_table", |view: &mut VaultTableView| {
+        let sort = view
+            .order()
+            .map(|(column, order)| (column.name().to_owned(), order == Ordering::Less));
+        let selected = view
+            .item()
+            .and_then(|row| view.borrow_item(row))
+            .map(|entry| entry.uuid);
 
+        (sort, selected)
+    });
+
+    let (sort, selected) = match table {
+        Some(table) => table,
+        None => return,
+    };
+
+    let search = search_content(siv);
+
+    let state = match siv.user_data::<Session>() {
+        Some(session) => api::ViewState {
+            sort,
+            favorites: session.filter.favorites,
+            folders: session
+                .filter
+                .folders
+                .as_ref()
+                .map(|(_, folders)| folders.clone())
+                .unwrap_or_default(),
+            archived: session.filter.archived,
+            search: search.to_string(),
+            selected,
+        },
+        None => return,
+    };
+
+    let _ = api::save_view_state(&state);
+}
+
+/// Drops the decrypted vault and asks for the master password again. The
+/// vault data is kept, so unlocking doesn't need to sync.
+pub fn lock(siv: &mut Cursive) {
+    if siv.user_data::<Session>().is_none() {
         return;
     }
 
-    let matcher = SkimMatcherV2::default();
+    info!("locking the vault");
+    save_view_state(siv);
+    hooks::run(Hook::Lock, &[]);
 
-    let mut items: Vec<(i64, VaultEntry)> = items
-        .iter()
-        .map(|entry| (matcher.fuzzy_match(&entry.name, content), entry.clone()))
-        .filter(|(score, _)| score.is_some())
-        .map(|(score, entry)| (score.unwrap(), entry))
-        .collect();
+    while siv.pop_layer().is_some() {}
+
+    if let Some(session) = siv.take_user_data::<Session>() {
+        session.cache.borrow_mut().purge();
+
+        let mut auth = session.auth;
+        // Only the master password unlocks it again, the keys are dropped
+        // (and scrubbed) here
+        auth.cipher = CipherSuite::default();
+
+        let app_data = api::AppData {
+            auth,
+            vault: session.vault,
+        };
 
-    items.sort_by(|a, b| a.0.cmp(&b.0).reverse());
+        // Reopened the way it was left, from the view state saved above
+        login::ask(siv, Some(app_data), StartupView::default());
+    }
+}
+
+/// UUID of the item currently selected in the vault table.
+pub fn selected_item(siv: &mut Cursive) -> Option<Uuid> {
+    siv.call_on_name("password_table", |view: &mut VaultTableView| {
+        view.item()
+            .and_then(|row| view.borrow_item(row))
+            .map(|entry| entry.uuid)
+    })
+    .and_then(|uuid| uuid)
+}
 
-    let items = items.iter().map(|(_, entry)| entry.clone()).collect();
+/// Marks the selected entry, or unmarks it if it is marked already.
+fn toggle_mark(siv: &mut Cursive) {
+    let uuid = match selected_item(siv) {
+        Some(uuid) => uuid,
+        None => return,
+    };
+
+    let marked = match siv.user_data::<Session>() {
+        Some(session) => {
+            if !session.marked.remove(&uuid) {
+                session.marked.insert(uuid);
+            }
+
+            session.marked.contains(&uuid)
+        }
+        None => return,
+    };
+
+    siv.call_on_name("password_table", |view: &mut VaultTableView| {
+        if let Some(entry) = view.item().and_then(|row| view.borrow_item_mut(row)) {
+            entry.marked = marked;
+        }
+    });
+}
+
+/// UUIDs of the marked entries, or of all entries in the table if none are
+/// marked.
+pub fn selected_or_shown(siv: &mut Cursive) -> Vec<Uuid> {
+    let marked = match siv.user_data::<Session>() {
+        Some(session) => session.marked.iter().cloned().collect::<Vec<Uuid>>(),
+        None => return Vec::new(),
+    };
+
+    if !marked.is_empty() {
+        return marked;
+    }
+
+    siv.call_on_name("password_table", |view: &mut VaultTableView| {
+        view.borrow_items().iter().map(|entry| entry.uuid).collect()
+    })
+    .unwrap_or_default()
+}
+
+/// Copies the current TOTP code of the selected entry, if it has a secret.
+fn copy_totp(siv: &mut Cursive) {
+    let uuid = selected_item(siv);
+
+    let code = match (uuid, siv.user_data::<Session>()) {
+        (Some(uuid), Some(session)) => session
+            .vault
+            .ciphers
+            .iter()
+            .find(|entry| entry.uuid == uuid)
+            .and_then(|entry| entry.data.totp.as_ref())
+            .and_then(|totp| totp.decrypt(&session.cipher))
+            .and_then(|totp| Totp::parse(&totp))
+            .map(|totp| {
+                let skew = session
+                    .vault
+                    .clock_skew
+                    .unwrap_or_else(chrono::Duration::zero);
+                totp.generate(Utc::now(), skew)
+            }),
+        _ => return,
+    };
+
+    if let Some(code) = code {
+        match clip::copy(&code) {
+            Ok(()) => {
+                activity::record(siv, "copied TOTP code", uuid);
+                copy_hook("totp", uuid);
+            }
+            Err(err) => siv.add_layer(Dialog::info(err)),
+        }
+    }
+}
+
+/// Lets the user pick a custom field of the selected entry by name and copies
+/// its value.
+fn copy_custom_field(siv: &mut Cursive) {
+    let uuid = selected_item(siv);
+
+    let fields = match (uuid, siv.user_data::<Session>()) {
+        (Some(uuid), Some(session)) => session
+            .vault
+            .ciphers
+            .iter()
+            .find(|entry| entry.uuid == uuid)
+            .map(|entry| {
+                entry
+                    .custom_fields()
+                    .iter()
+                    .map(|field| {
+                        let name = field
+                            .name
+                            .as_ref()
+                            .and_then(|name| name.decrypt(&session.cipher));
+                        let value = entry
+                            .field_value(field)
+                            .and_then(|value| value.decrypt(&session.cipher));
+
+                        (name.unwrap_or_default(), value.unwrap_or_default())
+                    })
+                    .collect::<Vec<(String, String)>>()
+            }),
+        _ => return,
+    };
+
+    let fields = match fields {
+        Some(fields) if !fields.is_empty() => fields,
+        Some(_) => return siv.add_layer(Dialog::info("The item has no custom fields")),
+        None => return,
+    };
+
+    let mut select = SelectView::new().on_submit(move |siv, value: &String| {
+        siv.pop_layer();
+
+        match clip::copy(value) {
+            Ok(()) => {
+                activity::record(siv, "copied custom field", uuid);
+                copy_hook("custom field", uuid);
+            }
+            Err(err) => siv.add_layer(Dialog::info(err)),
+        }
+    });
+
+    for (name, value) in fields {
+        select.add_item(name, value);
+    }
+
+    // Typing the first letters of a name jumps to it
+    siv.add_layer(
+        Dialog::around(select.autojump().scrollable())
+            .title("copy custom field")
+            .dismiss_button("Cancel")
+            .min_width(40),
+    );
+}
+
+/// Types the selected entry into the window focused after a short delay, so
+/// the user can switch to it first.
+fn auto_type(siv: &mut Cursive) {
+    let uuid = selected_item(siv);
+
+    let actions = match (uuid, siv.user_data::<Session>()) {
+        (Some(uuid), Some(session)) => {
+            let skew = session
+                .vault
+                .clock_skew
+                .unwrap_or_else(chrono::Duration::zero);
+
+            session
+                .vault
+                .ciphers
+                .iter()
+                .find(|entry| entry.uuid == uuid)
+                .map(|entry| autotype::actions(entry, &session.cipher, skew))
+        }
+        _ => return,
+    };
+
+    let actions = match actions {
+        Some(Ok(actions)) => actions,
+        Some(Err(err)) => return siv.add_layer(Dialog::info(err)),
+        None => return,
+    };
+
+    siv.add_layer(
+        Dialog::text(format!(
+            "Switch to the target window, typing starts in {}s ...",
+            AUTOTYPE_DELAY.as_secs()
+        ))
+        .title("auto-type")
+        .with_name("autotype_dialog"),
+    );
+
+    let cb_sink = siv.cb_sink().clone();
+
+    thread::spawn(move || {
+        thread::sleep(AUTOTYPE_DELAY);
+        let result = autotype::run(&actions);
+
+        let _ = cb_sink.send(Box::new(move |siv: &mut Cursive| {
+            if siv.find_name::<Dialog>("autotype_dialog").is_some() {
+                siv.pop_layer();
+            }
+
+            match result {
+                Ok(()) => activity::record(siv, "auto-typed", uuid),
+                Err(err) => siv.add_layer(Dialog::info(err)),
+            }
+        }));
+    });
+}
+
+/// Archives the selected entry, or brings it back if it is archived already.
+fn toggle_archived(siv: &mut Cursive) {
+    let uuid = selected_item(siv);
+
+    let archived = match (uuid, siv.user_data::<Session>()) {
+        (Some(uuid), Some(session)) => session
+            .vault
+            .ciphers
+            .iter()
+            .find(|entry| entry.uuid == uuid)
+            .map(|entry| (uuid, entry.archived_date.is_some())),
+        _ => return,
+    };
+
+    let (uuid, archive) = match archived {
+        Some((uuid, archived)) => (uuid, !archived),
+        None => return,
+    };
+
+    request(
+        siv,
+        move |api, auth| api.set_cipher_archived(auth, uuid, archive),
+        move |siv, result: Result<(), ApiError>| match result {
+            Ok(()) => {
+                let message = if archive {
+                    "Item archived, ^W shows the archive"
+                } else {
+                    "Item moved out of the archive"
+                };
+
+                siv.add_layer(Dialog::info(message));
+                sync_in_background(siv);
+            }
+            Err(err) => siv.add_layer(Dialog::info(err.to_string())),
+        },
+    );
+}
+
+/// Switches between the archived items and the rest of the vault.
+fn toggle_archived_view(siv: &mut Cursive) {
+    let title = match siv.user_data::<Session>() {
+        Some(session) => {
+            session.filter.archived = !session.filter.archived;
+            session.filter.title()
+        }
+        None => return,
+    };
+
+    siv.call_on_name("vault_dialog", |view: &mut Dialog| {
+        view.set_title(title);
+    });
+
+    let content = search_content(siv);
+    fuzzy_match_on_edit(siv, &content);
+}
+
+/// Runs an authenticated request on the worker, re-authenticating once if the
+/// access token got rejected. The refreshed token is kept in the session, if
+/// it can't be renewed the user is asked to log in again.
+pub fn request<T, F, D>(siv: &mut Cursive, request: F, done: D)
+where
+    T: Send + 'static,
+    F: Fn(&Api, &AuthData) -> Result<T, ApiError> + Send + 'static,
+    D: FnOnce(&mut Cursive, Result<T, ApiError>) + Send + 'static,
+{
+    let (mut auth, cipher, email) = match siv.user_data::<Session>() {
+        Some(session) => (
+            session.auth.clone(),
+            Arc::clone(&session.cipher),
+            session.vault.profile.email.clone(),
+        ),
+        None => return,
+    };
+
+    worker::run(
+        move |api| {
+            let result = api.retry_unauthorized(&email, &mut auth, &cipher, request);
+            (auth, result)
+        },
+        |siv, (auth, result): (AuthData, Result<T, ApiError>)| {
+            if let Some(session) = siv.user_data::<Session>() {
+                session.auth = auth;
+            }
+
+            let expired = match result {
+                Err(ApiError::SessionExpired) => true,
+                _ => false,
+            };
+
+            done(siv, result);
+
+            if expired {
+                session_expired(siv);
+            }
+        },
+    );
+}
+
+/// Syncs with the server without blocking the UI, the shown data gets
+/// replaced once the sync is done.
+pub fn sync_in_background(siv: &mut Cursive) {
+    let (mut auth, cipher, email, synced_at, stamp) = match siv.user_data::<Session>() {
+        Some(session) => (
+            session.auth.clone(),
+            Arc::clone(&session.cipher),
+            session.vault.profile.email.clone(),
+            session.vault.synced_at,
+            session.vault.profile.security_stamp.clone(),
+        ),
+        None => return,
+    };
+
+    worker::run(
+        move |api| {
+            let vault_data = api.retry_unauthorized(&email, &mut auth, &cipher, |api, auth| {
+                api.sync_if_changed(auth, synced_at)
+            })?;
+
+            // Unchanged, only the access token might have been renewed
+            let vault_data = match vault_data {
+                Some(vault_data) => vault_data,
+                None => {
+                    let _ = api::save_auth_data(&auth);
+                    return Ok((auth, None));
+                }
+            };
+
+            // The password was changed or all sessions revoked elsewhere,
+            // the cached tokens and keys mustn't be used any longer. Empty
+            // for caches of servers not sending it.
+            if !stamp.is_empty() && vault_data.profile.security_stamp != stamp {
+                return Err(ApiError::SecurityStampChanged);
+            }
+
+            let _ = api::save_app_data(&auth, &vault_data);
+            let report = verify::verify(&vault_data, &cipher);
+
+            Ok((auth, Some((vault_data, report))))
+        },
+        |siv, result: Result<(AuthData, Option<(VaultData, verify::Report)>), ApiError>| {
+            match result {
+                Ok((auth, synced)) => {
+                    if let Some(session) = siv.user_data::<Session>() {
+                        session.auth = auth;
+                    }
+
+                    set_offline_banner(siv, "");
+
+                    if let Some((vault_data, report)) = synced {
+                        reload(siv, vault_data);
+
+                        if !report.is_clean() {
+                            siv.add_layer(Dialog::info(report.summary()).title("vault check"));
+                        }
+                    }
+
+                    let items = siv
+                        .user_data::<Session>()
+                        .map_or(0, |session| session.vault.ciphers.len())
+                        .to_string();
+
+                    journal::replay(siv);
+                    hooks::run(Hook::PostSync, &[("BWTUI_ITEMS", items.as_str())]);
+                }
+                Err(ApiError::Offline { .. }) => {
+                    let synced_at = siv
+                        .user_data::<Session>()
+                        .and_then(|session| session.vault.synced_at)
+                        .map(|synced_at| {
+                            synced_at
+                                .with_timezone(&Local)
+                                .format("%Y-%m-%d %H:%M")
+                                .to_string()
+                        })
+                        .unwrap_or("an unknown date".to_owned());
+
+                    set_offline_banner(siv, &format!("offline — data from {}", synced_at));
+                    retry_sync_later(siv);
+                }
+                Err(ApiError::InvalidGrant { error }) => {
+                    // The master password which unlocked the cache isn't
+                    // accepted anymore, it was changed on another device.
+                    warn!("re-authentication failed: {}", error);
+                    relogin(
+                        siv,
+                        "master password changed on another device",
+                        login::password_changed,
+                    );
+                }
+                Err(ApiError::SecurityStampChanged) => {
+                    relogin(siv, "security stamp changed", login::security_stamp_changed)
+                }
+                Err(ApiError::SessionExpired) => session_expired(siv),
+                Err(err) => warn!("background sync failed: {}", err),
+            }
+        },
+    );
+}
+
+/// Closes the vault and discards its cache, whose tokens and keys became
+/// stale because of `reason`, and tells why on the login form with `notify`.
+/// Logging in again syncs afresh.
+fn relogin(siv: &mut Cursive, reason: &str, notify: fn(&mut Cursive, &str)) {
+    let session = match siv.take_user_data::<Session>() {
+        Some(session) => session,
+        None => return,
+    };
+
+    info!("{}, discarding the cache", reason);
+    hooks::run(Hook::Lock, &[]);
+
+    if let Err(err) = api::remove_app_data() {
+        warn!("failed to remove the stale cache: {}", err);
+    }
+
+    while siv.pop_layer().is_some() {}
+
+    login::ask(siv, None, StartupView::default());
+    notify(siv, &session.vault.profile.email);
+}
+
+/// Asks to log in again once the access token can't be renewed anymore. The
+/// vault stays usable until then, only syncing and changes don't work.
+fn session_expired(siv: &mut Cursive) {
+    if siv.find_name::<Dialog>("session_expired").is_some() {
+        return;
+    }
+
+    siv.add_layer(
+        Dialog::text(
+            "The session expired and renewing it needs you to log in again, \
+             e.g. for a second factor. Until then, the vault isn't synced \
+             and changes can't be saved.",
+        )
+        .title("session expired")
+        .button("Log in", log_in_again)
+        .dismiss_button("Later")
+        .with_name("session_expired"),
+    );
+}
+
+/// Closes the vault for a new login, keeping the cache which a fresh sync
+/// replaces afterwards.
+fn log_in_again(siv: &mut Cursive) {
+    save_view_state(siv);
+
+    let session = match siv.take_user_data::<Session>() {
+        Some(session) => session,
+        None => return,
+    };
+
+    info!("session expired, logging in again");
+    hooks::run(Hook::Lock, &[]);
+
+    while siv.pop_layer().is_some() {}
+
+    login::ask(siv, None, StartupView::default());
+    login::session_expired(siv, &session.vault.profile.email);
+}
+
+/// Fetches only the selected item again, rather than syncing everything, e.g.
+/// when it was just changed on another device.
+fn refresh_item(siv: &mut Cursive) {
+    let uuid = match selected_item(siv) {
+        Some(uuid) => uuid,
+        None => return,
+    };
+
+    request(
+        siv,
+        move |api, auth| api.cipher(auth, uuid),
+        move |siv, result: Result<Option<CipherEntry>, ApiError>| match result {
+            Ok(entry) => replace_item(siv, uuid, entry),
+            Err(err) => siv.add_layer(Dialog::info(err.to_string())),
+        },
+    );
+}
+
+/// Replaces a single item with the server's version and updates the cache,
+/// the item is dropped if `entry` is `None`.
+pub fn replace_item(siv: &mut Cursive, uuid: Uuid, entry: Option<CipherEntry>) {
+    let saved = match siv.user_data::<Session>() {
+        Some(session) => {
+            let ciphers = &mut session.vault.ciphers;
+            let index = ciphers.iter().position(|entry| entry.uuid == uuid);
+
+            match (index, entry) {
+                (Some(index), Some(entry)) => ciphers[index] = entry,
+                (Some(index), None) => {
+                    ciphers.remove(index);
+                }
+                (None, Some(entry)) => ciphers.push(entry),
+                (None, None) => (),
+            }
+
+            session.cache.borrow_mut().purge();
+            session.items = build_items(&session.vault, &session.cache);
+            session.index.update(&session.vault, &session.cipher);
+            session.last_search = None;
+
+            api::save_app_data(&session.auth, &session.vault)
+        }
+        None => return,
+    };
+
+    let content = search_content(siv);
+    fuzzy_match_on_edit(siv, &content);
+    update_rotation_status(siv);
+
+    if let Err(err) = saved {
+        siv.add_layer(Dialog::info(err.to_string()));
+    }
+}
+
+fn set_offline_banner(siv: &mut Cursive, message: &str) {
+    siv.call_on_name("offline_banner", |view: &mut TextView| {
+        view.set_content(message);
+    });
+}
+
+fn retry_sync_later(siv: &mut Cursive) {
+    let cb_sink = siv.cb_sink().clone();
+
+    thread::spawn(move || {
+        thread::sleep(OFFLINE_RETRY_INTERVAL);
+        let _ = cb_sink.send(Box::new(sync_in_background));
+    });
+}
+
+/// Keeps the access token fresh while the vault is open, so that it doesn't
+/// expire right when the user triggers a request.
+fn spawn_token_refresh(siv: &mut Cursive) {
+    let cb_sink = siv.cb_sink().clone();
+    let generation = TOKEN_REFRESH.fetch_add(1, atomic::Ordering::SeqCst) + 1;
+
+    thread::spawn(move || loop {
+        thread::sleep(TOKEN_CHECK_INTERVAL);
+
+        if TOKEN_REFRESH.load(atomic::Ordering::SeqCst) != generation {
+            break;
+        }
+
+        if cb_sink.send(Box::new(refresh_token_if_needed)).is_err() {
+            break;
+        }
+    });
+}
+
+fn refresh_token_if_needed(siv: &mut Cursive) {
+    let session = match siv.user_data::<Session>() {
+        Some(session) => session,
+        None => {
+            // Locked or logged out, the thread stops
+            TOKEN_REFRESH.fetch_add(1, atomic::Ordering::SeqCst);
+            return;
+        }
+    };
+
+    if !session.auth.expires_soon() {
+        return;
+    }
+
+    let mut auth = session.auth.clone();
+    let cipher = Arc::clone(&session.cipher);
+    let email = session.vault.profile.email.clone();
+
+    worker::run(
+        move |api| {
+            api.reauthenticate(&email, &mut auth, &cipher)?;
+            let _ = api::save_auth_data(&auth);
+
+            Ok(auth)
+        },
+        |siv, result: Result<AuthData, ApiError>| {
+            if let (Ok(auth), Some(session)) = (result, siv.user_data::<Session>()) {
+                session.auth = auth;
+            }
+        },
+    );
+}
+
+/// Replaces the shown vault data if `vault_data` was synced more recently,
+/// e.g. by another bwtui instance sharing the same cache.
+pub fn reload(siv: &mut Cursive, vault_data: VaultData) {
+    if let Some(state) = siv.user_data::<login::LoginState>() {
+        if let Some(app_data) = state.app_data.as_mut() {
+            if vault_data.synced_at > app_data.vault.synced_at {
+                app_data.vault = vault_data;
+            }
+        }
+
+        return;
+    }
+
+    match siv.user_data::<Session>() {
+        Some(session) if vault_data.synced_at > session.vault.synced_at => {
+            session.cache.borrow_mut().purge();
+            session.items = build_items(&vault_data, &session.cache);
+            session.index.update(&vault_data, &session.cipher);
+            session.last_search = None;
+            session.vault = vault_data;
+        }
+        _ => return,
+    }
+
+    let content = search_content(siv);
+    fuzzy_match_on_edit(siv, &content);
+    update_rotation_status(siv);
+}
+
+fn search_content(siv: &mut Cursive) -> Rc<String> {
+    siv.call_on_name("search_field", |view: &mut EditView| view.get_content())
+        .unwrap()
+}
+
+/// Filters the table once the search field wasn't edited for a short while,
+/// so typing quickly doesn't trigger a search for every single keystroke.
+fn schedule_search(siv: &mut Cursive, content: &str) {
+    let generation = match siv.user_data::<Session>() {
+        Some(session) => {
+            session.search_generation += 1;
+            session.search_generation
+        }
+        None => return,
+    };
+
+    // Resetting the search is cheap, no need to wait for that
+    if content.is_empty() {
+        fuzzy_match_on_edit(siv, content);
+        return;
+    }
+
+    let cb_sink = siv.cb_sink().clone();
+
+    thread::spawn(move || {
+        thread::sleep(SEARCH_DEBOUNCE);
+
+        let _ = cb_sink.send(Box::new(move |siv: &mut Cursive| {
+            let current = siv
+                .user_data::<Session>()
+                .map_or(false, |session| session.search_generation == generation);
+
+            if current {
+                let content = search_content(siv);
+                fuzzy_match_on_edit(siv, &content);
+            }
+        }));
+    });
+}
+
+fn fuzzy_match_on_edit(siv: &mut Cursive, content: &str) {
+    let items = match siv.user_data::<Session>() {
+        Some(session) if content.is_empty() => {
+            session.last_search = None;
+            session.with_marks(session.filter.apply(&session.items))
+        }
+        Some(session) => {
+            // Anything matching the extended query also matched the previous
+            // one, so only look at those results again.
+            let results = match session.last_search.take() {
+                Some((query, results)) if content.starts_with(&query) => {
+                    session.index.search_within(content, &results)
+                }
+                _ => session.index.search(content),
+            };
+
+            let items = results
+                .iter()
+                .map(|pos| &session.items[*pos])
+                .filter(|entry| session.filter.matches(entry))
+                .cloned()
+                .collect();
+
+            session.last_search = Some((content.to_owned(), results));
+            session.with_marks(items)
+        }
+        None => return,
+    };
+
+    let mut table = siv.find_name::<VaultTableView>("password_table").unwrap();
+
+    // If no search term is present, sort by name and favorite by default
+    if content.is_empty() {
+        table.set_items(items);
+
+        table.sort_by(VaultColumn::Name, Ordering::Less);
+        table.sort_by(VaultColumn::Favorite, Ordering::Less);
+
+        return;
+    }
 
     table.set_selected_row(0);
     table.set_items(items);
@@ -0,0 +1,120 @@
+// SPDX-License-Identifier: MIT
+
+use std::collections::HashSet;
+
+use rayon::prelude::*;
+use uuid::Uuid;
+
+use crate::api::{CipherEntry, VaultData};
+use crate::cipher::{CipherString, CipherSuite};
+
+
+/// Outcome of checking a freshly synced vault.
+#[derive(Debug, Default)]
+pub struct Report {
+        pub checked: usize,
+        // Organization items are encrypted with keys bwtui doesn't handle yet
+        pub skipped: usize,
+        pub unreadable: Vec<Uuid>,
+        pub missing_folders: Vec<Uuid>,
+        pub missing_collections: Vec<Uuid>,
+}
+
+impl Report {
+        pub fn is_clean(&self) -> bool {
+                self.unreadable.is_empty()
+                        && self.missing_folders.is_empty()
+                        && self.missing_collections.is_empty()
+        }
+
+        pub fn summary(&self) -> String {
+                let mut lines = vec![format!("{} items checked", self.checked)];
+
+                if !self.unreadable.is_empty() {
+                        lines.push(format!("{} items failed integrity checks", self.unreadable.len()));
+                }
+
+                if !self.missing_folders.is_empty() {
+                        lines.push(format!("{} items refer to unknown folders", self.missing_folders.len()));
+                }
+
+                if !self.missing_collections.is_empty() {
+                        lines.push(format!("{} items refer to unknown collections", self.missing_collections.len()));
+                }
+
+                if self.skipped > 0 {
+                        lines.push(format!("{} organization items were not checked", self.skipped));
+                }
+
+                lines.join("\n")
+        }
+}
+
+
+fn protected_fields(entry: &CipherEntry) -> Vec<&CipherString> {
+        let data = &entry.data;
+
+        let uris = data.uris.iter()
+                .flatten()
+                .filter_map(|uri| uri.uri.as_ref());
+
+        let fields = entry.custom_fields().iter()
+                .flat_map(|field| field.name.iter().chain(field.value.iter()));
+
+        let credentials = data.fido2_credentials.iter()
+                .flatten()
+                .flat_map(|credential| {
+                        Some(&credential.rp_id).into_iter()
+                                .chain(credential.rp_name.iter())
+                                .chain(credential.user_name.iter())
+                                .chain(credential.user_display_name.iter())
+                });
+
+        Some(&entry.name).into_iter()
+                .chain(data.username.iter())
+                .chain(data.password.iter())
+                .chain(data.totp.iter())
+                .chain(data.uri.iter())
+                .chain(uris)
+                .chain(fields)
+                .chain(credentials)
+                .collect()
+}
+
+/// Verifies the MACs of all protected fields and that all folder and
+/// collection references resolve, so broken items show up at once.
+pub fn verify(vault: &VaultData, cipher: &CipherSuite) -> Report {
+        let folders = vault.folders.iter()
+                .map(|folder| folder.uuid)
+                .collect::<HashSet<Uuid>>();
+
+        let collections = vault.collections.iter()
+                .map(|collection| collection.uuid)
+                .collect::<HashSet<Uuid>>();
+
+        let (own, shared): (Vec<&CipherEntry>, Vec<&CipherEntry>) = vault.ciphers.iter()
+                .partition(|entry| entry.organization_id.is_none());
+
+        let unreadable = own.par_iter()
+                .filter(|entry| !protected_fields(entry).iter().all(|value| value.verify(cipher)))
+                .map(|entry| entry.uuid)
+                .collect();
+
+        let missing_folders = vault.ciphers.iter()
+                .filter(|entry| entry.folder_id.map_or(false, |id| !folders.contains(&id)))
+                .map(|entry| entry.uuid)
+                .collect();
+
+        let missing_collections = vault.ciphers.iter()
+                .filter(|entry| entry.collection_ids.iter().any(|id| !collections.contains(id)))
+                .map(|entry| entry.uuid)
+                .collect();
+
+        Report {
+                checked: own.len(),
+                skipped: shared.len(),
+                unreadable,
+                missing_folders,
+                missing_collections,
+        }
+}
@@ -0,0 +1,60 @@
+// SPDX-License-Identifier: MIT
+
+use std::sync::mpsc::channel;
+use std::thread;
+use std::time::Duration;
+
+use cursive::{CbSink, Cursive};
+use notify::{DebouncedEvent, RecursiveMode, Watcher};
+
+use crate::api;
+use crate::vault;
+
+
+const VAULT_FILE: &str = "vault";
+
+
+/// Watches the data directory for cache writes done by other bwtui instances
+/// and hands newer vault data to the UI.
+pub fn spawn(cb_sink: CbSink) {
+        let path = match api::get_app_data_path() {
+                Ok(path) => path,
+                Err(_) => return,
+        };
+
+        thread::spawn(move || {
+                let (tx, rx) = channel();
+
+                let mut watcher = match notify::watcher(tx, Duration::from_millis(500)) {
+                        Ok(watcher) => watcher,
+                        Err(_) => return,
+                };
+
+                if watcher.watch(&path, RecursiveMode::NonRecursive).is_err() {
+                        return;
+                }
+
+                for event in rx {
+                        let changed = match event {
+                                DebouncedEvent::Create(path)
+                                | DebouncedEvent::Write(path)
+                                | DebouncedEvent::Rename(_, path) => path,
+                                _ => continue,
+                        };
+
+                        if changed.file_stem().map_or(true, |name| name != VAULT_FILE) {
+                                continue;
+                        }
+
+                        // Our own writes end up here too, vault::reload() ignores
+                        // data which isn't newer than what is currently loaded.
+                        if let Ok(vault_data) = api::read_vault_data() {
+                                let reload = move |siv: &mut Cursive| vault::reload(siv, vault_data);
+
+                                if cb_sink.send(Box::new(reload)).is_err() {
+                                        break;
+                                }
+                        }
+                }
+        });
+}
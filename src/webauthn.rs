@@ -0,0 +1,92 @@
+// SPDX-License-Identifier: MIT
+
+// WebAuthn second factor, signed by a FIDO2 security key over USB HID the way
+// the web vault would, as the browser isn't there to do it.
+
+use crate::api::WebAuthnChallenge;
+
+#[cfg(feature = "fido2")]
+use ctap_hid_fido2::fidokey::GetAssertionArgsBuilder;
+#[cfg(feature = "fido2")]
+use ctap_hid_fido2::{Cfg, FidoKeyHidFactory};
+#[cfg(feature = "fido2")]
+use serde_json::json;
+
+
+#[cfg(feature = "fido2")]
+fn encode(data: &[u8]) -> String {
+        base64::encode_config(data, base64::URL_SAFE_NO_PAD)
+}
+
+
+/// Has a security key sign `challenge` for the web vault at `origin`, `pin`
+/// being its PIN if it has one. Returns the token to log in with, the
+/// assertion as JSON the server expects. Blocks until the key is touched.
+#[cfg(feature = "fido2")]
+pub fn sign(challenge: &WebAuthnChallenge, origin: &str, pin: Option<&str>) -> Result<String, String> {
+        let rp_id = challenge.rp_id.clone()
+                .unwrap_or_else(|| origin.trim_start_matches("https://").to_owned());
+
+        let client_data = json!({
+                "type": "webauthn.get",
+                "challenge": challenge.challenge,
+                "origin": origin,
+                "crossOrigin": false,
+        }).to_string();
+
+        let device = FidoKeyHidFactory::create(&Cfg::init())
+                .map_err(|e| format!("no security key found: {}", e))?;
+
+        let credentials = challenge.allow_credentials.iter()
+                .filter_map(|credential| base64::decode_config(&credential.id, base64::URL_SAFE_NO_PAD).ok())
+                .collect::<Vec<Vec<u8>>>();
+
+        if credentials.is_empty() {
+                return Err("no security key registered with the account".to_owned());
+        }
+
+        let mut last_error = String::new();
+
+        // Only one credential is passed per request, try each until the key
+        // knows one of them
+        for credential in &credentials {
+                // The client data is hashed by the library into the client data
+                // hash the authenticator signs
+                let mut args = GetAssertionArgsBuilder::new(&rp_id, client_data.as_bytes())
+                        .credential_id(credential);
+                if let Some(pin) = pin {
+                        args = args.pin(pin);
+                }
+
+                match device.get_assertion_with_args(&args.build()) {
+                        Ok(assertions) => {
+                                let assertion = match assertions.into_iter().next() {
+                                        Some(assertion) => assertion,
+                                        None => continue,
+                                };
+
+                                let token = json!({
+                                        "id": encode(credential),
+                                        "rawId": encode(credential),
+                                        "type": "public-key",
+                                        "extensions": {},
+                                        "response": {
+                                                "authenticatorData": encode(&assertion.auth_data),
+                                                "clientDataJson": encode(client_data.as_bytes()),
+                                                "signature": encode(&assertion.signature),
+                                        },
+                                });
+
+                                return Ok(token.to_string());
+                        }
+                        Err(e) => last_error = e.to_string(),
+                }
+        }
+
+        Err(format!("the security key didn't sign: {}", last_error))
+}
+
+#[cfg(not(feature = "fido2"))]
+pub fn sign(_: &WebAuthnChallenge, _: &str, _: Option<&str>) -> Result<String, String> {
+        Err("bwtui was built without the fido2 feature".to_owned())
+}
@@ -0,0 +1,61 @@
+// SPDX-License-Identifier: MIT
+
+use std::cell::RefCell;
+use std::sync::mpsc::{channel, Sender};
+use std::thread;
+
+use cursive::{CbSink, Cursive};
+
+use crate::api::Api;
+
+
+type Callback = Box<dyn FnOnce(&mut Cursive) + Send>;
+type Job = Box<dyn FnOnce(&Api) -> Callback + Send>;
+
+
+thread_local! {
+        // Set once by spawn(), only ever accessed from the UI thread.
+        static JOBS: RefCell<Option<Sender<Job>>> = RefCell::new(None);
+}
+
+
+/// Starts the worker thread, which runs all blocking (network and disk)
+/// operations one after another, so that input handling and rendering never
+/// have to wait for them. It owns the API handle shared by all jobs.
+pub fn spawn(cb_sink: CbSink) {
+        let (tx, rx) = channel::<Job>();
+
+        thread::spawn(move || {
+                let api = Api::new();
+
+                for job in rx {
+                        if cb_sink.send(job(&api)).is_err() {
+                                break;
+                        }
+                }
+        });
+
+        JOBS.with(|jobs| *jobs.borrow_mut() = Some(tx));
+}
+
+
+/// Runs `job` on the worker thread and hands its result to `done`, back on the
+/// UI thread.
+pub fn run<T, J, D>(job: J, done: D)
+        where T: Send + 'static,
+              J: FnOnce(&Api) -> T + Send + 'static,
+              D: FnOnce(&mut Cursive, T) + Send + 'static
+{
+        let job: Job = Box::new(move |api| {
+                let result = job(api);
+                let callback: Callback = Box::new(move |siv| done(siv, result));
+
+                callback
+        });
+
+        JOBS.with(|jobs| {
+                if let Some(jobs) = &*jobs.borrow() {
+                        let _ = jobs.send(job);
+                }
+        });
+}